@@ -2,13 +2,18 @@
 use rustc_hash::FxHashMap;
 
 use hir::{db::HirDatabase, InFile, PathResolution};
-use ra_syntax::ast::{self, AstNode};
+use ra_syntax::{
+    ast::{self, AstNode},
+    SyntaxKind::{BREAK_EXPR, CONTINUE_EXPR, LABEL, LIFETIME},
+    SyntaxToken,
+};
 
 pub trait AstTransform<'a> {
     fn get_substitution(
         &self,
         node: InFile<&ra_syntax::SyntaxNode>,
     ) -> Option<ra_syntax::SyntaxNode>;
+    fn get_token_substitution(&self, token: &SyntaxToken) -> Option<SyntaxToken>;
 
     fn chain_before(self, other: Box<dyn AstTransform<'a> + 'a>) -> Box<dyn AstTransform<'a> + 'a>;
     fn or<T: AstTransform<'a> + 'a>(self, other: T) -> Box<dyn AstTransform<'a> + 'a>
@@ -28,6 +33,9 @@ impl<'a> AstTransform<'a> for NullTransformer {
     ) -> Option<ra_syntax::SyntaxNode> {
         None
     }
+    fn get_token_substitution(&self, _token: &SyntaxToken) -> Option<SyntaxToken> {
+        None
+    }
     fn chain_before(self, other: Box<dyn AstTransform<'a> + 'a>) -> Box<dyn AstTransform<'a> + 'a> {
         other
     }
@@ -36,6 +44,7 @@ impl<'a> AstTransform<'a> for NullTransformer {
 pub struct SubstituteTypeParams<'a, DB: HirDatabase> {
     db: &'a DB,
     substs: FxHashMap<hir::TypeParam, ast::TypeRef>,
+    lifetime_substs: FxHashMap<String, SyntaxToken>,
     previous: Box<dyn AstTransform<'a> + 'a>,
 }
 
@@ -45,7 +54,8 @@ impl<'a, DB: HirDatabase> SubstituteTypeParams<'a, DB> {
         trait_: hir::Trait,
         impl_block: ast::ImplBlock,
     ) -> SubstituteTypeParams<'a, DB> {
-        let substs = get_syntactic_substs(impl_block).unwrap_or_default();
+        let substs = get_syntactic_substs(&impl_block).unwrap_or_default();
+        let lifetime_substs = get_syntactic_lifetime_substs(&impl_block).unwrap_or_default();
         let generic_def: hir::GenericDef = trait_.into();
         let substs_by_param: FxHashMap<_, _> = generic_def
             .params(db)
@@ -54,21 +64,23 @@ impl<'a, DB: HirDatabase> SubstituteTypeParams<'a, DB> {
             .skip(1)
             .zip(substs.into_iter())
             .collect();
+        let lifetime_substs_by_name: FxHashMap<_, _> = generic_def
+            .lifetime_params(db)
+            .into_iter()
+            .map(|param| param.name(db).to_string())
+            .zip(lifetime_substs.into_iter())
+            .collect();
         return SubstituteTypeParams {
             db,
             substs: substs_by_param,
+            lifetime_substs: lifetime_substs_by_name,
             previous: Box::new(NullTransformer),
         };
 
         // FIXME: It would probably be nicer if we could get this via HIR (i.e. get the
         // trait ref, and then go from the types in the substs back to the syntax)
-        fn get_syntactic_substs(impl_block: ast::ImplBlock) -> Option<Vec<ast::TypeRef>> {
-            let target_trait = impl_block.target_trait()?;
-            let path_type = match target_trait {
-                ast::TypeRef::PathType(path) => path,
-                _ => return None,
-            };
-            let type_arg_list = path_type.path()?.segment()?.type_arg_list()?;
+        fn get_syntactic_substs(impl_block: &ast::ImplBlock) -> Option<Vec<ast::TypeRef>> {
+            let type_arg_list = trait_type_arg_list(impl_block)?;
             let mut result = Vec::new();
             for type_arg in type_arg_list.type_args() {
                 let type_arg: ast::TypeArg = type_arg;
@@ -76,6 +88,24 @@ impl<'a, DB: HirDatabase> SubstituteTypeParams<'a, DB> {
             }
             Some(result)
         }
+
+        fn get_syntactic_lifetime_substs(impl_block: &ast::ImplBlock) -> Option<Vec<SyntaxToken>> {
+            let type_arg_list = trait_type_arg_list(impl_block)?;
+            let mut result = Vec::new();
+            for lifetime_arg in type_arg_list.lifetime_args() {
+                result.push(lifetime_arg.lifetime_token()?);
+            }
+            Some(result)
+        }
+
+        fn trait_type_arg_list(impl_block: &ast::ImplBlock) -> Option<ast::TypeArgList> {
+            let target_trait = impl_block.target_trait()?;
+            let path_type = match target_trait {
+                ast::TypeRef::PathType(path) => path,
+                _ => return None,
+            };
+            path_type.path()?.segment()?.type_arg_list()
+        }
     }
     fn get_substitution_inner(
         &self,
@@ -93,6 +123,17 @@ impl<'a, DB: HirDatabase> SubstituteTypeParams<'a, DB> {
             _ => None,
         }
     }
+    fn get_token_substitution_inner(&self, token: &SyntaxToken) -> Option<SyntaxToken> {
+        if token.kind() != LIFETIME {
+            return None;
+        }
+        // labels look just like lifetimes, but must not be touched
+        match token.parent().kind() {
+            LABEL | BREAK_EXPR | CONTINUE_EXPR => return None,
+            _ => {}
+        }
+        self.lifetime_substs.get(token.text().as_str()).cloned()
+    }
 }
 
 impl<'a, DB: HirDatabase> AstTransform<'a> for SubstituteTypeParams<'a, DB> {
@@ -102,6 +143,10 @@ impl<'a, DB: HirDatabase> AstTransform<'a> for SubstituteTypeParams<'a, DB> {
     ) -> Option<ra_syntax::SyntaxNode> {
         self.get_substitution_inner(node).or_else(|| self.previous.get_substitution(node))
     }
+    fn get_token_substitution(&self, token: &SyntaxToken) -> Option<SyntaxToken> {
+        self.get_token_substitution_inner(token)
+            .or_else(|| self.previous.get_token_substitution(token))
+    }
     fn chain_before(self, other: Box<dyn AstTransform<'a> + 'a>) -> Box<dyn AstTransform<'a> + 'a> {
         Box::new(SubstituteTypeParams { previous: other, ..self })
     }
@@ -163,7 +208,10 @@ pub fn apply<'a, N: AstNode>(transformer: &dyn AstTransform<'a>, node: InFile<N>
             let replacement = transformer.get_substitution(node.with_value(&n))?;
             Some(replacement.into())
         }
-        _ => None,
+        ra_syntax::SyntaxElement::Token(t) => {
+            let replacement = transformer.get_token_substitution(t)?;
+            Some(replacement.into())
+        }
     });
     N::cast(result).unwrap()
 }
@@ -175,6 +223,9 @@ impl<'a, DB: HirDatabase> AstTransform<'a> for QualifyPaths<'a, DB> {
     ) -> Option<ra_syntax::SyntaxNode> {
         self.get_substitution_inner(node).or_else(|| self.previous.get_substitution(node))
     }
+    fn get_token_substitution(&self, token: &SyntaxToken) -> Option<SyntaxToken> {
+        self.previous.get_token_substitution(token)
+    }
     fn chain_before(self, other: Box<dyn AstTransform<'a> + 'a>) -> Box<dyn AstTransform<'a> + 'a> {
         Box::new(QualifyPaths { previous: other, ..self })
     }
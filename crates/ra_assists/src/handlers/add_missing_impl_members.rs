@@ -445,6 +445,23 @@ impl foo::Foo<Param> for S {
         );
     }
 
+    #[test]
+    fn test_substitute_lifetime_param() {
+        check_assist(
+            add_missing_impl_members,
+            "
+trait Foo<'a, T> { fn foo(&self, bar: &'a T) -> &'a str; }
+struct S;
+impl<'x> Foo<'x, u32> for S { <|> }",
+            "
+trait Foo<'a, T> { fn foo(&self, bar: &'a T) -> &'a str; }
+struct S;
+impl<'x> Foo<'x, u32> for S {
+    <|>fn foo(&self, bar: &'x u32) -> &'x str { unimplemented!() }
+}",
+        );
+    }
+
     #[test]
     fn test_qualify_path_associated_item() {
         check_assist(
@@ -12,8 +12,10 @@ SUBCOMMANDS:
     analysis-bench
     analysis-stats
     highlight
+    library-cache
     parse
-    symbols";
+    symbols
+    unused";
 
 pub const ANALYSIS_BENCH_HELP: &str = "ra_cli-analysis-bench
 
@@ -73,3 +75,26 @@ USAGE:
 FLAGS:
     -h, --help       Prints help inforamtion
         --no-dump";
+
+pub const LIBRARY_CACHE_HELP: &str = "ra-cli-library-cache
+
+USAGE:
+    ra_cli library-cache [FLAGS] [OPTIONS]
+
+FLAGS:
+    -h, --help       Prints help information
+        --purge      Removes all cached libraries
+
+OPTIONS:
+    --dir <DIR>      Cache directory (defaults to $RA_CACHE_DIR or ~/.cache/rust-analyzer)";
+
+pub const UNUSED_HELP: &str = "ra-cli-unused
+
+USAGE:
+    ra_cli unused [FLAGS] [PATH]
+
+FLAGS:
+    -h, --help    Prints help information
+
+ARGS:
+    <PATH>    Project to analyse";
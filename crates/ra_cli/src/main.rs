@@ -4,11 +4,12 @@ mod analysis_stats;
 mod analysis_bench;
 mod help;
 mod progress_report;
+mod unused;
 
 use std::{error::Error, fmt::Write, io::Read};
 
 use pico_args::Arguments;
-use ra_ide::{file_structure, Analysis};
+use ra_ide::{file_structure, Analysis, LibraryCache};
 use ra_prof::profile;
 use ra_syntax::{AstNode, SourceFile};
 
@@ -143,6 +144,51 @@ fn main() -> Result<()> {
             matches.finish().or_else(handle_extra_flags)?;
             analysis_bench::run(verbose, path.as_ref(), op)?;
         }
+        "library-cache" => {
+            if matches.contains(["-h", "--help"]) {
+                eprintln!("{}", help::LIBRARY_CACHE_HELP);
+                return Ok(());
+            }
+            let purge = matches.contains("--purge");
+            let dir: Option<String> = matches.opt_value_from_str("--dir")?;
+            matches.finish().or_else(handle_extra_flags)?;
+            let dir = match dir {
+                Some(it) => it.into(),
+                None => LibraryCache::default_dir().ok_or("Can't determine cache directory")?,
+            };
+            let cache = LibraryCache::new(dir);
+            if purge {
+                let n_entries = cache.purge()?;
+                println!("removed {} libraries from {}", n_entries, cache.dir().display());
+            } else {
+                let entries = cache.entries()?;
+                let total_size: u64 = entries.iter().map(|it| it.size).sum();
+                for entry in entries.iter() {
+                    println!("{:<48} {:>8}kb", entry.name, entry.size / 1024);
+                }
+                println!(
+                    "{} libraries, {}kb in {}",
+                    entries.len(),
+                    total_size / 1024,
+                    cache.dir().display()
+                );
+            }
+        }
+        "unused" => {
+            if matches.contains(["-h", "--help"]) {
+                eprintln!("{}", help::UNUSED_HELP);
+                return Ok(());
+            }
+            let path = {
+                let mut trailing = matches.free()?;
+                if trailing.len() != 1 {
+                    eprintln!("{}", help::UNUSED_HELP);
+                    Err("Invalid flags")?;
+                }
+                trailing.pop().unwrap()
+            };
+            unused::run(path.as_ref())?;
+        }
         _ => eprintln!("{}", help::GLOBAL_HELP),
     }
     Ok(())
@@ -0,0 +1,48 @@
+//! Reports items and imports which are never used, in the format of compiler
+//! warnings.
+
+use std::path::Path;
+
+use ra_db::SourceDatabaseExt;
+
+use crate::Result;
+
+pub fn run(path: &Path) -> Result<()> {
+    let (host, roots) = ra_batch::load_cargo(path)?;
+    let db = host.raw_database();
+    let analysis = host.analysis();
+
+    let mut num_unused = 0;
+    for (source_root_id, project_root) in roots {
+        if !project_root.is_member() {
+            continue;
+        }
+        let mut files: Vec<_> = db
+            .source_root(source_root_id)
+            .walk()
+            .map(|file_id| (db.file_relative_path(file_id), file_id))
+            .collect();
+        files.sort();
+        for (relative_path, file_id) in files {
+            let unused = analysis.unused(file_id).unwrap();
+            if unused.is_empty() {
+                continue;
+            }
+            let path = relative_path.to_path(project_root.path());
+            let line_index = analysis.file_line_index(file_id).unwrap();
+            for d in unused {
+                let line_col = line_index.line_col(d.range.start());
+                println!(
+                    "{}:{}:{}: {}",
+                    path.display(),
+                    line_col.line + 1,
+                    line_col.col_utf16 + 1,
+                    d.message
+                );
+                num_unused += 1;
+            }
+        }
+    }
+    println!("{} unused items", num_unused);
+    Ok(())
+}
@@ -110,6 +110,9 @@ struct CrateData {
     cfg_options: CfgOptions,
     env: Env,
     dependencies: Vec<Dependency>,
+    /// How many tokens macro expansions can produce, see
+    /// `CrateGraph::macro_token_limit`.
+    macro_token_limit: Option<usize>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -148,6 +151,17 @@ impl CrateGraph {
         &self.arena[&crate_id].cfg_options
     }
 
+    pub fn set_macro_token_limit(&mut self, crate_id: CrateId, limit: usize) {
+        self.arena.get_mut(&crate_id).unwrap().macro_token_limit = Some(limit);
+    }
+
+    /// How many tokens all the macros expanded while collecting the items of
+    /// the crate, or while lowering a single body, can produce together. This
+    /// is set by the client, as there's no attribute for it in the language.
+    pub fn macro_token_limit(&self, crate_id: CrateId) -> Option<usize> {
+        self.arena[&crate_id].macro_token_limit
+    }
+
     pub fn add_dep(
         &mut self,
         from: CrateId,
@@ -234,7 +248,14 @@ impl CrateId {
 
 impl CrateData {
     fn new(file_id: FileId, edition: Edition, cfg_options: CfgOptions, env: Env) -> CrateData {
-        CrateData { file_id, edition, dependencies: Vec::new(), cfg_options, env }
+        CrateData {
+            file_id,
+            edition,
+            dependencies: Vec::new(),
+            cfg_options,
+            env,
+            macro_token_limit: None,
+        }
     }
 
     fn add_dep(&mut self, name: SmolStr, crate_id: CrateId) {
@@ -11,13 +11,14 @@ use hir_def::{
     resolver::HasResolver,
     type_ref::{Mutability, TypeRef},
     AdtId, ConstId, DefWithBodyId, EnumId, FunctionId, GenericDefId, HasModule, ImplId,
-    LocalEnumVariantId, LocalModuleId, LocalStructFieldId, Lookup, ModuleId, StaticId, StructId,
-    TraitId, TypeAliasId, TypeParamId, UnionId,
+    LifetimeParamId, LocalEnumVariantId, LocalModuleId, LocalStructFieldId, Lookup, ModuleId,
+    StaticId, StructId, TraitId, TypeAliasId, TypeParamId, UnionId,
 };
 use hir_expand::{
+    builtin_attr::BuiltinAttrExpander,
     diagnostics::DiagnosticSink,
     name::{name, AsName},
-    MacroDefId,
+    HirFileId, MacroDefId, MacroDefKind,
 };
 use hir_ty::{
     autoderef, display::HirFormatter, expr::ExprValidator, method_resolution, ApplicationTy,
@@ -25,7 +26,7 @@ use hir_ty::{
 };
 use ra_db::{CrateId, Edition, FileId};
 use ra_prof::profile;
-use ra_syntax::ast::{self, AttrsOwner};
+use ra_syntax::ast::{self, AttrsOwner, NameOwner};
 
 use crate::{
     db::{DefDatabase, HirDatabase},
@@ -197,6 +198,8 @@ impl Module {
         for decl in self.declarations(db) {
             match decl {
                 crate::ModuleDef::Function(f) => f.diagnostics(db, sink),
+                crate::ModuleDef::Const(c) => c.diagnostics(db, sink),
+                crate::ModuleDef::Static(s) => s.diagnostics(db, sink),
                 crate::ModuleDef::Module(m) => {
                     // Only add diagnostics from inline modules
                     if crate_def_map[m.id.local_id].origin.is_inline() {
@@ -208,9 +211,15 @@ impl Module {
         }
 
         for impl_block in self.impl_blocks(db) {
+            // The code generated by builtin derives is not the user's concern.
+            if impl_block.is_builtin_derive(db).is_some() {
+                continue;
+            }
             for item in impl_block.items(db) {
-                if let AssocItem::Function(f) = item {
-                    f.diagnostics(db, sink);
+                match item {
+                    AssocItem::Function(f) => f.diagnostics(db, sink),
+                    AssocItem::Const(c) => c.diagnostics(db, sink),
+                    AssocItem::TypeAlias(_) => (),
                 }
             }
         }
@@ -536,8 +545,16 @@ impl Function {
         db.function_data(self.id).params.clone()
     }
 
+    /// If this function was generated by a builtin derive, returns the
+    /// `#[derive]` attribute which produced it.
+    pub fn is_builtin_derive(self, db: &impl DefDatabase) -> Option<InFile<ast::Attr>> {
+        builtin_derive_attr(db, self.source(db).file_id)
+    }
+
     pub fn diagnostics(self, db: &impl HirDatabase, sink: &mut DiagnosticSink) {
         let _p = profile("Function::diagnostics");
+        let (_, source_map) = db.body_with_source_map(self.id.into());
+        source_map.add_diagnostics(db, sink);
         let infer = db.infer(self.id.into());
         infer.add_diagnostics(db, self.id, sink);
         let mut validator = ExprValidator::new(self.id, infer, sink);
@@ -562,6 +579,11 @@ impl Const {
     pub fn name(self, db: &impl HirDatabase) -> Option<Name> {
         db.const_data(self.id).name.clone()
     }
+
+    pub fn diagnostics(self, db: &impl HirDatabase, sink: &mut DiagnosticSink) {
+        let (_, source_map) = db.body_with_source_map(self.id.into());
+        source_map.add_diagnostics(db, sink);
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -577,6 +599,11 @@ impl Static {
     pub fn krate(self, db: &impl DefDatabase) -> Option<Crate> {
         Some(self.module(db).krate())
     }
+
+    pub fn diagnostics(self, db: &impl HirDatabase, sink: &mut DiagnosticSink) {
+        let (_, source_map) = db.body_with_source_map(self.id.into());
+        source_map.add_diagnostics(db, sink);
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -639,6 +666,23 @@ pub struct MacroDef {
     pub(crate) id: MacroDefId,
 }
 
+impl MacroDef {
+    pub fn name(self, db: &impl DefDatabase) -> Option<Name> {
+        match self.id.ast_id {
+            Some(ast_id) => ast_id.to_node(db).name().map(|it| it.as_name()),
+            None => self.builtin_attr().map(|it| it.name()),
+        }
+    }
+
+    /// Returns the builtin attribute, like `#[test]`, this macro stands for.
+    pub fn builtin_attr(self) -> Option<BuiltinAttrExpander> {
+        match self.id.kind {
+            MacroDefKind::BuiltInAttr(it) => Some(it),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum AssocItem {
     Function(Function),
@@ -693,6 +737,17 @@ impl GenericDef {
             .map(|(local_id, _)| TypeParam { id: TypeParamId { parent: self.into(), local_id } })
             .collect()
     }
+
+    pub fn lifetime_params(self, db: &impl HirDatabase) -> Vec<LifetimeParam> {
+        let generics = db.generic_params(self.into());
+        generics
+            .lifetimes
+            .iter()
+            .map(|(local_id, _)| LifetimeParam {
+                id: LifetimeParamId { parent: self.into(), local_id },
+            })
+            .collect()
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -769,6 +824,22 @@ impl TypeParam {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LifetimeParam {
+    pub(crate) id: LifetimeParamId,
+}
+
+impl LifetimeParam {
+    pub fn name(self, db: &impl HirDatabase) -> Name {
+        let params = db.generic_params(self.id.parent);
+        params.lifetimes[self.id.local_id].name.clone()
+    }
+
+    pub fn module(self, db: &impl HirDatabase) -> Module {
+        self.id.parent.module(db).into()
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ImplBlock {
     pub(crate) id: ImplId,
@@ -821,27 +892,32 @@ impl ImplBlock {
     }
 
     pub fn is_builtin_derive(&self, db: &impl DefDatabase) -> Option<InFile<ast::Attr>> {
-        let src = self.source(db);
-        let item = src.file_id.is_builtin_derive(db)?;
-        let hygenic = hir_expand::hygiene::Hygiene::new(db, item.file_id);
-
-        let attr = item
-            .value
-            .attrs()
-            .filter_map(|it| {
-                let path = hir_def::path::ModPath::from_src(it.path()?, &hygenic)?;
-                if path.as_ident()?.to_string() == "derive" {
-                    Some(it)
-                } else {
-                    None
-                }
-            })
-            .last()?;
-
-        Some(item.with_value(attr))
+        builtin_derive_attr(db, self.source(db).file_id)
     }
 }
 
+/// If `file_id` is the expansion of a builtin derive, returns the `#[derive]`
+/// attribute of the item it was derived for.
+fn builtin_derive_attr(db: &impl DefDatabase, file_id: HirFileId) -> Option<InFile<ast::Attr>> {
+    let item = file_id.is_builtin_derive(db)?;
+    let hygenic = hir_expand::hygiene::Hygiene::new(db, item.file_id);
+
+    let attr = item
+        .value
+        .attrs()
+        .filter_map(|it| {
+            let path = hir_def::path::ModPath::from_src(it.path()?, &hygenic)?;
+            if path.as_ident()?.to_string() == "derive" {
+                Some(it)
+            } else {
+                None
+            }
+        })
+        .last()?;
+
+    Some(item.with_value(attr))
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Type {
     pub(crate) krate: CrateId,
@@ -914,6 +990,13 @@ impl Type {
         )
     }
 
+    /// If this is an `impl Trait` returned by some function, returns the type
+    /// hidden behind it, as inferred from the function's body.
+    pub fn hidden_type(&self, db: &impl HirDatabase) -> Option<Type> {
+        let ty = self.ty.value.hidden_type(db)?;
+        Some(self.derived(ty))
+    }
+
     // FIXME: this method is broken, as it doesn't take closures into account.
     pub fn as_callable(&self) -> Option<CallableDef> {
         Some(self.ty.value.as_callable()?.0)
@@ -1089,14 +1172,15 @@ impl Type {
     // FIXME: provide required accessors such that it becomes implementable from outside.
     pub fn is_equal_for_find_impls(&self, other: &Type) -> bool {
         match (&self.ty.value, &other.ty.value) {
-            (Ty::Apply(a_original_ty), Ty::Apply(ApplicationTy { ctor, parameters })) => match ctor
-            {
-                TypeCtor::Ref(..) => match parameters.as_single() {
-                    Ty::Apply(a_ty) => a_original_ty.ctor == a_ty.ctor,
-                    _ => false,
-                },
-                _ => a_original_ty.ctor == *ctor,
-            },
+            (Ty::Apply(a_original_ty), Ty::Apply(ApplicationTy { ctor, parameters, .. })) => {
+                match ctor {
+                    TypeCtor::Ref(..) => match parameters.as_single() {
+                        Ty::Apply(a_ty) => a_original_ty.ctor == a_ty.ctor,
+                        _ => false,
+                    },
+                    _ => a_original_ty.ctor == *ctor,
+                }
+            }
             _ => false,
         }
     }
@@ -1,4 +1,9 @@
 //! FIXME: write short doc here
-pub use hir_def::diagnostics::UnresolvedModule;
-pub use hir_expand::diagnostics::{AstDiagnostic, Diagnostic, DiagnosticSink};
-pub use hir_ty::diagnostics::{MissingFields, MissingOkInTailExpr, NoSuchField};
+pub use hir_def::diagnostics::{
+    ExpansionLimitKind, ExpansionLimitReached, UnresolvedImport, UnresolvedModule,
+};
+pub use hir_expand::diagnostics::{AstDiagnostic, Diagnostic, DiagnosticSink, MacroError};
+pub use hir_ty::diagnostics::{
+    BreakWithValueOutsideLoop, MissingFields, MissingOkInTailExpr, NoSuchField, NoSuchMethod,
+    OverflowingLiteral, TryInNonTryFunction, UnresolvedPath, UnsatisfiedTraitBound,
+};
@@ -103,8 +103,8 @@ impl HasSource for TypeAlias {
     }
 }
 impl HasSource for MacroDef {
-    type Ast = ast::MacroCall;
-    fn source(self, db: &impl DefDatabase) -> InFile<ast::MacroCall> {
+    type Ast = ast::Macro;
+    fn source(self, db: &impl DefDatabase) -> InFile<ast::Macro> {
         InFile {
             file_id: self.id.ast_id.expect("MacroDef without ast_id").file_id,
             value: self.id.ast_id.expect("MacroDef without ast_id").to_node(db),
@@ -119,7 +119,7 @@ impl HasSource for ImplBlock {
 }
 
 impl HasSource for TypeParam {
-    type Ast = Either<ast::TraitDef, ast::TypeParam>;
+    type Ast = Either<ast::TraitDef, Either<ast::TypeParam, ast::ConstParam>>;
     fn source(self, db: &impl DefDatabase) -> InFile<Self::Ast> {
         let child_source = self.id.parent.child_source(db);
         child_source.map(|it| it[self.id.local_id].clone())
@@ -40,12 +40,15 @@ mod has_source;
 pub use crate::{
     code_model::{
         Adt, AssocItem, AttrDef, Const, Crate, CrateDependency, DefWithBody, Docs, Enum,
-        EnumVariant, FieldSource, Function, GenericDef, HasAttrs, HasVisibility, ImplBlock, Local,
-        MacroDef, Module, ModuleDef, ScopeDef, Static, Struct, StructField, Trait, Type, TypeAlias,
-        TypeParam, Union, VariantDef,
+        EnumVariant, FieldSource, Function, GenericDef, HasAttrs, HasVisibility, ImplBlock,
+        LifetimeParam, Local, MacroDef, Module, ModuleDef, ScopeDef, Static, Struct, StructField,
+        Trait, Type, TypeAlias, TypeParam, Union, VariantDef,
     },
     has_source::HasSource,
-    source_analyzer::{PathResolution, ScopeEntryWithSyntax, SourceAnalyzer},
+    source_analyzer::{
+        CandidateStatus, Expansion, MethodResolutionStep, MethodResolutionTrace, PathResolution,
+        ScopeEntryWithSyntax, SourceAnalyzer,
+    },
     source_binder::SourceBinder,
 };
 
@@ -58,6 +61,9 @@ pub use hir_def::{
     type_ref::Mutability,
 };
 pub use hir_expand::{
-    name::Name, HirFileId, InFile, MacroCallId, MacroCallLoc, MacroDefId, MacroFile, Origin,
+    builtin_attr::BuiltinAttrExpander,
+    db::{ExpansionStep, MetaVarBinding, MetaVarFragments},
+    name::Name,
+    ExpansionInfo, HirFileId, InFile, MacroCallId, MacroCallLoc, MacroDefId, MacroFile, Origin,
 };
-pub use hir_ty::{display::HirDisplay, CallableDef};
+pub use hir_ty::{display::HirDisplay, CallableDef, TraitRef};
@@ -11,16 +11,20 @@ use either::Either;
 use hir_def::{
     body::{
         scope::{ExprScopes, ScopeId},
-        BodySourceMap,
+        Body, BodySourceMap,
     },
-    expr::{ExprId, PatId},
-    resolver::{self, resolver_for_scope, Resolver, TypeNs, ValueNs},
+    expr::{Expr, ExprId, PatId},
+    resolver::{self, resolver_for_expr, resolver_for_scope, Resolver, TypeNs, ValueNs},
+    type_ref::TypeRef,
     DefWithBodyId, TraitId,
 };
 use hir_expand::{
-    hygiene::Hygiene, name::AsName, AstId, HirFileId, InFile, MacroCallId, MacroCallKind,
+    builtin_derive::find_builtin_derive, hygiene::Hygiene, name::AsName, AstId, HirFileId, InFile,
+    MacroCallId, MacroCallKind,
+};
+use hir_ty::{
+    method_resolution, Canonical, InEnvironment, InferenceResult, TraitEnvironment, TraitRef,
 };
-use hir_ty::{InEnvironment, InferenceResult, TraitEnvironment};
 use ra_syntax::{
     ast::{self, AstNode},
     AstPtr, SyntaxNode, SyntaxNodePtr, SyntaxToken, TextRange, TextUnit,
@@ -28,8 +32,9 @@ use ra_syntax::{
 use rustc_hash::FxHashSet;
 
 use crate::{
-    db::HirDatabase, Adt, Const, DefWithBody, EnumVariant, Function, Local, MacroDef, Name, Path,
-    ScopeDef, Static, Struct, Trait, Type, TypeAlias, TypeParam,
+    db::HirDatabase, Adt, Const, DefWithBody, EnumVariant, ExpansionStep, Function, ImplBlock,
+    Local, MacroDef, Mutability, Name, Path, ScopeDef, Static, Struct, Trait, Type, TypeAlias,
+    TypeParam,
 };
 
 /// `SourceAnalyzer` is a convenience wrapper which exposes HIR API in terms of
@@ -39,6 +44,7 @@ pub struct SourceAnalyzer {
     file_id: HirFileId,
     resolver: Resolver,
     body_owner: Option<DefWithBody>,
+    body: Option<Arc<Body>>,
     body_source_map: Option<Arc<BodySourceMap>>,
     infer: Option<Arc<InferenceResult>>,
     scopes: Option<Arc<ExprScopes>>,
@@ -79,6 +85,60 @@ pub struct ReferenceDescriptor {
     pub name: String,
 }
 
+/// How the method of a method call was looked up; see
+/// `SourceAnalyzer::explain_method_call`.
+#[derive(Debug)]
+pub struct MethodResolutionTrace {
+    /// The receiver types that were tried, in order.
+    pub steps: Vec<MethodResolutionStep>,
+}
+
+#[derive(Debug)]
+pub struct MethodResolutionStep {
+    /// The receiver type, with autoderef and autoref applied.
+    pub receiver_ty: Type,
+    pub autoderefs: usize,
+    pub autoref: Option<Mutability>,
+    /// The methods with the right name that were considered at this step.
+    pub candidates: Vec<(Function, CandidateStatus)>,
+}
+
+/// Why a method was or wasn't picked at a step of a method lookup.
+#[derive(Debug)]
+pub enum CandidateStatus {
+    /// This is the method which gets called.
+    Picked,
+    /// The method has no `self` parameter, or one which doesn't match the
+    /// receiver type.
+    ReceiverMismatch,
+    /// The method is from a trait which isn't implemented for the type.
+    TraitNotImplemented {
+        /// The bound which doesn't hold, like `S: Clone`: either the trait
+        /// bound itself, or a where clause of the impl matching the type.
+        unsatisfied_bound: TraitRef,
+        /// The impls of the trait whose self type matches the type.
+        impls: Vec<ImplBlock>,
+    },
+}
+
+impl From<method_resolution::CandidateStatus> for CandidateStatus {
+    fn from(status: method_resolution::CandidateStatus) -> Self {
+        match status {
+            method_resolution::CandidateStatus::Picked => CandidateStatus::Picked,
+            method_resolution::CandidateStatus::ReceiverMismatch => {
+                CandidateStatus::ReceiverMismatch
+            }
+            method_resolution::CandidateStatus::TraitNotImplemented {
+                unsatisfied_bound,
+                impls,
+            } => CandidateStatus::TraitNotImplemented {
+                unsatisfied_bound,
+                impls: impls.into_iter().map(|id| ImplBlock { id }).collect(),
+            },
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Expansion {
     macro_call_id: MacroCallId,
@@ -97,6 +157,23 @@ impl Expansion {
     pub fn file_id(&self) -> HirFileId {
         self.macro_call_id.as_file()
     }
+
+    /// Expands this macro call with `hypothetical_args` instead of its actual
+    /// arguments, returning the expansion together with `token_to_map` mapped
+    /// into it.
+    pub fn expand_hypothetical(
+        &self,
+        db: &impl HirDatabase,
+        hypothetical_args: &ast::TokenTree,
+        token_to_map: SyntaxToken,
+    ) -> Option<(SyntaxNode, SyntaxToken)> {
+        hir_expand::db::expand_hypothetical(db, self.macro_call_id, hypothetical_args, token_to_map)
+    }
+
+    /// Describes which `macro_rules!` arm this call matched, and how.
+    pub fn trace(&self, db: &impl HirDatabase) -> Option<ExpansionStep> {
+        hir_expand::db::trace_expansion(db, self.macro_call_id)
+    }
 }
 
 impl SourceAnalyzer {
@@ -114,16 +191,24 @@ impl SourceAnalyzer {
         node: InFile<&SyntaxNode>,
         offset: Option<TextUnit>,
     ) -> SourceAnalyzer {
-        let (_body, source_map) = db.body_with_source_map(def);
+        let (body, source_map) = db.body_with_source_map(def);
         let scopes = db.expr_scopes(def);
-        let scope = match offset {
-            None => scope_for(&scopes, &source_map, node),
-            Some(offset) => scope_for_offset(&scopes, &source_map, node.with_value(offset)),
+        let resolver = match offset {
+            // Resolve names with the hygiene of the enclosing expression, which
+            // matters for nodes inside macro expansions.
+            None => match expr_for(&source_map, node) {
+                Some(expr) => resolver_for_expr(db, def, expr),
+                None => resolver_for_scope(db, def, None),
+            },
+            Some(offset) => {
+                let scope = scope_for_offset(&scopes, &source_map, node.with_value(offset));
+                resolver_for_scope(db, def, scope)
+            }
         };
-        let resolver = resolver_for_scope(db, def, scope);
         SourceAnalyzer {
             resolver,
             body_owner: Some(def.into()),
+            body: Some(body),
             body_source_map: Some(source_map),
             infer: Some(db.infer(def)),
             scopes: Some(scopes),
@@ -138,6 +223,7 @@ impl SourceAnalyzer {
         SourceAnalyzer {
             resolver,
             body_owner: None,
+            body: None,
             body_source_map: None,
             infer: None,
             scopes: None,
@@ -206,6 +292,105 @@ impl SourceAnalyzer {
         self.infer.as_ref()?.method_resolution(expr_id).map(Function::from)
     }
 
+    /// Like `resolve_method_call`, but if the call resolves to a trait method,
+    /// returns the method of the impl that is called instead.
+    pub fn resolve_method_call_impl(
+        &self,
+        db: &impl HirDatabase,
+        call: &ast::MethodCallExpr,
+    ) -> Option<Function> {
+        let expr_id = self.expr_id(&call.clone().into())?;
+        let infer = self.infer.as_ref()?;
+        let trait_method = infer.method_resolution(expr_id)?;
+        let receiver = self.expr_id(&call.expr()?)?;
+        let krate = self.resolver.krate()?;
+        let mut self_ty = infer[receiver].clone();
+        // For `&self` methods, a reference receiver is taken by value, so
+        // `Self` is the referenced type.
+        let takes_ref = match db.function_data(trait_method).params.first() {
+            Some(TypeRef::Reference(..)) => true,
+            _ => false,
+        };
+        if takes_ref {
+            if let Some((inner, _)) = self_ty.as_reference() {
+                self_ty = inner.clone();
+            }
+        }
+        loop {
+            let params = [self_ty.clone()];
+            if let Some(func) =
+                method_resolution::lookup_impl_method(db, krate, trait_method, &params)
+            {
+                return Some(func.into());
+            }
+            self_ty = self_ty.as_reference()?.0.clone();
+        }
+    }
+
+    /// Looks up the method of the method call again, recording each autoderef
+    /// and autoref step and the candidates considered along the way.
+    pub fn explain_method_call(
+        &self,
+        db: &impl HirDatabase,
+        call: &ast::MethodCallExpr,
+    ) -> Option<MethodResolutionTrace> {
+        let receiver = self.expr_id(&call.expr()?)?;
+        let receiver_ty = self.infer.as_ref()?[receiver].clone();
+        let name = call.name_ref()?.as_name();
+        let krate = self.resolver.krate()?;
+        let environment = self.trait_env(db);
+        let trace = method_resolution::trace_method_resolution(
+            &Canonical { value: receiver_ty, num_vars: 0 },
+            db,
+            environment.clone(),
+            krate,
+            &self.resolver.traits_in_scope(db),
+            &name,
+        );
+        let steps = trace
+            .steps
+            .into_iter()
+            .map(|step| MethodResolutionStep {
+                receiver_ty: Type {
+                    krate,
+                    ty: InEnvironment {
+                        value: step.receiver_ty.value,
+                        environment: environment.clone(),
+                    },
+                },
+                autoderefs: step.autoderefs,
+                autoref: step.autoref,
+                candidates: step
+                    .candidates
+                    .into_iter()
+                    .map(|candidate| (candidate.func.into(), candidate.status.into()))
+                    .collect(),
+            })
+            .collect();
+        Some(MethodResolutionTrace { steps })
+    }
+
+    /// Resolves an overloaded binary operator to the impl method it calls, or
+    /// to the operator trait's method if no impl can be selected.
+    pub fn resolve_bin_expr(
+        &self,
+        db: &impl HirDatabase,
+        bin_expr: &ast::BinExpr,
+    ) -> Option<Function> {
+        let expr_id = self.expr_id(&bin_expr.clone().into())?;
+        let infer = self.infer.as_ref()?;
+        let trait_method = infer.method_resolution(expr_id)?;
+        let (lhs, rhs) = match &self.body.as_ref()?[expr_id] {
+            Expr::BinaryOp { lhs, rhs, .. } => (*lhs, *rhs),
+            _ => return None,
+        };
+        let krate = self.resolver.krate()?;
+        let params = [infer[lhs].clone(), infer[rhs].clone()];
+        let func = method_resolution::lookup_impl_method(db, krate, trait_method, &params)
+            .unwrap_or(trait_method);
+        Some(func.into())
+    }
+
     pub fn resolve_field(&self, field: &ast::FieldExpr) -> Option<crate::StructField> {
         let expr_id = self.expr_id(&field.clone().into())?;
         self.infer.as_ref()?.field_resolution(expr_id).map(|it| it.into())
@@ -242,6 +427,16 @@ impl SourceAnalyzer {
         self.resolver.resolve_path_as_macro(db, path.mod_path()).map(|it| it.into())
     }
 
+    pub fn resolve_attr(
+        &self,
+        db: &impl HirDatabase,
+        attr: InFile<&ast::Attr>,
+    ) -> Option<MacroDef> {
+        let hygiene = Hygiene::new(db, attr.file_id);
+        let path = attr.value.path().and_then(|ast| Path::from_src(ast, &hygiene))?;
+        self.resolver.resolve_path_as_attr(db, path.mod_path()).map(|it| it.into())
+    }
+
     pub fn resolve_hir_path(
         &self,
         db: &impl HirDatabase,
@@ -271,6 +466,7 @@ impl SourceAnalyzer {
                     ValueNs::StaticId(it) => PathResolution::Def(Static::from(it).into()),
                     ValueNs::StructId(it) => PathResolution::Def(Struct::from(it).into()),
                     ValueNs::EnumVariantId(it) => PathResolution::Def(EnumVariant::from(it).into()),
+                    ValueNs::ConstParam(id) => PathResolution::TypeParam(TypeParam { id }),
                 };
                 Some(res)
             });
@@ -305,12 +501,30 @@ impl SourceAnalyzer {
         self.resolve_hir_path(db, &hir_path)
     }
 
+    /// For a path to a trait's associated const, returns the const of the impl
+    /// that is used, if it is known.
+    pub fn resolve_path_to_impl_const(&self, path: &ast::Path) -> Option<Const> {
+        let infer = self.infer.as_ref()?;
+        if let Some(path_expr) = path.syntax().parent().and_then(ast::PathExpr::cast) {
+            let expr_id = self.expr_id(&path_expr.into())?;
+            return infer.impl_const_resolution_for_expr(expr_id).map(Const::from);
+        }
+        if let Some(path_pat) = path.syntax().parent().and_then(ast::PathPat::cast) {
+            let pat_id = self.pat_id(&path_pat.into())?;
+            return infer.impl_const_resolution_for_pat(pat_id).map(Const::from);
+        }
+        None
+    }
+
     fn resolve_local_name(&self, name_ref: &ast::NameRef) -> Option<ScopeEntryWithSyntax> {
         let name = name_ref.as_name();
         let source_map = self.body_source_map.as_ref()?;
         let scopes = self.scopes.as_ref()?;
-        let scope = scope_for(scopes, source_map, InFile::new(self.file_id, name_ref.syntax()))?;
-        let entry = scopes.resolve_name_in_scope(scope, &name)?;
+        let node = InFile::new(self.file_id, name_ref.syntax());
+        let expr = expr_for(source_map, node)?;
+        let scope = scopes.scope_for(expr)?;
+        let hygiene = self.body.as_ref()?.expr_hygiene(expr);
+        let entry = scopes.resolve_name_in_scope(scope, &name, hygiene)?;
         Some(ScopeEntryWithSyntax {
             name: entry.name().clone(),
             ptr: source_map.pat_syntax(entry.pat())?.value,
@@ -370,18 +584,26 @@ impl SourceAnalyzer {
         );
         Some(Expansion { macro_call_id: def.as_call_id(db, MacroCallKind::FnLike(ast_id)) })
     }
+
+    /// Expands the builtin derive named by `derive`, an identifier in one of
+    /// the `#[derive]` attributes of `item`.
+    pub fn expand_derive(
+        &self,
+        db: &impl HirDatabase,
+        item: InFile<&ast::ModuleItem>,
+        derive: &SyntaxToken,
+    ) -> Option<Expansion> {
+        let def = find_builtin_derive(&Name::new_ident(derive))?;
+        let ast_id = AstId::new(item.file_id, db.ast_id_map(item.file_id).ast_id(item.value));
+        Some(Expansion { macro_call_id: def.as_call_id(db, MacroCallKind::Attr(ast_id)) })
+    }
 }
 
-fn scope_for(
-    scopes: &ExprScopes,
-    source_map: &BodySourceMap,
-    node: InFile<&SyntaxNode>,
-) -> Option<ScopeId> {
+fn expr_for(source_map: &BodySourceMap, node: InFile<&SyntaxNode>) -> Option<ExprId> {
     node.value
         .ancestors()
         .filter_map(ast::Expr::cast)
-        .filter_map(|it| source_map.node_expr(InFile::new(node.file_id, &it)))
-        .find_map(|it| scopes.scope_for(it))
+        .find_map(|it| source_map.node_expr(InFile::new(node.file_id, &it)))
 }
 
 fn scope_for_offset(
@@ -176,6 +176,7 @@ to_def_impls![
     (crate::StructField, ast::RecordFieldDef),
     (crate::EnumVariant, ast::EnumVariant),
     (crate::MacroDef, ast::MacroCall), // this one is dubious, not all calls are macros
+    (crate::MacroDef, ast::MacroDef),
 ];
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
@@ -262,10 +263,28 @@ impl ToId for ast::MacroCall {
 
         let krate = sb.to_module_def(src.file_id.original_file(sb.db))?.id.krate;
 
-        let ast_id =
-            Some(AstId::new(src.file_id, sb.db.ast_id_map(src.file_id).ast_id(&src.value)));
+        let ast_id = sb.db.ast_id_map(src.file_id).ast_id(&src.value);
+        let local_inner = sb.db.raw_items(src.file_id).is_local_inner_macro(ast_id);
+        let ast_id = Some(AstId::new(src.file_id, ast_id.upcast()));
 
-        Some(MacroDefId { krate: Some(krate), ast_id, kind })
+        Some(MacroDefId { krate: Some(krate), ast_id, kind, local_inner })
+    }
+}
+
+impl ToId for ast::MacroDef {
+    type ID = MacroDefId;
+    fn to_id<DB: HirDatabase>(
+        sb: &mut SourceBinder<'_, DB>,
+        src: InFile<Self>,
+    ) -> Option<Self::ID> {
+        let kind = MacroDefKind::Declarative;
+
+        let krate = sb.to_module_def(src.file_id.original_file(sb.db))?.id.krate;
+
+        let ast_id = sb.db.ast_id_map(src.file_id).ast_id(&src.value).upcast();
+        let ast_id = Some(AstId::new(src.file_id, ast_id));
+
+        Some(MacroDefId { krate: Some(krate), ast_id, kind, local_inner: false })
     }
 }
 
@@ -299,27 +318,50 @@ impl ToDef for ast::TypeParam {
         sb: &mut SourceBinder<'_, DB>,
         src: InFile<ast::TypeParam>,
     ) -> Option<TypeParam> {
+        let parent = generic_param_parent(sb.db, src.as_ref().map(|it| it.syntax()))?;
         let mut sb = SourceBinder::new(sb.db);
-        let file_id = src.file_id;
-        let parent: GenericDefId = src.value.syntax().ancestors().find_map(|it| {
-            let res = match_ast! {
-                match it {
-                    ast::FnDef(value) => { sb.to_id(InFile { value, file_id})?.into() },
-                    ast::StructDef(value) => { sb.to_id(InFile { value, file_id})?.into() },
-                    ast::EnumDef(value) => { sb.to_id(InFile { value, file_id})?.into() },
-                    ast::TraitDef(value) => { sb.to_id(InFile { value, file_id})?.into() },
-                    ast::TypeAliasDef(value) => { sb.to_id(InFile { value, file_id})?.into() },
-                    ast::ImplBlock(value) => { sb.to_id(InFile { value, file_id})?.into() },
-                    _ => return None,
-                }
-            };
-            Some(res)
-        })?;
         let &id = sb.child_by_source(parent.into())[keys::TYPE_PARAM].get(&src)?;
         Some(TypeParam { id })
     }
 }
 
+impl ToDef for ast::ConstParam {
+    type Def = TypeParam;
+
+    fn to_def<DB: HirDatabase>(
+        sb: &mut SourceBinder<'_, DB>,
+        src: InFile<ast::ConstParam>,
+    ) -> Option<TypeParam> {
+        let parent = generic_param_parent(sb.db, src.as_ref().map(|it| it.syntax()))?;
+        let mut sb = SourceBinder::new(sb.db);
+        let &id = sb.child_by_source(parent.into())[keys::CONST_PARAM].get(&src)?;
+        Some(TypeParam { id })
+    }
+}
+
+/// Finds the item which declares the generic param `src`.
+fn generic_param_parent<DB: HirDatabase>(
+    db: &DB,
+    src: InFile<&SyntaxNode>,
+) -> Option<GenericDefId> {
+    let mut sb = SourceBinder::new(db);
+    let file_id = src.file_id;
+    src.value.ancestors().find_map(|it| {
+        let res = match_ast! {
+            match it {
+                ast::FnDef(value) => { sb.to_id(InFile { value, file_id})?.into() },
+                ast::StructDef(value) => { sb.to_id(InFile { value, file_id})?.into() },
+                ast::EnumDef(value) => { sb.to_id(InFile { value, file_id})?.into() },
+                ast::TraitDef(value) => { sb.to_id(InFile { value, file_id})?.into() },
+                ast::TypeAliasDef(value) => { sb.to_id(InFile { value, file_id})?.into() },
+                ast::ImplBlock(value) => { sb.to_id(InFile { value, file_id})?.into() },
+                _ => return None,
+            }
+        };
+        Some(res)
+    })
+}
+
 impl ToId for ast::Module {
     type ID = ModuleId;
 
@@ -8,19 +8,23 @@ use std::{mem, ops::Index, sync::Arc};
 use drop_bomb::DropBomb;
 use either::Either;
 use hir_expand::{
-    ast_id_map::AstIdMap, hygiene::Hygiene, AstId, HirFileId, InFile, MacroCallKind, MacroDefId,
+    ast_id_map::AstIdMap,
+    diagnostics::{DiagnosticSink, MacroError},
+    hygiene::{Hygiene, SyntaxContext},
+    AstId, HirFileId, InFile, MacroCallId, MacroCallKind, MacroDefId,
 };
 use ra_arena::{map::ArenaMap, Arena};
 use ra_prof::profile;
-use ra_syntax::{ast, AstNode, AstPtr};
+use ra_syntax::{ast, AstNode, AstPtr, SyntaxToken};
 use rustc_hash::FxHashMap;
 
 use crate::{
     db::DefDatabase,
+    diagnostics::{ExpansionLimitKind, ExpansionLimitReached},
     expr::{Expr, ExprId, Pat, PatId},
     item_scope::BuiltinShadowMode,
     item_scope::ItemScope,
-    nameres::CrateDefMap,
+    nameres::{CrateDefMap, ExpansionLimits},
     path::{ModPath, Path},
     src::HasSource,
     DefWithBodyId, HasModule, Lookup, ModuleId,
@@ -32,6 +36,9 @@ pub(crate) struct Expander {
     hygiene: Hygiene,
     ast_id_map: Arc<AstIdMap>,
     module: ModuleId,
+    limits: ExpansionLimits,
+    /// How many tokens the macros expanded in this body produced so far.
+    expanded_tokens: usize,
 }
 
 impl Expander {
@@ -43,7 +50,16 @@ impl Expander {
         let crate_def_map = db.crate_def_map(module.krate);
         let hygiene = Hygiene::new(db, current_file_id);
         let ast_id_map = db.ast_id_map(current_file_id);
-        Expander { crate_def_map, current_file_id, hygiene, ast_id_map, module }
+        let limits = db.expansion_limits(module.krate);
+        Expander {
+            crate_def_map,
+            current_file_id,
+            hygiene,
+            ast_id_map,
+            module,
+            limits,
+            expanded_tokens: 0,
+        }
     }
 
     pub(crate) fn enter_expand<T: ast::AstNode, DB: DefDatabase>(
@@ -51,36 +67,58 @@ impl Expander {
         db: &DB,
         macro_call: ast::MacroCall,
     ) -> Option<(Mark, T)> {
+        let call_id = self.resolve_macro_call(db, &macro_call)?;
+        if self.exceeded_limit(db, call_id).is_some() {
+            return None;
+        }
+        self.expanded_tokens += expansion_token_count(db, call_id);
+        let file_id = call_id.as_file();
+        let node = db.parse_or_expand(file_id)?;
+        let expr = T::cast(node)?;
+        log::debug!("macro expansion {:#?}", expr.syntax());
+
+        let mark = Mark {
+            file_id: self.current_file_id,
+            ast_id_map: mem::take(&mut self.ast_id_map),
+            bomb: DropBomb::new("expansion mark dropped"),
+        };
+        self.hygiene = Hygiene::new(db, file_id);
+        self.current_file_id = file_id;
+        self.ast_id_map = db.ast_id_map(file_id);
+
+        Some((mark, expr))
+    }
+
+    /// Resolves the macro of `macro_call`, which must belong to the current
+    /// file, and interns the call.
+    pub(crate) fn resolve_macro_call(
+        &mut self,
+        db: &impl DefDatabase,
+        macro_call: &ast::MacroCall,
+    ) -> Option<MacroCallId> {
         let ast_id = AstId::new(
             self.current_file_id,
-            db.ast_id_map(self.current_file_id).ast_id(&macro_call),
+            db.ast_id_map(self.current_file_id).ast_id(macro_call),
         );
+        let path = self.parse_mod_path(macro_call.path()?)?;
+        let def = self.resolve_path_as_macro(db, &path)?;
+        Some(def.as_call_id(db, MacroCallKind::FnLike(ast_id)))
+    }
 
-        if let Some(path) = macro_call.path().and_then(|path| self.parse_mod_path(path)) {
-            if let Some(def) = self.resolve_path_as_macro(db, &path) {
-                let call_id = def.as_call_id(db, MacroCallKind::FnLike(ast_id));
-                let file_id = call_id.as_file();
-                if let Some(node) = db.parse_or_expand(file_id) {
-                    if let Some(expr) = T::cast(node) {
-                        log::debug!("macro expansion {:#?}", expr.syntax());
-
-                        let mark = Mark {
-                            file_id: self.current_file_id,
-                            ast_id_map: mem::take(&mut self.ast_id_map),
-                            bomb: DropBomb::new("expansion mark dropped"),
-                        };
-                        self.hygiene = Hygiene::new(db, file_id);
-                        self.current_file_id = file_id;
-                        self.ast_id_map = db.ast_id_map(file_id);
-
-                        return Some((mark, expr));
-                    }
-                }
-            }
+    /// Returns the limit expanding `call_id` would exceed: nesting deeper than
+    /// the crate's `#![recursion_limit]` allows, or producing more tokens in
+    /// this body than the crate's token limit allows.
+    pub(crate) fn exceeded_limit(
+        &self,
+        db: &impl DefDatabase,
+        call_id: MacroCallId,
+    ) -> Option<ExpansionLimitKind> {
+        if call_id.as_file().expansion_level(db) > self.limits.depth {
+            return Some(ExpansionLimitKind::Depth(self.limits.depth));
+        }
+        if self.expanded_tokens + expansion_token_count(db, call_id) > self.limits.token_count {
+            return Some(ExpansionLimitKind::TokenCount(self.limits.token_count));
         }
-
-        // FIXME: Instead of just dropping the error from expansion
-        // report it
         None
     }
 
@@ -99,6 +137,10 @@ impl Expander {
         Path::from_src(path, &self.hygiene)
     }
 
+    fn syntax_context(&self, db: &impl DefDatabase, token: &SyntaxToken) -> SyntaxContext {
+        self.hygiene.syntax_context(db, token)
+    }
+
     fn parse_mod_path(&mut self, path: ast::Path) -> Option<ModPath> {
         ModPath::from_src(path, &self.hygiene)
     }
@@ -116,6 +158,10 @@ impl Expander {
     }
 }
 
+fn expansion_token_count(db: &impl DefDatabase, call_id: MacroCallId) -> usize {
+    db.macro_expand(call_id).0.map_or(0, |it| it.count())
+}
+
 pub(crate) struct Mark {
     file_id: HirFileId,
     ast_id_map: Arc<AstIdMap>,
@@ -137,6 +183,10 @@ pub struct Body {
     /// The `ExprId` of the actual body expression.
     pub body_expr: ExprId,
     pub item_scope: ItemScope,
+    /// Syntax contexts of bindings, paths and labels which come from
+    /// `macro_rules!` definitions. Everything else has the root context.
+    pat_hygiene: FxHashMap<PatId, SyntaxContext>,
+    expr_hygiene: FxHashMap<ExprId, SyntaxContext>,
 }
 
 pub type ExprPtr = Either<AstPtr<ast::Expr>, AstPtr<ast::RecordField>>;
@@ -164,6 +214,9 @@ pub struct BodySourceMap {
     pat_map_back: ArenaMap<PatId, PatSource>,
     field_map: FxHashMap<(ExprId, usize), AstPtr<ast::RecordField>>,
     expansions: FxHashMap<InFile<AstPtr<ast::MacroCall>>, HirFileId>,
+    /// Macro calls in the body whose expansion produced an error.
+    failed_expansions: Vec<MacroCallId>,
+    limited_expansions: Vec<(MacroCallId, ExpansionLimitKind)>,
 }
 
 impl Body {
@@ -201,6 +254,16 @@ impl Body {
         db.body_with_source_map(def).0
     }
 
+    /// The syntax context of a binding.
+    pub fn pat_hygiene(&self, pat: PatId) -> SyntaxContext {
+        self.pat_hygiene.get(&pat).copied().unwrap_or_default()
+    }
+
+    /// The syntax context of a path, loop label, or `break`/`continue` label.
+    pub fn expr_hygiene(&self, expr: ExprId) -> SyntaxContext {
+        self.expr_hygiene.get(&expr).copied().unwrap_or_default()
+    }
+
     fn new(
         db: &impl DefDatabase,
         def: DefWithBodyId,
@@ -257,6 +320,17 @@ impl BodySourceMap {
         self.pat_map.get(&src).cloned()
     }
 
+    pub fn add_diagnostics(&self, db: &impl DefDatabase, sink: &mut DiagnosticSink) {
+        for &call_id in self.failed_expansions.iter() {
+            if let Some(err) = MacroError::from_macro_call(db, call_id) {
+                sink.push(err);
+            }
+        }
+        for &(call_id, kind) in self.limited_expansions.iter() {
+            sink.push(ExpansionLimitReached::new(db, call_id, kind));
+        }
+    }
+
     pub fn field_syntax(&self, expr: ExprId, field: usize) -> AstPtr<ast::RecordField> {
         self.field_map[&(expr, field)]
     }
@@ -3,14 +3,17 @@
 
 use either::Either;
 
-use hir_expand::name::{name, AsName, Name};
+use hir_expand::{
+    db::expansion_error,
+    name::{name, AsName, Name},
+};
 use ra_arena::Arena;
 use ra_syntax::{
     ast::{
         self, ArgListOwner, ArrayExprKind, LiteralKind, LoopBodyOwner, ModuleItemOwner, NameOwner,
         TypeAscriptionOwner,
     },
-    AstNode, AstPtr,
+    AstNode, AstPtr, SyntaxToken,
 };
 use test_utils::tested_by;
 
@@ -47,6 +50,8 @@ pub(super) fn lower(
             params: Vec::new(),
             body_expr: ExprId::dummy(),
             item_scope: Default::default(),
+            pat_hygiene: Default::default(),
+            expr_hygiene: Default::default(),
         },
     }
     .collect(params, body)
@@ -81,6 +86,7 @@ where
                     },
                     Either::Right(ptr),
                 );
+                self.record_pat_hygiene(param_pat, Some(self_param.self_kw_token()));
                 self.body.params.push(param_pat);
             }
 
@@ -127,8 +133,28 @@ where
         id
     }
 
+    /// Records the syntax context of the identifier or label `token` for an
+    /// expression, unless it is the root one.
+    fn record_expr_hygiene(&mut self, expr: ExprId, token: Option<SyntaxToken>) {
+        if let Some(token) = token {
+            let ctx = self.expander.syntax_context(self.db, &token);
+            if !ctx.is_root() {
+                self.body.expr_hygiene.insert(expr, ctx);
+            }
+        }
+    }
+
+    fn record_pat_hygiene(&mut self, pat: PatId, token: Option<SyntaxToken>) {
+        if let Some(token) = token {
+            let ctx = self.expander.syntax_context(self.db, &token);
+            if !ctx.is_root() {
+                self.body.pat_hygiene.insert(pat, ctx);
+            }
+        }
+    }
+
     fn empty_block(&mut self) -> ExprId {
-        let block = Expr::Block { statements: Vec::new(), tail: None };
+        let block = Expr::Block { statements: Vec::new(), tail: None, label: None };
         self.body.exprs.alloc(block)
     }
 
@@ -183,12 +209,25 @@ where
                 let body = self.collect_block_opt(e.body());
                 self.alloc_expr(Expr::TryBlock { body }, syntax_ptr)
             }
-            ast::Expr::BlockExpr(e) => self.collect_block(e),
+            ast::Expr::BlockExpr(e) => {
+                if e.is_async() {
+                    let body = self.collect_block(e);
+                    self.alloc_expr(Expr::Async { body }, syntax_ptr)
+                } else {
+                    self.collect_block(e)
+                }
+            }
             ast::Expr::LoopExpr(e) => {
+                let lifetime = e.label().and_then(|label| label.lifetime_token());
+                let label = lifetime.as_ref().map(Name::new_lifetime);
                 let body = self.collect_block_opt(e.loop_body());
-                self.alloc_expr(Expr::Loop { body }, syntax_ptr)
+                let id = self.alloc_expr(Expr::Loop { body, label }, syntax_ptr);
+                self.record_expr_hygiene(id, lifetime);
+                id
             }
             ast::Expr::WhileExpr(e) => {
+                let lifetime = e.label().and_then(|label| label.lifetime_token());
+                let label = lifetime.as_ref().map(Name::new_lifetime);
                 let body = self.collect_block_opt(e.loop_body());
 
                 let condition = match e.condition() {
@@ -201,25 +240,35 @@ where
                             let pat = self.collect_pat(pat);
                             let match_expr = self.collect_expr_opt(condition.expr());
                             let placeholder_pat = self.missing_pat();
-                            let break_ = self.alloc_expr_desugared(Expr::Break { expr: None });
+                            let break_ =
+                                self.alloc_expr_desugared(Expr::Break { expr: None, label: None });
                             let arms = vec![
                                 MatchArm { pat, expr: body, guard: None },
                                 MatchArm { pat: placeholder_pat, expr: break_, guard: None },
                             ];
                             let match_expr =
                                 self.alloc_expr_desugared(Expr::Match { expr: match_expr, arms });
-                            return self.alloc_expr(Expr::Loop { body: match_expr }, syntax_ptr);
+                            let id =
+                                self.alloc_expr(Expr::Loop { body: match_expr, label }, syntax_ptr);
+                            self.record_expr_hygiene(id, lifetime);
+                            return id;
                         }
                     },
                 };
 
-                self.alloc_expr(Expr::While { condition, body }, syntax_ptr)
+                let id = self.alloc_expr(Expr::While { condition, body, label }, syntax_ptr);
+                self.record_expr_hygiene(id, lifetime);
+                id
             }
             ast::Expr::ForExpr(e) => {
+                let lifetime = e.label().and_then(|label| label.lifetime_token());
+                let label = lifetime.as_ref().map(Name::new_lifetime);
                 let iterable = self.collect_expr_opt(e.iterable());
                 let pat = self.collect_pat_opt(e.pat());
                 let body = self.collect_block_opt(e.loop_body());
-                self.alloc_expr(Expr::For { iterable, pat, body }, syntax_ptr)
+                let id = self.alloc_expr(Expr::For { iterable, pat, body, label }, syntax_ptr);
+                self.record_expr_hygiene(id, lifetime);
+                id
             }
             ast::Expr::CallExpr(e) => {
                 let callee = self.collect_expr_opt(e.expr());
@@ -269,15 +318,24 @@ where
                     .and_then(|path| self.expander.parse_path(path))
                     .map(Expr::Path)
                     .unwrap_or(Expr::Missing);
-                self.alloc_expr(path, syntax_ptr)
+                let id = self.alloc_expr(path, syntax_ptr);
+                self.record_expr_hygiene(id, e.syntax().first_token());
+                id
             }
-            ast::Expr::ContinueExpr(_e) => {
-                // FIXME: labels
-                self.alloc_expr(Expr::Continue, syntax_ptr)
+            ast::Expr::ContinueExpr(e) => {
+                let lifetime = e.lifetime_token();
+                let label = lifetime.as_ref().map(Name::new_lifetime);
+                let id = self.alloc_expr(Expr::Continue { label }, syntax_ptr);
+                self.record_expr_hygiene(id, lifetime);
+                id
             }
             ast::Expr::BreakExpr(e) => {
+                let lifetime = e.lifetime_token();
+                let label = lifetime.as_ref().map(Name::new_lifetime);
                 let expr = e.expr().map(|e| self.collect_expr(e));
-                self.alloc_expr(Expr::Break { expr }, syntax_ptr)
+                let id = self.alloc_expr(Expr::Break { expr, label }, syntax_ptr);
+                self.record_expr_hygiene(id, lifetime);
+                id
             }
             ast::Expr::ParenExpr(e) => {
                 let inner = self.collect_expr_opt(e.expr());
@@ -291,6 +349,7 @@ where
                 self.alloc_expr(Expr::Return { expr }, syntax_ptr)
             }
             ast::Expr::RecordLit(e) => {
+                let path_token = e.path().and_then(|path| path.syntax().first_token());
                 let path = e.path().and_then(|path| self.expander.parse_path(path));
                 let mut field_ptrs = Vec::new();
                 let record_lit = if let Some(nfl) = e.record_field_list() {
@@ -306,10 +365,12 @@ where
                                 self.collect_expr(e)
                             } else if let Some(nr) = field.name_ref() {
                                 // field shorthand
-                                self.alloc_expr_field_shorthand(
+                                let id = self.alloc_expr_field_shorthand(
                                     Expr::Path(Path::from_name_ref(&nr)),
                                     AstPtr::new(&field),
-                                )
+                                );
+                                self.record_expr_hygiene(id, nr.syntax().first_token());
+                                id
                             } else {
                                 self.missing_expr()
                             },
@@ -325,6 +386,7 @@ where
                 for (i, ptr) in field_ptrs.into_iter().enumerate() {
                     self.source_map.field_map.insert((res, i), ptr);
                 }
+                self.record_expr_hygiene(res, path_token);
                 res
             }
             ast::Expr::FieldExpr(e) => {
@@ -415,7 +477,7 @@ where
                     LiteralKind::IntNumber { suffix } => {
                         let known_name = suffix.and_then(|it| BuiltinInt::from_suffix(&it));
 
-                        Literal::Int(Default::default(), known_name)
+                        Literal::Int(e.int_value().unwrap_or_default(), known_name)
                     }
                     LiteralKind::FloatNumber { suffix } => {
                         let known_name = suffix.and_then(|it| BuiltinFloat::from_suffix(&it));
@@ -448,6 +510,14 @@ where
             // FIXME expand to statements in statement position
             ast::Expr::MacroCall(e) => {
                 let macro_call = self.expander.to_source(AstPtr::new(&e));
+                // Expansions with errors are still lowered, as far as they go.
+                if let Some(call_id) = self.expander.resolve_macro_call(self.db, &e) {
+                    if let Some(kind) = self.expander.exceeded_limit(self.db, call_id) {
+                        self.source_map.limited_expansions.push((call_id, kind));
+                    } else if expansion_error(self.db, call_id).is_some() {
+                        self.source_map.failed_expansions.push(call_id);
+                    }
+                }
                 match self.expander.enter_expand(self.db, e.clone()) {
                     Some((mark, expansion)) => {
                         self.source_map
@@ -494,7 +564,11 @@ where
             })
             .collect();
         let tail = block.expr().map(|e| self.collect_expr(e));
-        self.alloc_expr(Expr::Block { statements, tail }, syntax_node_ptr)
+        let lifetime = expr.label().and_then(|label| label.lifetime_token());
+        let label = lifetime.as_ref().map(Name::new_lifetime);
+        let id = self.alloc_expr(Expr::Block { statements, tail, label }, syntax_node_ptr);
+        self.record_expr_hygiene(id, lifetime);
+        id
     }
 
     fn collect_block_items(&mut self, block: &ast::Block) {
@@ -629,7 +703,16 @@ where
             ast::Pat::SlicePat(_) | ast::Pat::RangePat(_) => Pat::Missing,
         };
         let ptr = AstPtr::new(&pat);
-        self.alloc_pat(pattern, Either::Left(ptr))
+        let id = self.alloc_pat(pattern, Either::Left(ptr));
+        let token = match &pat {
+            ast::Pat::BindPat(bp) => bp.name().and_then(|name| name.syntax().first_token()),
+            ast::Pat::TupleStructPat(p) => p.path().and_then(|path| path.syntax().first_token()),
+            ast::Pat::PathPat(p) => p.path().and_then(|path| path.syntax().first_token()),
+            ast::Pat::RecordPat(p) => p.path().and_then(|path| path.syntax().first_token()),
+            _ => None,
+        };
+        self.record_pat_hygiene(id, token);
+        id
     }
 
     fn collect_pat_opt(&mut self, pat: Option<ast::Pat>) -> PatId {
@@ -1,7 +1,7 @@
 //! Name resolution for expressions.
 use std::sync::Arc;
 
-use hir_expand::name::Name;
+use hir_expand::{hygiene::SyntaxContext, name::Name};
 use ra_arena::{impl_arena_id, Arena, RawId};
 use rustc_hash::FxHashMap;
 
@@ -20,12 +20,14 @@ impl_arena_id!(ScopeId);
 pub struct ExprScopes {
     scopes: Arena<ScopeId, ScopeData>,
     scope_by_expr: FxHashMap<ExprId, ScopeId>,
+    break_targets: FxHashMap<ExprId, ExprId>,
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct ScopeEntry {
     name: Name,
     pat: PatId,
+    hygiene: SyntaxContext,
 }
 
 impl ScopeEntry {
@@ -36,6 +38,11 @@ impl ScopeEntry {
     pub fn pat(&self) -> PatId {
         self.pat
     }
+
+    /// A binding is visible only to paths with the same syntax context.
+    pub fn hygiene(&self) -> SyntaxContext {
+        self.hygiene
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -51,11 +58,15 @@ impl ExprScopes {
     }
 
     fn new(body: &Body) -> ExprScopes {
-        let mut scopes =
-            ExprScopes { scopes: Arena::default(), scope_by_expr: FxHashMap::default() };
+        let mut scopes = ExprScopes {
+            scopes: Arena::default(),
+            scope_by_expr: FxHashMap::default(),
+            break_targets: FxHashMap::default(),
+        };
         let root = scopes.root_scope();
         scopes.add_params_bindings(body, root, &body.params);
         compute_expr_scopes(body.body_expr, body, &mut scopes, root);
+        compute_break_targets(body.body_expr, body, &mut Vec::new(), &mut scopes);
         scopes
     }
 
@@ -67,9 +78,21 @@ impl ExprScopes {
         std::iter::successors(scope, move |&scope| self.scopes[scope].parent)
     }
 
-    pub fn resolve_name_in_scope(&self, scope: ScopeId, name: &Name) -> Option<&ScopeEntry> {
-        self.scope_chain(Some(scope))
-            .find_map(|scope| self.entries(scope).iter().find(|it| it.name == *name))
+    pub fn resolve_name_in_scope(
+        &self,
+        scope: ScopeId,
+        name: &Name,
+        hygiene: SyntaxContext,
+    ) -> Option<&ScopeEntry> {
+        self.scope_chain(Some(scope)).find_map(|scope| {
+            self.entries(scope).iter().find(|it| it.name == *name && it.hygiene == hygiene)
+        })
+    }
+
+    /// Returns the loop or labeled block a `break` or `continue` expression
+    /// refers to.
+    pub fn break_target(&self, expr: ExprId) -> Option<ExprId> {
+        self.break_targets.get(&expr).copied()
     }
 
     pub fn scope_for(&self, expr: ExprId) -> Option<ScopeId> {
@@ -93,7 +116,7 @@ impl ExprScopes {
             Pat::Bind { name, .. } => {
                 // bind can have a sub pattern, but it's actually not allowed
                 // to bind to things in there
-                let entry = ScopeEntry { name: name.clone(), pat };
+                let entry = ScopeEntry { name: name.clone(), pat, hygiene: body.pat_hygiene(pat) };
                 self.scopes[scope].entries.push(entry)
             }
             p => p.walk_child_pats(|pat| self.add_bindings(body, scope, pat)),
@@ -140,10 +163,10 @@ fn compute_block_scopes(
 fn compute_expr_scopes(expr: ExprId, body: &Body, scopes: &mut ExprScopes, scope: ScopeId) {
     scopes.set_scope(expr, scope);
     match &body[expr] {
-        Expr::Block { statements, tail } => {
+        Expr::Block { statements, tail, .. } => {
             compute_block_scopes(&statements, *tail, body, scopes, scope);
         }
-        Expr::For { iterable, pat, body: body_expr } => {
+        Expr::For { iterable, pat, body: body_expr, .. } => {
             compute_expr_scopes(*iterable, body, scopes, scope);
             let scope = scopes.new_scope(scope);
             scopes.add_bindings(body, scope, *pat);
@@ -167,9 +190,73 @@ fn compute_expr_scopes(expr: ExprId, body: &Body, scopes: &mut ExprScopes, scope
     };
 }
 
+fn compute_break_targets(
+    expr: ExprId,
+    body: &Body,
+    loops: &mut Vec<ExprId>,
+    scopes: &mut ExprScopes,
+) {
+    match &body[expr] {
+        Expr::Break { label, .. } | Expr::Continue { label } => {
+            let target = match label {
+                // Like bindings, labels from a macro definition are only
+                // visible inside the same expansion.
+                Some(label) => {
+                    let hygiene = body.expr_hygiene(expr);
+                    loops.iter().rev().find(|&&it| {
+                        loop_label(body, it) == Some(label) && body.expr_hygiene(it) == hygiene
+                    })
+                }
+                // Blocks can only be exited with a labeled `break`.
+                None => loops.iter().rev().find(|&&it| !is_block(body, it)),
+            };
+            if let Some(&target) = target {
+                scopes.break_targets.insert(expr, target);
+            }
+        }
+        Expr::For { iterable, body: body_expr, .. } => {
+            compute_break_targets(*iterable, body, loops, scopes);
+            loops.push(expr);
+            compute_break_targets(*body_expr, body, loops, scopes);
+            loops.pop();
+            return;
+        }
+        Expr::Loop { .. } | Expr::While { .. } | Expr::Block { label: Some(_), .. } => {
+            loops.push(expr);
+            body[expr].walk_child_exprs(|it| compute_break_targets(it, body, loops, scopes));
+            loops.pop();
+            return;
+        }
+        // Closures and async blocks can't `break` out of the enclosing loops.
+        Expr::Lambda { body: body_expr, .. } | Expr::Async { body: body_expr } => {
+            compute_break_targets(*body_expr, body, &mut Vec::new(), scopes);
+            return;
+        }
+        _ => {}
+    }
+    body[expr].walk_child_exprs(|it| compute_break_targets(it, body, loops, scopes));
+}
+
+fn loop_label(body: &Body, expr: ExprId) -> Option<&Name> {
+    match &body[expr] {
+        Expr::Loop { label, .. }
+        | Expr::While { label, .. }
+        | Expr::For { label, .. }
+        | Expr::Block { label, .. } => label.as_ref(),
+        _ => None,
+    }
+}
+
+fn is_block(body: &Body, expr: ExprId) -> bool {
+    match &body[expr] {
+        Expr::Block { .. } => true,
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use hir_expand::{name::AsName, InFile};
+    use hir_expand::{hygiene::SyntaxContext, name::AsName, InFile};
     use ra_db::{fixture::WithFixture, FileId, SourceDatabase};
     use ra_syntax::{algo::find_node_at_offset, ast, AstNode};
     use test_utils::{assert_eq_text, covers, extract_offset};
@@ -320,7 +407,9 @@ mod tests {
             scopes.scope_for(expr_id).unwrap()
         };
 
-        let resolved = scopes.resolve_name_in_scope(expr_scope, &name_ref.as_name()).unwrap();
+        let resolved = scopes
+            .resolve_name_in_scope(expr_scope, &name_ref.as_name(), SyntaxContext::ROOT)
+            .unwrap();
         let pat_src = source_map.pat_syntax(resolved.pat()).unwrap();
 
         let local_name = pat_src.value.either(|it| it.syntax_node_ptr(), |it| it.syntax_node_ptr());
@@ -381,6 +470,88 @@ mod tests {
         );
     }
 
+    fn do_check_break_target(code: &str, expected_offset: u32) {
+        let (off, code) = extract_offset(code);
+
+        let (db, file_id) = TestDB::with_single_file(&code);
+
+        let file = db.parse(file_id).ok().unwrap();
+        let expected_loop = find_node_at_offset::<ast::Expr>(file.syntax(), expected_offset.into())
+            .expect("failed to find a loop at the target offset");
+        let break_expr: ast::BreakExpr = find_node_at_offset(file.syntax(), off).unwrap();
+
+        let function = find_function(&db, file_id);
+
+        let scopes = db.expr_scopes(function.into());
+        let (_body, source_map) = db.body_with_source_map(function.into());
+
+        let node_expr = |expr: &ast::Expr| {
+            source_map.node_expr(InFile { file_id: file_id.into(), value: expr }).unwrap()
+        };
+        let target = scopes.break_target(node_expr(&break_expr.into()));
+        assert_eq!(target, Some(node_expr(&expected_loop)));
+    }
+
+    #[test]
+    fn test_break_target_innermost_loop() {
+        do_check_break_target(
+            r"
+            fn foo() {
+                loop {
+                    while true {
+                        <|>break;
+                    }
+                }
+            }",
+            67,
+        );
+    }
+
+    #[test]
+    fn test_break_target_label() {
+        do_check_break_target(
+            r"
+            fn foo() {
+                'outer: loop {
+                    for x in xs {
+                        <|>break 'outer;
+                    }
+                }
+            }",
+            48,
+        );
+    }
+
+    #[test]
+    fn test_break_target_labeled_block() {
+        do_check_break_target(
+            r"
+            fn foo() {
+                'block: {
+                    loop {
+                        <|>break 'block;
+                    }
+                }
+            }",
+            40,
+        );
+    }
+
+    #[test]
+    fn test_unlabeled_break_skips_labeled_block() {
+        do_check_break_target(
+            r"
+            fn foo() {
+                loop {
+                    'block: {
+                        <|>break;
+                    }
+                }
+            }",
+            40,
+        );
+    }
+
     #[test]
     fn while_let_desugaring() {
         covers!(infer_resolve_while_let);
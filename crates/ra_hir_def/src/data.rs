@@ -12,7 +12,7 @@ use crate::{
     db::DefDatabase,
     path::{path, GenericArgs, Path},
     src::HasSource,
-    type_ref::{Mutability, TypeBound, TypeRef},
+    type_ref::{LifetimeRef, Mutability, TypeBound, TypeRef},
     AssocContainerId, AssocItemId, ConstId, ConstLoc, Expander, FunctionId, FunctionLoc, HasModule,
     ImplId, Intern, Lookup, ModuleId, StaticId, TraitId, TypeAliasId, TypeAliasLoc,
 };
@@ -39,13 +39,14 @@ impl FunctionData {
                     TypeRef::from_ast(type_ref)
                 } else {
                     let self_type = TypeRef::Path(name![Self].into());
+                    let lifetime = self_param.lifetime_token().map(|it| LifetimeRef::new(&it));
                     match self_param.kind() {
                         ast::SelfParamKind::Owned => self_type,
                         ast::SelfParamKind::Ref => {
-                            TypeRef::Reference(Box::new(self_type), Mutability::Shared)
+                            TypeRef::Reference(Box::new(self_type), lifetime, Mutability::Shared)
                         }
                         ast::SelfParamKind::MutRef => {
-                            TypeRef::Reference(Box::new(self_type), Mutability::Mut)
+                            TypeRef::Reference(Box::new(self_type), lifetime, Mutability::Mut)
                         }
                     }
                 };
@@ -174,6 +175,13 @@ impl TraitData {
             _ => None,
         })
     }
+
+    pub fn method_by_name(&self, name: &Name) -> Option<FunctionId> {
+        self.items.iter().find_map(|(item_name, item)| match item {
+            AssocItemId::FunctionId(t) if item_name == name => Some(*t),
+            _ => None,
+        })
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
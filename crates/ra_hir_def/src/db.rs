@@ -14,7 +14,7 @@ use crate::{
     docs::Documentation,
     generics::GenericParams,
     lang_item::{LangItemTarget, LangItems},
-    nameres::{raw::RawItems, CrateDefMap},
+    nameres::{raw::RawItems, CrateDefMap, ExpansionLimits},
     AttrDefId, ConstId, ConstLoc, DefWithBodyId, EnumId, EnumLoc, FunctionId, FunctionLoc,
     GenericDefId, ImplId, ImplLoc, ModuleId, StaticId, StaticLoc, StructId, StructLoc, TraitId,
     TraitLoc, TypeAliasId, TypeAliasLoc, UnionId, UnionLoc,
@@ -50,6 +50,9 @@ pub trait DefDatabase: InternDatabase + AstDatabase {
     #[salsa::transparent]
     fn crate_def_map(&self, krate: CrateId) -> Arc<CrateDefMap>;
 
+    #[salsa::invoke(ExpansionLimits::expansion_limits_query)]
+    fn expansion_limits(&self, krate: CrateId) -> ExpansionLimits;
+
     #[salsa::invoke(CrateDefMap::compute_crate_def_map_query)]
     fn compute_crate_def_map(&self, krate: CrateId) -> Arc<CrateDefMap>;
 
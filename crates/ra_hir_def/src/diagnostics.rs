@@ -2,9 +2,13 @@
 
 use std::any::Any;
 
-use hir_expand::diagnostics::Diagnostic;
+use hir_expand::{
+    db::AstDatabase,
+    diagnostics::{AstDiagnostic, Diagnostic},
+    MacroCallId,
+};
 use ra_db::RelativePathBuf;
-use ra_syntax::{ast, AstPtr, SyntaxNodePtr};
+use ra_syntax::{ast, AstNode, AstPtr, SyntaxNodePtr};
 
 use hir_expand::{HirFileId, InFile};
 
@@ -26,3 +30,100 @@ impl Diagnostic for UnresolvedModule {
         self
     }
 }
+
+#[derive(Debug)]
+pub struct UnresolvedImport {
+    pub file: HirFileId,
+    pub use_tree: AstPtr<ast::UseTree>,
+}
+
+impl Diagnostic for UnresolvedImport {
+    fn message(&self) -> String {
+        "unresolved import".to_string()
+    }
+    fn source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.use_tree.into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+impl AstDiagnostic for UnresolvedImport {
+    type AST = ast::UseTree;
+
+    fn ast(&self, db: &impl AstDatabase) -> Self::AST {
+        let root = db.parse_or_expand(self.source().file_id).unwrap();
+        let node = self.source().value.to_node(&root);
+        ast::UseTree::cast(node).unwrap()
+    }
+}
+
+/// Which of the crate's `ExpansionLimits` a macro call ran into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpansionLimitKind {
+    Depth(u32),
+    TokenCount(usize),
+}
+
+/// A macro call which was not expanded because it would exceed the crate's
+/// expansion limits. Points at the macro call in the original source which
+/// started the chain of expansions.
+#[derive(Debug)]
+pub struct ExpansionLimitReached {
+    pub file: HirFileId,
+    pub node: SyntaxNodePtr,
+    /// The name of the macro which was not expanded, if known.
+    pub macro_name: Option<String>,
+    pub kind: ExpansionLimitKind,
+}
+
+impl ExpansionLimitReached {
+    pub fn new(
+        db: &dyn AstDatabase,
+        macro_call: MacroCallId,
+        kind: ExpansionLimitKind,
+    ) -> ExpansionLimitReached {
+        let mut node = db.lookup_intern_macro(macro_call).kind.node(db);
+        let macro_name = ast::MacroCall::cast(node.value.clone())
+            .and_then(|it| it.path()?.segment()?.name_ref())
+            .map(|it| it.text().to_string());
+        while let Some(call_node) = node.file_id.call_node(db) {
+            node = call_node;
+        }
+        ExpansionLimitReached {
+            file: node.file_id,
+            node: SyntaxNodePtr::new(&node.value),
+            macro_name,
+            kind,
+        }
+    }
+}
+
+impl Diagnostic for ExpansionLimitReached {
+    fn message(&self) -> String {
+        let name = match &self.macro_name {
+            Some(name) => format!("`{}!`", name),
+            None => "a macro".to_string(),
+        };
+        match self.kind {
+            ExpansionLimitKind::Depth(limit) => format!(
+                "recursion limit reached while expanding {}; \
+                 consider adding `#![recursion_limit = \"{}\"]` to the crate root",
+                name,
+                limit * 2
+            ),
+            ExpansionLimitKind::TokenCount(limit) => format!(
+                "macro expansion limit of {} tokens reached while expanding {}; \
+                 consider raising the `rust-analyzer.macroTokenLimit` setting",
+                limit, name
+            ),
+        }
+    }
+    fn source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.node }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
@@ -11,7 +11,7 @@ use hir_expand::{
 };
 use ra_arena::{map::ArenaMap, Arena};
 use ra_db::FileId;
-use ra_syntax::ast::{self, NameOwner, TypeBoundsOwner, TypeParamsOwner};
+use ra_syntax::ast::{self, NameOwner, TypeAscriptionOwner, TypeBoundsOwner, TypeParamsOwner};
 
 use crate::{
     child_by_source::ChildBySource,
@@ -21,7 +21,7 @@ use crate::{
     src::HasChildSource,
     src::HasSource,
     type_ref::{TypeBound, TypeRef},
-    AdtId, GenericDefId, LocalTypeParamId, Lookup, TypeParamId,
+    AdtId, GenericDefId, LocalLifetimeParamId, LocalTypeParamId, Lookup, TypeParamId,
 };
 
 /// Data about a generic parameter (to a function, struct, impl, ...).
@@ -32,18 +32,29 @@ pub struct TypeParamData {
     pub provenance: TypeParamProvenance,
 }
 
+/// Data about a generic lifetime parameter (to a function, struct, impl, ...).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LifetimeParamData {
+    pub name: Name,
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum TypeParamProvenance {
     TypeParamList,
     TraitSelf,
     ArgumentImplTrait,
+    /// A `const N: usize` param. These live alongside the type params, so that
+    /// const arguments take up a slot in the substitutions like type arguments do.
+    ConstParam,
 }
 
 /// Data about the generic parameters of a function, struct, impl, etc.
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct GenericParams {
     pub types: Arena<LocalTypeParamId, TypeParamData>,
-    // lifetimes: Arena<LocalLifetimeParamId, LifetimeParamData>,
+    pub lifetimes: Arena<LocalLifetimeParamId, LifetimeParamData>,
+    /// The declared types of the const params, like the `usize` in `const N: usize`.
+    pub const_param_types: ArenaMap<LocalTypeParamId, TypeRef>,
     pub where_predicates: Vec<WherePredicate>,
 }
 
@@ -64,7 +75,8 @@ pub enum WherePredicateTarget {
     TypeParam(LocalTypeParamId),
 }
 
-type SourceMap = ArenaMap<LocalTypeParamId, Either<ast::TraitDef, ast::TypeParam>>;
+type SourceMap =
+    ArenaMap<LocalTypeParamId, Either<ast::TraitDef, Either<ast::TypeParam, ast::ConstParam>>>;
 
 impl GenericParams {
     pub(crate) fn generic_params_query(
@@ -76,7 +88,12 @@ impl GenericParams {
     }
 
     fn new(db: &impl DefDatabase, def: GenericDefId) -> (GenericParams, InFile<SourceMap>) {
-        let mut generics = GenericParams { types: Arena::default(), where_predicates: Vec::new() };
+        let mut generics = GenericParams {
+            types: Arena::default(),
+            lifetimes: Arena::default(),
+            const_param_types: ArenaMap::default(),
+            where_predicates: Vec::new(),
+        };
         let mut sm = ArenaMap::default();
         // FIXME: add `: Sized` bound for everything except for `Self` in traits
         let file_id = match def {
@@ -161,6 +178,12 @@ impl GenericParams {
     }
 
     fn fill_params(&mut self, sm: &mut SourceMap, params: ast::TypeParamList) {
+        for lifetime_param in params.lifetime_params() {
+            let name = lifetime_param
+                .lifetime_token()
+                .map_or_else(Name::missing, |it| Name::new_lifetime(&it));
+            self.lifetimes.alloc(LifetimeParamData { name });
+        }
         for type_param in params.type_params() {
             let name = type_param.name().map_or_else(Name::missing, |it| it.as_name());
             // FIXME: Use `Path::from_src`
@@ -171,11 +194,23 @@ impl GenericParams {
                 provenance: TypeParamProvenance::TypeParamList,
             };
             let param_id = self.types.alloc(param);
-            sm.insert(param_id, Either::Right(type_param.clone()));
+            sm.insert(param_id, Either::Right(Either::Left(type_param.clone())));
 
             let type_ref = TypeRef::Path(name.into());
             self.fill_bounds(&type_param, type_ref);
         }
+        for const_param in params.const_params() {
+            let name = const_param.name().map_or_else(Name::missing, |it| it.as_name());
+            let param = TypeParamData {
+                name: Some(name),
+                default: None,
+                provenance: TypeParamProvenance::ConstParam,
+            };
+            let param_id = self.types.alloc(param);
+            let ty = const_param.ascribed_type().map_or(TypeRef::Error, TypeRef::from_ast);
+            self.const_param_types.insert(param_id, ty);
+            sm.insert(param_id, Either::Right(Either::Right(const_param)));
+        }
     }
 
     fn fill_where_predicates(&mut self, where_clause: ast::WhereClause) {
@@ -226,6 +261,10 @@ impl GenericParams {
             .find_map(|(id, p)| if p.name.as_ref() == Some(name) { Some(id) } else { None })
     }
 
+    pub fn find_lifetime_by_name(&self, name: &Name) -> Option<LocalLifetimeParamId> {
+        self.lifetimes.iter().find_map(|(id, p)| if &p.name == name { Some(id) } else { None })
+    }
+
     pub fn find_trait_self_param(&self) -> Option<LocalTypeParamId> {
         self.types.iter().find_map(|(id, p)| {
             if p.provenance == TypeParamProvenance::TraitSelf {
@@ -239,7 +278,7 @@ impl GenericParams {
 
 impl HasChildSource for GenericDefId {
     type ChildId = LocalTypeParamId;
-    type Value = Either<ast::TraitDef, ast::TypeParam>;
+    type Value = Either<ast::TraitDef, Either<ast::TypeParam, ast::ConstParam>>;
     fn child_source(&self, db: &impl DefDatabase) -> InFile<SourceMap> {
         let (_, sm) = GenericParams::new(db, *self);
         sm
@@ -253,8 +292,14 @@ impl ChildBySource for GenericDefId {
         let arena_map = arena_map.as_ref();
         for (local_id, src) in arena_map.value.iter() {
             let id = TypeParamId { parent: *self, local_id };
-            if let Either::Right(type_param) = src {
-                res[keys::TYPE_PARAM].insert(arena_map.with_value(type_param.clone()), id)
+            match src {
+                Either::Right(Either::Left(type_param)) => {
+                    res[keys::TYPE_PARAM].insert(arena_map.with_value(type_param.clone()), id)
+                }
+                Either::Right(Either::Right(const_param)) => {
+                    res[keys::CONST_PARAM].insert(arena_map.with_value(const_param.clone()), id)
+                }
+                Either::Left(_) => (),
             }
         }
         res
@@ -174,6 +174,16 @@ pub struct TypeParamId {
 pub struct LocalTypeParamId(RawId);
 impl_arena_id!(LocalTypeParamId);
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LifetimeParamId {
+    pub parent: GenericDefId,
+    pub local_id: LocalLifetimeParamId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LocalLifetimeParamId(RawId);
+impl_arena_id!(LocalLifetimeParamId);
+
 macro_rules! impl_froms {
     ($e:ident: $($v:ident $(($($sv:ident),*))?),*) => {
         $(
@@ -9,6 +9,8 @@ test_utils::marks!(
     glob_across_crates
     std_prelude
     macro_rules_from_other_crates_are_visible_with_macro_use
+    macro_use_imports_only_listed_macros
+    plain_macro_falls_back_to_prelude
     prelude_is_macro_use
     macro_dollar_crate_self
     macro_dollar_crate_other
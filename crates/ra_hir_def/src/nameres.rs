@@ -46,6 +46,10 @@
 //! unexpanded macros. On every iteration, we try to resolve each macro call
 //! path and, upon success, we run macro expansion and "collect module" phase on
 //! the result
+//!
+//! Expansion is bounded by the crate's `ExpansionLimits`, so that a runaway
+//! recursive macro stops at the `#![recursion_limit]` instead of spinning
+//! until the fixed-point loop gives up.
 
 pub(crate) mod raw;
 mod collector;
@@ -57,20 +61,21 @@ mod tests;
 
 use std::sync::Arc;
 
-use hir_expand::{diagnostics::DiagnosticSink, name::Name, InFile};
+use hir_expand::{diagnostics::DiagnosticSink, hygiene::Hygiene, name::Name, InFile};
 use ra_arena::Arena;
 use ra_db::{CrateId, Edition, FileId};
 use ra_prof::profile;
-use ra_syntax::ast;
-use rustc_hash::FxHashMap;
+use ra_syntax::{ast, SmolStr};
+use rustc_hash::{FxHashMap, FxHashSet};
 
 use crate::{
+    attr::Attrs,
     db::DefDatabase,
     item_scope::{BuiltinShadowMode, ItemScope},
     nameres::{diagnostics::DefDiagnostic, path_resolution::ResolveMode},
     path::ModPath,
     per_ns::PerNs,
-    AstId, LocalModuleId, ModuleDefId, ModuleId,
+    AdtId, AstId, HasModule, LocalModuleId, Lookup, ModuleDefId, ModuleId,
 };
 
 /// Contains all top-level defs from a macro-expanded crate
@@ -86,9 +91,93 @@ pub struct CrateDefMap {
     pub(crate) extern_prelude: FxHashMap<Name, ModuleDefId>,
 
     edition: Edition,
+    /// The modules with macro calls which couldn't be resolved or expanded,
+    /// and the modules glob-importing from them. Names which are missing from
+    /// these modules might be defined by the macros.
+    unexpanded_macro_modules: FxHashSet<LocalModuleId>,
+    /// The ADTs and traits with derives or attribute macros we couldn't
+    /// expand, see `may_lack_impls`.
+    defs_with_unexpanded_attrs: FxHashSet<ModuleDefId>,
+    /// The names used in the headers of impls inside of function bodies, which
+    /// aren't collected yet, see `may_lack_impls`.
+    block_impl_names: FxHashSet<Name>,
+    /// The modules defining macros 2.0, whose bodies are resolved there.
+    macro_def_modules: FxHashMap<AstId<ast::Macro>, LocalModuleId>,
     diagnostics: Vec<DefDiagnostic>,
 }
 
+/// Limits on macro expansion within a crate, which keep runaway macros from
+/// hanging analysis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExpansionLimits {
+    /// How deeply macro calls can nest inside of expansions. Set with
+    /// `#![recursion_limit = "N"]`, like in rustc.
+    pub depth: u32,
+    /// How many tokens all the macros expanded while collecting the items of
+    /// the crate, or while lowering a single body, can produce together. Set
+    /// in the crate graph, see `CrateGraph::macro_token_limit`.
+    pub token_count: usize,
+}
+
+impl Default for ExpansionLimits {
+    fn default() -> ExpansionLimits {
+        ExpansionLimits { depth: 128, token_count: 1 << 24 }
+    }
+}
+
+impl ExpansionLimits {
+    pub(crate) fn expansion_limits_query(db: &impl DefDatabase, krate: CrateId) -> ExpansionLimits {
+        // This is a separate query, so that `CrateDefMap` doesn't depend on
+        // the syntax of the crate root directly.
+        let crate_graph = db.crate_graph();
+        let source_file = db.parse(crate_graph.crate_root(krate)).tree();
+        let attrs = Attrs::new(&source_file, &Hygiene::new_unhygienic());
+        let mut limits = ExpansionLimits::default();
+        let recursion_limit =
+            attrs.by_key("recursion_limit").string_value().cloned().or_else(|| {
+                let cfg_options = crate_graph.cfg_options(krate);
+                attrs
+                    .by_key("cfg_attr")
+                    .tt_values()
+                    .filter(|tt| cfg_options.is_cfg_enabled(tt) == Some(true))
+                    .find_map(recursion_limit_in_cfg_attr)
+            });
+        if let Some(depth) = recursion_limit.and_then(|it| it.parse().ok()) {
+            limits.depth = depth;
+        }
+        if let Some(token_count) = crate_graph.macro_token_limit(krate) {
+            limits.token_count = token_count;
+        }
+        limits
+    }
+}
+
+/// Finds `recursion_limit = "N"` among the attributes of
+/// `#![cfg_attr(predicate, attr1, attr2)]`.
+fn recursion_limit_in_cfg_attr(tt: &tt::Subtree) -> Option<SmolStr> {
+    fn leaf(it: &tt::TokenTree) -> Option<&tt::Leaf> {
+        match it {
+            tt::TokenTree::Leaf(it) => Some(it),
+            tt::TokenTree::Subtree(_) => None,
+        }
+    }
+    let is_comma = |it: &tt::TokenTree| match leaf(it) {
+        Some(tt::Leaf::Punct(punct)) => punct.char == ',',
+        _ => false,
+    };
+    tt.token_trees.split(is_comma).skip(1).find_map(|attr| {
+        let leaves = attr.iter().map(leaf).collect::<Option<Vec<_>>>()?;
+        match leaves.as_slice() {
+            [tt::Leaf::Ident(key), tt::Leaf::Punct(eq), tt::Leaf::Literal(value)]
+                if key.text == "recursion_limit" && eq.char == '=' =>
+            {
+                Some(value.text.trim_matches('"').into())
+            }
+            _ => None,
+        }
+    })
+}
+
 impl std::ops::Index<LocalModuleId> for CrateDefMap {
     type Output = ModuleData;
     fn index(&self, id: LocalModuleId) -> &ModuleData {
@@ -195,6 +284,10 @@ impl CrateDefMap {
                 prelude: None,
                 root,
                 modules,
+                unexpanded_macro_modules: FxHashSet::default(),
+                defs_with_unexpanded_attrs: FxHashSet::default(),
+                block_impl_names: FxHashSet::default(),
+                macro_def_modules: FxHashMap::default(),
                 diagnostics: Vec::new(),
             }
         };
@@ -211,6 +304,46 @@ impl CrateDefMap {
         self.diagnostics.iter().for_each(|it| it.add_to(db, module, sink))
     }
 
+    /// If this is `true`, names missing from `module` might actually be
+    /// defined by a macro we failed to expand, so they shouldn't be reported
+    /// as errors.
+    pub fn has_unexpanded_macros(&self, module: LocalModuleId) -> bool {
+        self.unexpanded_macro_modules.contains(&module)
+    }
+
+    /// If this is `true`, this crate might have impls for `def`, an ADT or a
+    /// trait, which we don't know about: they are generated by its derives or
+    /// attribute macros we can't expand, by macro calls we can't expand next to
+    /// it, or declared inside of function bodies. So `def` lacking a method or
+    /// a trait impl shouldn't be reported as an error.
+    pub fn may_lack_impls(&self, db: &impl DefDatabase, def: ModuleDefId) -> bool {
+        let (name, module) = match def {
+            ModuleDefId::AdtId(adt) => {
+                let name = match adt {
+                    AdtId::StructId(it) => db.struct_data(it).name.clone(),
+                    AdtId::UnionId(it) => db.union_data(it).name.clone(),
+                    AdtId::EnumId(it) => db.enum_data(it).name.clone(),
+                };
+                (name, adt.module(db))
+            }
+            ModuleDefId::TraitId(it) => {
+                (db.trait_data(it).name.clone(), it.lookup(db).container.module(db))
+            }
+            _ => return false,
+        };
+        if self.block_impl_names.contains(&name) {
+            return true;
+        }
+        module.krate == self.krate
+            && (self.defs_with_unexpanded_attrs.contains(&def)
+                || self.unexpanded_macro_modules.contains(&module.local_id))
+    }
+
+    /// The module defining the macro 2.0 `ast_id`.
+    pub fn macro_def_module(&self, ast_id: AstId<ast::Macro>) -> Option<LocalModuleId> {
+        self.macro_def_modules.get(&ast_id).copied()
+    }
+
     pub fn modules_for_file(&self, file_id: FileId) -> impl Iterator<Item = LocalModuleId> + '_ {
         self.modules
             .iter()
@@ -293,11 +426,23 @@ pub enum ModuleSource {
 }
 
 mod diagnostics {
-    use hir_expand::diagnostics::DiagnosticSink;
+    use hir_expand::{
+        diagnostics::{DiagnosticSink, MacroError},
+        hygiene::Hygiene,
+        MacroCallId,
+    };
     use ra_db::RelativePathBuf;
     use ra_syntax::{ast, AstPtr};
 
-    use crate::{db::DefDatabase, diagnostics::UnresolvedModule, nameres::LocalModuleId, AstId};
+    use crate::{
+        db::DefDatabase,
+        diagnostics::{
+            ExpansionLimitKind, ExpansionLimitReached, UnresolvedImport, UnresolvedModule,
+        },
+        nameres::LocalModuleId,
+        path::ModPath,
+        AstId,
+    };
 
     #[derive(Debug, PartialEq, Eq)]
     pub(super) enum DefDiagnostic {
@@ -306,6 +451,21 @@ mod diagnostics {
             declaration: AstId<ast::Module>,
             candidate: RelativePathBuf,
         },
+        UnresolvedImport {
+            module: LocalModuleId,
+            use_item: AstId<ast::UseItem>,
+            /// Index of the import among the leaf use trees of `use_item`.
+            index: usize,
+        },
+        MacroError {
+            module: LocalModuleId,
+            macro_call: MacroCallId,
+        },
+        ExpansionLimitReached {
+            module: LocalModuleId,
+            macro_call: MacroCallId,
+            kind: ExpansionLimitKind,
+        },
     }
 
     impl DefDiagnostic {
@@ -327,6 +487,47 @@ mod diagnostics {
                         candidate: candidate.clone(),
                     })
                 }
+                DefDiagnostic::UnresolvedImport { module, use_item, index } => {
+                    if *module != target_module {
+                        return;
+                    }
+                    // Lower the item once more to find the use tree, the same
+                    // way `RawItems` did.
+                    let item = use_item.to_node(db);
+                    let hygiene = Hygiene::new(db, use_item.file_id);
+                    let mut use_tree = None;
+                    let mut i = 0;
+                    ModPath::expand_use_item(
+                        use_item.with_value(item),
+                        &hygiene,
+                        |_, tree, _, _| {
+                            if i == *index {
+                                use_tree = Some(tree.clone());
+                            }
+                            i += 1;
+                        },
+                    );
+                    if let Some(use_tree) = use_tree {
+                        sink.push(UnresolvedImport {
+                            file: use_item.file_id,
+                            use_tree: AstPtr::new(&use_tree),
+                        })
+                    }
+                }
+                DefDiagnostic::MacroError { module, macro_call } => {
+                    if *module != target_module {
+                        return;
+                    }
+                    if let Some(err) = MacroError::from_macro_call(db, *macro_call) {
+                        sink.push(err)
+                    }
+                }
+                DefDiagnostic::ExpansionLimitReached { module, macro_call, kind } => {
+                    if *module != target_module {
+                        return;
+                    }
+                    sink.push(ExpansionLimitReached::new(db, *macro_call, *kind))
+                }
             }
         }
     }
@@ -4,23 +4,27 @@
 //! resolves imports and expands macros.
 
 use hir_expand::{
+    builtin_attr::find_builtin_attr,
     builtin_derive::find_builtin_derive,
     builtin_macro::find_builtin_macro,
+    db::expansion_error,
     name::{name, AsName, Name},
     HirFileId, MacroCallId, MacroCallKind, MacroDefId, MacroDefKind,
 };
-use ra_cfg::CfgOptions;
+use ra_cfg::{CfgExpr, CfgOptions};
 use ra_db::{CrateId, FileId};
 use ra_syntax::ast;
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use test_utils::tested_by;
 
 use crate::{
     attr::Attrs,
     db::DefDatabase,
+    diagnostics::ExpansionLimitKind,
     nameres::{
         diagnostics::DefDiagnostic, mod_resolution::ModDir, path_resolution::ReachedFixedPoint,
-        raw, BuiltinShadowMode, CrateDefMap, ModuleData, ModuleOrigin, ResolveMode,
+        raw, BuiltinShadowMode, CrateDefMap, ExpansionLimits, ModuleData, ModuleOrigin,
+        ResolveMode,
     },
     path::{ImportAlias, ModPath, PathKind},
     per_ns::PerNs,
@@ -61,8 +65,11 @@ pub(super) fn collect_defs(db: &impl DefDatabase, mut def_map: CrateDefMap) -> C
 
         unexpanded_macros: Vec::new(),
         unexpanded_attribute_macros: Vec::new(),
+        item_attributes: Vec::new(),
         mod_dirs: FxHashMap::default(),
         cfg_options,
+        limits: db.expansion_limits(def_map.krate),
+        expanded_tokens: 0,
     };
     collector.collect();
     collector.finish()
@@ -91,6 +98,7 @@ impl PartialResolvedImport {
 #[derive(Clone, Debug, Eq, PartialEq)]
 struct ImportDirective {
     module_id: LocalModuleId,
+    file_id: HirFileId,
     import_id: raw::Import,
     import: raw::ImportData,
     status: PartialResolvedImport,
@@ -102,6 +110,9 @@ struct MacroDirective {
     ast_id: AstId<ast::MacroCall>,
     path: ModPath,
     legacy: Option<MacroCallId>,
+    /// Whether this is a plain `name!()` call, which is looked up in the std
+    /// prelude if it isn't defined in its module.
+    fallback_to_prelude: bool,
 }
 
 /// Walks the tree of module recursively
@@ -112,9 +123,15 @@ struct DefCollector<'a, DB> {
     unresolved_imports: Vec<ImportDirective>,
     resolved_imports: Vec<ImportDirective>,
     unexpanded_macros: Vec<MacroDirective>,
-    unexpanded_attribute_macros: Vec<(LocalModuleId, AstId<ast::ModuleItem>, ModPath)>,
+    unexpanded_attribute_macros: Vec<(LocalModuleId, AstId<ast::ModuleItem>, ModPath, ModuleDefId)>,
+    /// The attributes of ADTs and traits, which are checked for attribute
+    /// macros once imports are resolved.
+    item_attributes: Vec<(LocalModuleId, ModPath, ModuleDefId)>,
     mod_dirs: FxHashMap<LocalModuleId, ModDir>,
     cfg_options: &'a CfgOptions,
+    limits: ExpansionLimits,
+    /// The number of tokens produced by macro expansions so far.
+    expanded_tokens: usize,
 }
 
 impl<DB> DefCollector<'_, DB>
@@ -142,7 +159,11 @@ where
             self.db.check_canceled();
             self.resolve_imports();
 
-            match self.resolve_macros() {
+            let res = match self.resolve_macros() {
+                ReachedFixedPoint::Yes => self.resolve_macros_in_prelude(),
+                ReachedFixedPoint::No => ReachedFixedPoint::No,
+            };
+            match res {
                 ReachedFixedPoint::Yes => break,
                 ReachedFixedPoint::No => i += 1,
             }
@@ -168,10 +189,81 @@ where
         self.unresolved_imports.extend(partial_resolved);
         self.resolve_imports();
 
+        let unexpanded_modules = self.unexpanded_macros.iter().map(|it| it.module_id);
+        self.def_map.unexpanded_macro_modules.extend(unexpanded_modules);
+        self.propagate_unexpanded_macros_through_globs();
+        let unexpanded_derives = self.unexpanded_attribute_macros.iter().map(|it| it.3);
+        self.def_map.defs_with_unexpanded_attrs.extend(unexpanded_derives);
+        self.record_attribute_macros();
+
         let unresolved_imports = std::mem::replace(&mut self.unresolved_imports, Vec::new());
         // show unresolved imports in completion, etc
         for directive in unresolved_imports {
-            self.record_resolved_import(&directive)
+            self.record_resolved_import(&directive);
+            self.report_unresolved_import(&directive);
+        }
+    }
+
+    fn report_unresolved_import(&mut self, directive: &ImportDirective) {
+        let (use_item, index) = match directive.import.use_tree {
+            Some(it) => it,
+            None => return,
+        };
+        // FIXME: report unresolved imports from macro expansions as well.
+        if HirFileId::from(directive.file_id.original_file(self.db)) != directive.file_id {
+            return;
+        }
+        if self.may_be_defined_by_macro(directive.module_id, &directive.import.path) {
+            return;
+        }
+        self.def_map.diagnostics.push(DefDiagnostic::UnresolvedImport {
+            module: directive.module_id,
+            use_item: AstId::new(directive.file_id, use_item),
+            index,
+        });
+    }
+
+    /// Whether the missing item `path` refers to might be defined by a macro
+    /// we failed to expand. That's the case if the last module the path gets
+    /// to has such macros, in this crate or in another one.
+    fn may_be_defined_by_macro(&self, module_id: LocalModuleId, path: &ModPath) -> bool {
+        let parent = (0..path.segments.len()).rev().find_map(|len| {
+            let prefix = ModPath::from_segments(path.kind, path.segments[..len].iter().cloned());
+            let res = self.def_map.resolve_path_fp_with_macro(
+                self.db,
+                ResolveMode::Import,
+                module_id,
+                &prefix,
+                BuiltinShadowMode::Module,
+            );
+            match res.resolved_def.take_types() {
+                Some(ModuleDefId::ModuleId(it)) => Some(it),
+                _ => None,
+            }
+        });
+        match parent {
+            Some(module) if module.krate != self.def_map.krate => {
+                self.db.crate_def_map(module.krate).has_unexpanded_macros(module.local_id)
+            }
+            Some(module) => self.def_map.has_unexpanded_macros(module.local_id),
+            // The first segment of a plain path is looked up in the module
+            // itself.
+            None => self.def_map.has_unexpanded_macros(module_id),
+        }
+    }
+
+    /// Names missing from the modules glob-importing from a module with
+    /// unexpanded macros might be defined by those macros as well.
+    fn propagate_unexpanded_macros_through_globs(&mut self) {
+        let mut worklist =
+            self.def_map.unexpanded_macro_modules.iter().copied().collect::<Vec<_>>();
+        while let Some(module_id) = worklist.pop() {
+            let importers = self.glob_imports.get(&module_id).into_iter().flatten();
+            for &(importer, _) in importers {
+                if self.def_map.unexpanded_macro_modules.insert(importer) {
+                    worklist.push(importer);
+                }
+            }
         }
     }
 
@@ -235,10 +327,13 @@ where
     }
 
     /// Import macros from `#[macro_use] extern crate`.
+    ///
+    /// `#[macro_use(foo, bar)]` only imports the listed macros.
     fn import_macros_from_extern_crate(
         &mut self,
         current_module_id: LocalModuleId,
         import: &raw::ImportData,
+        attrs: &Attrs,
     ) {
         log::debug!(
             "importing macros from extern crate: {:?} ({:?})",
@@ -255,7 +350,30 @@ where
 
         if let Some(ModuleDefId::ModuleId(m)) = res.take_types() {
             tested_by!(macro_rules_from_other_crates_are_visible_with_macro_use);
-            self.import_all_macros_exported(current_module_id, m.krate);
+            match macro_use_list(attrs) {
+                Some(names) => {
+                    tested_by!(macro_use_imports_only_listed_macros);
+                    self.import_macros_exported(current_module_id, m.krate, &names)
+                }
+                None => self.import_all_macros_exported(current_module_id, m.krate),
+            }
+        }
+    }
+
+    /// Import the given exported macros from another crate, ignoring the
+    /// names which don't refer to a macro.
+    fn import_macros_exported(
+        &mut self,
+        current_module_id: LocalModuleId,
+        krate: CrateId,
+        names: &[Name],
+    ) {
+        let def_map = self.db.crate_def_map(krate);
+        for name in names {
+            let def = def_map[def_map.root].scope.get(name, BuiltinShadowMode::Other).take_macros();
+            if let Some(def) = def {
+                self.define_legacy_macro(current_module_id, name.clone(), def);
+            }
         }
     }
 
@@ -370,6 +488,9 @@ where
                         tested_by!(glob_across_crates);
                         // glob import from other crate => we can just import everything once
                         let item_map = self.db.crate_def_map(m.krate);
+                        if item_map.has_unexpanded_macros(m.local_id) {
+                            self.def_map.unexpanded_macro_modules.insert(module_id);
+                        }
                         let scope = &item_map[m.local_id].scope;
 
                         // Module scoped macros is included
@@ -532,7 +653,7 @@ where
 
             true
         });
-        attribute_macros.retain(|(module_id, ast_id, path)| {
+        attribute_macros.retain(|(module_id, ast_id, path, _)| {
             let resolved_res = self.resolve_attribute_macro(path);
 
             if let Some(def) = resolved_res {
@@ -555,6 +676,77 @@ where
         res
     }
 
+    /// Path-based scopes take precedence over the std prelude, so plain macro
+    /// calls are only looked up in the prelude once nothing more can be added
+    /// to the module scopes.
+    fn resolve_macros_in_prelude(&mut self) -> ReachedFixedPoint {
+        let mut macros = std::mem::replace(&mut self.unexpanded_macros, Vec::new());
+        let mut resolved = Vec::new();
+        macros.retain(|directive| {
+            if !directive.fallback_to_prelude {
+                return true;
+            }
+            let name = match directive.path.segments.first() {
+                Some(it) => it,
+                None => return true,
+            };
+            let def = self.def_map.resolve_in_prelude(self.db, name, BuiltinShadowMode::Other);
+            match def.take_macros() {
+                Some(def) => {
+                    tested_by!(plain_macro_falls_back_to_prelude);
+                    let call_id = def.as_call_id(self.db, MacroCallKind::FnLike(directive.ast_id));
+                    resolved.push((directive.module_id, call_id));
+                    false
+                }
+                None => true,
+            }
+        });
+        self.unexpanded_macros = macros;
+
+        if resolved.is_empty() {
+            return ReachedFixedPoint::Yes;
+        }
+        for (module_id, macro_call_id) in resolved {
+            self.collect_macro_expansion(module_id, macro_call_id);
+        }
+        ReachedFixedPoint::No
+    }
+
+    /// Resolves the path of an attribute to the attribute macro it invokes, like
+    /// `Resolver::resolve_path_as_attr`: a macro named `test` in scope shadows
+    /// the builtin attribute.
+    fn resolve_attr(&self, module_id: LocalModuleId, path: &ModPath) -> Option<MacroDefId> {
+        if let Some(name) = path.as_ident() {
+            let legacy = self.def_map[module_id].scope.get_legacy_macro(name);
+            if legacy.is_some() {
+                return legacy;
+            }
+        }
+        let res = self.def_map.resolve_path_fp_with_macro(
+            self.db,
+            ResolveMode::Other,
+            module_id,
+            path,
+            BuiltinShadowMode::Module,
+        );
+        res.resolved_def.take_macros().or_else(|| find_builtin_attr(path.as_ident()?))
+    }
+
+    /// Records the ADTs and traits carrying attribute macros, which we can't
+    /// expand yet, so they might have impls we don't know about. Inert
+    /// attributes like `#[repr]` and tool attributes don't resolve to a macro.
+    fn record_attribute_macros(&mut self) {
+        let item_attributes = std::mem::replace(&mut self.item_attributes, Vec::new());
+        for (module_id, path, def) in item_attributes {
+            match self.resolve_attr(module_id, &path) {
+                Some(MacroDefId { kind: MacroDefKind::BuiltInAttr(_), .. }) | None => {}
+                Some(_) => {
+                    self.def_map.defs_with_unexpanded_attrs.insert(def);
+                }
+            }
+        }
+    }
+
     fn resolve_attribute_macro(&self, path: &ModPath) -> Option<MacroDefId> {
         // FIXME this is currently super hacky, just enough to support the
         // built-in derives
@@ -571,7 +763,40 @@ where
 
     fn collect_macro_expansion(&mut self, module_id: LocalModuleId, macro_call_id: MacroCallId) {
         let file_id: HirFileId = macro_call_id.as_file();
+        if file_id.expansion_level(self.db) > self.limits.depth {
+            let kind = ExpansionLimitKind::Depth(self.limits.depth);
+            self.report_expansion_limit(module_id, macro_call_id, kind);
+            return;
+        }
+
+        if expansion_error(self.db, macro_call_id).is_some() {
+            // The best-effort expansion may well be missing items the macro
+            // was meant to define, so don't report failing lookups of those.
+            self.def_map.unexpanded_macro_modules.insert(module_id);
+            self.def_map
+                .diagnostics
+                .push(DefDiagnostic::MacroError { module: module_id, macro_call: macro_call_id });
+        }
+        let (expansion, _) = self.db.macro_expand(macro_call_id);
+        if let Some(expansion) = expansion {
+            let was_within_limit = self.expanded_tokens <= self.limits.token_count;
+            self.expanded_tokens += expansion.count();
+            if self.expanded_tokens > self.limits.token_count {
+                // Report only the call which used up the budget, all the
+                // following ones would be reported as well otherwise.
+                if was_within_limit {
+                    let kind = ExpansionLimitKind::TokenCount(self.limits.token_count);
+                    self.report_expansion_limit(module_id, macro_call_id, kind);
+                }
+                self.def_map.unexpanded_macro_modules.insert(module_id);
+                return;
+            }
+        }
+
         let raw_items = self.db.raw_items(file_id);
+        if raw_items.expansion_failed() {
+            self.def_map.unexpanded_macro_modules.insert(module_id);
+        }
         let mod_dir = self.mod_dirs[&module_id].clone();
         ModCollector {
             def_collector: &mut *self,
@@ -583,6 +808,20 @@ where
         .collect(raw_items.items());
     }
 
+    fn report_expansion_limit(
+        &mut self,
+        module_id: LocalModuleId,
+        macro_call_id: MacroCallId,
+        kind: ExpansionLimitKind,
+    ) {
+        self.def_map.unexpanded_macro_modules.insert(module_id);
+        self.def_map.diagnostics.push(DefDiagnostic::ExpansionLimitReached {
+            module: module_id,
+            macro_call: macro_call_id,
+            kind,
+        });
+    }
+
     fn finish(self) -> CrateDefMap {
         self.def_map
     }
@@ -606,6 +845,9 @@ where
         // for macros.
         self.def_collector.mod_dirs.insert(self.module_id, self.mod_dir.clone());
 
+        let block_impl_names = self.raw_items.block_impl_names().iter().cloned();
+        self.def_collector.def_map.block_impl_names.extend(block_impl_names);
+
         // Prelude module is always considered to be `#[macro_use]`.
         if let Some(prelude_module) = self.def_collector.def_map.prelude {
             if prelude_module.krate != self.def_collector.def_map.krate {
@@ -622,14 +864,18 @@ where
                 if let raw::RawItemKind::Import(import_id) = item.kind {
                     let import = self.raw_items[import_id].clone();
                     if import.is_extern_crate && import.is_macro_use {
-                        self.def_collector.import_macros_from_extern_crate(self.module_id, &import);
+                        self.def_collector.import_macros_from_extern_crate(
+                            self.module_id,
+                            &import,
+                            &item.attrs,
+                        );
                     }
                 }
             }
         }
 
         for item in items {
-            if self.is_cfg_enabled(&item.attrs) {
+            if self.is_cfg_enabled(&item.attrs) && !self.is_removed_by_builtin_attr(&item.attrs) {
                 match item.kind {
                     raw::RawItemKind::Module(m) => {
                         self.collect_module(&self.raw_items[m], &item.attrs)
@@ -637,6 +883,7 @@ where
                     raw::RawItemKind::Import(import_id) => {
                         self.def_collector.unresolved_imports.push(ImportDirective {
                             module_id: self.module_id,
+                            file_id: self.file_id,
                             import_id,
                             import: self.raw_items[import_id].clone(),
                             status: PartialResolvedImport::Unresolved,
@@ -646,6 +893,7 @@ where
                         self.define_def(&self.raw_items[def], &item.attrs)
                     }
                     raw::RawItemKind::Macro(mac) => self.collect_macro(&self.raw_items[mac]),
+                    raw::RawItemKind::MacroDef(mac) => self.collect_macro_def(&self.raw_items[mac]),
                     raw::RawItemKind::Impl(imp) => {
                         let module = ModuleId {
                             krate: self.def_collector.def_map.krate,
@@ -764,11 +1012,10 @@ where
         // in which case we don't add the invocation, just a single attribute
         // macro invocation
 
-        self.collect_derives(attrs, def);
-
         let name = def.name.clone();
         let container = ContainerId::ModuleId(module);
         let vis = &def.visibility;
+        let ast_id = AstId::new(self.file_id, def.kind.ast_id());
         let def: ModuleDefId = match def.kind {
             raw::DefKind::Function(ast_id) => FunctionLoc {
                 container: container.into(),
@@ -813,6 +1060,11 @@ where
             .intern(self.def_collector.db)
             .into(),
         };
+        self.collect_derives(attrs, ast_id, def);
+        if let ModuleDefId::AdtId(_) | ModuleDefId::TraitId(_) = def {
+            let attrs = attrs.iter().map(|attr| (self.module_id, attr.path.clone(), def));
+            self.def_collector.item_attributes.extend(attrs);
+        }
         self.def_collector.def_map.modules[self.module_id].scope.define_def(def);
         let vis = self
             .def_collector
@@ -822,7 +1074,7 @@ where
         self.def_collector.update(self.module_id, &[(name, PerNs::from_def(def, vis))], vis)
     }
 
-    fn collect_derives(&mut self, attrs: &Attrs, def: &raw::DefData) {
+    fn collect_derives(&mut self, attrs: &Attrs, ast_id: AstId<ast::ModuleItem>, def: ModuleDefId) {
         for derive_subtree in attrs.by_key("derive").tt_values() {
             // for #[derive(Copy, Clone)], `derive_subtree` is the `(Copy, Clone)` subtree
             for tt in &derive_subtree.token_trees {
@@ -833,12 +1085,36 @@ where
                 };
                 let path = ModPath::from_tt_ident(ident);
 
-                let ast_id = AstId::new(self.file_id, def.kind.ast_id());
-                self.def_collector.unexpanded_attribute_macros.push((self.module_id, ast_id, path));
+                let directive = (self.module_id, ast_id, path, def);
+                self.def_collector.unexpanded_attribute_macros.push(directive);
             }
         }
     }
 
+    /// Macros 2.0 are ordinary items: unlike `macro_rules!`, they are not
+    /// added to the textual (legacy) scope, and are only visible by path,
+    /// subject to their declared visibility.
+    fn collect_macro_def(&mut self, mac: &raw::MacroDefData) {
+        let ast_id = AstId::new(self.file_id, mac.ast_id.upcast());
+        let macro_id = MacroDefId {
+            ast_id: Some(ast_id),
+            krate: Some(self.def_collector.def_map.krate),
+            kind: MacroDefKind::Declarative,
+            local_inner: false,
+        };
+        self.def_collector.def_map.macro_def_modules.insert(ast_id, self.module_id);
+        let vis = self
+            .def_collector
+            .def_map
+            .resolve_visibility(self.def_collector.db, self.module_id, &mac.visibility)
+            .unwrap_or(Visibility::Public);
+        self.def_collector.update(
+            self.module_id,
+            &[(mac.name.clone(), PerNs::macros(macro_id, vis))],
+            vis,
+        );
+    }
+
     fn collect_macro(&mut self, mac: &raw::MacroData) {
         let ast_id = AstId::new(self.file_id, mac.ast_id);
 
@@ -862,9 +1138,10 @@ where
         if is_macro_rules(&mac.path) {
             if let Some(name) = &mac.name {
                 let macro_id = MacroDefId {
-                    ast_id: Some(ast_id),
+                    ast_id: Some(AstId::new(ast_id.file_id, ast_id.value.upcast())),
                     krate: Some(self.def_collector.def_map.krate),
                     kind: MacroDefKind::Declarative,
+                    local_inner: mac.local_inner,
                 };
                 self.def_collector.define_macro(self.module_id, name.clone(), macro_id, mac.export);
             }
@@ -883,6 +1160,7 @@ where
                 path: mac.path.clone(),
                 ast_id,
                 legacy: Some(macro_call_id),
+                fallback_to_prelude: false,
             });
 
             return;
@@ -890,8 +1168,10 @@ where
 
         // Case 3: resolve in module scope, expand during name resolution.
         // We rewrite simple path `macro_name` to `self::macro_name` to force resolve in module scope only.
+        // If that fails, the std prelude is tried as a last resort.
         let mut path = mac.path.clone();
-        if path.is_ident() {
+        let fallback_to_prelude = path.is_ident();
+        if fallback_to_prelude {
             path.kind = PathKind::Super(0);
         }
 
@@ -900,6 +1180,7 @@ where
             path,
             ast_id,
             legacy: None,
+            fallback_to_prelude,
         });
     }
 
@@ -917,12 +1198,49 @@ where
             .tt_values()
             .all(|tt| self.def_collector.cfg_options.is_cfg_enabled(tt) != Some(false))
     }
+
+    /// Builtin attributes are applied after `cfg`s are evaluated: items
+    /// marked with `#[test]` or `#[bench]` only exist under `cfg(test)`.
+    fn is_removed_by_builtin_attr(&self, attrs: &Attrs) -> bool {
+        let cfg_test = CfgExpr::Atom("test".into());
+        if self.def_collector.cfg_options.check(&cfg_test) == Some(true) {
+            return false;
+        }
+        attrs.iter().filter_map(|attr| self.resolve_attr(&attr.path)).any(|def| match def.kind {
+            MacroDefKind::BuiltInAttr(expander) => expander.is_test_only(),
+            _ => false,
+        })
+    }
+
+    /// Resolves the path of an attribute in the current module, see
+    /// `DefCollector::resolve_attr`.
+    ///
+    /// FIXME: items are collected before imports are resolved, so a macro
+    /// imported under the name of a builtin attribute doesn't shadow it yet.
+    fn resolve_attr(&self, path: &ModPath) -> Option<MacroDefId> {
+        self.def_collector.resolve_attr(self.module_id, path)
+    }
 }
 
 fn is_macro_rules(path: &ModPath) -> bool {
     path.as_ident() == Some(&name![macro_rules])
 }
 
+/// Returns the macros listed in `#[macro_use(foo, bar)]`, or `None` if all
+/// macros should be imported.
+fn macro_use_list(attrs: &Attrs) -> Option<Vec<Name>> {
+    let list = attrs.by_key("macro_use").tt_values().next()?;
+    let names = list
+        .token_trees
+        .iter()
+        .filter_map(|it| match it {
+            tt::TokenTree::Leaf(tt::Leaf::Ident(ident)) => Some(ident.as_name()),
+            _ => None,
+        })
+        .collect();
+    Some(names)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{db::DefDatabase, test_db::TestDB};
@@ -940,8 +1258,11 @@ mod tests {
             resolved_imports: Vec::new(),
             unexpanded_macros: Vec::new(),
             unexpanded_attribute_macros: Vec::new(),
+            item_attributes: Vec::new(),
             mod_dirs: FxHashMap::default(),
             cfg_options: &CfgOptions::default(),
+            limits: ExpansionLimits::default(),
+            expanded_tokens: 0,
         };
         collector.collect();
         collector.def_map
@@ -962,6 +1283,10 @@ mod tests {
                 prelude: None,
                 root,
                 modules,
+                unexpanded_macro_modules: FxHashSet::default(),
+                defs_with_unexpanded_attrs: FxHashSet::default(),
+                block_impl_names: FxHashSet::default(),
+                macro_def_modules: FxHashMap::default(),
                 diagnostics: Vec::new(),
             }
         };
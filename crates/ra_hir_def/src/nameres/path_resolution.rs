@@ -296,7 +296,7 @@ impl CrateDefMap {
         from_crate_root.or(from_extern_prelude)
     }
 
-    fn resolve_in_prelude(
+    pub(super) fn resolve_in_prelude(
         &self,
         db: &impl DefDatabase,
         name: &Name,
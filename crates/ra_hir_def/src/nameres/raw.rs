@@ -17,7 +17,7 @@ use ra_arena::{impl_arena_id, Arena, RawId};
 use ra_prof::profile;
 use ra_syntax::{
     ast::{self, AttrsOwner, NameOwner, VisibilityOwner},
-    AstNode,
+    AstNode, SyntaxNode,
 };
 use test_utils::tested_by;
 
@@ -39,9 +39,16 @@ pub struct RawItems {
     imports: Arena<Import, ImportData>,
     defs: Arena<Def, DefData>,
     macros: Arena<Macro, MacroData>,
+    macro_defs: Arena<MacroDef, MacroDefData>,
     impls: Arena<Impl, ImplData>,
     /// items for top-level module
     items: Vec<RawItem>,
+    /// Set for macro expansions which couldn't be parsed as items.
+    expansion_failed: bool,
+    /// The names of the types and traits of impls inside of function bodies.
+    /// These aren't collected yet, so the methods and trait impls they define
+    /// are missing.
+    block_impl_names: Vec<Name>,
 }
 
 impl RawItems {
@@ -56,12 +63,18 @@ impl RawItems {
             file_id,
             hygiene: Hygiene::new(db, file_id),
         };
-        if let Some(node) = db.parse_or_expand(file_id) {
-            if let Some(source_file) = ast::SourceFile::cast(node.clone()) {
-                collector.process_module(None, source_file);
-            } else if let Some(item_list) = ast::MacroItems::cast(node) {
-                collector.process_module(None, item_list);
+        match db.parse_or_expand(file_id) {
+            Some(node) => {
+                collector.raw_items.block_impl_names = block_impl_names(&node);
+                if let Some(source_file) = ast::SourceFile::cast(node.clone()) {
+                    collector.process_module(None, source_file);
+                } else if let Some(item_list) = ast::MacroItems::cast(node) {
+                    collector.process_module(None, item_list);
+                } else {
+                    collector.raw_items.expansion_failed = true;
+                }
             }
+            None => collector.raw_items.expansion_failed = true,
         }
         let raw_items = collector.raw_items;
         Arc::new(raw_items)
@@ -70,6 +83,20 @@ impl RawItems {
     pub(super) fn items(&self) -> &[RawItem] {
         &self.items
     }
+
+    pub(super) fn expansion_failed(&self) -> bool {
+        self.expansion_failed
+    }
+
+    pub(super) fn block_impl_names(&self) -> &[Name] {
+        &self.block_impl_names
+    }
+
+    /// Whether the macro declared by the `macro_rules!` call `ast_id` is
+    /// `#[macro_export(local_inner_macros)]`.
+    pub fn is_local_inner_macro(&self, ast_id: FileAstId<ast::MacroCall>) -> bool {
+        self.macros.iter().any(|(_, it)| it.ast_id == ast_id && it.local_inner)
+    }
 }
 
 impl Index<Module> for RawItems {
@@ -100,6 +127,13 @@ impl Index<Macro> for RawItems {
     }
 }
 
+impl Index<MacroDef> for RawItems {
+    type Output = MacroDefData;
+    fn index(&self, idx: MacroDef) -> &MacroDefData {
+        &self.macro_defs[idx]
+    }
+}
+
 impl Index<Impl> for RawItems {
     type Output = ImplData;
     fn index(&self, idx: Impl) -> &ImplData {
@@ -119,6 +153,7 @@ pub(super) enum RawItemKind {
     Import(Import),
     Def(Def),
     Macro(Macro),
+    MacroDef(MacroDef),
     Impl(Impl),
 }
 
@@ -154,6 +189,9 @@ pub struct ImportData {
     pub(super) is_extern_crate: bool,
     pub(super) is_macro_use: bool,
     pub(super) visibility: RawVisibility,
+    /// The `use` item this import was lowered from, together with the index of
+    /// the import among the item's leaf use trees. `None` for `extern crate`.
+    pub(super) use_tree: Option<(FileAstId<ast::UseItem>, usize)>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -204,9 +242,22 @@ pub(super) struct MacroData {
     pub(super) path: ModPath,
     pub(super) name: Option<Name>,
     pub(super) export: bool,
+    pub(super) local_inner: bool,
     pub(super) builtin: bool,
 }
 
+/// A declarative macro 2.0, `macro m { .. }`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) struct MacroDef(RawId);
+impl_arena_id!(MacroDef);
+
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct MacroDefData {
+    pub(super) ast_id: FileAstId<ast::MacroDef>,
+    pub(super) name: Name,
+    pub(super) visibility: RawVisibility,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub(super) struct Impl(RawId);
 impl_arena_id!(Impl);
@@ -228,6 +279,7 @@ impl RawItemsCollector {
         for item_or_macro in body.items_with_macros() {
             match item_or_macro {
                 ast::ItemOrMacro::Macro(m) => self.add_macro(current_module, m),
+                ast::ItemOrMacro::MacroDef(m) => self.add_macro_def(current_module, m),
                 ast::ItemOrMacro::Item(item) => self.add_item(current_module, item),
             }
         }
@@ -324,6 +376,7 @@ impl RawItemsCollector {
         let is_prelude = use_item.has_atom_attr("prelude_import");
         let attrs = self.parse_attrs(&use_item);
         let visibility = RawVisibility::from_ast_with_hygiene(use_item.visibility(), &self.hygiene);
+        let ast_id = self.source_ast_id_map.ast_id(&use_item);
 
         let mut buf = Vec::new();
         ModPath::expand_use_item(
@@ -338,6 +391,7 @@ impl RawItemsCollector {
                     is_extern_crate: false,
                     is_macro_use: false,
                     visibility: visibility.clone(),
+                    use_tree: Some((ast_id, buf.len())),
                 };
                 buf.push(import_data);
             },
@@ -363,7 +417,7 @@ impl RawItemsCollector {
             });
             let attrs = self.parse_attrs(&extern_crate);
             // FIXME: cfg_attr
-            let is_macro_use = extern_crate.has_atom_attr("macro_use");
+            let is_macro_use = attrs.by_key("macro_use").exists();
             let import_data = ImportData {
                 path,
                 alias,
@@ -372,6 +426,7 @@ impl RawItemsCollector {
                 is_extern_crate: true,
                 is_macro_use,
                 visibility,
+                use_tree: None,
             };
             self.push_import(current_module, attrs, import_data);
         }
@@ -387,16 +442,44 @@ impl RawItemsCollector {
         let name = m.name().map(|it| it.as_name());
         let ast_id = self.source_ast_id_map.ast_id(&m);
         // FIXME: cfg_attr
-        let export = m.attrs().filter_map(|x| x.simple_name()).any(|name| name == "macro_export");
+        let export = attrs.by_key("macro_export").exists();
+        let local_inner = attrs
+            .by_key("macro_export")
+            .tt_values()
+            .flat_map(|it| it.token_trees.iter())
+            .any(|it| match it {
+                tt::TokenTree::Leaf(tt::Leaf::Ident(ident)) => ident.text == "local_inner_macros",
+                _ => false,
+            });
 
         // FIXME: cfg_attr
         let builtin =
             m.attrs().filter_map(|x| x.simple_name()).any(|name| name == "rustc_builtin_macro");
 
-        let m = self.raw_items.macros.alloc(MacroData { ast_id, path, name, export, builtin });
+        let m = self.raw_items.macros.alloc(MacroData {
+            ast_id,
+            path,
+            name,
+            export,
+            local_inner,
+            builtin,
+        });
         self.push_item(current_module, attrs, RawItemKind::Macro(m));
     }
 
+    fn add_macro_def(&mut self, current_module: Option<Module>, m: ast::MacroDef) {
+        let name = match m.name() {
+            Some(it) => it.as_name(),
+            None => return,
+        };
+        let attrs = self.parse_attrs(&m);
+        let visibility = RawVisibility::from_ast_with_hygiene(m.visibility(), &self.hygiene);
+        let ast_id = self.source_ast_id_map.ast_id(&m);
+
+        let m = self.raw_items.macro_defs.alloc(MacroDefData { ast_id, name, visibility });
+        self.push_item(current_module, attrs, RawItemKind::MacroDef(m));
+    }
+
     fn add_impl(&mut self, current_module: Option<Module>, imp: ast::ImplBlock) {
         let attrs = self.parse_attrs(&imp);
         let ast_id = self.source_ast_id_map.ast_id(&imp);
@@ -424,3 +507,16 @@ impl RawItemsCollector {
         Attrs::new(item, &self.hygiene)
     }
 }
+
+/// Collects the names in the self types and traits of impls inside of blocks:
+/// for `impl<T> Into<T> for Wrapper<T>`, that's `Into`, `T` and `Wrapper`.
+fn block_impl_names(node: &SyntaxNode) -> Vec<Name> {
+    node.descendants()
+        .filter_map(ast::ImplBlock::cast)
+        .filter(|it| it.syntax().ancestors().any(|it| ast::Block::can_cast(it.kind())))
+        .flat_map(|it| it.target_type().into_iter().chain(it.target_trait()))
+        .flat_map(|it| it.syntax().descendants().filter_map(ast::PathType::cast))
+        .filter_map(|it| it.path()?.segment()?.name_ref())
+        .map(|it| it.as_name())
+        .collect()
+}
@@ -6,6 +6,7 @@ mod primitives;
 
 use std::sync::Arc;
 
+use hir_expand::diagnostics::DiagnosticSink;
 use insta::assert_snapshot;
 use ra_db::{fixture::WithFixture, SourceDatabase};
 use test_utils::covers;
@@ -515,6 +516,80 @@ fn cfg_test() {
     "###);
 }
 
+#[test]
+fn test_functions_only_exist_under_cfg_test() {
+    let map = def_map(
+        r#"
+        //- /main.rs crate:main deps:std
+        use std::{test_foo, bench_foo, helper, alloc};
+
+        #[test]
+        fn test_main() {}
+
+        //- /lib.rs crate:std
+        #[test]
+        pub fn test_foo() {}
+        #[bench]
+        pub fn bench_foo() {}
+        #[cfg(test)]
+        #[test]
+        pub fn cfg_test_foo() {}
+        pub fn helper() {}
+        #[global_allocator]
+        pub static alloc: () = ();
+        "#,
+    );
+
+    assert_snapshot!(map, @r###"
+        ⋮crate
+        ⋮alloc: v
+        ⋮bench_foo: _
+        ⋮helper: v
+        ⋮test_foo: _
+    "###);
+
+    let map = def_map(
+        r#"
+        //- /main.rs crate:main cfg:test
+        #[test]
+        fn test_foo() {}
+        #[cfg(not(test))]
+        #[test]
+        fn not_test_foo() {}
+        "#,
+    );
+
+    assert_snapshot!(map, @r###"
+        ⋮crate
+        ⋮test_foo: v
+    "###);
+}
+
+#[test]
+fn test_attribute_resolves_to_macro_in_scope() {
+    let map = def_map(
+        r#"
+        //- /main.rs
+        macro_rules! test {
+            ($($tt:tt)*) => { $($tt)* };
+        }
+
+        #[test]
+        fn foo() {}
+        #[cfg_eval]
+        fn bar() {}
+        #[bench]
+        fn baz() {}
+        "#,
+    );
+
+    assert_snapshot!(map, @r###"
+        ⋮crate
+        ⋮bar: v
+        ⋮foo: v
+    "###);
+}
+
 #[test]
 fn infer_multiple_namespace() {
     let map = def_map(
@@ -546,3 +621,93 @@ mod b {
     ⋮T: t v
 "###);
 }
+
+fn def_map_diagnostics(fixture: &str) -> String {
+    let db = TestDB::with_files(fixture);
+    let krate = db.crate_graph().iter().next().unwrap();
+    let crate_def_map = db.crate_def_map(krate);
+
+    let mut buf = String::new();
+    let mut sink = DiagnosticSink::new(|d| {
+        buf += &format!("{:?}: {}\n", d.syntax_node(&db).text(), d.message());
+    });
+    for (module_id, _) in crate_def_map.modules.iter() {
+        crate_def_map.add_diagnostics(&db, module_id, &mut sink);
+    }
+    drop(sink);
+    buf
+}
+
+#[test]
+fn unresolved_import_diagnostics() {
+    let diagnostics = def_map_diagnostics(
+        r"
+        //- /lib.rs
+        mod foo {
+            pub struct Bar;
+            use super::nope;
+        }
+        use foo::{Bar, Baz, self};
+        use missing::Thing;
+        use foo::*;
+        ",
+    );
+
+    assert_snapshot!(diagnostics, @r###"
+    "Baz": unresolved import
+    "missing::Thing": unresolved import
+    "super::nope": unresolved import
+    "###);
+}
+
+#[test]
+fn unresolved_imports_are_not_reported_with_unexpanded_macros() {
+    let diagnostics = def_map_diagnostics(
+        r"
+        //- /lib.rs
+        generate_foo!();
+        use foo::Bar;
+        ",
+    );
+
+    assert_snapshot!(diagnostics, @"");
+}
+
+#[test]
+fn unresolved_imports_are_reported_outside_of_modules_with_unexpanded_macros() {
+    let diagnostics = def_map_diagnostics(
+        r"
+        //- /lib.rs
+        mod a { generate_bar!(); }
+        mod b {}
+        mod c { pub use super::a::*; }
+        use a::Bar;
+        use b::Bar;
+        use c::Bar;
+        use Baz;
+        ",
+    );
+
+    assert_snapshot!(diagnostics, @r###"
+    "b::Bar": unresolved import
+    "Baz": unresolved import
+    "###);
+}
+
+#[test]
+fn unresolved_imports_are_not_reported_with_failed_macro_expansions() {
+    let diagnostics = def_map_diagnostics(
+        r"
+        //- /lib.rs
+        macro_rules! gen {
+            ($name:ident) => { pub mod $name { pub struct Bar; } };
+        }
+        gen!(1);
+        use foo::Bar;
+        ",
+    );
+
+    assert_snapshot!(diagnostics, @r###"
+    "gen!(1);": no rules expected this macro input: rule #1 got furthest: expected `$name:ident`, found `1`
+    "###);
+}
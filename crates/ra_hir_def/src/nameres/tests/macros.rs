@@ -624,3 +624,316 @@ fn expand_multiple_derive() {
     );
     assert_eq!(map.modules[map.root].scope.impls().len(), 2);
 }
+
+#[test]
+fn macros_2_0_are_scoped_by_path() {
+    let map = def_map(
+        "
+        //- /main.rs
+        mod m {
+            pub macro structs($i:ident) { struct $i; }
+            macro private($i:ident) { struct $i; }
+            pub(crate) macro multi {
+                ($i:ident) => { struct $i; },
+                ($i:ident, $j:ident) => { struct $i; struct $j; },
+            }
+
+            structs!(InM);
+        }
+
+        use m::*;
+
+        m::structs!(Foo);
+        m::multi!(Bar, Baz);
+        structs!(ViaGlob);
+        private!(NotImported);
+
+        mod n {
+            structs!(NotTextual);
+            super::m::structs!(ViaPath);
+        }
+        ",
+    );
+    assert_snapshot!(map, @r###"
+        ⋮crate
+        ⋮Bar: t v
+        ⋮Baz: t v
+        ⋮Foo: t v
+        ⋮ViaGlob: t v
+        ⋮m: t
+        ⋮multi: m
+        ⋮n: t
+        ⋮structs: m
+        ⋮
+        ⋮crate::m
+        ⋮InM: t v
+        ⋮multi: m
+        ⋮private: m
+        ⋮structs: m
+        ⋮
+        ⋮crate::n
+        ⋮ViaPath: t v
+    "###);
+}
+
+#[test]
+fn partially_expanded_macro_still_defines_items() {
+    let map = def_map(
+        "
+        //- /lib.rs
+        macro_rules! items {
+            ($name:ident, $ty:ty) => {
+                struct $name;
+                type Alias = $ty;
+                fn helper() {}
+            }
+        }
+        items!(Foo, 1 +);
+        ",
+    );
+    assert_snapshot!(map, @r###"
+   ⋮crate
+   ⋮Alias: t
+   ⋮Foo: t v
+   ⋮helper: v
+    "###);
+}
+
+#[test]
+fn recursive_macro_stops_at_recursion_limit() {
+    let map = def_map(
+        r#"
+        //- /lib.rs
+        #![recursion_limit = "8"]
+        macro_rules! rec {
+            ($name:ident) => {
+                struct $name;
+                rec!($name);
+            }
+        }
+        rec!(Foo);
+        struct Bar;
+        "#,
+    );
+    assert_snapshot!(map, @r###"
+   ⋮crate
+   ⋮Bar: t v
+   ⋮Foo: t v
+    "###);
+}
+
+#[test]
+fn recursion_limit_respects_cfg_attr() {
+    fn recursion_limit(fixture: &str) -> u32 {
+        let db = TestDB::with_files(fixture);
+        let krate = db.crate_graph().iter().next().unwrap();
+        db.expansion_limits(krate).depth
+    }
+
+    let enabled = recursion_limit(
+        r#"
+        //- /lib.rs crate:foo cfg:test
+        #![cfg_attr(test, recursion_limit = "8")]
+        "#,
+    );
+    assert_eq!(enabled, 8);
+
+    let disabled = recursion_limit(
+        r#"
+        //- /lib.rs crate:foo cfg:test
+        #![cfg_attr(not(test), recursion_limit = "8")]
+        "#,
+    );
+    assert_eq!(disabled, ExpansionLimits::default().depth);
+}
+
+#[test]
+fn macro_use_imports_only_listed_macros() {
+    covers!(macro_use_imports_only_listed_macros);
+    let map = def_map(
+        r#"
+        //- /main.rs crate:main deps:foo
+        #[macro_use(structs, structs_other)]
+        extern crate foo;
+
+        structs!(Foo);
+        structs_other!(Bar);
+        not_listed!(NotResolved);
+
+        //- /lib.rs crate:foo
+        #[macro_export]
+        macro_rules! structs {
+            ($i:ident) => { struct $i; }
+        }
+        #[macro_export]
+        macro_rules! structs_other {
+            ($i:ident) => { struct $i; }
+        }
+        #[macro_export]
+        macro_rules! not_listed {
+            ($i:ident) => { struct $i; }
+        }
+        "#,
+    );
+    assert_snapshot!(map, @r###"
+        ⋮crate
+        ⋮Bar: t v
+        ⋮Foo: t v
+        ⋮foo: t
+    "###);
+}
+
+#[test]
+fn local_inner_macros_resolve_in_defining_crate() {
+    let map = def_map(
+        r#"
+        //- /main.rs crate:main deps:foo
+        macro_rules! inner {
+            ($i:ident) => { struct WrongInner; }
+        }
+
+        foo::structs!(Foo);
+
+        //- /lib.rs crate:foo
+        #[macro_export(local_inner_macros)]
+        macro_rules! structs {
+            ($i:ident) => { inner!($i); }
+        }
+        #[macro_export]
+        macro_rules! inner {
+            ($i:ident) => { struct $i; }
+        }
+        "#,
+    );
+    assert_snapshot!(map, @r###"
+        ⋮crate
+        ⋮Foo: t v
+    "###);
+}
+
+#[test]
+fn local_inner_macros_keep_macro_calls_from_caller() {
+    let map = def_map(
+        r#"
+        //- /main.rs crate:main deps:foo
+        macro_rules! inner {
+            ($i:ident) => { struct $i; }
+        }
+
+        foo::wrap!(inner!(Bar));
+
+        //- /lib.rs crate:foo
+        #[macro_export(local_inner_macros)]
+        macro_rules! wrap {
+            ($($tt:tt)*) => { $($tt)* }
+        }
+        #[macro_export]
+        macro_rules! inner {
+            ($i:ident) => { struct WrongInner; }
+        }
+        "#,
+    );
+    assert_snapshot!(map, @r###"
+        ⋮crate
+        ⋮Bar: t v
+    "###);
+}
+
+#[test]
+fn macros_reexported_with_pub_use_are_visible() {
+    let map = def_map(
+        r#"
+        //- /main.rs crate:main deps:foo
+        use foo::reexported;
+
+        foo::reexported!(ViaPath);
+        reexported!(ViaUse);
+        m::local!(ViaLocalReexport);
+
+        mod m {
+            macro_rules! local {
+                ($i:ident) => { struct $i; }
+            }
+            pub(crate) use local;
+        }
+
+        //- /lib.rs crate:foo deps:bar
+        pub use bar::structs as reexported;
+
+        //- /bar.rs crate:bar
+        #[macro_export]
+        macro_rules! structs {
+            ($i:ident) => { struct $i; }
+        }
+        "#,
+    );
+    assert_snapshot!(map, @r###"
+        ⋮crate
+        ⋮ViaLocalReexport: t v
+        ⋮ViaPath: t v
+        ⋮ViaUse: t v
+        ⋮m: t
+        ⋮reexported: m
+        ⋮
+        ⋮crate::m
+        ⋮local: m
+    "###);
+}
+
+#[test]
+fn legacy_macros_take_precedence_over_path_scoped_ones() {
+    let map = def_map(
+        r#"
+        //- /main.rs crate:main deps:foo
+        use foo::structs;
+
+        structs!(BeforeLegacy);
+
+        macro_rules! structs {
+            ($i:ident) => { struct Legacy; }
+        }
+
+        structs!(AfterLegacy);
+
+        //- /lib.rs crate:foo
+        #[macro_export]
+        macro_rules! structs {
+            ($i:ident) => { struct $i; }
+        }
+        "#,
+    );
+    assert_snapshot!(map, @r###"
+        ⋮crate
+        ⋮BeforeLegacy: t v
+        ⋮Legacy: t v
+        ⋮structs: m
+    "###);
+}
+
+#[test]
+fn plain_macro_falls_back_to_prelude() {
+    covers!(plain_macro_falls_back_to_prelude);
+    let map = def_map(
+        r#"
+        //- /main.rs crate:main deps:std
+        structs!(FromPrelude);
+
+        //- /std.rs crate:std
+        #[prelude_import]
+        use self::prelude::*;
+
+        mod prelude {
+            pub use crate::structs_impl as structs;
+        }
+
+        #[macro_export]
+        macro_rules! structs_impl {
+            ($i:ident) => { struct $i; }
+        }
+        "#,
+    );
+    assert_snapshot!(map, @r###"
+        ⋮crate
+        ⋮FromPrelude: t v
+    "###);
+}
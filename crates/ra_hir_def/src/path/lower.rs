@@ -13,7 +13,7 @@ use ra_syntax::ast::{self, AstNode, TypeAscriptionOwner};
 
 use crate::{
     path::{GenericArg, GenericArgs, ModPath, Path, PathKind},
-    type_ref::TypeRef,
+    type_ref::{ConstRef, LifetimeRef, TypeRef},
 };
 
 pub(super) use lower_use::lower_use_tree;
@@ -21,6 +21,7 @@ pub(super) use lower_use::lower_use_tree;
 /// Converts an `ast::Path` to `Path`. Works with use trees.
 /// It correctly handles `$crate` based path from macro call.
 pub(super) fn lower_path(mut path: ast::Path, hygiene: &Hygiene) -> Option<Path> {
+    let is_macro_call_path = path.syntax().parent().and_then(ast::MacroCall::cast).is_some();
     let mut kind = PathKind::Plain;
     let mut type_anchor = None;
     let mut segments = Vec::new();
@@ -112,6 +113,20 @@ pub(super) fn lower_path(mut path: ast::Path, hygiene: &Hygiene) -> Option<Path>
     }
     segments.reverse();
     generic_args.reverse();
+
+    // Like rustc, we resolve plain macro calls written in the body of a
+    // `local_inner_macros` macro in the crate which defines the macro.
+    if is_macro_call_path
+        && kind == PathKind::Plain
+        && segments.len() == 1
+        && segments[0] != name![macro_rules]
+    {
+        let name_token = path.segment().and_then(|it| it.name_ref()?.syntax().first_token());
+        if let Some(crate_id) = name_token.and_then(|it| hygiene.local_inner_macros(&it)) {
+            kind = PathKind::DollarCrate(crate_id);
+        }
+    }
+
     let mod_path = ModPath { kind, segments };
     return Some(Path { type_anchor, mod_path, generic_args });
 
@@ -129,11 +144,20 @@ pub(super) fn lower_path(mut path: ast::Path, hygiene: &Hygiene) -> Option<Path>
 
 pub(super) fn lower_generic_args(node: ast::TypeArgList) -> Option<GenericArgs> {
     let mut args = Vec::new();
-    for type_arg in node.type_args() {
-        let type_ref = TypeRef::from_ast_opt(type_arg.type_ref());
-        args.push(GenericArg::Type(type_ref));
+    for lifetime_arg in node.lifetime_args() {
+        if let Some(lifetime) = lifetime_arg.lifetime_token() {
+            args.push(GenericArg::Lifetime(LifetimeRef::new(&lifetime)));
+        }
+    }
+    // type and const args may be interleaved, so keep them in source order
+    for arg in node.syntax().children() {
+        if let Some(type_arg) = ast::TypeArg::cast(arg.clone()) {
+            let type_ref = TypeRef::from_ast_opt(type_arg.type_ref());
+            args.push(GenericArg::Type(type_ref));
+        } else if let Some(const_arg) = ast::ConstArg::cast(arg) {
+            args.push(GenericArg::Const(ConstRef::from_ast(const_arg)));
+        }
     }
-    // lifetimes ignored for now
     let mut bindings = Vec::new();
     for assoc_type_arg in node.assoc_type_args() {
         if let Some(name_ref) = assoc_type_arg.name_ref() {
@@ -2,6 +2,8 @@
 use std::sync::Arc;
 
 use hir_expand::{
+    builtin_attr::find_builtin_attr,
+    hygiene::SyntaxContext,
     name::{name, Name},
     MacroDefId,
 };
@@ -14,15 +16,15 @@ use crate::{
     builtin_type::BuiltinType,
     db::DefDatabase,
     expr::{ExprId, PatId},
-    generics::GenericParams,
+    generics::{GenericParams, TypeParamProvenance},
     item_scope::BuiltinShadowMode,
     nameres::CrateDefMap,
     path::{ModPath, PathKind},
     per_ns::PerNs,
     visibility::{RawVisibility, Visibility},
     AdtId, AssocContainerId, ConstId, ContainerId, DefWithBodyId, EnumId, EnumVariantId,
-    FunctionId, GenericDefId, HasModule, ImplId, LocalModuleId, Lookup, ModuleDefId, ModuleId,
-    StaticId, StructId, TraitId, TypeAliasId, TypeParamId, VariantId,
+    FunctionId, GenericDefId, HasModule, ImplId, LifetimeParamId, LocalModuleId, Lookup,
+    ModuleDefId, ModuleId, StaticId, StructId, TraitId, TypeAliasId, TypeParamId, VariantId,
 };
 
 #[derive(Debug, Clone, Default)]
@@ -43,6 +45,8 @@ struct ExprScope {
     owner: DefWithBodyId,
     expr_scopes: Arc<ExprScopes>,
     scope_id: ScopeId,
+    /// Only the bindings with this syntax context are visible.
+    hygiene: SyntaxContext,
 }
 
 #[derive(Debug, Clone)]
@@ -92,6 +96,8 @@ pub enum ValueNs {
     StaticId(StaticId),
     StructId(StructId),
     EnumVariantId(EnumVariantId),
+    /// A const generic param, like the `N` of `const N: usize`.
+    ConstParam(TypeParamId),
 }
 
 impl Resolver {
@@ -271,11 +277,9 @@ impl Resolver {
                 }
 
                 Scope::ExprScope(scope) if n_segments <= 1 => {
-                    let entry = scope
-                        .expr_scopes
-                        .entries(scope.scope_id)
-                        .iter()
-                        .find(|entry| entry.name() == first_name);
+                    let entry = scope.expr_scopes.entries(scope.scope_id).iter().find(|entry| {
+                        entry.name() == first_name && entry.hygiene() == scope.hygiene
+                    });
 
                     if let Some(e) = entry {
                         return Some(ResolveValueResult::ValueNs(ValueNs::LocalBinding(e.pat())));
@@ -289,7 +293,14 @@ impl Resolver {
                         return Some(ResolveValueResult::Partial(ty, 1));
                     }
                 }
-                Scope::GenericParams { .. } => continue,
+                Scope::GenericParams { params, def } => {
+                    if let Some(local_id) = params.find_by_name(first_name) {
+                        if params.types[local_id].provenance == TypeParamProvenance::ConstParam {
+                            let id = TypeParamId { local_id, parent: *def };
+                            return Some(ResolveValueResult::ValueNs(ValueNs::ConstParam(id)));
+                        }
+                    }
+                }
 
                 Scope::ImplBlockScope(impl_) if n_segments > 1 => {
                     if first_name == &name![Self] {
@@ -383,6 +394,16 @@ impl Resolver {
         item_map.resolve_path(db, module, &path, BuiltinShadowMode::Other).0.take_macros()
     }
 
+    /// Resolves the path of an attribute to the attribute macro it invokes.
+    /// Builtin attributes like `#[test]` resolve even if they are not in scope.
+    pub fn resolve_path_as_attr(
+        &self,
+        db: &impl DefDatabase,
+        path: &ModPath,
+    ) -> Option<MacroDefId> {
+        self.resolve_path_as_macro(db, path).or_else(|| find_builtin_attr(path.as_ident()?))
+    }
+
     pub fn process_all_names(&self, db: &impl DefDatabase, f: &mut dyn FnMut(Name, ScopeDef)) {
         for scope in self.scopes.iter().rev() {
             scope.process_names(db, f);
@@ -433,6 +454,18 @@ impl Resolver {
             .flat_map(|params| params.where_predicates.iter())
     }
 
+    /// Resolves a lifetime like `'a` to the generic lifetime parameter it
+    /// refers to.
+    pub fn resolve_lifetime_param(&self, name: &Name) -> Option<LifetimeParamId> {
+        self.scopes.iter().rev().find_map(|scope| match scope {
+            Scope::GenericParams { params, def } => {
+                let local_id = params.find_lifetime_by_name(name)?;
+                Some(LifetimeParamId { parent: *def, local_id })
+            }
+            _ => None,
+        })
+    }
+
     pub fn generic_def(&self) -> Option<GenericDefId> {
         self.scopes.iter().rev().find_map(|scope| match scope {
             Scope::GenericParams { def, .. } => Some(*def),
@@ -505,9 +538,14 @@ impl Scope {
                 f(name![Self], ScopeDef::AdtSelfType((*i).into()));
             }
             Scope::ExprScope(scope) => {
-                scope.expr_scopes.entries(scope.scope_id).iter().for_each(|e| {
-                    f(e.name().clone(), ScopeDef::Local(e.pat()));
-                });
+                scope
+                    .expr_scopes
+                    .entries(scope.scope_id)
+                    .iter()
+                    .filter(|e| e.hygiene() == scope.hygiene)
+                    .for_each(|e| {
+                        f(e.name().clone(), ScopeDef::Local(e.pat()));
+                    });
             }
         }
     }
@@ -516,7 +554,8 @@ impl Scope {
 // needs arbitrary_self_types to be a method... or maybe move to the def?
 pub fn resolver_for_expr(db: &impl DefDatabase, owner: DefWithBodyId, expr_id: ExprId) -> Resolver {
     let scopes = db.expr_scopes(owner);
-    resolver_for_scope(db, owner, scopes.scope_for(expr_id))
+    let hygiene = db.body(owner).expr_hygiene(expr_id);
+    resolver_for_scope_with_hygiene(db, owner, scopes.scope_for(expr_id), hygiene)
 }
 
 pub fn resolver_for_scope(
@@ -524,16 +563,42 @@ pub fn resolver_for_scope(
     owner: DefWithBodyId,
     scope_id: Option<ScopeId>,
 ) -> Resolver {
-    let mut r = owner.resolver(db);
-    r = r.push_local_items_scope(db.body(owner));
+    resolver_for_scope_with_hygiene(db, owner, scope_id, SyntaxContext::ROOT)
+}
+
+fn resolver_for_scope_with_hygiene(
+    db: &impl DefDatabase,
+    owner: DefWithBodyId,
+    scope_id: Option<ScopeId>,
+    hygiene: SyntaxContext,
+) -> Resolver {
+    let mut r = match def_site_module(db, hygiene) {
+        // Paths from the body of a macro 2.0 see the items around the macro
+        // definition, not the ones around the call.
+        Some(module) => module.resolver(db),
+        None => owner.resolver(db).push_local_items_scope(db.body(owner)),
+    };
     let scopes = db.expr_scopes(owner);
     let scope_chain = scopes.scope_chain(scope_id).collect::<Vec<_>>();
     for scope in scope_chain.into_iter().rev() {
-        r = r.push_expr_scope(owner, Arc::clone(&scopes), scope);
+        r = r.push_expr_scope(owner, Arc::clone(&scopes), scope, hygiene);
     }
     r
 }
 
+/// The resolver for paths with the syntax context `hygiene`, if they come from
+/// the body of a macro 2.0, and so are resolved at its definition site.
+pub fn def_site_resolver(db: &impl DefDatabase, hygiene: SyntaxContext) -> Option<Resolver> {
+    def_site_module(db, hygiene).map(|module| module.resolver(db))
+}
+
+fn def_site_module(db: &impl DefDatabase, hygiene: SyntaxContext) -> Option<ModuleId> {
+    let macro_def = hygiene.def_site_macro(db)?;
+    let krate = macro_def.krate?;
+    let local_id = db.crate_def_map(krate).macro_def_module(macro_def.ast_id?)?;
+    Some(ModuleId { krate, local_id })
+}
+
 impl Resolver {
     fn push_scope(mut self, scope: Scope) -> Resolver {
         self.scopes.push(scope);
@@ -570,8 +635,9 @@ impl Resolver {
         owner: DefWithBodyId,
         expr_scopes: Arc<ExprScopes>,
         scope_id: ScopeId,
+        hygiene: SyntaxContext,
     ) -> Resolver {
-        self.push_scope(Scope::ExprScope(ExprScope { owner, expr_scopes, scope_id }))
+        self.push_scope(Scope::ExprScope(ExprScope { owner, expr_scopes, scope_id, hygiene }))
     }
 }
 
@@ -1,9 +1,15 @@
 //! HIR for references to types. Paths in these are not yet resolved. They can
 //! be directly created from an ast::TypeRef, without further queries.
 
-use ra_syntax::ast::{self, TypeAscriptionOwner, TypeBoundsOwner};
+use hir_expand::name::Name;
+use std::fmt;
 
-use crate::path::Path;
+use ra_syntax::{
+    ast::{self, LiteralKind, TypeAscriptionOwner, TypeBoundsOwner},
+    SyntaxToken,
+};
+
+use crate::path::{GenericArg, Path};
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum Mutability {
@@ -43,7 +49,7 @@ pub enum TypeRef {
     Tuple(Vec<TypeRef>),
     Path(Path),
     RawPtr(Box<TypeRef>, Mutability),
-    Reference(Box<TypeRef>, Mutability),
+    Reference(Box<TypeRef>, Option<LifetimeRef>, Mutability),
     Array(Box<TypeRef> /*, Expr*/),
     Slice(Box<TypeRef>),
     /// A fn pointer. Last element of the vector is the return type.
@@ -54,6 +60,81 @@ pub enum TypeRef {
     Error,
 }
 
+/// A lifetime, like `'a` or `'static`, as written in the source.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct LifetimeRef {
+    pub name: Name,
+}
+
+impl LifetimeRef {
+    pub(crate) fn new(lifetime: &SyntaxToken) -> Self {
+        LifetimeRef { name: Name::new_lifetime(lifetime) }
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.name.to_string() == "'static"
+    }
+}
+
+/// A const generic argument, like the `16` in `Buf<16>`.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ConstRef {
+    Scalar(ConstScalar),
+    /// A const argument we can't evaluate, like `{ N + 1 }`.
+    Unknown,
+}
+
+/// The value of a const generic argument.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ConstScalar {
+    Int(u128),
+    Bool(bool),
+    Char(char),
+}
+
+impl ConstRef {
+    pub(crate) fn from_ast(arg: ast::ConstArg) -> Self {
+        // `{ 2 }` is just as good as `2`
+        let literal = arg.literal().or_else(|| match arg.block_expr()?.block()?.expr()? {
+            ast::Expr::Literal(it) => Some(it),
+            _ => None,
+        });
+        match literal.and_then(|it| ConstScalar::from_literal(&it)) {
+            Some(scalar) => ConstRef::Scalar(scalar),
+            None => ConstRef::Unknown,
+        }
+    }
+}
+
+impl ConstScalar {
+    fn from_literal(literal: &ast::Literal) -> Option<Self> {
+        let token = literal.token();
+        let text = token.text().as_str();
+        match literal.kind() {
+            LiteralKind::IntNumber { .. } => literal.int_value().map(ConstScalar::Int),
+            LiteralKind::Bool => Some(ConstScalar::Bool(text == "true")),
+            LiteralKind::Char => {
+                let mut chars = text.trim_start_matches('\'').trim_end_matches('\'').chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if c != '\\' => Some(ConstScalar::Char(c)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ConstScalar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstScalar::Int(it) => write!(f, "{}", it),
+            ConstScalar::Bool(it) => write!(f, "{}", it),
+            ConstScalar::Char(it) => write!(f, "{:?}", it),
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum TypeBound {
     Path(Path),
@@ -88,8 +169,9 @@ impl TypeRef {
             }
             ast::TypeRef::ReferenceType(inner) => {
                 let inner_ty = TypeRef::from_ast_opt(inner.type_ref());
+                let lifetime = inner.lifetime_token().map(|it| LifetimeRef::new(&it));
                 let mutability = Mutability::from_mutable(inner.is_mut());
-                TypeRef::Reference(Box::new(inner_ty), mutability)
+                TypeRef::Reference(Box::new(inner_ty), lifetime, mutability)
             }
             ast::TypeRef::PlaceholderType(_inner) => TypeRef::Placeholder,
             ast::TypeRef::FnPointerType(inner) => {
@@ -133,7 +215,7 @@ impl TypeRef {
             match type_ref {
                 TypeRef::Fn(types) | TypeRef::Tuple(types) => types.iter().for_each(|t| go(t, f)),
                 TypeRef::RawPtr(type_ref, _)
-                | TypeRef::Reference(type_ref, _)
+                | TypeRef::Reference(type_ref, ..)
                 | TypeRef::Array(type_ref)
                 | TypeRef::Slice(type_ref) => go(&type_ref, f),
                 TypeRef::ImplTrait(bounds) | TypeRef::DynTrait(bounds) => {
@@ -156,8 +238,9 @@ impl TypeRef {
             for segment in path.segments().iter() {
                 if let Some(args_and_bindings) = segment.args_and_bindings {
                     for arg in &args_and_bindings.args {
-                        let crate::path::GenericArg::Type(type_ref) = arg;
-                        go(type_ref, f);
+                        if let GenericArg::Type(type_ref) = arg {
+                            go(type_ref, f);
+                        }
                     }
                     for (_, type_ref) in &args_and_bindings.bindings {
                         go(type_ref, f);
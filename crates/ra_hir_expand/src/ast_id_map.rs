@@ -1,5 +1,5 @@
 //! `AstIdMap` allows to create stable IDs for "large" syntax nodes like items
-//! and macro calls.
+//! and macro calls and definitions.
 //!
 //! Specifically, it enumerates all items in a file and uses position of a an
 //! item as an ID. That way, id's don't change unless the set of items itself
@@ -70,8 +70,8 @@ impl AstIdMap {
         bfs(node, |it| {
             if let Some(module_item) = ast::ModuleItem::cast(it.clone()) {
                 res.alloc(module_item.syntax());
-            } else if let Some(macro_call) = ast::MacroCall::cast(it) {
-                res.alloc(macro_call.syntax());
+            } else if let Some(macro_) = ast::Macro::cast(it) {
+                res.alloc(macro_.syntax());
             }
         });
         res
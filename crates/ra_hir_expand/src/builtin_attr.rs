@@ -0,0 +1,148 @@
+//! Builtin attributes.
+//!
+//! Attributes like `#[test]` are attribute macros defined by the compiler.
+//! Their effect on name resolution (for example, test functions only exist
+//! when compiling with `cfg(test)`) is handled by the def collector; their
+//! expansion only removes the attribute itself.
+
+use crate::db::AstDatabase;
+use crate::{name, MacroCallId, MacroDefId, MacroDefKind};
+
+macro_rules! register_builtin {
+    ( $(($name:ident, $kind: ident) => $expand:ident),* ) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum BuiltinAttrExpander {
+            $($kind),*
+        }
+
+        impl BuiltinAttrExpander {
+            pub fn expand(
+                &self,
+                db: &dyn AstDatabase,
+                id: MacroCallId,
+                tt: &tt::Subtree,
+            ) -> Result<tt::Subtree, mbe::ExpandError> {
+                let expander = match *self {
+                    $( BuiltinAttrExpander::$kind => $expand, )*
+                };
+                expander(db, id, tt, &self.name())
+            }
+
+            pub fn name(&self) -> name::Name {
+                match *self {
+                    $( BuiltinAttrExpander::$kind => name::name![$name], )*
+                }
+            }
+
+            fn by_name(ident: &name::Name) -> Option<BuiltinAttrExpander> {
+                match ident {
+                    $( id if id == &name::name![$name] => Some(BuiltinAttrExpander::$kind), )*
+                    _ => return None,
+                }
+            }
+        }
+
+        pub fn find_builtin_attr(ident: &name::Name) -> Option<MacroDefId> {
+            let kind = BuiltinAttrExpander::by_name(ident)?;
+            Some(MacroDefId {
+                krate: None,
+                ast_id: None,
+                kind: MacroDefKind::BuiltInAttr(kind),
+                local_inner: false,
+            })
+        }
+    };
+}
+
+register_builtin! {
+    (bench, Bench) => strip_attr_expand,
+    (cfg_eval, CfgEval) => strip_attr_expand,
+    (global_allocator, GlobalAllocator) => strip_attr_expand,
+    (test, Test) => strip_attr_expand,
+    (test_case, TestCase) => strip_attr_expand
+}
+
+impl BuiltinAttrExpander {
+    /// Whether items carrying this attribute only exist when compiling with
+    /// `cfg(test)`.
+    pub fn is_test_only(&self) -> bool {
+        match self {
+            BuiltinAttrExpander::Bench
+            | BuiltinAttrExpander::Test
+            | BuiltinAttrExpander::TestCase => true,
+            BuiltinAttrExpander::CfgEval | BuiltinAttrExpander::GlobalAllocator => false,
+        }
+    }
+}
+
+/// Returns the annotated item unchanged, except for the `#[name]` attribute
+/// which invoked the expansion.
+fn strip_attr_expand(
+    _db: &dyn AstDatabase,
+    _id: MacroCallId,
+    tt: &tt::Subtree,
+    name: &name::Name,
+) -> Result<tt::Subtree, mbe::ExpandError> {
+    let mut token_trees = tt.token_trees.clone();
+    // Attributes of the item are not token trees, so `#[test]` is converted
+    // into the flat sequence `#`, `[`, `test`, `]`.
+    if let Some(start) = token_trees.windows(4).position(|it| is_simple_attr(it, name)) {
+        token_trees.drain(start..start + 4);
+    }
+    Ok(tt::Subtree { delimiter: tt.delimiter, token_trees })
+}
+
+fn is_simple_attr(tokens: &[tt::TokenTree], name: &name::Name) -> bool {
+    let leaf = |idx: usize| match &tokens[idx] {
+        tt::TokenTree::Leaf(it) => Some(it),
+        tt::TokenTree::Subtree(_) => None,
+    };
+    let is_punct = |idx: usize, c: char| match leaf(idx) {
+        Some(tt::Leaf::Punct(it)) => it.char == c,
+        _ => false,
+    };
+    let is_name = match leaf(2) {
+        Some(tt::Leaf::Ident(it)) => it.text == name.to_string(),
+        _ => false,
+    };
+    is_punct(0, '#') && is_punct(1, '[') && is_name && is_punct(3, ']')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_db::TestDB, AstId, MacroCallKind, MacroCallLoc};
+    use ra_db::{fixture::WithFixture, SourceDatabase};
+    use ra_syntax::{ast, AstNode};
+
+    fn expand_builtin_attr(s: &str, name: name::Name) -> String {
+        let def = find_builtin_attr(&name).unwrap();
+        let (db, file_id) = TestDB::with_single_file(&s);
+        let parsed = db.parse(file_id);
+        let items: Vec<_> =
+            parsed.syntax_node().descendants().filter_map(ast::ModuleItem::cast).collect();
+
+        let ast_id_map = db.ast_id_map(file_id.into());
+        let attr_id = AstId::new(file_id.into(), ast_id_map.ast_id(&items[0]));
+
+        let loc = MacroCallLoc { def, kind: MacroCallKind::Attr(attr_id) };
+        let id: MacroCallId = db.intern_macro(loc);
+        let expanded = db.macro_expand(id).0.unwrap();
+        assert!(db.parse_or_expand(id.as_file()).is_some());
+        expanded.to_string()
+    }
+
+    #[test]
+    fn test_expand_test_attr() {
+        let expanded = expand_builtin_attr(
+            r#"
+        #[test]
+        #[should_panic]
+        fn foo() {}
+"#,
+            name::known::test,
+        );
+
+        assert_eq!(expanded, "#[ should_panic ] fn foo () {}");
+    }
+}
@@ -4,8 +4,8 @@ use log::debug;
 
 use ra_parser::FragmentKind;
 use ra_syntax::{
-    ast::{self, AstNode, ModuleItemOwner, NameOwner, TypeParamsOwner},
-    match_ast,
+    ast::{self, AstNode, ModuleItemOwner, NameOwner, TypeBoundsOwner, TypeParamsOwner},
+    match_ast, SyntaxNode, TextRange,
 };
 
 use crate::db::AstDatabase;
@@ -38,7 +38,12 @@ macro_rules! register_builtin {
                  _ => return None,
             };
 
-            Some(MacroDefId { krate: None, ast_id: None, kind: MacroDefKind::BuiltInDerive(kind) })
+            Some(MacroDefId {
+                krate: None,
+                ast_id: None,
+                kind: MacroDefKind::BuiltInDerive(kind),
+                local_inner: false,
+            })
         }
     };
 }
@@ -57,7 +62,233 @@ register_builtin! {
 
 struct BasicAdtInfo {
     name: tt::Ident,
-    type_params: usize,
+    /// Lifetime parameters, as written (`'a: 'b`), together with the lifetime.
+    lifetime_params: Vec<(tt::Ident, Vec<tt::TokenTree>)>,
+    /// Type parameters with their original bounds.
+    type_params: Vec<(tt::Ident, Vec<tt::TokenTree>)>,
+    /// Const parameters, as written (`const N: usize`), together with the name.
+    const_params: Vec<(tt::Ident, Vec<tt::TokenTree>)>,
+    where_clause: Vec<tt::TokenTree>,
+    shape: AdtShape,
+}
+
+enum AdtShape {
+    Struct(VariantShape),
+    Enum(Vec<(tt::Ident, VariantShape)>),
+    Union,
+}
+
+enum VariantShape {
+    Record(Vec<tt::Ident>),
+    Tuple(usize),
+    Unit,
+}
+
+impl VariantShape {
+    fn new(kind: ast::StructKind, token_map: &mbe::TokenMap) -> VariantShape {
+        match kind {
+            ast::StructKind::Record(fields) => VariantShape::Record(
+                fields
+                    .fields()
+                    .filter_map(|it| it.name())
+                    .map(|it| name_to_ident(&it, token_map))
+                    .collect(),
+            ),
+            ast::StructKind::Tuple(fields) => VariantShape::Tuple(fields.fields().count()),
+            ast::StructKind::Unit => VariantShape::Unit,
+        }
+    }
+
+    fn field_count(&self) -> usize {
+        match self {
+            VariantShape::Record(fields) => fields.len(),
+            VariantShape::Tuple(n) => *n,
+            VariantShape::Unit => 0,
+        }
+    }
+
+    /// A pattern binding each field to `{prefix}_{index}`, like
+    /// `Foo { a: __self_0, b: __self_1 }`.
+    fn pattern(
+        &self,
+        path: &[tt::TokenTree],
+        prefix: &str,
+    ) -> (Vec<tt::TokenTree>, Vec<tt::Ident>) {
+        let bindings: Vec<_> =
+            (0..self.field_count()).map(|i| make_ident(&format!("{}_{}", prefix, i))).collect();
+        let pattern = match self {
+            VariantShape::Record(fields) => {
+                let fields = fields.iter().zip(bindings.iter()).map(|(field, binding)| {
+                    quote! { #field : #binding , }
+                });
+                self.construct(path, fields.collect())
+            }
+            VariantShape::Tuple(_) => {
+                let fields = bindings.iter().map(|binding| quote! { #binding , });
+                self.construct(path, fields.collect())
+            }
+            VariantShape::Unit => path.to_vec(),
+        };
+        (pattern, bindings)
+    }
+
+    /// A pattern matching the variant without binding anything.
+    fn wildcard_pattern(&self, path: &[tt::TokenTree]) -> Vec<tt::TokenTree> {
+        let path = path.to_vec();
+        let pattern = match self {
+            VariantShape::Record(_) => quote! { ##path { .. } },
+            VariantShape::Tuple(_) => quote! { ##path ( .. ) },
+            VariantShape::Unit => quote! { ##path },
+        };
+        pattern.token_trees
+    }
+
+    /// An expression (or pattern) of the variant, with fields given by
+    /// `values`. For record variants, each value is expected to include the
+    /// field name.
+    fn construct(&self, path: &[tt::TokenTree], values: Vec<tt::Subtree>) -> Vec<tt::TokenTree> {
+        let path = path.to_vec();
+        let values = values.into_iter().flat_map(|it| it.token_trees);
+        let expr = match self {
+            VariantShape::Record(_) => quote! { ##path { ##values } },
+            VariantShape::Tuple(_) => quote! { ##path ( ##values ) },
+            VariantShape::Unit => quote! { ##path },
+        };
+        expr.token_trees
+    }
+
+    fn construct_with(
+        &self,
+        path: &[tt::TokenTree],
+        mut value: impl FnMut() -> tt::Subtree,
+    ) -> Vec<tt::TokenTree> {
+        let values = match self {
+            VariantShape::Record(fields) => fields
+                .iter()
+                .map(|field| {
+                    let value = value().token_trees;
+                    quote! { #field : ##value , }
+                })
+                .collect(),
+            VariantShape::Tuple(n) => (0..*n)
+                .map(|_| {
+                    let value = value().token_trees;
+                    quote! { ##value , }
+                })
+                .collect(),
+            VariantShape::Unit => Vec::new(),
+        };
+        self.construct(path, values)
+    }
+}
+
+impl BasicAdtInfo {
+    /// Paths, names and shapes of all variants, a struct being its only
+    /// variant.
+    fn variants(&self) -> Vec<(Vec<tt::TokenTree>, &tt::Ident, &VariantShape)> {
+        let name = &self.name;
+        match &self.shape {
+            AdtShape::Struct(shape) => vec![(quote! { #name }.token_trees, name, shape)],
+            AdtShape::Enum(variants) => variants
+                .iter()
+                .map(|(variant, shape)| (quote! { #name :: #variant }.token_trees, variant, shape))
+                .collect(),
+            AdtShape::Union => Vec::new(),
+        }
+    }
+
+    fn is_multi_variant_enum(&self) -> bool {
+        match &self.shape {
+            AdtShape::Enum(variants) => variants.len() > 1,
+            _ => false,
+        }
+    }
+
+    /// `<'a, T: Bound + Trait, const N: usize>`: the parameters of the derived impl.
+    fn impl_params(&self, trait_path: &[tt::TokenTree]) -> Vec<tt::TokenTree> {
+        let lifetime_params = self.lifetime_params.iter().map(|(_, param)| param.clone());
+        let type_params = self.type_params.iter().map(|(name, bounds)| {
+            let trait_path = trait_path.to_vec();
+            let bound = if bounds.is_empty() {
+                quote! { #name : ##trait_path }
+            } else {
+                let bounds = bounds.clone();
+                quote! { #name : ##bounds + ##trait_path }
+            };
+            bound.token_trees
+        });
+        let const_params = self.const_params.iter().map(|(_, param)| param.clone());
+        angle_bracketed(lifetime_params.chain(type_params).chain(const_params).collect())
+    }
+
+    /// `<'a, T, N>`: the arguments of the type in the derived impl.
+    fn type_args(&self) -> Vec<tt::TokenTree> {
+        let lifetimes = self.lifetime_params.iter().map(|(lifetime, _)| lifetime);
+        let types = self.type_params.iter().map(|(name, _)| name);
+        let consts = self.const_params.iter().map(|(name, _)| name);
+        let args = lifetimes.chain(types).chain(consts);
+        angle_bracketed(args.map(|it| quote! { #it }.token_trees).collect())
+    }
+}
+
+fn angle_bracketed(items: Vec<Vec<tt::TokenTree>>) -> Vec<tt::TokenTree> {
+    if items.is_empty() {
+        return Vec::new();
+    }
+    let mut tokens = Vec::new();
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 {
+            tokens.extend(quote! { , }.token_trees);
+        }
+        tokens.extend(item);
+    }
+    quote! { < ##tokens > }.token_trees
+}
+
+fn make_ident(text: &str) -> tt::Ident {
+    tt::Ident { text: text.into(), id: tt::TokenId::unspecified() }
+}
+
+fn name_to_ident(name: &ast::Name, token_map: &mbe::TokenMap) -> tt::Ident {
+    let id = token_map
+        .token_by_range(name.syntax().text_range())
+        .unwrap_or_else(tt::TokenId::unspecified);
+    tt::Ident { id, text: name.text().clone() }
+}
+
+/// Converts a node of the parsed macro input back to tokens, keeping the ids
+/// of the original tokens.
+fn node_to_tokens(node: &SyntaxNode, token_map: &mbe::TokenMap) -> Vec<tt::TokenTree> {
+    let (mut subtree, node_map) = match mbe::syntax_node_to_token_tree(node) {
+        Some(it) => it,
+        None => return Vec::new(),
+    };
+    let offset = node.text_range().start();
+    let original_id = |id: tt::TokenId| -> tt::TokenId {
+        let range = match node_map.range_by_token(id) {
+            Some(mbe::TokenTextRange::Token(it)) => it,
+            Some(mbe::TokenTextRange::Delimiter(open, _)) => open,
+            None => return tt::TokenId::unspecified(),
+        };
+        let range = TextRange::offset_len(range.start() + offset, range.len());
+        token_map.token_by_range(range).unwrap_or_else(tt::TokenId::unspecified)
+    };
+    remap_ids(&mut subtree, &original_id);
+    subtree.token_trees
+}
+
+fn remap_ids(subtree: &mut tt::Subtree, f: &impl Fn(tt::TokenId) -> tt::TokenId) {
+    if let Some(delimiter) = &mut subtree.delimiter {
+        delimiter.id = f(delimiter.id);
+    }
+    for token in subtree.token_trees.iter_mut() {
+        match token {
+            tt::TokenTree::Leaf(tt::Leaf::Ident(it)) => it.id = f(it.id),
+            tt::TokenTree::Leaf(tt::Leaf::Punct(it)) => it.id = f(it.id),
+            tt::TokenTree::Leaf(tt::Leaf::Literal(it)) => it.id = f(it.id),
+            tt::TokenTree::Subtree(it) => remap_ids(it, f),
+        }
+    }
 }
 
 fn parse_adt(tt: &tt::Subtree) -> Result<BasicAdtInfo, mbe::ExpandError> {
@@ -71,11 +302,27 @@ fn parse_adt(tt: &tt::Subtree) -> Result<BasicAdtInfo, mbe::ExpandError> {
         mbe::ExpandError::NoMatchingRule
     })?;
     let node = item.syntax();
-    let (name, params) = match_ast! {
+    let (name, params, where_clause, shape) = match_ast! {
         match node {
-            ast::StructDef(it) => { (it.name(), it.type_param_list()) },
-            ast::EnumDef(it) => { (it.name(), it.type_param_list()) },
-            ast::UnionDef(it) => { (it.name(), it.type_param_list()) },
+            ast::StructDef(it) => {
+                let shape = AdtShape::Struct(VariantShape::new(it.kind(), &token_map));
+                (it.name(), it.type_param_list(), it.where_clause(), shape)
+            },
+            ast::EnumDef(it) => {
+                let variants = it
+                    .variant_list()
+                    .into_iter()
+                    .flat_map(|it| it.variants())
+                    .filter_map(|variant| {
+                        let name = name_to_ident(&variant.name()?, &token_map);
+                        Some((name, VariantShape::new(variant.kind(), &token_map)))
+                    })
+                    .collect();
+                (it.name(), it.type_param_list(), it.where_clause(), AdtShape::Enum(variants))
+            },
+            ast::UnionDef(it) => {
+                (it.name(), it.type_param_list(), it.where_clause(), AdtShape::Union)
+            },
             _ => {
                 debug!("unexpected node is {:?}", node);
                 return Err(mbe::ExpandError::ConversionError)
@@ -91,64 +338,68 @@ fn parse_adt(tt: &tt::Subtree) -> Result<BasicAdtInfo, mbe::ExpandError> {
         mbe::ExpandError::ConversionError
     })?;
     let name_token = tt::Ident { id: name_token_id, text: name.text().clone() };
-    let type_params = params.map_or(0, |type_param_list| type_param_list.type_params().count());
-    Ok(BasicAdtInfo { name: name_token, type_params })
-}
 
-fn make_type_args(n: usize, bound: Vec<tt::TokenTree>) -> Vec<tt::TokenTree> {
-    let mut result = Vec::<tt::TokenTree>::new();
-    result.push(
-        tt::Leaf::Punct(tt::Punct {
-            char: '<',
-            spacing: tt::Spacing::Alone,
-            id: tt::TokenId::unspecified(),
-        })
-        .into(),
-    );
-    for i in 0..n {
-        if i > 0 {
-            result.push(
-                tt::Leaf::Punct(tt::Punct {
-                    char: ',',
-                    spacing: tt::Spacing::Alone,
-                    id: tt::TokenId::unspecified(),
-                })
-                .into(),
-            );
+    let mut lifetime_params = Vec::new();
+    let mut type_params = Vec::new();
+    let mut const_params = Vec::new();
+    if let Some(params) = params {
+        for param in params.lifetime_params() {
+            let lifetime = match param.lifetime_token() {
+                Some(it) => it,
+                None => continue,
+            };
+            let id = token_map
+                .token_by_range(lifetime.text_range())
+                .unwrap_or_else(tt::TokenId::unspecified);
+            let lifetime = tt::Ident { id, text: lifetime.text().clone() };
+            lifetime_params.push((lifetime, node_to_tokens(param.syntax(), &token_map)));
+        }
+        for param in params.type_params() {
+            let name = match param.name() {
+                Some(it) => name_to_ident(&it, &token_map),
+                None => continue,
+            };
+            let bounds = param
+                .type_bound_list()
+                .map(|it| node_to_tokens(it.syntax(), &token_map))
+                .unwrap_or_default();
+            type_params.push((name, bounds));
+        }
+        for param in params.const_params() {
+            let name = match param.name() {
+                Some(it) => name_to_ident(&it, &token_map),
+                None => continue,
+            };
+            const_params.push((name, node_to_tokens(param.syntax(), &token_map)));
         }
-        result.push(
-            tt::Leaf::Ident(tt::Ident {
-                id: tt::TokenId::unspecified(),
-                text: format!("T{}", i).into(),
-            })
-            .into(),
-        );
-        result.extend(bound.iter().cloned());
     }
-    result.push(
-        tt::Leaf::Punct(tt::Punct {
-            char: '>',
-            spacing: tt::Spacing::Alone,
-            id: tt::TokenId::unspecified(),
-        })
-        .into(),
-    );
-    result
+    let where_clause =
+        where_clause.map(|it| node_to_tokens(it.syntax(), &token_map)).unwrap_or_default();
+
+    Ok(BasicAdtInfo {
+        name: name_token,
+        lifetime_params,
+        type_params,
+        const_params,
+        where_clause,
+        shape,
+    })
 }
 
 fn expand_simple_derive(
     tt: &tt::Subtree,
     trait_path: tt::Subtree,
+    make_items: impl FnOnce(&BasicAdtInfo) -> tt::Subtree,
 ) -> Result<tt::Subtree, mbe::ExpandError> {
     let info = parse_adt(tt)?;
-    let name = info.name;
-    let trait_path_clone = trait_path.token_trees.clone();
-    let bound = (quote! { : ##trait_path_clone }).token_trees;
-    let type_params = make_type_args(info.type_params, bound);
-    let type_args = make_type_args(info.type_params, Vec::new());
+    let name = &info.name;
     let trait_path = trait_path.token_trees;
+    let impl_params = info.impl_params(&trait_path);
+    let type_args = info.type_args();
+    let where_clause = info.where_clause.clone();
+    let items = make_items(&info).token_trees;
     let expanded = quote! {
-        impl ##type_params ##trait_path for #name ##type_args {}
+        impl ##impl_params ##trait_path for #name ##type_args ##where_clause { ##items }
     };
     Ok(expanded)
 }
@@ -158,7 +409,7 @@ fn copy_expand(
     _id: MacroCallId,
     tt: &tt::Subtree,
 ) -> Result<tt::Subtree, mbe::ExpandError> {
-    expand_simple_derive(tt, quote! { std::marker::Copy })
+    expand_simple_derive(tt, quote! { std::marker::Copy }, |_| quote! {})
 }
 
 fn clone_expand(
@@ -166,7 +417,26 @@ fn clone_expand(
     _id: MacroCallId,
     tt: &tt::Subtree,
 ) -> Result<tt::Subtree, mbe::ExpandError> {
-    expand_simple_derive(tt, quote! { std::clone::Clone })
+    expand_simple_derive(tt, quote! { std::clone::Clone }, |info| {
+        if let AdtShape::Union = info.shape {
+            // Unions can only be `Clone` if they are `Copy`.
+            return quote! { fn clone(&self) -> Self { *self } };
+        }
+        let arms = info.variants().into_iter().flat_map(|(path, _, shape)| {
+            let (pattern, bindings) = shape.pattern(&path, "__self");
+            let mut bindings = bindings.iter();
+            let expr = shape.construct_with(&path, || {
+                let binding = bindings.next().unwrap();
+                quote! { std::clone::Clone::clone(#binding) }
+            });
+            quote! { ##pattern => ##expr , }.token_trees
+        });
+        quote! {
+            fn clone(&self) -> Self {
+                match self { ##arms }
+            }
+        }
+    })
 }
 
 fn default_expand(
@@ -174,7 +444,22 @@ fn default_expand(
     _id: MacroCallId,
     tt: &tt::Subtree,
 ) -> Result<tt::Subtree, mbe::ExpandError> {
-    expand_simple_derive(tt, quote! { std::default::Default })
+    expand_simple_derive(tt, quote! { std::default::Default }, |info| {
+        let (path, shape) = match &info.shape {
+            AdtShape::Struct(shape) => {
+                let name = &info.name;
+                (quote! { #name }.token_trees, shape)
+            }
+            // Only structs can derive `Default`.
+            AdtShape::Enum(_) | AdtShape::Union => return quote! {},
+        };
+        let expr = shape.construct_with(&path, || quote! { std::default::Default::default() });
+        quote! {
+            fn default() -> Self {
+                ##expr
+            }
+        }
+    })
 }
 
 fn debug_expand(
@@ -182,7 +467,37 @@ fn debug_expand(
     _id: MacroCallId,
     tt: &tt::Subtree,
 ) -> Result<tt::Subtree, mbe::ExpandError> {
-    expand_simple_derive(tt, quote! { std::fmt::Debug })
+    expand_simple_derive(tt, quote! { std::fmt::Debug }, |info| {
+        if let AdtShape::Union = info.shape {
+            return quote! {};
+        }
+        let arms = info.variants().into_iter().flat_map(|(path, name, shape)| {
+            let (pattern, bindings) = shape.pattern(&path, "__self");
+            let name = name.text.to_string();
+            let expr = match shape {
+                VariantShape::Record(fields) => {
+                    let fields = fields.iter().zip(bindings.iter()).flat_map(|(field, binding)| {
+                        let field = field.text.to_string();
+                        quote! { .field(#field, #binding) }.token_trees
+                    });
+                    quote! { f.debug_struct(#name) ##fields .finish() }
+                }
+                VariantShape::Tuple(_) => {
+                    let fields =
+                        bindings.iter().flat_map(|binding| quote! { .field(#binding) }.token_trees);
+                    quote! { f.debug_tuple(#name) ##fields .finish() }
+                }
+                VariantShape::Unit => quote! { f.write_str(#name) },
+            }
+            .token_trees;
+            quote! { ##pattern => ##expr , }.token_trees
+        });
+        quote! {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                match self { ##arms }
+            }
+        }
+    })
 }
 
 fn hash_expand(
@@ -190,7 +505,31 @@ fn hash_expand(
     _id: MacroCallId,
     tt: &tt::Subtree,
 ) -> Result<tt::Subtree, mbe::ExpandError> {
-    expand_simple_derive(tt, quote! { std::hash::Hash })
+    expand_simple_derive(tt, quote! { std::hash::Hash }, |info| {
+        if let AdtShape::Union = info.shape {
+            return quote! {};
+        }
+        let discriminant = match info.shape {
+            AdtShape::Enum(_) => {
+                quote! { std::hash::Hash::hash(&std::mem::discriminant(self), state); }
+            }
+            _ => quote! {},
+        }
+        .token_trees;
+        let arms = info.variants().into_iter().flat_map(|(path, _, shape)| {
+            let (pattern, bindings) = shape.pattern(&path, "__self");
+            let stmts = bindings
+                .iter()
+                .flat_map(|binding| quote! { std::hash::Hash::hash(#binding, state); }.token_trees);
+            quote! { ##pattern => { ##stmts } }.token_trees
+        });
+        quote! {
+            fn hash<__H: std::hash::Hasher>(&self, state: &mut __H) {
+                ##discriminant
+                match self { ##arms }
+            }
+        }
+    })
 }
 
 fn eq_expand(
@@ -198,7 +537,7 @@ fn eq_expand(
     _id: MacroCallId,
     tt: &tt::Subtree,
 ) -> Result<tt::Subtree, mbe::ExpandError> {
-    expand_simple_derive(tt, quote! { std::cmp::Eq })
+    expand_simple_derive(tt, quote! { std::cmp::Eq }, |_| quote! {})
 }
 
 fn partial_eq_expand(
@@ -206,7 +545,37 @@ fn partial_eq_expand(
     _id: MacroCallId,
     tt: &tt::Subtree,
 ) -> Result<tt::Subtree, mbe::ExpandError> {
-    expand_simple_derive(tt, quote! { std::cmp::PartialEq })
+    expand_simple_derive(tt, quote! { std::cmp::PartialEq }, |info| {
+        if let AdtShape::Union = info.shape {
+            return quote! {};
+        }
+        let mut arms: Vec<tt::TokenTree> = info
+            .variants()
+            .into_iter()
+            .flat_map(|(path, _, shape)| {
+                let (self_pattern, self_bindings) = shape.pattern(&path, "__self");
+                let (other_pattern, other_bindings) = shape.pattern(&path, "__other");
+                let mut expr = quote! { true }.token_trees;
+                for (i, (lhs, rhs)) in self_bindings.iter().zip(other_bindings.iter()).enumerate() {
+                    let cmp = quote! { #lhs == #rhs }.token_trees;
+                    if i == 0 {
+                        expr = cmp;
+                    } else {
+                        expr.extend(quote! { && ##cmp }.token_trees);
+                    }
+                }
+                quote! { (##self_pattern, ##other_pattern) => ##expr , }.token_trees
+            })
+            .collect();
+        if info.is_multi_variant_enum() {
+            arms.extend(quote! { _ => false, }.token_trees);
+        }
+        quote! {
+            fn eq(&self, other: &Self) -> bool {
+                match (self, other) { ##arms }
+            }
+        }
+    })
 }
 
 fn ord_expand(
@@ -214,7 +583,21 @@ fn ord_expand(
     _id: MacroCallId,
     tt: &tt::Subtree,
 ) -> Result<tt::Subtree, mbe::ExpandError> {
-    expand_simple_derive(tt, quote! { std::cmp::Ord })
+    expand_simple_derive(tt, quote! { std::cmp::Ord }, |info| {
+        if let AdtShape::Union = info.shape {
+            return quote! {};
+        }
+        let arms = compare_arms(
+            info,
+            |lhs, rhs| quote! { std::cmp::Ord::cmp(#lhs, #rhs) },
+            quote! { std::cmp::Ordering::Equal },
+        );
+        quote! {
+            fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+                match (self, other) { ##arms }
+            }
+        }
+    })
 }
 
 fn partial_ord_expand(
@@ -222,7 +605,74 @@ fn partial_ord_expand(
     _id: MacroCallId,
     tt: &tt::Subtree,
 ) -> Result<tt::Subtree, mbe::ExpandError> {
-    expand_simple_derive(tt, quote! { std::cmp::PartialOrd })
+    expand_simple_derive(tt, quote! { std::cmp::PartialOrd }, |info| {
+        if let AdtShape::Union = info.shape {
+            return quote! {};
+        }
+        let arms = compare_arms(
+            info,
+            |lhs, rhs| quote! { std::cmp::PartialOrd::partial_cmp(#lhs, #rhs) },
+            quote! { std::option::Option::Some(std::cmp::Ordering::Equal) },
+        );
+        quote! {
+            fn partial_cmp(&self, other: &Self) -> std::option::Option<std::cmp::Ordering> {
+                match (self, other) { ##arms }
+            }
+        }
+    })
+}
+
+/// Match arms of a lexicographic comparison of `self` and `other`, like the
+/// ones of derived `Ord` and `PartialOrd`. Fields are compared in order with
+/// `compare` until one isn't `equal`, and different variants are ordered by
+/// declaration order.
+fn compare_arms(
+    info: &BasicAdtInfo,
+    compare: impl Fn(&tt::Ident, &tt::Ident) -> tt::Subtree,
+    equal: tt::Subtree,
+) -> Vec<tt::TokenTree> {
+    let mut arms: Vec<tt::TokenTree> = info
+        .variants()
+        .into_iter()
+        .flat_map(|(path, _, shape)| {
+            let (self_pattern, self_bindings) = shape.pattern(&path, "__self");
+            let (other_pattern, other_bindings) = shape.pattern(&path, "__other");
+            let stmts = self_bindings.iter().zip(other_bindings.iter()).flat_map(|(lhs, rhs)| {
+                let cmp = compare(lhs, rhs).token_trees;
+                let equal = equal.token_trees.clone();
+                quote! {
+                    match ##cmp {
+                        ##equal => {}
+                        cmp => return cmp,
+                    }
+                }
+                .token_trees
+            });
+            let equal = equal.token_trees.clone();
+            quote! { (##self_pattern, ##other_pattern) => { ##stmts ##equal } }.token_trees
+        })
+        .collect();
+    if info.is_multi_variant_enum() {
+        let variant_indices =
+            info.variants().into_iter().enumerate().flat_map(|(i, (path, _, shape))| {
+                let pattern = shape.wildcard_pattern(&path);
+                quote! { ##pattern => #i , }.token_trees
+            });
+        let variant_indices: Vec<_> = variant_indices.collect();
+        let other_variant_indices = variant_indices.clone();
+        let cmp = compare(&make_ident("__self_vi"), &make_ident("__other_vi")).token_trees;
+        arms.extend(
+            quote! {
+                _ => {
+                    let __self_vi = &match self { ##variant_indices };
+                    let __other_vi = &match other { ##other_variant_indices };
+                    ##cmp
+                }
+            }
+            .token_trees,
+        );
+    }
+    arms
 }
 
 #[cfg(test)]
@@ -240,8 +690,12 @@ mod tests {
         let ast_id_map = db.ast_id_map(file_id.into());
 
         // the first one should be a macro_rules
-        let def =
-            MacroDefId { krate: None, ast_id: None, kind: MacroDefKind::BuiltInDerive(expander) };
+        let def = MacroDefId {
+            krate: None,
+            ast_id: None,
+            kind: MacroDefKind::BuiltInDerive(expander),
+            local_inner: false,
+        };
 
         let loc = MacroCallLoc {
             def,
@@ -266,7 +720,7 @@ mod tests {
             BuiltinDeriveExpander::Copy,
         );
 
-        assert_eq!(expanded, "impl <>std::marker::CopyforFoo <>{}");
+        assert_eq!(expanded, "implstd::marker::CopyforFoo{}");
     }
 
     #[test]
@@ -281,7 +735,7 @@ mod tests {
 
         assert_eq!(
             expanded,
-            "impl<T0:std::marker::Copy,T1:std::marker::Copy>std::marker::CopyforFoo<T0,T1>{}"
+            "impl<A:std::marker::Copy,B:std::marker::Copy>std::marker::CopyforFoo<A,B>{}"
         );
     }
 
@@ -295,11 +749,26 @@ mod tests {
             BuiltinDeriveExpander::Copy,
         );
 
-        // We currently just ignore lifetimes
+        assert_eq!(
+            expanded,
+            "impl<'a,'b,A:std::marker::Copy,\
+             B:std::marker::Copy>std::marker::CopyforFoo<'a,'b,A,B>{}"
+        );
+    }
+
+    #[test]
+    fn test_copy_expand_with_const_params() {
+        let expanded = expand_builtin_derive(
+            r#"
+        #[derive(Copy)]
+        struct Buf<T, const N: usize>;
+"#,
+            BuiltinDeriveExpander::Copy,
+        );
 
         assert_eq!(
             expanded,
-            "impl<T0:std::marker::Copy,T1:std::marker::Copy>std::marker::CopyforFoo<T0,T1>{}"
+            "impl<T:std::marker::Copy,constN:usize>std::marker::CopyforBuf<T,N>{}"
         );
     }
 
@@ -315,7 +784,102 @@ mod tests {
 
         assert_eq!(
             expanded,
-            "impl<T0:std::clone::Clone,T1:std::clone::Clone>std::clone::CloneforFoo<T0,T1>{}"
+            "impl<A:std::clone::Clone,B:std::clone::Clone>std::clone::CloneforFoo<A,B>\
+             {fnclone(&self)->Self{matchself{Foo=>Foo,}}}"
+        );
+    }
+
+    #[test]
+    fn test_clone_expand_keeps_bounds_and_where_clause() {
+        let expanded = expand_builtin_derive(
+            r#"
+        #[derive(Clone)]
+        struct Foo<'a, T: Debug> where T: Copy { a: &'a T, b: u32 }
+"#,
+            BuiltinDeriveExpander::Clone,
+        );
+
+        assert_eq!(
+            expanded,
+            "impl<'a,T:Debug+std::clone::Clone>std::clone::CloneforFoo<'a,T>whereT:Copy\
+             {fnclone(&self)->Self{matchself{Foo{a:__self_0,b:__self_1,}=>Foo\
+             {a:std::clone::Clone::clone(__self_0),b:std::clone::Clone::clone(__self_1),\
+             },}}}"
+        );
+    }
+
+    #[test]
+    fn test_default_expand() {
+        let expanded = expand_builtin_derive(
+            r#"
+        #[derive(Default)]
+        struct Foo(u32);
+"#,
+            BuiltinDeriveExpander::Default,
+        );
+
+        assert_eq!(
+            expanded,
+            "implstd::default::DefaultforFoo{fndefault()->Self\
+             {Foo(std::default::Default::default(),)}}"
+        );
+    }
+
+    #[test]
+    fn test_debug_expand_enum() {
+        let expanded = expand_builtin_derive(
+            r#"
+        #[derive(Debug)]
+        enum Command { Quit, Move { x: i32 }, Write(String) }
+"#,
+            BuiltinDeriveExpander::Debug,
+        );
+
+        assert_eq!(
+            expanded,
+            "implstd::fmt::DebugforCommand{fnfmt(&self,\
+             f :&mutstd::fmt::Formatter)->std::fmt::Result{matchself\
+             {Command::Quit=>f.write_str(\"Quit\"),Command::Move{x:__self_0,\
+             }=>f.debug_struct(\"Move\").field(\"x\",__self_0).finish(),\
+             Command::Write(__self_0,\
+             )=>f.debug_tuple(\"Write\").field(__self_0).finish(),}}}"
+        );
+    }
+
+    #[test]
+    fn test_partial_eq_expand_enum() {
+        let expanded = expand_builtin_derive(
+            r#"
+        #[derive(PartialEq)]
+        enum E { A(u32), B }
+"#,
+            BuiltinDeriveExpander::PartialEq,
+        );
+
+        assert_eq!(
+            expanded,
+            "implstd::cmp::PartialEqforE{fneq(&self,other :&Self)->bool{match(self,\
+             other){(E::A(__self_0,),E::A(__other_0,))=>__self_0==__other_0,(E::B,\
+             E::B)=>true , _=>false,}}}"
+        );
+    }
+
+    #[test]
+    fn test_ord_expand() {
+        let expanded = expand_builtin_derive(
+            r#"
+        #[derive(Ord)]
+        struct S { a: u32 }
+"#,
+            BuiltinDeriveExpander::Ord,
+        );
+
+        assert_eq!(
+            expanded,
+            "implstd::cmp::OrdforS{fncmp(&self,other :&Self)->std::cmp::Ordering\
+             {match(self,other){(S{a:__self_0,},S{a:__other_0,})=>\
+             {matchstd::cmp::Ord::cmp(__self_0,__other_0){std::cmp::Ordering::Equal=>\
+             {}cmp=>returncmp,}std::cmp::Ordering::Equal}}}}"
         );
     }
 }
@@ -42,7 +42,13 @@ macro_rules! register_builtin {
         ) -> Option<MacroDefId> {
             let kind = BuiltinFnLikeExpander::by_name(ident)?;
 
-            Some(MacroDefId { krate: Some(krate), ast_id: Some(ast_id), kind: MacroDefKind::BuiltIn(kind) })
+            let ast_id = AstId::new(ast_id.file_id, ast_id.value.upcast());
+            Some(MacroDefId {
+                krate: Some(krate),
+                ast_id: Some(ast_id),
+                kind: MacroDefKind::BuiltIn(kind),
+                local_inner: false,
+            })
         }
     };
 }
@@ -232,8 +238,9 @@ mod tests {
         // the first one should be a macro_rules
         let def = MacroDefId {
             krate: Some(CrateId(0)),
-            ast_id: Some(AstId::new(file_id.into(), ast_id_map.ast_id(&macro_calls[0]))),
+            ast_id: Some(AstId::new(file_id.into(), ast_id_map.ast_id(&macro_calls[0]).upcast())),
             kind: MacroDefKind::BuiltIn(expander),
+            local_inner: false,
         };
 
         let loc = MacroCallLoc {
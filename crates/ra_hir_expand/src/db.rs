@@ -6,11 +6,13 @@ use mbe::MacroRules;
 use ra_db::{salsa, SourceDatabase};
 use ra_parser::FragmentKind;
 use ra_prof::profile;
-use ra_syntax::{AstNode, Parse, SyntaxKind::*, SyntaxNode};
+use ra_syntax::{
+    algo, ast, AstNode, Parse, SmolStr, SyntaxKind::*, SyntaxNode, SyntaxToken, TextRange,
+};
 
 use crate::{
-    ast_id_map::AstIdMap, BuiltinDeriveExpander, BuiltinFnLikeExpander, HirFileId, HirFileIdRepr,
-    MacroCallId, MacroCallLoc, MacroDefId, MacroDefKind, MacroFile,
+    ast_id_map::AstIdMap, BuiltinAttrExpander, BuiltinDeriveExpander, BuiltinFnLikeExpander,
+    HirFileId, HirFileIdRepr, MacroCallId, MacroCallLoc, MacroDefId, MacroDefKind, MacroFile,
 };
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -18,6 +20,7 @@ pub enum TokenExpander {
     MacroRules(mbe::MacroRules),
     Builtin(BuiltinFnLikeExpander),
     BuiltinDerive(BuiltinDeriveExpander),
+    BuiltinAttr(BuiltinAttrExpander),
 }
 
 impl TokenExpander {
@@ -26,11 +29,12 @@ impl TokenExpander {
         db: &dyn AstDatabase,
         id: MacroCallId,
         tt: &tt::Subtree,
-    ) -> Result<tt::Subtree, mbe::ExpandError> {
+    ) -> mbe::ExpandResult<tt::Subtree> {
         match self {
             TokenExpander::MacroRules(it) => it.expand(tt),
-            TokenExpander::Builtin(it) => it.expand(db, id, tt),
-            TokenExpander::BuiltinDerive(it) => it.expand(db, id, tt),
+            TokenExpander::Builtin(it) => it.expand(db, id, tt).into(),
+            TokenExpander::BuiltinDerive(it) => it.expand(db, id, tt).into(),
+            TokenExpander::BuiltinAttr(it) => it.expand(db, id, tt).into(),
         }
     }
 
@@ -39,6 +43,7 @@ impl TokenExpander {
             TokenExpander::MacroRules(it) => it.map_id_down(id),
             TokenExpander::Builtin(..) => id,
             TokenExpander::BuiltinDerive(..) => id,
+            TokenExpander::BuiltinAttr(..) => id,
         }
     }
 
@@ -47,6 +52,7 @@ impl TokenExpander {
             TokenExpander::MacroRules(it) => it.map_id_up(id),
             TokenExpander::Builtin(..) => (id, mbe::Origin::Call),
             TokenExpander::BuiltinDerive(..) => (id, mbe::Origin::Call),
+            TokenExpander::BuiltinAttr(..) => (id, mbe::Origin::Call),
         }
     }
 }
@@ -63,9 +69,153 @@ pub trait AstDatabase: SourceDatabase {
     fn intern_macro(&self, macro_call: MacroCallLoc) -> MacroCallId;
     fn macro_arg(&self, id: MacroCallId) -> Option<Arc<(tt::Subtree, mbe::TokenMap)>>;
     fn macro_def(&self, id: MacroDefId) -> Option<Arc<(TokenExpander, mbe::TokenMap)>>;
-    fn parse_macro(&self, macro_file: MacroFile)
-        -> Option<(Parse<SyntaxNode>, Arc<mbe::TokenMap>)>;
-    fn macro_expand(&self, macro_call: MacroCallId) -> Result<Arc<tt::Subtree>, String>;
+    /// Parses the expansion of a macro call. Like the expansion itself, the
+    /// tree can be returned together with an error, in which case it's only
+    /// the part of the expansion which could be parsed.
+    fn parse_macro(
+        &self,
+        macro_file: MacroFile,
+    ) -> (Option<(Parse<SyntaxNode>, Arc<mbe::TokenMap>)>, Option<String>);
+    /// Expands a macro call. Expansion is error tolerant: an expansion can be
+    /// returned together with an error, in which case it is a best-effort
+    /// result which callers may still analyze.
+    fn macro_expand(&self, macro_call: MacroCallId) -> (Option<Arc<tt::Subtree>>, Option<String>);
+}
+
+/// Expands `actual_macro_call` as if its arguments were `hypothetical_args`,
+/// and maps `token_to_map` (a token of `hypothetical_args`) into the result.
+///
+/// This is used by completion, which inserts a fake identifier at the cursor
+/// and needs to know where this identifier ends up after expansion, without
+/// polluting the salsa database with the modified arguments.
+pub fn expand_hypothetical(
+    db: &dyn AstDatabase,
+    actual_macro_call: MacroCallId,
+    hypothetical_args: &ast::TokenTree,
+    token_to_map: SyntaxToken,
+) -> Option<(SyntaxNode, SyntaxToken)> {
+    let macro_file = MacroFile { macro_call_id: actual_macro_call };
+    let (tt, tmap_1) = mbe::syntax_node_to_token_tree(hypothetical_args.syntax())?;
+    let range =
+        token_to_map.text_range().checked_sub(hypothetical_args.syntax().text_range().start())?;
+    let token_id = tmap_1.token_by_range(range)?;
+    let macro_def = expander(db, actual_macro_call)?;
+    let (node, tmap_2) = parse_macro_with_arg(db, macro_file, Some(Arc::new((tt, tmap_1)))).0?;
+    let token_id = macro_def.0.map_id_down(token_id);
+    let range = tmap_2.range_by_token(token_id)?.by_kind(token_to_map.kind())?;
+    let token = algo::find_covering_element(&node.syntax_node(), range).into_token()?;
+    Some((node.syntax_node(), token))
+}
+
+/// A single step of macro expansion, for debugging `macro_rules!`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpansionStep {
+    /// Index of the `macro_rules!` arm which matched.
+    pub rule: usize,
+    pub bindings: Vec<MetaVarBinding>,
+}
+
+/// The fragments of the macro input bound to a metavariable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetaVarBinding {
+    pub name: SmolStr,
+    pub fragments: MetaVarFragments,
+}
+
+/// What a metavariable is bound to, nested like the `$(...)*` repetitions the
+/// metavariable is in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetaVarFragments<R = TextRange> {
+    /// The text of a fragment, together with its range in the file of the
+    /// macro call, if it could be mapped back.
+    Fragment(String, Option<R>),
+    /// The fragments of each iteration of a repetition.
+    Repeated(Vec<MetaVarFragments<R>>),
+}
+
+impl<R> MetaVarFragments<R> {
+    pub fn map_range<S>(self, f: &impl Fn(R) -> S) -> MetaVarFragments<S> {
+        match self {
+            MetaVarFragments::Fragment(text, range) => {
+                MetaVarFragments::Fragment(text, range.map(f))
+            }
+            MetaVarFragments::Repeated(it) => {
+                MetaVarFragments::Repeated(it.into_iter().map(|it| it.map_range(f)).collect())
+            }
+        }
+    }
+}
+
+/// Describes how `id`, a call to a `macro_rules!`, was expanded.
+pub fn trace_expansion(db: &dyn AstDatabase, id: MacroCallId) -> Option<ExpansionStep> {
+    let loc = db.lookup_intern_macro(id);
+    let def = db.macro_def(loc.def)?;
+    let rules = match &def.0 {
+        TokenExpander::MacroRules(it) => it,
+        TokenExpander::Builtin(_)
+        | TokenExpander::BuiltinDerive(_)
+        | TokenExpander::BuiltinAttr(_) => return None,
+    };
+    let arg = db.macro_arg(id)?;
+    let arg_start = loc.kind.arg(db)?.text_range().start();
+    let trace = rules.expand_traced(&arg.0).ok()?;
+
+    let range_of_id = |id: tt::TokenId| -> Option<TextRange> {
+        let (id, origin) = rules.map_id_up(id);
+        if origin != mbe::Origin::Call {
+            return None;
+        }
+        let range = match arg.1.range_by_token(id)? {
+            mbe::TokenTextRange::Token(it) => it,
+            mbe::TokenTextRange::Delimiter(open, close) => {
+                TextRange::from_to(open.start(), close.end())
+            }
+        };
+        Some(TextRange::offset_len(arg_start + range.start(), range.len()))
+    };
+
+    let bindings = trace
+        .bindings
+        .iter()
+        .map(|(name, fragments)| MetaVarBinding {
+            name: name.clone(),
+            fragments: lower_fragments(fragments, &range_of_id),
+        })
+        .collect();
+    Some(ExpansionStep { rule: trace.rule, bindings })
+}
+
+fn lower_fragments(
+    fragments: &mbe::MetaVarFragments,
+    range_of_id: &dyn Fn(tt::TokenId) -> Option<TextRange>,
+) -> MetaVarFragments {
+    match fragments {
+        mbe::MetaVarFragments::Fragment(it) => {
+            MetaVarFragments::Fragment(it.to_string(), fragment_range(it, range_of_id))
+        }
+        mbe::MetaVarFragments::Repeated(it) => MetaVarFragments::Repeated(
+            it.iter().map(|it| lower_fragments(it, range_of_id)).collect(),
+        ),
+    }
+}
+
+fn fragment_range(
+    tt: &tt::TokenTree,
+    range_of_id: &dyn Fn(tt::TokenId) -> Option<TextRange>,
+) -> Option<TextRange> {
+    match tt {
+        tt::TokenTree::Leaf(tt::Leaf::Ident(it)) => range_of_id(it.id),
+        tt::TokenTree::Leaf(tt::Leaf::Punct(it)) => range_of_id(it.id),
+        tt::TokenTree::Leaf(tt::Leaf::Literal(it)) => range_of_id(it.id),
+        tt::TokenTree::Subtree(it) => match it.delimiter {
+            Some(delimiter) => range_of_id(delimiter.id),
+            None => {
+                let first = fragment_range(it.token_trees.first()?, range_of_id)?;
+                let last = fragment_range(it.token_trees.last()?, range_of_id)?;
+                Some(TextRange::from_to(first.start(), last.end()))
+            }
+        },
+    }
 }
 
 pub(crate) fn ast_id_map(db: &dyn AstDatabase, file_id: HirFileId) -> Arc<AstIdMap> {
@@ -80,13 +230,17 @@ pub(crate) fn macro_def(
 ) -> Option<Arc<(TokenExpander, mbe::TokenMap)>> {
     match id.kind {
         MacroDefKind::Declarative => {
-            let macro_call = id.ast_id?.to_node(db);
-            let arg = macro_call.token_tree()?;
+            let macro_def = id.ast_id?.to_node(db);
+            let arg = macro_def.token_tree()?;
             let (tt, tmap) = mbe::ast_to_token_tree(&arg).or_else(|| {
                 log::warn!("fail on macro_def to token tree: {:#?}", arg);
                 None
             })?;
-            let rules = MacroRules::parse(&tt).ok().or_else(|| {
+            let rules = match macro_def {
+                ast::Macro::MacroCall(_) => MacroRules::parse(&tt),
+                ast::Macro::MacroDef(_) => MacroRules::parse_macro2(&tt),
+            };
+            let rules = rules.ok().or_else(|| {
                 log::warn!("fail on macro_def parse: {:#?}", tt);
                 None
             })?;
@@ -98,6 +252,9 @@ pub(crate) fn macro_def(
         MacroDefKind::BuiltInDerive(expander) => {
             Some(Arc::new((TokenExpander::BuiltinDerive(expander), mbe::TokenMap::default())))
         }
+        MacroDefKind::BuiltInAttr(expander) => {
+            Some(Arc::new((TokenExpander::BuiltinAttr(expander), mbe::TokenMap::default())))
+        }
     }
 }
 
@@ -114,25 +271,44 @@ pub(crate) fn macro_arg(
 pub(crate) fn macro_expand(
     db: &dyn AstDatabase,
     id: MacroCallId,
-) -> Result<Arc<tt::Subtree>, String> {
+) -> (Option<Arc<tt::Subtree>>, Option<String>) {
+    macro_expand_with_arg(db, id, None)
+}
+
+fn expander(db: &dyn AstDatabase, id: MacroCallId) -> Option<Arc<(TokenExpander, mbe::TokenMap)>> {
+    let loc = db.lookup_intern_macro(id);
+    db.macro_def(loc.def)
+}
+
+fn macro_expand_with_arg(
+    db: &dyn AstDatabase,
+    id: MacroCallId,
+    arg: Option<Arc<(tt::Subtree, mbe::TokenMap)>>,
+) -> (Option<Arc<tt::Subtree>>, Option<String>) {
     let loc = db.lookup_intern_macro(id);
-    let macro_arg = db.macro_arg(id).ok_or("Fail to args in to tt::TokenTree")?;
+    let macro_arg = match arg.or_else(|| db.macro_arg(id)) {
+        Some(it) => it,
+        None => return (None, Some("failed to convert the macro input into tokens".into())),
+    };
 
-    let macro_rules = db.macro_def(loc.def).ok_or("Fail to find macro definition")?;
-    let tt = macro_rules.0.expand(db, id, &macro_arg.0).map_err(|err| format!("{:?}", err))?;
+    let macro_rules = match db.macro_def(loc.def) {
+        Some(it) => it,
+        None => return (None, Some("failed to parse the macro definition".into())),
+    };
+    let mbe::ExpandResult(tt, err) = macro_rules.0.expand(db, id, &macro_arg.0);
     // Set a hard limit for the expanded tt
     let count = tt.count();
     if count > 65536 {
-        return Err(format!("Total tokens count exceed limit : count = {}", count));
+        return (None, Some(format!("macro expansion is too large: {} tokens", count)));
     }
-    Ok(Arc::new(tt))
+    (Some(Arc::new(tt)), err.map(|err| err.to_string()))
 }
 
 pub(crate) fn parse_or_expand(db: &dyn AstDatabase, file_id: HirFileId) -> Option<SyntaxNode> {
     match file_id.0 {
         HirFileIdRepr::FileId(file_id) => Some(db.parse(file_id).tree().syntax().clone()),
         HirFileIdRepr::MacroFile(macro_file) => {
-            db.parse_macro(macro_file).map(|(it, _)| it.syntax_node())
+            db.parse_macro(macro_file).0.map(|(it, _)| it.syntax_node())
         }
     }
 }
@@ -140,24 +316,51 @@ pub(crate) fn parse_or_expand(db: &dyn AstDatabase, file_id: HirFileId) -> Optio
 pub(crate) fn parse_macro(
     db: &dyn AstDatabase,
     macro_file: MacroFile,
-) -> Option<(Parse<SyntaxNode>, Arc<mbe::TokenMap>)> {
+) -> (Option<(Parse<SyntaxNode>, Arc<mbe::TokenMap>)>, Option<String>) {
+    parse_macro_with_arg(db, macro_file, None)
+}
+
+fn parse_macro_with_arg(
+    db: &dyn AstDatabase,
+    macro_file: MacroFile,
+    arg: Option<Arc<(tt::Subtree, mbe::TokenMap)>>,
+) -> (Option<(Parse<SyntaxNode>, Arc<mbe::TokenMap>)>, Option<String>) {
     let _p = profile("parse_macro_query");
 
     let macro_call_id = macro_file.macro_call_id;
-    let tt = db
-        .macro_expand(macro_call_id)
-        .map_err(|err| {
-            // Note:
-            // The final goal we would like to make all parse_macro success,
-            // such that the following log will not call anyway.
-            log::warn!("fail on macro_parse: (reason: {})", err,);
-        })
-        .ok()?;
+    let (tt, err) = if let Some(arg) = arg {
+        macro_expand_with_arg(db, macro_call_id, Some(arg))
+    } else {
+        db.macro_expand(macro_call_id)
+    };
+    if let Some(err) = &err {
+        // Note:
+        // The final goal we would like to make all parse_macro success,
+        // such that the following log will not call anyway.
+        log::warn!("fail on macro_parse: (reason: {})", err);
+    }
+    // Even if the expansion failed, its best-effort result is worth parsing.
+    let tt = match tt {
+        Some(it) => it,
+        None => return (None, err),
+    };
 
     let fragment_kind = to_fragment_kind(db, macro_call_id);
 
-    let (parse, rev_token_map) = mbe::token_tree_to_syntax_node(&tt, fragment_kind).ok()?;
-    Some((parse, Arc::new(rev_token_map)))
+    let mbe::ExpandResult(parse, parse_err) =
+        mbe::token_tree_to_partial_syntax_node(&tt, fragment_kind);
+    let parse_err = parse_err.map(|parse_err| {
+        log::warn!("fail on macro_parse: (reason: {:?})", parse_err);
+        format!("failed to parse the macro expansion: {}", parse_err)
+    });
+    let parse = parse.map(|(parse, rev_token_map)| (parse, Arc::new(rev_token_map)));
+    (parse, err.or(parse_err))
+}
+
+/// Returns why `id` couldn't be fully expanded: either the expansion itself
+/// failed, or its result doesn't parse as the expected syntax fragment.
+pub fn expansion_error(db: &dyn AstDatabase, id: MacroCallId) -> Option<String> {
+    db.parse_macro(MacroFile { macro_call_id: id }).1
 }
 
 /// Given a `MacroCallId`, return what `FragmentKind` it belongs to.
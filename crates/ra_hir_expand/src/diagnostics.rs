@@ -18,7 +18,10 @@ use std::{any::Any, fmt};
 
 use ra_syntax::{SyntaxNode, SyntaxNodePtr, TextRange};
 
-use crate::{db::AstDatabase, InFile};
+use crate::{
+    db::{expansion_error, AstDatabase, TokenExpander},
+    HirFileId, InFile, MacroCallId,
+};
 
 pub trait Diagnostic: Any + Send + Sync + fmt::Debug + 'static {
     fn message(&self) -> String;
@@ -83,3 +86,80 @@ impl<'a> DiagnosticSink<'a> {
         (self.default_callback)(d)
     }
 }
+
+/// A macro call which failed to expand.
+#[derive(Debug)]
+pub struct MacroError {
+    pub file: HirFileId,
+    pub node: SyntaxNodePtr,
+    pub message: String,
+    /// The range of the input token at which the expansion failed, if known.
+    pub token_range: Option<TextRange>,
+}
+
+impl MacroError {
+    /// Returns the error of expanding `id`, or `None` if it expands fine.
+    pub fn from_macro_call(db: &dyn AstDatabase, id: MacroCallId) -> Option<MacroError> {
+        let message = expansion_error(db, id)?;
+        let node = db.lookup_intern_macro(id).kind.node(db);
+        let mut res = MacroError {
+            file: node.file_id,
+            node: SyntaxNodePtr::new(&node.value),
+            message,
+            token_range: None,
+        };
+        res.explain_no_match(db, id);
+
+        // Ranges inside of expansions mean nothing to the user, so calls
+        // produced by other macros are reported at the outermost call.
+        let mut outermost = node;
+        while let Some(call_node) = outermost.file_id.call_node(db) {
+            outermost = call_node;
+        }
+        if outermost.file_id != res.file {
+            res.file = outermost.file_id;
+            res.node = SyntaxNodePtr::new(&outermost.value);
+            res.token_range = None;
+        }
+        Some(res)
+    }
+
+    /// For `macro_rules!`, points at the token where the rule which got
+    /// furthest stopped matching.
+    fn explain_no_match(&mut self, db: &dyn AstDatabase, id: MacroCallId) -> Option<()> {
+        let loc = db.lookup_intern_macro(id);
+        let def = db.macro_def(loc.def)?;
+        let rules = match &def.0 {
+            TokenExpander::MacroRules(it) => it,
+            TokenExpander::Builtin(_)
+            | TokenExpander::BuiltinDerive(_)
+            | TokenExpander::BuiltinAttr(_) => return None,
+        };
+        let arg = db.macro_arg(id)?;
+        let explanation = rules.explain_no_match(&arg.0)?;
+        self.message = format!("{}: {}", self.message, explanation);
+
+        let range = match arg.1.range_by_token(explanation.found_id?)? {
+            mbe::TokenTextRange::Token(it) => it,
+            mbe::TokenTextRange::Delimiter(open, _) => open,
+        };
+        let arg_start = loc.kind.arg(db)?.text_range().start();
+        self.token_range = Some(TextRange::offset_len(arg_start + range.start(), range.len()));
+        Some(())
+    }
+}
+
+impl Diagnostic for MacroError {
+    fn message(&self) -> String {
+        self.message.clone()
+    }
+    fn source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.node }
+    }
+    fn highlight_range(&self) -> TextRange {
+        self.token_range.unwrap_or_else(|| self.node.range())
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
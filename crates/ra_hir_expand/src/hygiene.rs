@@ -1,41 +1,61 @@
 //! This modules handles hygiene information.
 //!
 //! Specifically, `ast` + `Hygiene` allows you to create a `Name`. Note that, at
-//! this moment, this is horribly incomplete and handles only `$crate`.
+//! this moment, this is horribly incomplete: paths only handle `$crate`, and
+//! local variables and labels are tracked via `SyntaxContext`.
 use either::Either;
 use ra_db::CrateId;
-use ra_syntax::ast;
+use ra_syntax::{ast, SyntaxToken};
 
 use crate::{
     db::AstDatabase,
     name::{AsName, Name},
-    HirFileId, HirFileIdRepr, MacroDefKind,
+    ExpansionInfo, HirFileId, HirFileIdRepr, InFile, MacroCallId, MacroDefId, MacroDefKind, Origin,
 };
 
 #[derive(Debug)]
 pub struct Hygiene {
     // This is what `$crate` expands to
     def_crate: Option<CrateId>,
+    // For files from a `#[macro_export(local_inner_macros)]` macro, maps
+    // tokens to the macro definition or the call
+    local_inner: Option<ExpansionInfo>,
+    // The file names come from, `None` for unhygienic names
+    file_id: Option<HirFileId>,
 }
 
 impl Hygiene {
     pub fn new(db: &impl AstDatabase, file_id: HirFileId) -> Hygiene {
-        let def_crate = match file_id.0 {
-            HirFileIdRepr::FileId(_) => None,
+        let (def_crate, local_inner) = match file_id.0 {
+            HirFileIdRepr::FileId(_) => (None, false),
             HirFileIdRepr::MacroFile(macro_file) => {
                 let loc = db.lookup_intern_macro(macro_file.macro_call_id);
                 match loc.def.kind {
-                    MacroDefKind::Declarative => loc.def.krate,
-                    MacroDefKind::BuiltIn(_) => None,
-                    MacroDefKind::BuiltInDerive(_) => None,
+                    MacroDefKind::Declarative => (loc.def.krate, loc.def.local_inner),
+                    MacroDefKind::BuiltIn(_) => (None, false),
+                    MacroDefKind::BuiltInDerive(_) => (None, false),
+                    MacroDefKind::BuiltInAttr(_) => (None, false),
                 }
             }
         };
-        Hygiene { def_crate }
+        let local_inner = if local_inner { file_id.expansion_info(db) } else { None };
+        Hygiene { def_crate, local_inner, file_id: Some(file_id) }
     }
 
     pub fn new_unhygienic() -> Hygiene {
-        Hygiene { def_crate: None }
+        Hygiene { def_crate: None, local_inner: None, file_id: None }
+    }
+
+    /// The crate a plain macro call should be resolved in, if `name`, the
+    /// name of the called macro, comes from the body of a
+    /// `#[macro_export(local_inner_macros)]` macro. Macro calls passed in by
+    /// the caller are resolved as usual.
+    pub fn local_inner_macros(&self, name: &SyntaxToken) -> Option<CrateId> {
+        let info = self.local_inner.as_ref()?;
+        match info.map_token_up(InFile::new(self.file_id?, name))? {
+            (_, Origin::Def) => self.def_crate,
+            (_, Origin::Call) => None,
+        }
     }
 
     // FIXME: this should just return name
@@ -47,4 +67,70 @@ impl Hygiene {
         }
         Either::Left(name_ref.as_name())
     }
+
+    /// Computes the syntax context of `token`, which must belong to the file
+    /// this `Hygiene` was created for.
+    pub fn syntax_context(&self, db: &impl AstDatabase, token: &SyntaxToken) -> SyntaxContext {
+        match self.file_id {
+            Some(file_id) => syntax_context(db, InFile::new(file_id, token.clone())),
+            None => SyntaxContext::ROOT,
+        }
+    }
+}
+
+/// The hygienic context of an identifier.
+///
+/// We implement `macro_rules!` mixed-site hygiene: local variables and labels
+/// introduced by a macro definition are visible only to identifiers from the
+/// same expansion, while everything else is resolved at the call site. Macros
+/// 2.0 use definition-site hygiene: on top of that, paths from their body are
+/// resolved in the module defining the macro, see `def_site_macro`.
+///
+/// Identifiers written by the user, including the ones passed to a macro as
+/// arguments, have the root context. Identifiers coming from the body of a
+/// `macro_rules!` or a macro 2.0 are marked with the macro call which produced
+/// them.
+///
+/// FIXME: only the paths of expressions and patterns are resolved at the
+/// definition site. Type references don't record their syntax context, so
+/// types, like the names of items defined by a macro 2.0, still behave like in
+/// `macro_rules!`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct SyntaxContext(Option<MacroCallId>);
+
+impl SyntaxContext {
+    pub const ROOT: SyntaxContext = SyntaxContext(None);
+
+    pub fn is_root(self) -> bool {
+        self.0.is_none()
+    }
+
+    /// The macro 2.0 whose body the identifiers with this context come from.
+    pub fn def_site_macro(self, db: &impl AstDatabase) -> Option<MacroDefId> {
+        let def = db.lookup_intern_macro(self.0?).def;
+        match def.ast_id?.to_node(db) {
+            ast::Macro::MacroDef(_) => Some(def),
+            ast::Macro::MacroCall(_) => None,
+        }
+    }
+}
+
+fn syntax_context(db: &impl AstDatabase, token: InFile<SyntaxToken>) -> SyntaxContext {
+    let macro_call_id = match token.file_id.0 {
+        HirFileIdRepr::FileId(_) => return SyntaxContext::ROOT,
+        HirFileIdRepr::MacroFile(macro_file) => macro_file.macro_call_id,
+    };
+    let info = match token.file_id.expansion_info(db) {
+        Some(it) => it,
+        None => return SyntaxContext::ROOT,
+    };
+    match info.map_token_up(token.as_ref()) {
+        // Tokens from the macro arguments keep the context they had at the
+        // call site.
+        Some((call_site, Origin::Call)) => syntax_context(db, call_site),
+        Some((_, Origin::Def)) => SyntaxContext(Some(macro_call_id)),
+        // Tokens which don't originate from either side (like the ones
+        // produced by builtin macros) are treated as if written by the user.
+        None => SyntaxContext::ROOT,
+    }
 }
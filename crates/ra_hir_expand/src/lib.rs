@@ -9,6 +9,7 @@ pub mod ast_id_map;
 pub mod name;
 pub mod hygiene;
 pub mod diagnostics;
+pub mod builtin_attr;
 pub mod builtin_derive;
 pub mod builtin_macro;
 pub mod quote;
@@ -24,6 +25,7 @@ use ra_syntax::{
 };
 
 use crate::ast_id_map::FileAstId;
+use crate::builtin_attr::BuiltinAttrExpander;
 use crate::builtin_derive::BuiltinDeriveExpander;
 use crate::builtin_macro::BuiltinFnLikeExpander;
 
@@ -76,6 +78,18 @@ impl HirFileId {
         }
     }
 
+    /// Returns how many macro expansions deep this file is: `0` for real
+    /// files, `1` for expansions of macro calls in real files, and so on.
+    pub fn expansion_level(self, db: &dyn db::AstDatabase) -> u32 {
+        let mut level = 0;
+        let mut curr = self;
+        while let HirFileIdRepr::MacroFile(macro_file) = curr.0 {
+            level += 1;
+            curr = db.lookup_intern_macro(macro_file.macro_call_id).kind.file_id();
+        }
+        level
+    }
+
     /// If this is a macro call, returns the syntax node of the call.
     pub fn call_node(self, db: &dyn db::AstDatabase) -> Option<InFile<SyntaxNode>> {
         match self.0 {
@@ -98,7 +112,7 @@ impl HirFileId {
                 let def_tt = loc.def.ast_id?.to_node(db).token_tree()?;
 
                 let macro_def = db.macro_def(loc.def)?;
-                let (parse, exp_map) = db.parse_macro(macro_file)?;
+                let (parse, exp_map) = db.parse_macro(macro_file).0?;
                 let macro_arg = db.macro_arg(macro_file.macro_call_id)?;
 
                 Some(ExpansionInfo {
@@ -156,8 +170,12 @@ pub struct MacroDefId {
     // (which will probably require touching this code), we can instead use
     // that (and also remove the hacks for resolving built-in derives).
     pub krate: Option<CrateId>,
-    pub ast_id: Option<AstId<ast::MacroCall>>,
+    pub ast_id: Option<AstId<ast::Macro>>,
     pub kind: MacroDefKind,
+
+    /// Whether the macro is `#[macro_export(local_inner_macros)]`: plain
+    /// macro calls in its expansion are resolved as `$crate::name!()`.
+    pub local_inner: bool,
 }
 
 impl MacroDefId {
@@ -172,6 +190,7 @@ pub enum MacroDefKind {
     BuiltIn(BuiltinFnLikeExpander),
     // FIXME: maybe just Builtin and rename BuiltinFnLikeExpander to BuiltinExpander
     BuiltInDerive(BuiltinDeriveExpander),
+    BuiltInAttr(BuiltinAttrExpander),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -2,11 +2,12 @@
 
 use std::fmt;
 
-use ra_syntax::{ast, SmolStr};
+use ra_syntax::{ast, SmolStr, SyntaxToken};
 
 /// `Name` is a wrapper around string, which is used in hir for both references
 /// and declarations. In theory, names should also carry hygiene info, but we are
-/// not there yet!
+/// not there yet! For now, the hygiene of local variables and labels is tracked
+/// on the side, see `hygiene::SyntaxContext`.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Name(Repr);
 
@@ -52,6 +53,17 @@ impl Name {
         }
     }
 
+    /// Creates a name from an identifier token which is not part of a
+    /// `Name` or a `NameRef` node, like the ones in attribute arguments.
+    pub fn new_ident(ident: &SyntaxToken) -> Name {
+        Name::resolve(ident.text())
+    }
+
+    /// Creates a name for a label, like `'outer`.
+    pub fn new_lifetime(lifetime: &SyntaxToken) -> Name {
+        Name::new_text(lifetime.text().clone())
+    }
+
     pub fn missing() -> Name {
         Name::new_text("[missing name]".into())
     }
@@ -148,11 +160,13 @@ pub mod known {
         future,
         result,
         boxed,
+        convert,
         // Components of known path (type name)
         IntoIterator,
         Item,
         Try,
         Ok,
+        Error,
         Future,
         Result,
         Output,
@@ -167,6 +181,7 @@ pub mod known {
         Neg,
         Not,
         Index,
+        From,
         // Builtin macros
         file,
         column,
@@ -187,6 +202,39 @@ pub mod known {
         PartialOrd,
         Eq,
         PartialEq,
+        // Builtin attributes
+        bench,
+        cfg_eval,
+        global_allocator,
+        test,
+        test_case,
+        // Operator trait methods
+        add,
+        add_assign,
+        sub,
+        sub_assign,
+        mul,
+        mul_assign,
+        div,
+        div_assign,
+        rem,
+        rem_assign,
+        shl,
+        shl_assign,
+        shr,
+        shr_assign,
+        bitxor,
+        bitxor_assign,
+        bitor,
+        bitor_assign,
+        bitand,
+        bitand_assign,
+        eq,
+        ne,
+        lt,
+        le,
+        gt,
+        ge,
     );
 
     // self/Self cannot be used as an identifier
@@ -97,13 +97,22 @@ macro_rules! __quote {
     // Puncts
     // FIXME: Not all puncts are handled
     ( -> ) => {$crate::__quote!(@PUNCT '-', '>')};
+    ( => ) => {$crate::__quote!(@PUNCT '=', '>')};
+    ( == ) => {$crate::__quote!(@PUNCT '=', '=')};
+    ( && ) => {$crate::__quote!(@PUNCT '&', '&')};
+    ( .. ) => {$crate::__quote!(@PUNCT '.', '.')};
     ( & ) => {$crate::__quote!(@PUNCT '&')};
     ( , ) => {$crate::__quote!(@PUNCT ',')};
     ( : ) => {$crate::__quote!(@PUNCT ':')};
+    ( ; ) => {$crate::__quote!(@PUNCT ';')};
     ( :: ) => {$crate::__quote!(@PUNCT ':', ':')};
     ( . ) => {$crate::__quote!(@PUNCT '.')};
     ( < ) => {$crate::__quote!(@PUNCT '<')};
     ( > ) => {$crate::__quote!(@PUNCT '>')};
+    ( = ) => {$crate::__quote!(@PUNCT '=')};
+    ( + ) => {$crate::__quote!(@PUNCT '+')};
+    ( * ) => {$crate::__quote!(@PUNCT '*')};
+    ( _ ) => {$crate::__quote!(@PUNCT '_')};
 
     ( $first:tt $($tail:tt)+ ) => {
         {
@@ -190,6 +199,7 @@ impl_to_to_tokentrees! {
     u32 => self { tt::Literal{text: self.to_string().into(), id: tt::TokenId::unspecified()} };
     usize => self { tt::Literal{text: self.to_string().into(), id: tt::TokenId::unspecified()}};
     i32 => self { tt::Literal{text: self.to_string().into(), id: tt::TokenId::unspecified()}};
+    bool => self { tt::Literal{text: self.to_string().into(), id: tt::TokenId::unspecified()}};
     tt::Leaf => self { self };
     tt::Literal => self { self };
     tt::Ident => self { self };
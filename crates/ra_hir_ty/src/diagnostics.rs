@@ -5,7 +5,7 @@ use std::any::Any;
 use hir_expand::{db::AstDatabase, name::Name, HirFileId, InFile};
 use ra_syntax::{ast, AstNode, AstPtr, SyntaxNodePtr};
 
-pub use hir_def::diagnostics::UnresolvedModule;
+pub use hir_def::diagnostics::{UnresolvedImport, UnresolvedModule};
 pub use hir_expand::diagnostics::{AstDiagnostic, Diagnostic, DiagnosticSink};
 
 #[derive(Debug)]
@@ -89,3 +89,155 @@ impl AstDiagnostic for MissingOkInTailExpr {
         ast::Expr::cast(node).unwrap()
     }
 }
+
+#[derive(Debug)]
+pub struct UnresolvedPath {
+    pub file: HirFileId,
+    pub expr: AstPtr<ast::Expr>,
+}
+
+impl Diagnostic for UnresolvedPath {
+    fn message(&self) -> String {
+        "unresolved path".to_string()
+    }
+    fn source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.expr.into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+impl AstDiagnostic for UnresolvedPath {
+    type AST = ast::Path;
+
+    fn ast(&self, db: &impl AstDatabase) -> Self::AST {
+        let root = db.parse_or_expand(self.file).unwrap();
+        let node = self.source().value.to_node(&root);
+        ast::PathExpr::cast(node).and_then(|it| it.path()).unwrap()
+    }
+}
+
+#[derive(Debug)]
+pub struct BreakWithValueOutsideLoop {
+    pub file: HirFileId,
+    pub expr: AstPtr<ast::Expr>,
+}
+
+impl Diagnostic for BreakWithValueOutsideLoop {
+    fn message(&self) -> String {
+        "`break` with value from a `while` or `for` loop".to_string()
+    }
+    fn source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.expr.into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct TryInNonTryFunction {
+    pub file: HirFileId,
+    pub expr: AstPtr<ast::Expr>,
+    /// The return type the function would need for the `?` to work, if we
+    /// can suggest one.
+    pub suggested_ret_ty: Option<String>,
+}
+
+impl Diagnostic for TryInNonTryFunction {
+    fn message(&self) -> String {
+        "the `?` operator can only be used in a function that returns `Result` or `Option`"
+            .to_string()
+    }
+    fn source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.expr.into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+impl AstDiagnostic for TryInNonTryFunction {
+    type AST = ast::TryExpr;
+
+    fn ast(&self, db: &impl AstDatabase) -> Self::AST {
+        let root = db.parse_or_expand(self.file).unwrap();
+        let node = self.source().value.to_node(&root);
+        ast::TryExpr::cast(node).unwrap()
+    }
+}
+
+#[derive(Debug)]
+pub struct OverflowingLiteral {
+    pub file: HirFileId,
+    pub expr: AstPtr<ast::Expr>,
+    /// The integer type the literal doesn't fit into.
+    pub ty: String,
+}
+
+impl Diagnostic for OverflowingLiteral {
+    fn message(&self) -> String {
+        format!("literal out of range for `{}`", self.ty)
+    }
+    fn source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.expr.into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct NoSuchMethod {
+    pub file: HirFileId,
+    pub expr: AstPtr<ast::Expr>,
+    pub method: String,
+    pub receiver_ty: String,
+    /// Paths of traits which would provide the method if they were imported.
+    pub traits: Vec<String>,
+}
+
+impl Diagnostic for NoSuchMethod {
+    fn message(&self) -> String {
+        let mut message =
+            format!("no method named `{}` found for `{}`", self.method, self.receiver_ty);
+        if !self.traits.is_empty() {
+            let traits: Vec<_> = self.traits.iter().map(|it| format!("`{}`", it)).collect();
+            message += &format!("; it is provided by {}, which is not in scope", traits.join(", "));
+        }
+        message
+    }
+    fn source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.expr.into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct UnsatisfiedTraitBound {
+    pub file: HirFileId,
+    pub expr: AstPtr<ast::Expr>,
+    /// The bound that doesn't hold, e.g. `Wrapper<S>: Clone`.
+    pub bound: String,
+    /// The where clause of an impl that made `bound` fail, e.g. `S: Clone`.
+    pub nested: Option<String>,
+}
+
+impl Diagnostic for UnsatisfiedTraitBound {
+    fn message(&self) -> String {
+        let mut message = format!("the trait bound `{}` is not satisfied", self.bound);
+        if let Some(nested) = &self.nested {
+            message += &format!(", because `{}` is not satisfied", nested);
+        }
+        message
+    }
+    fn source(&self) -> InFile<SyntaxNodePtr> {
+        InFile { file_id: self.file, value: self.expr.into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
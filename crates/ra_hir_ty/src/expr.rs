@@ -54,7 +54,7 @@ impl<'a, 'b> ExprValidator<'a, 'b> {
         }
 
         let body_expr = &body[body.body_expr];
-        if let Expr::Block { statements: _, tail: Some(t) } = body_expr {
+        if let Expr::Block { tail: Some(t), .. } = body_expr {
             self.validate_results_in_tail_expr(body.body_expr, *t, db);
         }
     }
@@ -134,7 +134,9 @@ impl<'a, 'b> ExprValidator<'a, 'b> {
 
         let std_result_ctor = TypeCtor::Adt(AdtId::EnumId(std_result_enum));
         let params = match &mismatch.expected {
-            Ty::Apply(ApplicationTy { ctor, parameters }) if ctor == &std_result_ctor => parameters,
+            Ty::Apply(ApplicationTy { ctor, parameters, .. }) if ctor == &std_result_ctor => {
+                parameters
+            }
             _ => return,
         };
 
@@ -14,42 +14,45 @@
 //! the `ena` crate, which is extracted from rustc.
 
 use std::borrow::Cow;
+use std::iter;
 use std::mem;
 use std::ops::Index;
 use std::sync::Arc;
 
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 
 use hir_def::{
     body::Body,
     data::{ConstData, FunctionData},
-    expr::{BindingAnnotation, ExprId, PatId},
+    expr::{BinaryOp, BindingAnnotation, Expr, ExprId, Literal, PatId, UnaryOp},
     lang_item::LangItemTarget,
     path::{path, Path},
-    resolver::{HasResolver, Resolver, TypeNs},
+    resolver::{def_site_resolver, HasResolver, Resolver, TypeNs},
     type_ref::{Mutability, TypeRef},
-    AdtId, AssocItemId, DefWithBodyId, FunctionId, StructFieldId, TypeAliasId, VariantId,
+    AdtId, AssocItemId, ConstId, DefWithBodyId, FunctionId, HasModule, Lookup, ModuleDefId,
+    StructFieldId, TraitId, TypeAliasId, VariantId,
 };
-use hir_expand::{diagnostics::DiagnosticSink, name::name};
+use hir_expand::{diagnostics::DiagnosticSink, hygiene::SyntaxContext, name::name};
 use ra_arena::map::ArenaMap;
+use ra_db::CrateId;
 use ra_prof::profile;
 use ra_syntax::SmolStr;
 
 use super::{
-    primitive::{FloatTy, IntTy},
+    primitive::{FloatTy, IntTy, Signedness},
     traits::{Guidance, Obligation, ProjectionPredicate, Solution},
-    ApplicationTy, GenericPredicate, InEnvironment, ProjectionTy, Substs, TraitEnvironment,
+    ApplicationTy, Canonical, ImplTraitId, InEnvironment, ProjectionTy, Substs, TraitEnvironment,
     TraitRef, Ty, TypeCtor, TypeWalk, Uncertain,
 };
 use crate::{
-    db::HirDatabase, infer::diagnostics::InferenceDiagnostic, lower::ImplTraitLoweringMode,
+    db::HirDatabase, infer::diagnostics::InferenceDiagnostic, lower::ImplTraitLoweringMode, op,
 };
 
 pub(crate) use unify::unify;
 
 macro_rules! ty_app {
     ($ctor:pat, $param:pat) => {
-        crate::Ty::Apply(crate::ApplicationTy { ctor: $ctor, parameters: $param })
+        crate::Ty::Apply(crate::ApplicationTy { ctor: $ctor, parameters: $param, .. })
     };
     ($ctor:pat) => {
         ty_app!($ctor, _)
@@ -131,10 +134,16 @@ pub struct InferenceResult {
     variant_resolutions: FxHashMap<ExprOrPatId, VariantId>,
     /// For each associated item record what it resolves to
     assoc_resolutions: FxHashMap<ExprOrPatId, AssocItemId>,
+    /// For each path to a trait's associated const, records the const of the
+    /// impl that is used, if it is known.
+    impl_const_resolutions: FxHashMap<ExprOrPatId, ConstId>,
     diagnostics: Vec<InferenceDiagnostic>,
     pub type_of_expr: ArenaMap<ExprId, Ty>,
     pub type_of_pat: ArenaMap<PatId, Ty>,
     pub(super) type_mismatches: ArenaMap<ExprId, TypeMismatch>,
+    /// For each `impl Trait` in the return type of the function, the type
+    /// hidden behind it, indexed like in `ImplTraitId::ReturnTypeImplTrait`.
+    hidden_types: FxHashMap<u16, Ty>,
 }
 
 impl InferenceResult {
@@ -159,6 +168,15 @@ impl InferenceResult {
     pub fn assoc_resolutions_for_pat(&self, id: PatId) -> Option<AssocItemId> {
         self.assoc_resolutions.get(&id.into()).copied()
     }
+    pub fn impl_const_resolution_for_expr(&self, id: ExprId) -> Option<ConstId> {
+        self.impl_const_resolutions.get(&id.into()).copied()
+    }
+    pub fn impl_const_resolution_for_pat(&self, id: PatId) -> Option<ConstId> {
+        self.impl_const_resolutions.get(&id.into()).copied()
+    }
+    pub fn hidden_type(&self, idx: u16) -> Option<&Ty> {
+        self.hidden_types.get(&idx)
+    }
     pub fn type_mismatch_for_expr(&self, expr: ExprId) -> Option<&TypeMismatch> {
         self.type_mismatches.get(expr)
     }
@@ -198,6 +216,9 @@ struct InferenceContext<'a, D: HirDatabase> {
     table: unify::InferenceTable,
     trait_env: Arc<TraitEnvironment>,
     obligations: Vec<Obligation>,
+    /// Trait bounds of called functions and constructed structs, checked once
+    /// inference is done to report the ones that don't hold.
+    bound_checks: Vec<(ExprId, TraitRef)>,
     result: InferenceResult,
     /// The return type of the function being inferred, or the closure if we're
     /// currently within one.
@@ -212,6 +233,31 @@ struct InferenceContext<'a, D: HirDatabase> {
     // FIXME: Use trait solver for this.
     // Chalk seems unable to work well with builtin impl of `Unsize` now.
     coerce_unsized_map: FxHashMap<(TypeCtor, TypeCtor), usize>,
+
+    /// The loops and labeled blocks we are currently inside of, innermost
+    /// last.
+    breakables: Vec<BreakableContext>,
+
+    /// Whether we're inside of a `try` block, which is where `?` returns to
+    /// instead of the function.
+    in_try_block: bool,
+
+    /// The type variables standing in for the `impl Trait`s in the return
+    /// type of the function, by their index.
+    hidden_type_vars: FxHashMap<u16, Ty>,
+}
+
+#[derive(Clone, Debug)]
+struct BreakableContext {
+    /// The loop or labeled block `break` exits.
+    expr: ExprId,
+    /// Whether `break` may carry a value, which is not the case for `while`
+    /// and `for` loops.
+    allows_value: bool,
+    /// Whether there is any `break` targeting this context.
+    may_break: bool,
+    /// The type all `break` values are coerced to.
+    break_ty: Ty,
 }
 
 impl<'a, D: HirDatabase> InferenceContext<'a, D> {
@@ -220,9 +266,13 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             result: InferenceResult::default(),
             table: unify::InferenceTable::new(),
             obligations: Vec::default(),
+            bound_checks: Vec::new(),
             return_ty: Ty::Unknown, // set in collect_fn_signature
             trait_env: TraitEnvironment::lower(db, &resolver),
             coerce_unsized_map: Self::init_coerce_unsized_map(db, &resolver),
+            breakables: Vec::new(),
+            in_try_block: false,
+            hidden_type_vars: FxHashMap::default(),
             db,
             owner,
             body: db.body(owner.into()),
@@ -241,9 +291,117 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             let resolved = self.table.resolve_ty_completely(mem::replace(ty, Ty::Unknown));
             *ty = resolved;
         }
+        for (idx, var) in mem::replace(&mut self.hidden_type_vars, FxHashMap::default()) {
+            result.hidden_types.insert(idx, self.table.resolve_ty_completely(var));
+        }
+        self.report_overflowing_literals(&mut result);
+        self.report_unsatisfied_bounds(&mut result);
         result
     }
 
+    /// Reports integer literals which don't fit into the type they ended up
+    /// with. This needs to happen after the `i32` fallback.
+    fn report_overflowing_literals(&self, result: &mut InferenceResult) {
+        // `-128i8` is fine, even though `128i8` is not.
+        let negated: FxHashSet<ExprId> = self
+            .body
+            .exprs
+            .iter()
+            .filter_map(|(_, expr)| match expr {
+                Expr::UnaryOp { expr, op: UnaryOp::Neg } => Some(*expr),
+                _ => None,
+            })
+            .collect();
+        for (expr, data) in self.body.exprs.iter() {
+            let value = match data {
+                Expr::Literal(Literal::Int(value, _)) => *value,
+                _ => continue,
+            };
+            let ty = match result.type_of_expr.get(expr) {
+                Some(ty_app!(TypeCtor::Int(Uncertain::Known(ty)))) => *ty,
+                _ => continue,
+            };
+            let max = match ty.signedness {
+                Signedness::Signed if negated.contains(&expr) => ty.max_value() + 1,
+                _ => ty.max_value(),
+            };
+            if value > max {
+                result.diagnostics.push(InferenceDiagnostic::OverflowingLiteral { expr, ty });
+            }
+        }
+    }
+
+    /// Reports trait bounds of calls and struct literals which definitely don't
+    /// hold. Bounds involving types we failed to infer, or types the trait
+    /// solver doesn't know all impls for, are skipped.
+    fn report_unsatisfied_bounds(&mut self, result: &mut InferenceResult) {
+        let krate = match self.resolver.krate() {
+            Some(it) => it,
+            None => return,
+        };
+        let sized_trait = self.resolve_lang_item("sized").and_then(|it| it.as_trait());
+        let bound_checks = mem::replace(&mut self.bound_checks, Vec::new());
+        let mut reported = FxHashSet::default();
+        for (expr, trait_ref) in bound_checks {
+            if Some(trait_ref.trait_) == sized_trait || self.db.trait_data(trait_ref.trait_).auto {
+                continue;
+            }
+            let trait_ref = TraitRef {
+                trait_: trait_ref.trait_,
+                substs: Substs(
+                    trait_ref
+                        .substs
+                        .iter()
+                        .map(|ty| self.table.resolve_ty_completely(ty.clone()))
+                        .collect(),
+                ),
+            };
+            if !is_checkable_bound(&trait_ref)
+                || self.may_lack_impls(krate, &trait_ref)
+                || !reported.insert((expr, trait_ref.clone()))
+            {
+                continue;
+            }
+            let goal = Canonical {
+                num_vars: 0,
+                value: InEnvironment::new(
+                    self.trait_env.clone(),
+                    Obligation::Trait(trait_ref.clone()),
+                ),
+            };
+            if self.db.trait_solve(krate, goal).is_some() {
+                continue;
+            }
+            let nested = crate::method_resolution::unsatisfied_impl_bound(
+                self.db,
+                self.trait_env.clone(),
+                krate,
+                &trait_ref,
+            );
+            result.diagnostics.push(InferenceDiagnostic::UnsatisfiedTraitBound {
+                expr,
+                trait_ref,
+                nested,
+            });
+        }
+    }
+
+    /// Whether an impl satisfying the bound might be one we don't know about,
+    /// like one generated by a macro we couldn't expand, for the trait or the
+    /// self type. The impl could be in the current crate or in the crates
+    /// defining either of them.
+    fn may_lack_impls(&self, krate: CrateId, trait_ref: &TraitRef) -> bool {
+        let trait_: ModuleDefId = trait_ref.trait_.into();
+        let trait_krate = trait_ref.trait_.lookup(self.db).container.module(self.db).krate;
+        let self_adt = trait_ref.self_ty().as_adt().map(|(adt, _)| adt);
+        let self_adt_krate = self_adt.map(|adt| adt.module(self.db).krate);
+        iter::once(krate).chain(iter::once(trait_krate)).chain(self_adt_krate).any(|krate| {
+            let def_map = self.db.crate_def_map(krate);
+            def_map.may_lack_impls(self.db, trait_)
+                || self_adt.map_or(false, |adt| def_map.may_lack_impls(self.db, adt.into()))
+        })
+    }
+
     fn write_expr_ty(&mut self, expr: ExprId, ty: Ty) {
         self.result.type_of_expr.insert(expr, ty);
     }
@@ -365,25 +523,6 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
     ) -> Ty {
         match assoc_ty {
             Some(res_assoc_ty) => {
-                // FIXME:
-                // Check if inner_ty is is `impl Trait` and contained input TypeAlias id
-                // this is a workaround while Chalk assoc type projection doesn't always work yet,
-                // but once that is fixed I don't think we should keep this
-                // (we'll probably change how associated types are resolved anyway)
-                if let Ty::Opaque(ref predicates) = inner_ty {
-                    for p in predicates.iter() {
-                        if let GenericPredicate::Projection(projection) = p {
-                            if projection.projection_ty.associated_ty == res_assoc_ty {
-                                if let ty_app!(_, params) = &projection.ty {
-                                    if params.len() == 0 {
-                                        return projection.ty.clone();
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-
                 let ty = self.table.new_type_var();
                 let builder = Substs::build_for_def(self.db, res_assoc_ty)
                     .push(inner_ty)
@@ -424,13 +563,23 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         var
     }
 
-    fn resolve_variant(&mut self, path: Option<&Path>) -> (Ty, Option<VariantId>) {
+    /// The resolver for a path with the syntax context `hygiene`: paths from the
+    /// body of a macro 2.0 see the items around the macro definition.
+    fn resolver_for_hygiene(&self, hygiene: SyntaxContext) -> Resolver {
+        def_site_resolver(self.db, hygiene).unwrap_or_else(|| self.resolver.clone())
+    }
+
+    fn resolve_variant(
+        &mut self,
+        path: Option<&Path>,
+        hygiene: SyntaxContext,
+    ) -> (Ty, Option<VariantId>) {
         let path = match path {
             Some(path) => path,
             None => return (Ty::Unknown, None),
         };
-        let resolver = &self.resolver;
-        let ctx = crate::lower::TyLoweringContext::new(self.db, &self.resolver);
+        let resolver = self.resolver_for_hygiene(hygiene);
+        let ctx = crate::lower::TyLoweringContext::new(self.db, &resolver);
         // FIXME: this should resolve assoc items as well, see this example:
         // https://play.rust-lang.org/?gist=087992e9e22495446c01c0d4e2d69521
         match resolver.resolve_path_in_type_ns_fully(self.db, path.mod_path()) {
@@ -466,8 +615,48 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
 
             self.infer_pat(*pat, &ty, BindingMode::default());
         }
-        let return_ty = self.make_ty_with_mode(&data.ret_type, ImplTraitLoweringMode::Disallowed); // FIXME implement RPIT
-        self.return_ty = return_ty;
+        let return_ty = self.make_ty_with_mode(&data.ret_type, ImplTraitLoweringMode::Opaque);
+        self.return_ty = self.insert_hidden_type_vars(return_ty);
+    }
+
+    /// Replaces the `impl Trait`s in the return type of the function we're
+    /// checking by type variables, so the hidden types get inferred from the
+    /// body. The bounds of the `impl Trait`s are registered as obligations
+    /// for these variables.
+    fn insert_hidden_type_vars<T: TypeWalk>(&mut self, t: T) -> T {
+        let func = match self.owner {
+            DefWithBodyId::FunctionId(it) => it,
+            _ => return t,
+        };
+        t.fold(&mut |ty| {
+            let (opaque_ty_id, parameters) = match &ty {
+                ty_app!(TypeCtor::OpaqueType(opaque_ty_id), parameters) => {
+                    (*opaque_ty_id, parameters.clone())
+                }
+                _ => return ty,
+            };
+            let idx = match self.db.lookup_intern_impl_trait_id(opaque_ty_id) {
+                ImplTraitId::ReturnTypeImplTrait(it, idx) if it == func => idx,
+                _ => return ty,
+            };
+            let var = self.table.new_type_var();
+            self.hidden_type_vars.insert(idx, var.clone());
+            let impl_traits = self.db.return_type_impl_traits(func);
+            let bounds = match impl_traits.impl_traits.get(idx as usize) {
+                Some(it) => it.bounds.clone(),
+                None => return var,
+            };
+            let substs =
+                Substs(parameters.iter().cloned().chain(iter::once(var.clone())).collect());
+            for predicate in bounds.subst(&substs) {
+                // the bounds may contain nested `impl Trait`s
+                let predicate = self.insert_hidden_type_vars(predicate);
+                if let Some(obligation) = Obligation::from_predicate(predicate) {
+                    self.obligations.push(obligation);
+                }
+            }
+            var
+        })
     }
 
     fn infer_body(&mut self) {
@@ -486,12 +675,35 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         self.db.trait_data(trait_).associated_type_by_name(&name![Item])
     }
 
-    fn resolve_ops_try_ok(&self) -> Option<TypeAliasId> {
+    fn resolve_ops_try(&self) -> Option<TraitId> {
+        if let Some(trait_) = self.resolve_lang_item("try").and_then(|it| it.as_trait()) {
+            return Some(trait_);
+        }
         let path = path![std::ops::Try];
-        let trait_ = self.resolver.resolve_known_trait(self.db, &path)?;
+        self.resolver.resolve_known_trait(self.db, &path)
+    }
+
+    fn resolve_ops_try_ok(&self) -> Option<TypeAliasId> {
+        let trait_ = self.resolve_ops_try()?;
         self.db.trait_data(trait_).associated_type_by_name(&name![Ok])
     }
 
+    fn resolve_ops_try_error(&self) -> Option<TypeAliasId> {
+        let trait_ = self.resolve_ops_try()?;
+        self.db.trait_data(trait_).associated_type_by_name(&name![Error])
+    }
+
+    fn resolve_result_enum(&self) -> Option<AdtId> {
+        let path = path![std::result::Result];
+        let enum_ = self.resolver.resolve_known_enum(self.db, &path)?;
+        Some(enum_.into())
+    }
+
+    fn resolve_convert_from(&self) -> Option<TraitId> {
+        let path = path![std::convert::From];
+        self.resolver.resolve_known_trait(self.db, &path)
+    }
+
     fn resolve_ops_neg_output(&self) -> Option<TypeAliasId> {
         let trait_ = self.resolve_lang_item("neg")?.as_trait()?;
         self.db.trait_data(trait_).associated_type_by_name(&name![Output])
@@ -552,6 +764,10 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         let trait_ = self.resolve_lang_item("index")?.as_trait()?;
         self.db.trait_data(trait_).associated_type_by_name(&name![Output])
     }
+
+    fn resolve_binary_op_trait(&self, op: BinaryOp) -> Option<TraitId> {
+        self.resolve_lang_item(op::binary_op_lang_item(op)?)?.as_trait()
+    }
 }
 
 /// The kinds of placeholders we need during type inference. There's separate
@@ -609,15 +825,54 @@ impl Expectation {
     }
 }
 
-mod diagnostics {
-    use hir_def::{expr::ExprId, src::HasSource, FunctionId, Lookup};
-    use hir_expand::diagnostics::DiagnosticSink;
+/// Whether we can trust the trait solver's answer for `trait_ref`. Tuples,
+/// arrays, function types and `!` get many of their impls from the compiler,
+/// which the trait solver doesn't know about.
+fn is_checkable_bound(trait_ref: &TraitRef) -> bool {
+    let mut checkable = true;
+    trait_ref.walk(&mut |ty| match ty {
+        Ty::Unknown | Ty::Infer(_) => checkable = false,
+        Ty::Apply(a_ty) => match a_ty.ctor {
+            TypeCtor::Tuple { .. }
+            | TypeCtor::Array
+            | TypeCtor::FnDef(_)
+            | TypeCtor::FnPtr { .. }
+            | TypeCtor::Closure { .. }
+            | TypeCtor::Never => checkable = false,
+            _ => {}
+        },
+        _ => {}
+    });
+    checkable
+}
 
-    use crate::{db::HirDatabase, diagnostics::NoSuchField};
+mod diagnostics {
+    use hir_def::{
+        expr::ExprId, find_path::find_path, item_scope::ItemInNs, src::HasSource, FunctionId,
+        HasModule, Lookup, TraitId,
+    };
+    use hir_expand::{diagnostics::DiagnosticSink, name::Name};
+
+    use crate::{
+        db::HirDatabase,
+        diagnostics::{
+            BreakWithValueOutsideLoop, NoSuchField, NoSuchMethod, OverflowingLiteral,
+            TryInNonTryFunction, UnresolvedPath, UnsatisfiedTraitBound,
+        },
+        display::HirDisplay,
+        primitive::IntTy,
+        TraitRef, Ty,
+    };
 
     #[derive(Debug, PartialEq, Eq, Clone)]
     pub(super) enum InferenceDiagnostic {
         NoSuchField { expr: ExprId, field: usize },
+        UnresolvedPath { expr: ExprId },
+        BreakWithValueOutsideLoop { expr: ExprId },
+        TryInNonTryFunction { expr: ExprId, suggested_ret_ty: Option<String> },
+        OverflowingLiteral { expr: ExprId, ty: IntTy },
+        NoSuchMethod { expr: ExprId, receiver_ty: Ty, method: Name, traits: Vec<TraitId> },
+        UnsatisfiedTraitBound { expr: ExprId, trait_ref: TraitRef, nested: Option<TraitRef> },
     }
 
     impl InferenceDiagnostic {
@@ -634,6 +889,101 @@ mod diagnostics {
                     let field = source_map.field_syntax(*expr, *field);
                     sink.push(NoSuchField { file, field })
                 }
+                InferenceDiagnostic::UnresolvedPath { expr } => {
+                    let file = owner.lookup(db).source(db).file_id;
+                    let (_, source_map) = db.body_with_source_map(owner.into());
+                    // Paths produced by macros are not reported: we can't
+                    // highlight them properly.
+                    let src = match source_map.expr_syntax(*expr) {
+                        Some(it) if it.file_id == file => it,
+                        _ => return,
+                    };
+                    if let Some(expr) = src.value.left() {
+                        sink.push(UnresolvedPath { file, expr })
+                    }
+                }
+                InferenceDiagnostic::BreakWithValueOutsideLoop { expr } => {
+                    let file = owner.lookup(db).source(db).file_id;
+                    let (_, source_map) = db.body_with_source_map(owner.into());
+                    let src = match source_map.expr_syntax(*expr) {
+                        Some(it) if it.file_id == file => it,
+                        _ => return,
+                    };
+                    if let Some(expr) = src.value.left() {
+                        sink.push(BreakWithValueOutsideLoop { file, expr })
+                    }
+                }
+                InferenceDiagnostic::TryInNonTryFunction { expr, suggested_ret_ty } => {
+                    let file = owner.lookup(db).source(db).file_id;
+                    let (_, source_map) = db.body_with_source_map(owner.into());
+                    let src = match source_map.expr_syntax(*expr) {
+                        Some(it) if it.file_id == file => it,
+                        _ => return,
+                    };
+                    if let Some(expr) = src.value.left() {
+                        sink.push(TryInNonTryFunction {
+                            file,
+                            expr,
+                            suggested_ret_ty: suggested_ret_ty.clone(),
+                        })
+                    }
+                }
+                InferenceDiagnostic::OverflowingLiteral { expr, ty } => {
+                    let file = owner.lookup(db).source(db).file_id;
+                    let (_, source_map) = db.body_with_source_map(owner.into());
+                    let src = match source_map.expr_syntax(*expr) {
+                        Some(it) if it.file_id == file => it,
+                        _ => return,
+                    };
+                    if let Some(expr) = src.value.left() {
+                        sink.push(OverflowingLiteral { file, expr, ty: ty.to_string() })
+                    }
+                }
+                InferenceDiagnostic::NoSuchMethod { expr, receiver_ty, method, traits } => {
+                    let file = owner.lookup(db).source(db).file_id;
+                    let (_, source_map) = db.body_with_source_map(owner.into());
+                    let src = match source_map.expr_syntax(*expr) {
+                        Some(it) if it.file_id == file => it,
+                        _ => return,
+                    };
+                    let expr = match src.value.left() {
+                        Some(it) => it,
+                        None => return,
+                    };
+                    let module = owner.lookup(db).container.module(db);
+                    let traits = traits
+                        .iter()
+                        .map(|&trait_| {
+                            match find_path(db, ItemInNs::Types(trait_.into()), module) {
+                                Some(path) => path.to_string(),
+                                None => db.trait_data(trait_).name.to_string(),
+                            }
+                        })
+                        .collect();
+                    sink.push(NoSuchMethod {
+                        file,
+                        expr,
+                        method: method.to_string(),
+                        receiver_ty: receiver_ty.display(db).to_string(),
+                        traits,
+                    })
+                }
+                InferenceDiagnostic::UnsatisfiedTraitBound { expr, trait_ref, nested } => {
+                    let file = owner.lookup(db).source(db).file_id;
+                    let (_, source_map) = db.body_with_source_map(owner.into());
+                    let src = match source_map.expr_syntax(*expr) {
+                        Some(it) if it.file_id == file => it,
+                        _ => return,
+                    };
+                    if let Some(expr) = src.value.left() {
+                        sink.push(UnsatisfiedTraitBound {
+                            file,
+                            expr,
+                            bound: trait_ref.display(db).to_string(),
+                            nested: nested.as_ref().map(|it| it.display(db).to_string()),
+                        })
+                    }
+                }
             }
         }
     }
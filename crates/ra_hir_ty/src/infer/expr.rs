@@ -1,6 +1,6 @@
 //! Type inference for expressions.
 
-use std::iter::{repeat, repeat_with};
+use std::iter::{self, repeat, repeat_with};
 use std::sync::Arc;
 
 use hir_def::{
@@ -8,22 +8,26 @@ use hir_def::{
     expr::{Array, BinaryOp, Expr, ExprId, Literal, Statement, UnaryOp},
     path::{GenericArg, GenericArgs},
     resolver::resolver_for_expr,
-    AdtId, AssocContainerId, Lookup, StructFieldId,
+    AdtId, AssocContainerId, GenericDefId, HasModule, Lookup, StructFieldId, TraitId, VariantId,
 };
-use hir_expand::name::Name;
+use hir_expand::name::{name, Name};
 use ra_syntax::ast::RangeOp;
+use rustc_hash::FxHashSet;
 
 use crate::{
     autoderef,
     db::HirDatabase,
+    display::HirDisplay,
     method_resolution, op,
     traits::InEnvironment,
     utils::{generics, variant_data, Generics},
-    ApplicationTy, Binders, CallableDef, InferTy, IntTy, Mutability, Obligation, Substs, TraitRef,
-    Ty, TypeCtor, Uncertain,
+    ApplicationTy, Binders, CallableDef, Canonical, GenericPredicate, InferTy, IntTy, Mutability,
+    Obligation, Substs, TraitRef, Ty, TypeCtor, TypeWalk, Uncertain,
 };
 
-use super::{BindingMode, Expectation, InferenceContext, InferenceDiagnostic, TypeMismatch};
+use super::{
+    BindingMode, BreakableContext, Expectation, InferenceContext, InferenceDiagnostic, TypeMismatch,
+};
 
 impl<'a, D: HirDatabase> InferenceContext<'a, D> {
     pub(super) fn infer_expr(&mut self, tgt_expr: ExprId, expected: &Expectation) -> Ty {
@@ -75,31 +79,92 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
 
                 self.coerce_merge_branch(&then_ty, &else_ty)
             }
-            Expr::Block { statements, tail } => self.infer_block(statements, *tail, expected),
+            Expr::Block { statements, tail, label: None } => {
+                self.infer_block(statements, *tail, expected)
+            }
+            Expr::Block { statements, tail, label: Some(_) } => {
+                let break_ty = self.breakable_ty(expected);
+                self.breakables.push(BreakableContext {
+                    expr: tgt_expr,
+                    allows_value: true,
+                    may_break: false,
+                    break_ty: break_ty.clone(),
+                });
+                let ty = self.infer_block(statements, *tail, &Expectation::has_type(break_ty));
+                let ctxt = self.breakables.pop().expect("breakable stack broken");
+                if ctxt.may_break {
+                    self.coerce(&ty, &ctxt.break_ty);
+                    ctxt.break_ty
+                } else {
+                    ty
+                }
+            }
             Expr::TryBlock { body } => {
+                let prev_in_try_block = std::mem::replace(&mut self.in_try_block, true);
                 let _inner = self.infer_expr(*body, expected);
+                self.in_try_block = prev_in_try_block;
                 // FIXME should be std::result::Result<{inner}, _>
                 Ty::Unknown
             }
-            Expr::Loop { body } => {
+            Expr::Async { body } => {
+                // Like in closures, `?` and `return` in the block return from
+                // the block
+                let ret_ty = self.table.new_type_var();
+                let prev_ret_ty = std::mem::replace(&mut self.return_ty, ret_ty.clone());
+                let prev_in_try_block = std::mem::replace(&mut self.in_try_block, false);
+
+                self.infer_expr_coerce(*body, &Expectation::has_type(ret_ty));
+
+                self.return_ty = prev_ret_ty;
+                self.in_try_block = prev_in_try_block;
+                // FIXME should be impl Future<Output = {ret_ty}>
+                Ty::Unknown
+            }
+            Expr::Loop { body, .. } => {
+                // FIXME: `while let` is desugared to `loop`, so `break` with a
+                // value inside of it is not reported
+                self.breakables.push(BreakableContext {
+                    expr: tgt_expr,
+                    allows_value: true,
+                    may_break: false,
+                    break_ty: self.breakable_ty(expected),
+                });
                 self.infer_expr(*body, &Expectation::has_type(Ty::unit()));
-                // FIXME handle break with value
-                Ty::simple(TypeCtor::Never)
+                let ctxt = self.breakables.pop().expect("breakable stack broken");
+                if ctxt.may_break {
+                    ctxt.break_ty
+                } else {
+                    Ty::simple(TypeCtor::Never)
+                }
             }
-            Expr::While { condition, body } => {
+            Expr::While { condition, body, .. } => {
                 // while let is desugared to a match loop, so this is always simple while
                 self.infer_expr(*condition, &Expectation::has_type(Ty::simple(TypeCtor::Bool)));
+                self.breakables.push(BreakableContext {
+                    expr: tgt_expr,
+                    allows_value: false,
+                    may_break: false,
+                    break_ty: Ty::unit(),
+                });
                 self.infer_expr(*body, &Expectation::has_type(Ty::unit()));
+                self.breakables.pop().expect("breakable stack broken");
                 Ty::unit()
             }
-            Expr::For { iterable, body, pat } => {
+            Expr::For { iterable, body, pat, .. } => {
                 let iterable_ty = self.infer_expr(*iterable, &Expectation::none());
 
                 let pat_ty =
                     self.resolve_associated_type(iterable_ty, self.resolve_into_iter_item());
 
                 self.infer_pat(*pat, &pat_ty, BindingMode::default());
+                self.breakables.push(BreakableContext {
+                    expr: tgt_expr,
+                    allows_value: false,
+                    may_break: false,
+                    break_ty: Ty::unit(),
+                });
                 self.infer_expr(*body, &Expectation::has_type(Ty::unit()));
+                self.breakables.pop().expect("breakable stack broken");
                 Ty::unit()
             }
             Expr::Lambda { body, args, ret_type, arg_types } => {
@@ -138,10 +203,14 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 self.coerce(&closure_ty, &expected.ty);
 
                 let prev_ret_ty = std::mem::replace(&mut self.return_ty, ret_ty.clone());
+                // `?` in the closure returns from the closure, even inside of a
+                // `try` block
+                let prev_in_try_block = std::mem::replace(&mut self.in_try_block, false);
 
                 self.infer_expr_coerce(*body, &Expectation::has_type(ret_ty));
 
                 self.return_ty = prev_ret_ty;
+                self.in_try_block = prev_in_try_block;
 
                 closure_ty
             }
@@ -155,7 +224,7 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                         (Vec::new(), Ty::Unknown)
                     }
                 };
-                self.register_obligations_for_call(&callee_ty);
+                self.register_obligations_for_call(tgt_expr, &callee_ty);
                 self.check_call_arguments(args, &param_tys);
                 let ret_ty = self.normalize_associated_types_in(ret_ty);
                 ret_ty
@@ -185,13 +254,38 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             Expr::Path(p) => {
                 // FIXME this could be more efficient...
                 let resolver = resolver_for_expr(self.db, self.owner.into(), tgt_expr);
-                self.infer_path(&resolver, p, tgt_expr.into()).unwrap_or(Ty::Unknown)
+                self.infer_path(&resolver, p, tgt_expr.into()).unwrap_or_else(|| {
+                    self.report_unresolved_path(&resolver, p, tgt_expr);
+                    Ty::Unknown
+                })
             }
-            Expr::Continue => Ty::simple(TypeCtor::Never),
-            Expr::Break { expr } => {
-                if let Some(expr) = expr {
-                    // FIXME handle break with value
-                    self.infer_expr(*expr, &Expectation::none());
+            Expr::Continue { .. } => Ty::simple(TypeCtor::Never),
+            Expr::Break { expr, .. } => {
+                let target = self.db.expr_scopes(self.owner).break_target(tgt_expr);
+                let ctxt = target.and_then(|target| {
+                    self.breakables.iter().rposition(|ctxt| ctxt.expr == target)
+                });
+                let (allows_value, break_ty) = match ctxt {
+                    Some(idx) => {
+                        let ctxt = &mut self.breakables[idx];
+                        ctxt.may_break = true;
+                        (ctxt.allows_value, ctxt.break_ty.clone())
+                    }
+                    None => (true, Ty::Unknown),
+                };
+                match expr {
+                    Some(expr) if allows_value => {
+                        self.infer_expr_coerce(*expr, &Expectation::has_type(break_ty));
+                    }
+                    Some(expr) => {
+                        self.infer_expr(*expr, &Expectation::none());
+                        self.push_diagnostic(InferenceDiagnostic::BreakWithValueOutsideLoop {
+                            expr: tgt_expr,
+                        });
+                    }
+                    None => {
+                        self.coerce(&Ty::unit(), &break_ty);
+                    }
                 }
                 Ty::simple(TypeCtor::Never)
             }
@@ -205,7 +299,8 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 Ty::simple(TypeCtor::Never)
             }
             Expr::RecordLit { path, fields, spread } => {
-                let (ty, def_id) = self.resolve_variant(path.as_ref());
+                let hygiene = self.body.expr_hygiene(tgt_expr);
+                let (ty, def_id) = self.resolve_variant(path.as_ref(), hygiene);
                 if let Some(variant) = def_id {
                     self.write_variant_resolution(tgt_expr.into(), variant);
                 }
@@ -213,6 +308,14 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 self.unify(&ty, &expected.ty);
 
                 let substs = ty.substs().unwrap_or_else(Substs::empty);
+                if let Some(variant) = def_id {
+                    let adt: AdtId = match variant {
+                        VariantId::StructId(it) => it.into(),
+                        VariantId::UnionId(it) => it.into(),
+                        VariantId::EnumVariantId(it) => it.parent.into(),
+                    };
+                    self.register_generic_predicates(tgt_expr, adt.into(), &substs);
+                }
                 let field_types =
                     def_id.map(|it| self.db.field_types(it.into())).unwrap_or_default();
                 let variant_data = def_id.map(|it| variant_data(self.db, it));
@@ -264,7 +367,11 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                                 self.write_field_resolution(tgt_expr, field);
                                 self.db.field_types(s.into())[field.local_id]
                                     .clone()
-                                    .subst(&a_ty.parameters)
+                                    .subst_with_lifetimes(
+                                        &a_ty.parameters,
+                                        s.into(),
+                                        &a_ty.lifetimes,
+                                    )
                             })
                         }
                         // FIXME:
@@ -285,8 +392,8 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             }
             Expr::Try { expr } => {
                 let inner_ty = self.infer_expr_inner(*expr, &Expectation::none());
-                let ty = self.resolve_associated_type(inner_ty, self.resolve_ops_try_ok());
-                ty
+                self.check_try_return_ty(tgt_expr, &inner_ty);
+                self.resolve_associated_type(inner_ty, self.resolve_ops_try_ok())
             }
             Expr::Cast { expr, type_ref } => {
                 let _inner_ty = self.infer_expr_inner(*expr, &Expectation::none());
@@ -381,13 +488,14 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                         _ => Expectation::none(),
                     };
                     let lhs_ty = self.infer_expr(*lhs, &lhs_expectation);
-                    // FIXME: find implementation of trait corresponding to operation
-                    // symbol and resolve associated `Output` type
                     let rhs_expectation = op::binary_op_rhs_expectation(*op, lhs_ty.clone());
                     let rhs_ty = self.infer_expr(*rhs, &Expectation::has_type(rhs_expectation));
 
-                    // FIXME: similar as above, return ty is often associated trait type
-                    op::binary_op_return_ty(*op, lhs_ty, rhs_ty)
+                    if op::is_builtin_binop(*op, &lhs_ty, &rhs_ty) {
+                        op::binary_op_return_ty(*op, lhs_ty, rhs_ty)
+                    } else {
+                        self.infer_overloaded_binop(tgt_expr, *op, lhs_ty, rhs_ty)
+                    }
                 }
                 _ => Ty::Unknown,
             },
@@ -508,6 +616,15 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         ty
     }
 
+    /// The type `break` values are coerced to, for a loop or labeled block
+    /// expected to have type `expected`.
+    fn breakable_ty(&mut self, expected: &Expectation) -> Ty {
+        match expected.ty {
+            Ty::Unknown => self.table.new_type_var(),
+            ref ty => ty.clone(),
+        }
+    }
+
     fn infer_block(
         &mut self,
         statements: &[Statement],
@@ -585,12 +702,20 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 self.write_method_resolution(tgt_expr, func);
                 (ty, self.db.value_ty(func.into()), Some(generics(self.db, func.into())))
             }
-            None => (receiver_ty, Binders::new(0, Ty::Unknown), None),
+            None => {
+                self.report_missing_method(
+                    tgt_expr,
+                    &canonicalized_receiver.value,
+                    &traits_in_scope,
+                    method_name,
+                );
+                (receiver_ty, Binders::new(0, Ty::Unknown), None)
+            }
         };
         let substs = self.substs_for_method_call(def_generics, generic_args, &derefed_receiver_ty);
         let method_ty = method_ty.subst(&substs);
         let method_ty = self.insert_type_vars(method_ty);
-        self.register_obligations_for_call(&method_ty);
+        self.register_obligations_for_call(tgt_expr, &method_ty);
         let (expected_receiver_ty, param_tys, ret_ty) = match method_ty.callable_sig(self.db) {
             Some(sig) => {
                 if !sig.params().is_empty() {
@@ -614,6 +739,167 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         ret_ty
     }
 
+    /// Infers a binary operator on non-primitive operands through the
+    /// corresponding operator trait, e.g. `a + b` as `<A as Add<B>>::Output`.
+    fn infer_overloaded_binop(
+        &mut self,
+        tgt_expr: ExprId,
+        op: BinaryOp,
+        lhs_ty: Ty,
+        rhs_ty: Ty,
+    ) -> Ty {
+        let trait_ = match self.resolve_binary_op_trait(op) {
+            Some(it) => it,
+            None => return op::binary_op_return_ty(op, lhs_ty, rhs_ty),
+        };
+        let trait_data = self.db.trait_data(trait_);
+        let method = op::binary_op_method_name(op).and_then(|it| trait_data.method_by_name(&it));
+        if let Some(method) = method {
+            self.write_method_resolution(tgt_expr, method);
+        }
+        match op {
+            BinaryOp::ArithOp(_) => {
+                let output = trait_data.associated_type_by_name(&name![Output]);
+                self.resolve_associated_type_with_params(lhs_ty, output, &[rhs_ty])
+            }
+            _ => {
+                // Comparisons and compound assignments have a fixed result
+                // type, but the trait bound still guides inference of the
+                // operands.
+                let substs = Substs::build_for_def(self.db, trait_)
+                    .push(lhs_ty.clone())
+                    .fill(iter::once(rhs_ty.clone()).chain(repeat(Ty::Unknown)))
+                    .build();
+                self.obligations.push(Obligation::Trait(TraitRef { trait_, substs }));
+                op::binary_op_return_ty(op, lhs_ty, rhs_ty)
+            }
+        }
+    }
+
+    /// Reports a method call which doesn't resolve, unless that might just be
+    /// because of information we're missing.
+    fn report_missing_method(
+        &mut self,
+        tgt_expr: ExprId,
+        receiver_ty: &Canonical<Ty>,
+        traits_in_scope: &FxHashSet<TraitId>,
+        name: &Name,
+    ) {
+        let krate = match self.resolver.krate() {
+            Some(it) => it,
+            None => return,
+        };
+        if receiver_ty.num_vars != 0 || !is_fully_known(&receiver_ty.value) {
+            return;
+        }
+        // Only ADTs are checked: other types get most of their methods from
+        // the standard library, which might not be loaded.
+        let mut ty = &receiver_ty.value;
+        while let Some((inner, _)) = ty.as_reference() {
+            ty = inner;
+        }
+        let adt = match ty.as_adt() {
+            Some((adt, _)) => adt,
+            None => return,
+        };
+        // The method might come from an impl we don't know about, like one
+        // generated by a derive we can't expand.
+        let adt_krate = adt.module(self.db).krate;
+        if [krate, adt_krate]
+            .iter()
+            .any(|&krate| self.db.crate_def_map(krate).may_lack_impls(self.db, adt.into()))
+        {
+            return;
+        }
+        let traits = method_resolution::traits_providing_method(
+            receiver_ty,
+            self.db,
+            self.trait_env.clone(),
+            krate,
+            traits_in_scope,
+            name,
+        );
+        self.push_diagnostic(InferenceDiagnostic::NoSuchMethod {
+            expr: tgt_expr,
+            receiver_ty: receiver_ty.value.clone(),
+            method: name.clone(),
+            traits,
+        });
+    }
+
+    /// Reports a `?` in a function whose return type doesn't implement `Try`,
+    /// i.e. which has no way to return the error.
+    fn check_try_return_ty(&mut self, tgt_expr: ExprId, inner_ty: &Ty) {
+        if self.in_try_block {
+            return;
+        }
+        let try_trait = match self.resolve_ops_try() {
+            Some(it) => it,
+            None => return,
+        };
+        let krate = match self.resolver.krate() {
+            Some(it) => it,
+            None => return,
+        };
+        let ret_ty = self.resolve_ty_as_possible(self.return_ty.clone());
+        if !is_fully_known(&ret_ty) {
+            return;
+        }
+        let inner_ty = self.resolve_ty_as_possible(inner_ty.clone());
+        let canonical_ret_ty = Canonical { value: ret_ty.clone(), num_vars: 0 };
+        let env = self.trait_env.clone();
+        if method_resolution::implements_trait(&canonical_ret_ty, self.db, env, krate, try_trait) {
+            self.check_try_error_conversion(tgt_expr, &inner_ty, &ret_ty);
+            return;
+        }
+
+        // If the operand is a `Result`, we can suggest returning a `Result`
+        // with the same error type
+        let suggested_ret_ty = match (inner_ty.as_adt(), self.resolve_result_enum()) {
+            (Some((adt, substs)), Some(result_enum)) if adt == result_enum && substs.len() == 2 => {
+                let err_ty = &substs[1];
+                if is_fully_known(err_ty) {
+                    Some(format!(
+                        "Result<{}, {}>",
+                        ret_ty.display(self.db),
+                        err_ty.display(self.db)
+                    ))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        self.push_diagnostic(InferenceDiagnostic::TryInNonTryFunction {
+            expr: tgt_expr,
+            suggested_ret_ty,
+        });
+    }
+
+    /// `?` on a `Result` converts the error with `From` before returning it:
+    /// checks that the error type of the returned `Result` can be created
+    /// from the error type of the operand.
+    fn check_try_error_conversion(&mut self, tgt_expr: ExprId, inner_ty: &Ty, ret_ty: &Ty) {
+        let (result_enum, from_trait) =
+            match (self.resolve_result_enum(), self.resolve_convert_from()) {
+                (Some(result_enum), Some(from_trait)) => (result_enum, from_trait),
+                _ => return,
+            };
+        let err_ty = |ty: &Ty| match ty.as_adt() {
+            Some((adt, substs)) if adt == result_enum && substs.len() == 2 => {
+                Some(substs[1].clone())
+            }
+            _ => None,
+        };
+        let (inner_err_ty, ret_err_ty) = match (err_ty(inner_ty), err_ty(ret_ty)) {
+            (Some(inner_err_ty), Some(ret_err_ty)) => (inner_err_ty, ret_err_ty),
+            _ => return,
+        };
+        let substs =
+            Substs::build_for_def(self.db, from_trait).push(ret_err_ty).push(inner_err_ty).build();
+        self.bound_checks.push((tgt_expr, TraitRef { trait_: from_trait, substs }));
+    }
+
     fn check_call_arguments(&mut self, args: &[ExprId], param_tys: &[Ty]) {
         // Quoting https://github.com/rust-lang/rust/blob/6ef275e6c3cb1384ec78128eceeb4963ff788dca/src/librustc_typeck/check/mod.rs#L3325 --
         // We do this in a pretty awful way: first we type-check any arguments
@@ -662,13 +948,17 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         // handle provided type arguments
         if let Some(generic_args) = generic_args {
             // if args are provided, it should be all of them, but we can't rely on that
-            for arg in generic_args.args.iter().take(type_params) {
-                match arg {
-                    GenericArg::Type(type_ref) => {
-                        let ty = self.make_ty(type_ref);
-                        substs.push(ty);
-                    }
-                }
+            let type_args = generic_args.args.iter().filter(|arg| match arg {
+                GenericArg::Type(_) | GenericArg::Const(_) => true,
+                GenericArg::Lifetime(_) => false,
+            });
+            for arg in type_args.take(type_params) {
+                let ty = match arg {
+                    GenericArg::Type(type_ref) => self.make_ty(type_ref),
+                    GenericArg::Const(const_ref) => Ty::from_const_ref(const_ref),
+                    GenericArg::Lifetime(_) => unreachable!(),
+                };
+                substs.push(ty);
             }
         };
         let supplied_params = substs.len();
@@ -679,16 +969,10 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         Substs(substs.into())
     }
 
-    fn register_obligations_for_call(&mut self, callable_ty: &Ty) {
+    fn register_obligations_for_call(&mut self, tgt_expr: ExprId, callable_ty: &Ty) {
         if let Ty::Apply(a_ty) = callable_ty {
             if let TypeCtor::FnDef(def) = a_ty.ctor {
-                let generic_predicates = self.db.generic_predicates(def.into());
-                for predicate in generic_predicates.iter() {
-                    let predicate = predicate.clone().subst(&a_ty.parameters);
-                    if let Some(obligation) = Obligation::from_predicate(predicate) {
-                        self.obligations.push(obligation);
-                    }
-                }
+                self.register_generic_predicates(tgt_expr, def.into(), &a_ty.parameters);
                 // add obligation for trait implementation, if this is a trait method
                 match def {
                     CallableDef::FunctionId(f) => {
@@ -696,10 +980,9 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                             // construct a TraitDef
                             let substs =
                                 a_ty.parameters.prefix(generics(self.db, trait_.into()).len());
-                            self.obligations.push(Obligation::Trait(TraitRef {
-                                trait_: trait_.into(),
-                                substs,
-                            }));
+                            let trait_ref = TraitRef { trait_: trait_.into(), substs };
+                            self.bound_checks.push((tgt_expr, trait_ref.clone()));
+                            self.obligations.push(Obligation::Trait(trait_ref));
                         }
                     }
                     CallableDef::StructId(_) | CallableDef::EnumVariantId(_) => {}
@@ -707,4 +990,34 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             }
         }
     }
+
+    /// Registers the where clauses of `def` as obligations, and remembers the
+    /// trait bounds among them so unsatisfied ones can be reported later.
+    fn register_generic_predicates(
+        &mut self,
+        tgt_expr: ExprId,
+        def: GenericDefId,
+        substs: &Substs,
+    ) {
+        let generic_predicates = self.db.generic_predicates(def);
+        for predicate in generic_predicates.iter() {
+            let predicate = predicate.clone().subst(substs);
+            if let GenericPredicate::Implemented(trait_ref) = &predicate {
+                self.bound_checks.push((tgt_expr, trait_ref.clone()));
+            }
+            if let Some(obligation) = Obligation::from_predicate(predicate) {
+                self.obligations.push(obligation);
+            }
+        }
+    }
+}
+
+fn is_fully_known(ty: &Ty) -> bool {
+    let mut known = true;
+    ty.walk(&mut |ty| {
+        if let Ty::Infer(_) | Ty::Unknown = ty {
+            known = false;
+        }
+    });
+    known
 }
@@ -21,8 +21,9 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         subpats: &[PatId],
         expected: &Ty,
         default_bm: BindingMode,
+        id: PatId,
     ) -> Ty {
-        let (ty, def) = self.resolve_variant(path);
+        let (ty, def) = self.resolve_variant(path, self.body.pat_hygiene(id));
         let var_data = def.map(|it| variant_data(self.db, it));
         self.unify(&ty, expected);
 
@@ -50,7 +51,7 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         default_bm: BindingMode,
         id: PatId,
     ) -> Ty {
-        let (ty, def) = self.resolve_variant(path);
+        let (ty, def) = self.resolve_variant(path, self.body.pat_hygiene(id));
         let var_data = def.map(|it| variant_data(self.db, it));
         if let Some(variant) = def {
             self.write_variant_resolution(id.into(), variant);
@@ -152,14 +153,14 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 Ty::apply_one(TypeCtor::Ref(*mutability), subty)
             }
             Pat::TupleStruct { path: p, args: subpats } => {
-                self.infer_tuple_struct_pat(p.as_ref(), subpats, expected, default_bm)
+                self.infer_tuple_struct_pat(p.as_ref(), subpats, expected, default_bm, pat)
             }
             Pat::Record { path: p, args: fields } => {
                 self.infer_record_pat(p.as_ref(), fields, expected, default_bm, pat)
             }
             Pat::Path(path) => {
                 // FIXME use correct resolver for the surrounding expression
-                let resolver = self.resolver.clone();
+                let resolver = self.resolver_for_hygiene(body.pat_hygiene(pat));
                 self.infer_path(&resolver, &path, pat.into()).unwrap_or(Ty::Unknown)
             }
             Pat::Bind { mode, name: _, subpat } => {
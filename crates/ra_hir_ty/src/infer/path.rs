@@ -3,15 +3,16 @@
 use std::iter;
 
 use hir_def::{
+    expr::ExprId,
     path::{Path, PathSegment},
     resolver::{ResolveValueResult, Resolver, TypeNs, ValueNs},
-    AssocContainerId, AssocItemId, Lookup,
+    AssocContainerId, AssocItemId, ConstId, Lookup, ModuleDefId, ModuleId,
 };
 use hir_expand::name::Name;
 
-use crate::{db::HirDatabase, method_resolution, Substs, Ty, ValueTyDefId};
+use crate::{db::HirDatabase, method_resolution, Substs, Ty, TypeWalk, ValueTyDefId};
 
-use super::{ExprOrPatId, InferenceContext, TraitRef};
+use super::{ExprOrPatId, InferenceContext, InferenceDiagnostic, TraitRef};
 
 impl<'a, D: HirDatabase> InferenceContext<'a, D> {
     pub(super) fn infer_path(
@@ -26,6 +27,56 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         Some(ty)
     }
 
+    /// Reports a path in expression position which doesn't resolve to
+    /// anything, unless a macro we failed to expand might have defined it.
+    pub(super) fn report_unresolved_path(
+        &mut self,
+        resolver: &Resolver,
+        path: &Path,
+        expr: ExprId,
+    ) {
+        // If a prefix of the path resolves, it's the associated item which is
+        // missing, and we don't know all the impls yet.
+        if path.type_anchor().is_some()
+            || resolver.resolve_path_in_value_ns(self.db, path.mod_path()).is_some()
+        {
+            return;
+        }
+        // A lowercase name most likely refers to a local binding, and bindings
+        // in slice, box or macro patterns aren't lowered yet.
+        let is_local = path.qualifier().is_none()
+            && path.mod_path().segments.first().map_or(false, |name| {
+                name.to_string().starts_with(|c: char| c.is_lowercase() || c == '_')
+            });
+        if is_local {
+            return;
+        }
+        let module = match path.qualifier() {
+            None => resolver.module(),
+            // A macro we failed to expand might define the missing name in the
+            // module the qualifier leads to, or, if the qualifier itself
+            // doesn't resolve, in the current module.
+            Some(qualifier) => {
+                match resolver
+                    .resolve_module_path_in_items(self.db, qualifier.mod_path())
+                    .take_types()
+                {
+                    Some(ModuleDefId::ModuleId(it)) => Some(it),
+                    Some(_) => None,
+                    None => resolver.module(),
+                }
+            }
+        };
+        if module.map_or(true, |it| self.may_be_defined_by_macro(it)) {
+            return;
+        }
+        self.push_diagnostic(InferenceDiagnostic::UnresolvedPath { expr });
+    }
+
+    fn may_be_defined_by_macro(&self, module: ModuleId) -> bool {
+        self.db.crate_def_map(module.krate).has_unexpanded_macros(module.local_id)
+    }
+
     fn resolve_value_path(
         &mut self,
         resolver: &Resolver,
@@ -63,6 +114,7 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 let ty = self.resolve_ty_as_possible(ty);
                 return Some(ty);
             }
+            ValueNs::ConstParam(it) => return Some(crate::lower::const_param_ty(self.db, it)),
             ValueNs::FunctionId(it) => it.into(),
             ValueNs::ConstId(it) => it.into(),
             ValueNs::StaticId(it) => it.into(),
@@ -175,7 +227,12 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             AssocItemId::TypeAliasId(_) => unreachable!(),
         };
 
-        self.write_assoc_resolution(id, item);
+        match item {
+            AssocItemId::ConstId(konst) => {
+                self.write_trait_const_resolution(id, konst, &trait_ref.substs)
+            }
+            _ => self.write_assoc_resolution(id, item),
+        }
         Some((def, Some(trait_ref.substs)))
     }
 
@@ -228,6 +285,10 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                             trait_,
                             substs: trait_substs.clone(),
                         }));
+                        if let AssocItemId::ConstId(konst) = item {
+                            self.write_trait_const_resolution(id, konst, &trait_substs);
+                            return Some((def, Some(trait_substs)));
+                        }
                         Some(trait_substs)
                     }
                     AssocContainerId::ContainerId(_) => None,
@@ -238,4 +299,31 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             },
         )
     }
+
+    /// Records the resolution of a trait's associated const. The path resolves
+    /// to the trait's const, so that e.g. find references sees it; if the
+    /// trait parameters are already known, the const of the impl that will be
+    /// used is recorded as well, for goto definition on `Foo::ID` to lead to
+    /// the value.
+    fn write_trait_const_resolution(&mut self, id: ExprOrPatId, konst: ConstId, substs: &Substs) {
+        let params: Vec<Ty> =
+            substs.iter().map(|ty| self.resolve_ty_as_possible(ty.clone())).collect();
+        let mut fully_known = true;
+        for ty in &params {
+            ty.walk(&mut |ty| match ty {
+                Ty::Infer(_) | Ty::Param(_) | Ty::Unknown => fully_known = false,
+                _ => {}
+            });
+        }
+        let impl_const = match self.resolver.krate() {
+            Some(krate) if fully_known => {
+                method_resolution::lookup_impl_const(self.db, krate, konst, &params)
+            }
+            _ => None,
+        };
+        self.write_assoc_resolution(id, konst.into());
+        if let Some(impl_const) = impl_const {
+            self.result.impl_const_resolutions.insert(id, impl_const);
+        }
+    }
 }
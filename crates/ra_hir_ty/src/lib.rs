@@ -39,15 +39,20 @@ mod tests;
 mod test_db;
 mod marks;
 
+use std::hash::{Hash, Hasher};
 use std::ops::Deref;
 use std::sync::Arc;
 use std::{fmt, iter, mem};
 
 use hir_def::{
-    expr::ExprId, generics::TypeParamProvenance, type_ref::Mutability, AdtId, AssocContainerId,
-    DefWithBodyId, GenericDefId, HasModule, Lookup, TraitId, TypeAliasId, TypeParamId,
+    expr::ExprId,
+    generics::TypeParamProvenance,
+    type_ref::{ConstScalar, Mutability},
+    AdtId, AssocContainerId, DefWithBodyId, FunctionId, GenericDefId, HasModule, LifetimeParamId,
+    Lookup, TraitId, TypeAliasId, TypeParamId,
 };
 use hir_expand::name::Name;
+use ra_arena::ArenaId;
 use ra_db::{impl_intern_key, salsa, CrateId};
 
 use crate::{
@@ -143,6 +148,20 @@ pub enum TypeCtor {
     /// The closure signature is stored in a `FnPtr` type in the first type
     /// parameter.
     Closure { def: DefWithBodyId, expr: ExprId },
+
+    /// The value of a const generic argument, like the `16` in `Buf<16>`.
+    /// Const arguments are represented as types in the substs, so they can be
+    /// passed through the type machinery (and Chalk) unchanged.
+    Const(ConstScalar),
+
+    /// An opaque type, i.e. an `impl Trait` in the return type of a function.
+    /// It only unifies with itself; the type parameters are those of the
+    /// function. The bounds can be looked up with `Ty::impl_trait_bounds`.
+    ///
+    /// An `impl Trait` in argument position isn't opaque: like in rustc, it is
+    /// an anonymous type parameter of the function (see
+    /// `ImplTraitLoweringMode::Param`), so it has no hidden type.
+    OpaqueType(OpaqueTyId),
 }
 
 /// This exists just for Chalk, because Chalk just has a single `StructId` where
@@ -152,6 +171,35 @@ pub enum TypeCtor {
 pub struct TypeCtorId(salsa::InternId);
 impl_intern_key!(TypeCtorId);
 
+/// Where an `impl Trait` type comes from. For `ReturnTypeImplTrait`, the index
+/// is the position of the `impl Trait` in the return type, counting nested ones
+/// as well (so in `impl Iterator<Item = impl Display>`, the outer one is 0 and
+/// the inner one 1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImplTraitId {
+    ReturnTypeImplTrait(FunctionId, u16),
+}
+
+/// The interned `ImplTraitId` of an opaque type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OpaqueTyId(salsa::InternId);
+impl_intern_key!(OpaqueTyId);
+
+/// The `impl Trait`s in the return type of a function, indexed like in
+/// `ImplTraitId::ReturnTypeImplTrait`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ReturnTypeImplTraits {
+    pub impl_traits: Vec<ReturnTypeImplTrait>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ReturnTypeImplTrait {
+    /// The bounds of the `impl Trait`. These are bound over the generic
+    /// parameters of the function, followed by the opaque type itself, which
+    /// is the self type of the bounds.
+    pub bounds: Binders<Vec<GenericPredicate>>,
+}
+
 impl TypeCtor {
     pub fn num_ty_params(self, db: &impl HirDatabase) -> usize {
         match self {
@@ -160,13 +208,19 @@ impl TypeCtor {
             | TypeCtor::Int(_)
             | TypeCtor::Float(_)
             | TypeCtor::Str
-            | TypeCtor::Never => 0,
+            | TypeCtor::Never
+            | TypeCtor::Const(_) => 0,
             TypeCtor::Slice
             | TypeCtor::Array
             | TypeCtor::RawPtr(_)
             | TypeCtor::Ref(_)
             | TypeCtor::Closure { .. } // 1 param representing the signature of the closure
             => 1,
+            TypeCtor::OpaqueType(opaque_ty_id) => {
+                match db.lookup_intern_impl_trait_id(opaque_ty_id) {
+                    ImplTraitId::ReturnTypeImplTrait(func, _) => generics(db, func.into()).len(),
+                }
+            }
             TypeCtor::Adt(adt) => {
                 let generic_params = generics(db, AdtId::from(adt).into());
                 generic_params.len()
@@ -197,12 +251,30 @@ impl TypeCtor {
             | TypeCtor::RawPtr(_)
             | TypeCtor::Ref(_)
             | TypeCtor::FnPtr { .. }
-            | TypeCtor::Tuple { .. } => None,
+            | TypeCtor::Tuple { .. }
+            | TypeCtor::Const(_) => None,
             // Closure's krate is irrelevant for coherence I would think?
             TypeCtor::Closure { .. } => None,
             TypeCtor::Adt(adt) => Some(adt.module(db).krate),
             TypeCtor::FnDef(callable) => Some(callable.krate(db)),
             TypeCtor::AssociatedType(type_alias) => Some(type_alias.lookup(db).module(db).krate),
+            TypeCtor::OpaqueType(opaque_ty_id) => {
+                match db.lookup_intern_impl_trait_id(opaque_ty_id) {
+                    ImplTraitId::ReturnTypeImplTrait(func, _) => {
+                        Some(func.lookup(db).module(db).krate)
+                    }
+                }
+            }
+        }
+    }
+
+    /// The number of lifetimes in the `lifetimes` of an `ApplicationTy` with
+    /// this constructor.
+    pub fn num_lifetime_params(self, db: &impl HirDatabase) -> usize {
+        match self {
+            TypeCtor::Ref(_) => 1,
+            TypeCtor::Adt(adt) => db.generic_params(adt.into()).lifetimes.len(),
+            _ => 0,
         }
     }
 
@@ -220,7 +292,9 @@ impl TypeCtor {
             | TypeCtor::Ref(_)
             | TypeCtor::FnPtr { .. }
             | TypeCtor::Tuple { .. }
-            | TypeCtor::Closure { .. } => None,
+            | TypeCtor::Closure { .. }
+            | TypeCtor::Const(_)
+            | TypeCtor::OpaqueType(_) => None,
             TypeCtor::Adt(adt) => Some(adt.into()),
             TypeCtor::FnDef(callable) => Some(callable.into()),
             TypeCtor::AssociatedType(type_alias) => Some(type_alias.into()),
@@ -231,10 +305,46 @@ impl TypeCtor {
 /// A nominal type with (maybe 0) type parameters. This might be a primitive
 /// type like `bool`, a struct, tuple, function pointer, reference or
 /// several other things.
-#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+#[derive(Clone, Debug)]
 pub struct ApplicationTy {
     pub ctor: TypeCtor,
     pub parameters: Substs,
+    /// The lifetimes of a reference or the lifetime arguments of an ADT. We
+    /// don't do any region checking, so these are ignored when comparing
+    /// types, and they are not part of `parameters`; instantiating a generic
+    /// item substitutes them separately, see `Binders::subst_with_lifetimes`.
+    pub lifetimes: Arc<[Lifetime]>,
+}
+
+impl PartialEq for ApplicationTy {
+    fn eq(&self, other: &ApplicationTy) -> bool {
+        self.ctor == other.ctor && self.parameters == other.parameters
+    }
+}
+
+impl Eq for ApplicationTy {}
+
+impl Hash for ApplicationTy {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.ctor.hash(state);
+        self.parameters.hash(state);
+    }
+}
+
+/// A lifetime, as it appears in a reference type or in the lifetime arguments
+/// of an ADT.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum Lifetime {
+    /// A generic lifetime parameter, like `'a` in `fn foo<'a>(x: &'a u32)`.
+    Param(LifetimeParamId),
+    /// A bound lifetime variable. This is only used when lowering impls to
+    /// Chalk, where the lifetime parameters of the impl become variables,
+    /// following the type parameters.
+    Bound(u32),
+    /// The `'static` lifetime.
+    Static,
+    /// An elided, erased or unresolved lifetime.
+    Unknown,
 }
 
 /// A "projection" type corresponds to an (unnormalized)
@@ -311,12 +421,6 @@ pub enum Ty {
     /// didn't seem worth the overhead yet.
     Dyn(Arc<[GenericPredicate]>),
 
-    /// An opaque type (`impl Trait`).
-    ///
-    /// The predicates are quantified over the `Self` type; see `Ty::Dyn` for
-    /// more.
-    Opaque(Arc<[GenericPredicate]>),
-
     /// A placeholder for a type which could not be computed; this is propagated
     /// to avoid useless error messages. Doubles as a placeholder where type
     /// variables are inserted before type checking, since we want to try to
@@ -463,10 +567,32 @@ impl<T> Binders<T> {
 }
 
 impl<T: TypeWalk> Binders<T> {
-    /// Substitutes all variables.
+    /// Substitutes all variables. As we don't do any region inference, the
+    /// lifetimes in the value are erased; use `subst_with_lifetimes` if the
+    /// lifetime arguments are known.
     pub fn subst(self, subst: &Substs) -> T {
         assert_eq!(subst.len(), self.num_binders);
-        self.value.subst_bound_vars(subst)
+        self.value.map_lifetimes(&mut |_| Lifetime::Unknown).subst_bound_vars(subst)
+    }
+
+    /// Like `subst`, but also substitutes the lifetime parameters of `def`,
+    /// which is what the binders are over, by `lifetimes`.
+    pub fn subst_with_lifetimes(
+        self,
+        subst: &Substs,
+        def: GenericDefId,
+        lifetimes: &[Lifetime],
+    ) -> T {
+        assert_eq!(subst.len(), self.num_binders);
+        self.value
+            .map_lifetimes(&mut |lifetime| match lifetime {
+                Lifetime::Param(id) if id.parent == def => {
+                    let idx = u32::from(id.local_id.into_raw()) as usize;
+                    lifetimes.get(idx).copied().unwrap_or(Lifetime::Unknown)
+                }
+                _ => Lifetime::Unknown,
+            })
+            .subst_bound_vars(subst)
     }
 
     /// Substitutes just a prefix of the variables (shifting the rest).
@@ -558,6 +684,20 @@ impl TypeWalk for GenericPredicate {
     }
 }
 
+impl<T: TypeWalk> TypeWalk for Vec<T> {
+    fn walk(&self, f: &mut impl FnMut(&Ty)) {
+        for t in self {
+            t.walk(f);
+        }
+    }
+
+    fn walk_mut_binders(&mut self, f: &mut impl FnMut(&mut Ty, usize), binders: usize) {
+        for t in self {
+            t.walk_mut_binders(f, binders);
+        }
+    }
+}
+
 /// Basically a claim (currently not validated / checked) that the contained
 /// type / trait ref contains no inference variables; any inference variables it
 /// contained have been replaced by bound variables, and `num_vars` tells us how
@@ -614,13 +754,20 @@ impl TypeWalk for FnSig {
 
 impl Ty {
     pub fn simple(ctor: TypeCtor) -> Ty {
-        Ty::Apply(ApplicationTy { ctor, parameters: Substs::empty() })
+        Ty::apply(ctor, Substs::empty())
     }
     pub fn apply_one(ctor: TypeCtor, param: Ty) -> Ty {
-        Ty::Apply(ApplicationTy { ctor, parameters: Substs::single(param) })
+        Ty::apply(ctor, Substs::single(param))
     }
     pub fn apply(ctor: TypeCtor, parameters: Substs) -> Ty {
-        Ty::Apply(ApplicationTy { ctor, parameters })
+        Ty::apply_with_lifetimes(ctor, parameters, Arc::new([]))
+    }
+    pub fn apply_with_lifetimes(
+        ctor: TypeCtor,
+        parameters: Substs,
+        lifetimes: Arc<[Lifetime]>,
+    ) -> Ty {
+        Ty::Apply(ApplicationTy { ctor, parameters, lifetimes })
     }
     pub fn unit() -> Self {
         Ty::apply(TypeCtor::Tuple { cardinality: 0 }, Substs::empty())
@@ -628,7 +775,7 @@ impl Ty {
 
     pub fn as_reference(&self) -> Option<(&Ty, Mutability)> {
         match self {
-            Ty::Apply(ApplicationTy { ctor: TypeCtor::Ref(mutability), parameters }) => {
+            Ty::Apply(ApplicationTy { ctor: TypeCtor::Ref(mutability), parameters, .. }) => {
                 Some((parameters.as_single(), *mutability))
             }
             _ => None,
@@ -637,7 +784,7 @@ impl Ty {
 
     pub fn as_adt(&self) -> Option<(AdtId, &Substs)> {
         match self {
-            Ty::Apply(ApplicationTy { ctor: TypeCtor::Adt(adt_def), parameters }) => {
+            Ty::Apply(ApplicationTy { ctor: TypeCtor::Adt(adt_def), parameters, .. }) => {
                 Some((*adt_def, parameters))
             }
             _ => None,
@@ -646,7 +793,7 @@ impl Ty {
 
     pub fn as_tuple(&self) -> Option<&Substs> {
         match self {
-            Ty::Apply(ApplicationTy { ctor: TypeCtor::Tuple { .. }, parameters }) => {
+            Ty::Apply(ApplicationTy { ctor: TypeCtor::Tuple { .. }, parameters, .. }) => {
                 Some(parameters)
             }
             _ => None,
@@ -655,9 +802,9 @@ impl Ty {
 
     pub fn as_callable(&self) -> Option<(CallableDef, &Substs)> {
         match self {
-            Ty::Apply(ApplicationTy { ctor: TypeCtor::FnDef(callable_def), parameters }) => {
-                Some((*callable_def, parameters))
-            }
+            Ty::Apply(ApplicationTy {
+                ctor: TypeCtor::FnDef(callable_def), parameters, ..
+            }) => Some((*callable_def, parameters)),
             _ => None,
         }
     }
@@ -697,9 +844,9 @@ impl Ty {
     /// `Option<u32>` afterwards.)
     pub fn apply_substs(self, substs: Substs) -> Ty {
         match self {
-            Ty::Apply(ApplicationTy { ctor, parameters: previous_substs }) => {
+            Ty::Apply(ApplicationTy { ctor, parameters: previous_substs, lifetimes }) => {
                 assert_eq!(previous_substs.len(), substs.len());
-                Ty::Apply(ApplicationTy { ctor, parameters: substs })
+                Ty::Apply(ApplicationTy { ctor, parameters: substs, lifetimes })
             }
             _ => self,
         }
@@ -715,13 +862,65 @@ impl Ty {
     }
 
     /// If this is an `impl Trait` or `dyn Trait`, returns that trait.
-    pub fn inherent_trait(&self) -> Option<TraitId> {
+    pub fn inherent_trait(&self, db: &impl HirDatabase) -> Option<TraitId> {
+        let first_trait = |predicates: &[GenericPredicate]| {
+            predicates.iter().find_map(|pred| match pred {
+                GenericPredicate::Implemented(tr) => Some(tr.trait_),
+                _ => None,
+            })
+        };
         match self {
-            Ty::Dyn(predicates) | Ty::Opaque(predicates) => {
-                predicates.iter().find_map(|pred| match pred {
-                    GenericPredicate::Implemented(tr) => Some(tr.trait_),
-                    _ => None,
-                })
+            Ty::Dyn(predicates) => first_trait(predicates),
+            _ => first_trait(&self.impl_trait_bounds(db)?),
+        }
+    }
+
+    /// If this is an `impl Trait` type, returns its bounds, with the opaque
+    /// type itself as their self type.
+    pub fn impl_trait_bounds(&self, db: &impl HirDatabase) -> Option<Vec<GenericPredicate>> {
+        match self {
+            Ty::Apply(ApplicationTy {
+                ctor: TypeCtor::OpaqueType(opaque_ty_id),
+                parameters,
+                ..
+            }) => match db.lookup_intern_impl_trait_id(*opaque_ty_id) {
+                ImplTraitId::ReturnTypeImplTrait(func, idx) => {
+                    let impl_traits = db.return_type_impl_traits(func);
+                    let bounds = impl_traits.impl_traits.get(idx as usize)?.bounds.clone();
+                    let substs = Substs(
+                        parameters.iter().cloned().chain(iter::once(self.clone())).collect(),
+                    );
+                    Some(bounds.subst(&substs))
+                }
+            },
+            _ => None,
+        }
+    }
+
+    /// If this is an `impl Trait` type, returns the type hidden behind it, as
+    /// inferred from the body of the function that returns it.
+    pub fn hidden_type(&self, db: &impl HirDatabase) -> Option<Ty> {
+        match self {
+            Ty::Apply(ApplicationTy {
+                ctor: TypeCtor::OpaqueType(opaque_ty_id),
+                parameters,
+                ..
+            }) => {
+                match db.lookup_intern_impl_trait_id(*opaque_ty_id) {
+                    ImplTraitId::ReturnTypeImplTrait(func, idx) => {
+                        let hidden_ty = db.infer(func.into()).hidden_type(idx)?.clone();
+                        // the hidden type refers to the function's own type
+                        // params; replace them by the opaque type's parameters
+                        let generics = generics(db, func.into());
+                        Some(hidden_ty.fold(&mut |ty| match ty {
+                            Ty::Param(id) => match generics.param_idx(id) {
+                                Some(idx) => parameters[idx as usize].clone(),
+                                None => Ty::Param(id),
+                            },
+                            ty => ty,
+                        }))
+                    }
+                }
             }
             _ => None,
         }
@@ -743,8 +942,7 @@ pub trait TypeWalk {
     /// and blindly replace each instance of a certain index; when we 'enter'
     /// things that introduce new bound variables, we have to keep track of
     /// that. Currently, the only thing that introduces bound variables on our
-    /// side are `Ty::Dyn` and `Ty::Opaque`, which each introduce a bound
-    /// variable for the self type.
+    /// side is `Ty::Dyn`, which introduces a bound variable for the self type.
     fn walk_mut_binders(&mut self, f: &mut impl FnMut(&mut Ty, usize), binders: usize);
 
     fn fold(mut self, f: &mut impl FnMut(Ty) -> Ty) -> Self
@@ -780,6 +978,21 @@ pub trait TypeWalk {
         self
     }
 
+    /// Replaces the lifetimes of all references and ADTs using `f`.
+    fn map_lifetimes(mut self, f: &mut impl FnMut(Lifetime) -> Lifetime) -> Self
+    where
+        Self: Sized,
+    {
+        self.walk_mut(&mut |ty| {
+            if let Ty::Apply(a_ty) = ty {
+                if !a_ty.lifetimes.is_empty() {
+                    a_ty.lifetimes = a_ty.lifetimes.iter().map(|&it| f(it)).collect();
+                }
+            }
+        });
+        self
+    }
+
     /// Shifts up `Ty::Bound` vars by `n`.
     fn shift_bound_vars(self, n: i32) -> Self
     where
@@ -808,7 +1021,7 @@ impl TypeWalk for Ty {
                     t.walk(f);
                 }
             }
-            Ty::Dyn(predicates) | Ty::Opaque(predicates) => {
+            Ty::Dyn(predicates) => {
                 for p in predicates.iter() {
                     p.walk(f);
                 }
@@ -826,7 +1039,7 @@ impl TypeWalk for Ty {
             Ty::Projection(p_ty) => {
                 p_ty.parameters.walk_mut_binders(f, binders);
             }
-            Ty::Dyn(predicates) | Ty::Opaque(predicates) => {
+            Ty::Dyn(predicates) => {
                 for p in make_mut_slice(predicates) {
                     p.walk_mut_binders(f, binders + 1);
                 }
@@ -876,9 +1089,15 @@ impl HirDisplay for ApplicationTy {
                 } else {
                     t.display(f.db)
                 };
-                write!(f, "&{}{}", m.as_keyword_for_ref(), ty_display)?;
+                write!(f, "&")?;
+                match self.lifetimes.first() {
+                    Some(Lifetime::Unknown) | None => {}
+                    Some(lifetime) => write!(f, "{} ", lifetime.display(f.db))?,
+                }
+                write!(f, "{}{}", m.as_keyword_for_ref(), ty_display)?;
             }
             TypeCtor::Never => write!(f, "!")?,
+            TypeCtor::Const(value) => write!(f, "{}", value)?,
             TypeCtor::Tuple { .. } => {
                 let ts = &self.parameters;
                 if ts.len() == 1 {
@@ -934,8 +1153,17 @@ impl HirDisplay for ApplicationTy {
                     AdtId::EnumId(it) => f.db.enum_data(it).name.clone(),
                 };
                 write!(f, "{}", name)?;
-                if self.parameters.len() > 0 {
+                // Only print the lifetimes if at least one of them is known,
+                // to not clutter the output with `'_`.
+                let lifetimes: &[Lifetime] =
+                    if self.lifetimes.iter().any(|it| *it != Lifetime::Unknown) {
+                        &*self.lifetimes
+                    } else {
+                        &[]
+                    };
+                if self.parameters.len() > 0 || !lifetimes.is_empty() {
                     write!(f, "<")?;
+                    f.write_joined(lifetimes.iter().copied(), ", ")?;
 
                     let mut non_default_parameters = Vec::with_capacity(self.parameters.len());
                     let parameters_to_write = if f.omit_verbose_types() {
@@ -967,6 +1195,9 @@ impl HirDisplay for ApplicationTy {
                         self.parameters.0.as_ref()
                     };
 
+                    if !lifetimes.is_empty() && !parameters_to_write.is_empty() {
+                        write!(f, ", ")?;
+                    }
                     f.write_joined(parameters_to_write, ", ")?;
                     write!(f, ">")?;
                 }
@@ -1000,11 +1231,29 @@ impl HirDisplay for ApplicationTy {
                     write!(f, "| -> {}", return_type_hint)?;
                 };
             }
+            TypeCtor::OpaqueType(_) => {
+                write!(f, "impl ")?;
+                let bounds = Ty::Apply(self.clone()).impl_trait_bounds(f.db).unwrap_or_default();
+                write_bounds_like_dyn_trait(&bounds, f)?;
+            }
         }
         Ok(())
     }
 }
 
+impl HirDisplay for Lifetime {
+    fn hir_fmt(&self, f: &mut HirFormatter<impl HirDatabase>) -> fmt::Result {
+        match self {
+            Lifetime::Param(id) => {
+                let generics = f.db.generic_params(id.parent);
+                write!(f, "{}", generics.lifetimes[id.local_id].name)
+            }
+            Lifetime::Static => write!(f, "'static"),
+            Lifetime::Bound(_) | Lifetime::Unknown => write!(f, "'_"),
+        }
+    }
+}
+
 impl HirDisplay for ProjectionTy {
     fn hir_fmt(&self, f: &mut HirFormatter<impl HirDatabase>) -> fmt::Result {
         if f.should_truncate() {
@@ -1036,7 +1285,9 @@ impl HirDisplay for Ty {
                 let generics = generics(f.db, id.parent);
                 let param_data = &generics.params.types[id.local_id];
                 match param_data.provenance {
-                    TypeParamProvenance::TypeParamList | TypeParamProvenance::TraitSelf => {
+                    TypeParamProvenance::TypeParamList
+                    | TypeParamProvenance::TraitSelf
+                    | TypeParamProvenance::ConstParam => {
                         write!(f, "{}", param_data.name.clone().unwrap_or_else(Name::missing))?
                     }
                     TypeParamProvenance::ArgumentImplTrait => {
@@ -1051,12 +1302,8 @@ impl HirDisplay for Ty {
                 }
             }
             Ty::Bound(idx) => write!(f, "?{}", idx)?,
-            Ty::Dyn(predicates) | Ty::Opaque(predicates) => {
-                match self {
-                    Ty::Dyn(_) => write!(f, "dyn ")?,
-                    Ty::Opaque(_) => write!(f, "impl ")?,
-                    _ => unreachable!(),
-                };
+            Ty::Dyn(predicates) => {
+                write!(f, "dyn ")?;
                 write_bounds_like_dyn_trait(&predicates, f)?;
             }
             Ty::Unknown => write!(f, "{{unknown}}")?,
@@ -13,10 +13,10 @@ use hir_def::{
     generics::{TypeParamProvenance, WherePredicate, WherePredicateTarget},
     path::{GenericArg, Path, PathSegment, PathSegments},
     resolver::{HasResolver, Resolver, TypeNs},
-    type_ref::{TypeBound, TypeRef},
-    AdtId, ConstId, EnumId, EnumVariantId, FunctionId, GenericDefId, HasModule, ImplId,
-    LocalStructFieldId, Lookup, StaticId, StructId, TraitId, TypeAliasId, TypeParamId, UnionId,
-    VariantId,
+    type_ref::{ConstRef, LifetimeRef, TypeBound, TypeRef},
+    AdtId, AssocContainerId, ConstId, EnumId, EnumVariantId, FunctionId, GenericDefId, HasModule,
+    ImplId, LocalStructFieldId, Lookup, StaticId, StructId, TraitId, TypeAliasId, TypeParamId,
+    UnionId, VariantId,
 };
 use ra_arena::map::ArenaMap;
 use ra_db::CrateId;
@@ -28,8 +28,9 @@ use crate::{
         all_super_traits, associated_type_by_name_including_super_traits, generics, make_mut_slice,
         variant_data,
     },
-    Binders, FnSig, GenericPredicate, PolyFnSig, ProjectionPredicate, ProjectionTy, Substs,
-    TraitEnvironment, TraitRef, Ty, TypeCtor,
+    Binders, FnSig, GenericPredicate, ImplTraitId, Lifetime, PolyFnSig, ProjectionPredicate,
+    ProjectionTy, ReturnTypeImplTrait, ReturnTypeImplTraits, Substs, TraitEnvironment, TraitRef,
+    Ty, TypeCtor,
 };
 
 #[derive(Debug)]
@@ -43,6 +44,9 @@ pub struct TyLoweringContext<'a, DB: HirDatabase> {
     pub type_param_mode: TypeParamLoweringMode,
     pub impl_trait_mode: ImplTraitLoweringMode,
     pub impl_trait_counter: std::cell::Cell<u16>,
+    /// The bounds of the `impl Trait`s lowered in `ImplTraitLoweringMode::Opaque`,
+    /// in the order of their `ImplTraitId` indices.
+    pub opaque_type_data: std::cell::RefCell<Vec<ReturnTypeImplTrait>>,
 }
 
 impl<'a, DB: HirDatabase> TyLoweringContext<'a, DB> {
@@ -50,7 +54,15 @@ impl<'a, DB: HirDatabase> TyLoweringContext<'a, DB> {
         let impl_trait_counter = std::cell::Cell::new(0);
         let impl_trait_mode = ImplTraitLoweringMode::Disallowed;
         let type_param_mode = TypeParamLoweringMode::Placeholder;
-        Self { db, resolver, impl_trait_mode, impl_trait_counter, type_param_mode }
+        let opaque_type_data = std::cell::RefCell::new(Vec::new());
+        Self {
+            db,
+            resolver,
+            impl_trait_mode,
+            impl_trait_counter,
+            type_param_mode,
+            opaque_type_data,
+        }
     }
 
     pub fn with_impl_trait_mode(self, impl_trait_mode: ImplTraitLoweringMode) -> Self {
@@ -112,9 +124,14 @@ impl Ty {
                 let inner_ty = Ty::from_hir(ctx, inner);
                 Ty::apply_one(TypeCtor::Slice, inner_ty)
             }
-            TypeRef::Reference(inner, mutability) => {
+            TypeRef::Reference(inner, lifetime, mutability) => {
                 let inner_ty = Ty::from_hir(ctx, inner);
-                Ty::apply_one(TypeCtor::Ref(*mutability), inner_ty)
+                let lifetime = Lifetime::from_hir(ctx, lifetime.as_ref());
+                Ty::apply_with_lifetimes(
+                    TypeCtor::Ref(*mutability),
+                    Substs::single(inner_ty),
+                    Arc::new([lifetime]),
+                )
             }
             TypeRef::Placeholder => Ty::Unknown,
             TypeRef::Fn(params) => {
@@ -132,14 +149,38 @@ impl Ty {
             TypeRef::ImplTrait(bounds) => {
                 match ctx.impl_trait_mode {
                     ImplTraitLoweringMode::Opaque => {
-                        let self_ty = Ty::Bound(0);
+                        let func = match ctx.resolver.generic_def() {
+                            Some(GenericDefId::FunctionId(it)) => it,
+                            // FIXME: report error (impl Trait outside of a fn return type)
+                            _ => return Ty::Unknown,
+                        };
+                        let generics = generics(ctx.db, func.into());
+                        // reserve the index first, the bounds may contain
+                        // nested `impl Trait`s
+                        let idx = ctx.opaque_type_data.borrow().len();
+                        ctx.opaque_type_data
+                            .borrow_mut()
+                            .push(ReturnTypeImplTrait { bounds: Binders::new(0, Vec::new()) });
+                        let self_ty = Ty::Bound(generics.len() as u32);
                         let predicates = bounds
                             .iter()
                             .flat_map(|b| {
                                 GenericPredicate::from_type_bound(ctx, b, self_ty.clone())
                             })
                             .collect();
-                        Ty::Opaque(predicates)
+                        ctx.opaque_type_data.borrow_mut()[idx] = ReturnTypeImplTrait {
+                            bounds: Binders::new(generics.len() + 1, predicates),
+                        };
+                        let opaque_ty_id = ctx.db.intern_impl_trait_id(
+                            ImplTraitId::ReturnTypeImplTrait(func, idx as u16),
+                        );
+                        let parameters = match ctx.type_param_mode {
+                            TypeParamLoweringMode::Placeholder => {
+                                Substs::type_params_for_generics(&generics)
+                            }
+                            TypeParamLoweringMode::Variable => Substs::bound_vars(&generics),
+                        };
+                        Ty::apply(TypeCtor::OpaqueType(opaque_ty_id), parameters)
                     }
                     ImplTraitLoweringMode::Param => {
                         let idx = ctx.impl_trait_counter.get();
@@ -185,6 +226,15 @@ impl Ty {
         }
     }
 
+    /// Lowers a const generic argument. Const arguments take up a slot in the
+    /// substs just like type arguments, see `TypeCtor::Const`.
+    pub fn from_const_ref(const_ref: &ConstRef) -> Self {
+        match const_ref {
+            ConstRef::Scalar(value) => Ty::simple(TypeCtor::Const(*value)),
+            ConstRef::Unknown => Ty::Unknown,
+        }
+    }
+
     /// This is only for `generic_predicates_for_param`, where we can't just
     /// lower the self types of the predicates since that could lead to cycles.
     /// So we just check here if the `type_ref` resolves to a generic param, and which.
@@ -382,7 +432,14 @@ impl Ty {
             TyDefId::TypeAliasId(it) => Some(it.into()),
         };
         let substs = substs_from_path_segment(ctx, segment, generic_def, false);
-        ctx.db.ty(typable).subst(&substs)
+        let ty = ctx.db.ty(typable).subst(&substs);
+        match (typable, ty) {
+            (TyDefId::AdtId(adt), Ty::Apply(a_ty)) => {
+                let lifetimes = lifetimes_from_path_segment(ctx, segment, adt.into());
+                Ty::apply_with_lifetimes(a_ty.ctor, a_ty.parameters, lifetimes)
+            }
+            (_, ty) => ty,
+        }
     }
 
     /// Collect generic arguments from a path into a `Substs`. See also
@@ -420,6 +477,25 @@ impl Ty {
     }
 }
 
+/// Collect the lifetime arguments from a path, filling up the ones that were
+/// left out with `Lifetime::Unknown`.
+fn lifetimes_from_path_segment(
+    ctx: &TyLoweringContext<'_, impl HirDatabase>,
+    segment: PathSegment<'_>,
+    def: GenericDefId,
+) -> Arc<[Lifetime]> {
+    let expected_num = ctx.db.generic_params(def).lifetimes.len();
+    let lifetime_args = segment
+        .args_and_bindings
+        .iter()
+        .flat_map(|generic_args| generic_args.args.iter())
+        .filter_map(|arg| match arg {
+            GenericArg::Lifetime(lifetime) => Some(Lifetime::from_hir(ctx, Some(lifetime))),
+            GenericArg::Type(_) | GenericArg::Const(_) => None,
+        });
+    lifetime_args.chain(iter::repeat(Lifetime::Unknown)).take(expected_num).collect()
+}
+
 pub(super) fn substs_from_path_segment(
     ctx: &TyLoweringContext<'_, impl HirDatabase>,
     segment: PathSegment<'_>,
@@ -440,14 +516,12 @@ pub(super) fn substs_from_path_segment(
             if generic_args.has_self_type { self_params + type_params } else { type_params };
         let skip = if generic_args.has_self_type && self_params == 0 { 1 } else { 0 };
         // if args are provided, it should be all of them, but we can't rely on that
-        for arg in generic_args.args.iter().skip(skip).take(expected_num) {
-            match arg {
-                GenericArg::Type(type_ref) => {
-                    let ty = Ty::from_hir(ctx, type_ref);
-                    substs.push(ty);
-                }
-            }
-        }
+        let type_args = generic_args.args.iter().filter_map(|arg| match arg {
+            GenericArg::Type(type_ref) => Some(Ty::from_hir(ctx, type_ref)),
+            GenericArg::Const(const_ref) => Some(Ty::from_const_ref(const_ref)),
+            GenericArg::Lifetime(_) => None,
+        });
+        substs.extend(type_args.skip(skip).take(expected_num));
     }
     let total_len = parent_params + self_params + type_params + impl_trait_params;
     // add placeholders for args that were not provided
@@ -471,6 +545,22 @@ pub(super) fn substs_from_path_segment(
     Substs(substs.into())
 }
 
+impl Lifetime {
+    pub fn from_hir(
+        ctx: &TyLoweringContext<'_, impl HirDatabase>,
+        lifetime: Option<&LifetimeRef>,
+    ) -> Self {
+        match lifetime {
+            Some(lifetime) if lifetime.is_static() => Lifetime::Static,
+            Some(lifetime) => ctx
+                .resolver
+                .resolve_lifetime_param(&lifetime.name)
+                .map_or(Lifetime::Unknown, Lifetime::Param),
+            None => Lifetime::Unknown,
+        }
+    }
+}
+
 impl TraitRef {
     fn from_path(
         ctx: &TyLoweringContext<'_, impl HirDatabase>,
@@ -672,11 +762,25 @@ impl TraitEnvironment {
     pub fn lower(db: &impl HirDatabase, resolver: &Resolver) -> Arc<TraitEnvironment> {
         let ctx = TyLoweringContext::new(db, &resolver)
             .with_type_param_mode(TypeParamLoweringMode::Placeholder);
-        let predicates = resolver
+        let mut predicates = resolver
             .where_predicates_in_scope()
             .flat_map(|pred| GenericPredicate::from_where_predicate(&ctx, pred))
             .collect::<Vec<_>>();
 
+        let container = match resolver.generic_def() {
+            Some(GenericDefId::FunctionId(it)) => Some(it.lookup(db).container),
+            Some(GenericDefId::ConstId(it)) => Some(it.lookup(db).container),
+            Some(GenericDefId::TypeAliasId(it)) => Some(it.lookup(db).container),
+            Some(GenericDefId::TraitId(it)) => Some(AssocContainerId::TraitId(it)),
+            _ => None,
+        };
+        if let Some(AssocContainerId::TraitId(trait_)) = container {
+            // inside of a trait, `Self: Trait<..>` holds, which is what makes
+            // `Self::CONST` and `self.method()` in default bodies work
+            let substs = Substs::type_params(db, trait_);
+            predicates.push(GenericPredicate::Implemented(TraitRef { trait_, substs }));
+        }
+
         Arc::new(TraitEnvironment { predicates })
     }
 }
@@ -727,6 +831,19 @@ fn fn_sig_for_fn(db: &impl HirDatabase, def: FunctionId) -> PolyFnSig {
     Binders::new(num_binders, FnSig::from_params_and_return(params, ret))
 }
 
+pub(crate) fn return_type_impl_traits_query(
+    db: &impl HirDatabase,
+    def: FunctionId,
+) -> Arc<ReturnTypeImplTraits> {
+    let data = db.function_data(def);
+    let resolver = def.resolver(db);
+    let ctx_ret = TyLoweringContext::new(db, &resolver)
+        .with_impl_trait_mode(ImplTraitLoweringMode::Opaque)
+        .with_type_param_mode(TypeParamLoweringMode::Variable);
+    let _ret = Ty::from_hir(&ctx_ret, &data.ret_type);
+    Arc::new(ReturnTypeImplTraits { impl_traits: ctx_ret.opaque_type_data.into_inner() })
+}
+
 /// Build the declared type of a function. This should not need to look at the
 /// function body.
 fn type_for_fn(db: &impl HirDatabase, def: FunctionId) -> Binders<Ty> {
@@ -909,6 +1026,19 @@ pub(crate) fn value_ty_query(db: &impl HirDatabase, def: ValueTyDefId) -> Binder
     }
 }
 
+/// Build the declared type of a const generic param, like the `usize` of
+/// `const N: usize`.
+pub(crate) fn const_param_ty(db: &impl HirDatabase, id: TypeParamId) -> Ty {
+    let params = db.generic_params(id.parent);
+    let type_ref = match params.const_param_types.get(id.local_id) {
+        Some(it) => it,
+        None => return Ty::Unknown,
+    };
+    let resolver = id.parent.resolver(db);
+    let ctx = TyLoweringContext::new(db, &resolver);
+    Ty::from_hir(&ctx, type_ref)
+}
+
 pub(crate) fn impl_self_ty_query(db: &impl HirDatabase, impl_id: ImplId) -> Binders<Ty> {
     let impl_data = db.impl_data(impl_id);
     let resolver = impl_id.resolver(db);
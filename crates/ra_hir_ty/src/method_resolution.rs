@@ -6,8 +6,8 @@ use std::sync::Arc;
 
 use arrayvec::ArrayVec;
 use hir_def::{
-    lang_item::LangItemTarget, type_ref::Mutability, AssocContainerId, AssocItemId, FunctionId,
-    HasModule, ImplId, Lookup, TraitId,
+    lang_item::LangItemTarget, type_ref::Mutability, AssocContainerId, AssocItemId, ConstId,
+    FunctionId, HasModule, ImplId, Lookup, TraitId,
 };
 use hir_expand::name::Name;
 use ra_db::CrateId;
@@ -20,7 +20,7 @@ use crate::{
     db::HirDatabase,
     primitive::{FloatBitness, Uncertain},
     utils::all_super_traits,
-    Canonical, InEnvironment, TraitEnvironment, TraitRef, Ty, TypeCtor, TypeWalk,
+    Canonical, GenericPredicate, InEnvironment, TraitEnvironment, TraitRef, Ty, TypeCtor, TypeWalk,
 };
 
 /// This is used as a key for indexing impls.
@@ -164,6 +164,175 @@ pub(crate) fn lookup_method(
     )
 }
 
+/// A record of how a method call was looked up, for explaining it to the
+/// user: each receiver type we tried, in order, and the methods with the
+/// right name we found for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodResolutionTrace {
+    pub steps: Vec<MethodResolutionStep>,
+}
+
+impl MethodResolutionTrace {
+    /// The method which gets called, if any.
+    pub fn picked(&self) -> Option<FunctionId> {
+        self.steps
+            .iter()
+            .flat_map(|step| step.candidates.iter())
+            .find(|candidate| candidate.status == CandidateStatus::Picked)
+            .map(|candidate| candidate.func)
+    }
+
+    fn start_step(
+        &mut self,
+        receiver_ty: &Canonical<Ty>,
+        autoderefs: usize,
+        autoref: Option<Mutability>,
+    ) {
+        let receiver_ty = receiver_ty.clone();
+        let candidates = Vec::new();
+        self.steps.push(MethodResolutionStep { receiver_ty, autoderefs, autoref, candidates });
+    }
+
+    /// Records a method with the right name for the current step. A method can
+    /// come up for several self types; we keep the status that got furthest.
+    fn record(&mut self, func: FunctionId, status: CandidateStatus) {
+        let step = match self.steps.last_mut() {
+            Some(it) => it,
+            None => return,
+        };
+        match step.candidates.iter_mut().find(|it| it.func == func) {
+            Some(existing) => {
+                if status.rank() > existing.status.rank() {
+                    existing.status = status;
+                }
+            }
+            None => step.candidates.push(MethodCandidate { func, status }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodResolutionStep {
+    /// The receiver type at this step, with autoderef and autoref applied.
+    pub receiver_ty: Canonical<Ty>,
+    /// How often the original receiver was dereferenced.
+    pub autoderefs: usize,
+    /// The autoref applied after dereferencing, if any.
+    pub autoref: Option<Mutability>,
+    pub candidates: Vec<MethodCandidate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodCandidate {
+    pub func: FunctionId,
+    pub status: CandidateStatus,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CandidateStatus {
+    /// This is the method which gets called.
+    Picked,
+    /// The method has no `self` parameter, or one which doesn't match the
+    /// receiver type.
+    ReceiverMismatch,
+    /// The method is from a trait which isn't implemented for the type.
+    TraitNotImplemented {
+        /// The bound which doesn't hold: the innermost failing where clause
+        /// of the impl matching the type (see `unsatisfied_impl_bound`), or
+        /// the trait bound itself if no impl matches.
+        unsatisfied_bound: TraitRef,
+        /// The impls of the trait whose self type matches the type.
+        impls: Vec<ImplId>,
+    },
+}
+
+impl CandidateStatus {
+    fn rank(&self) -> u8 {
+        match self {
+            CandidateStatus::TraitNotImplemented { .. } => 0,
+            CandidateStatus::ReceiverMismatch => 1,
+            CandidateStatus::Picked => 2,
+        }
+    }
+}
+
+/// Looks up the method like `lookup_method`, but records every step on the
+/// way, up to the method that gets picked.
+pub fn trace_method_resolution(
+    ty: &Canonical<Ty>,
+    db: &impl HirDatabase,
+    env: Arc<TraitEnvironment>,
+    krate: CrateId,
+    traits_in_scope: &FxHashSet<TraitId>,
+    name: &Name,
+) -> MethodResolutionTrace {
+    let mut trace = MethodResolutionTrace { steps: Vec::new() };
+    iterate_method_candidates_impl(
+        ty,
+        db,
+        env,
+        krate,
+        traits_in_scope,
+        Some(name),
+        LookupMode::MethodCall,
+        Some(&mut trace),
+        &mut |_, item| match item {
+            AssocItemId::FunctionId(_) => Some(()),
+            _ => None,
+        },
+    );
+    trace
+}
+
+/// Finds traits which aren't in scope, but which would provide a method
+/// called `name` for the receiver type if they were imported.
+pub fn traits_providing_method(
+    ty: &Canonical<Ty>,
+    db: &impl HirDatabase,
+    env: Arc<TraitEnvironment>,
+    krate: CrateId,
+    traits_in_scope: &FxHashSet<TraitId>,
+    name: &Name,
+) -> Vec<TraitId> {
+    let crate_graph = db.crate_graph();
+    let mut crates = vec![krate];
+    let mut i = 0;
+    while i < crates.len() {
+        for dep in crate_graph.dependencies(crates[i]) {
+            if !crates.contains(&dep.crate_id) {
+                crates.push(dep.crate_id);
+            }
+        }
+        i += 1;
+    }
+
+    let in_env = InEnvironment { value: ty.clone(), environment: env.clone() };
+    let deref_chain: Vec<_> = autoderef::autoderef(db, Some(krate), in_env).collect();
+    let mut res = Vec::new();
+    for impl_crate in crates {
+        for impl_id in db.impls_in_crate(impl_crate).all_impls() {
+            let trait_ = match db.impl_trait(impl_id) {
+                Some(it) => it.value.trait_,
+                None => continue,
+            };
+            if res.contains(&trait_)
+                || traits_in_scope.contains(&trait_)
+                || db.trait_data(trait_).method_by_name(name).is_none()
+            {
+                continue;
+            }
+            let implemented = deref_chain.iter().any(|self_ty| {
+                let goal = generic_implements_goal(db, env.clone(), trait_, self_ty.clone());
+                db.trait_solve(krate.into(), goal).is_some()
+            });
+            if implemented {
+                res.push(trait_);
+            }
+        }
+    }
+    res
+}
+
 /// Whether we're looking up a dotted method call (like `v.len()`) or a path
 /// (like `Vec::new`).
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -188,6 +357,30 @@ pub fn iterate_method_candidates<T>(
     name: Option<&Name>,
     mode: LookupMode,
     mut callback: impl FnMut(&Ty, AssocItemId) -> Option<T>,
+) -> Option<T> {
+    iterate_method_candidates_impl(
+        ty,
+        db,
+        env,
+        krate,
+        traits_in_scope,
+        name,
+        mode,
+        None,
+        &mut callback,
+    )
+}
+
+fn iterate_method_candidates_impl<T>(
+    ty: &Canonical<Ty>,
+    db: &impl HirDatabase,
+    env: Arc<TraitEnvironment>,
+    krate: CrateId,
+    traits_in_scope: &FxHashSet<TraitId>,
+    name: Option<&Name>,
+    mode: LookupMode,
+    mut trace: Option<&mut MethodResolutionTrace>,
+    callback: &mut dyn FnMut(&Ty, AssocItemId) -> Option<T>,
 ) -> Option<T> {
     match mode {
         LookupMode::MethodCall => {
@@ -218,12 +411,14 @@ pub fn iterate_method_candidates<T>(
             for i in 0..deref_chain.len() {
                 if let Some(result) = iterate_method_candidates_with_autoref(
                     &deref_chain[i..],
+                    i,
                     db,
                     env.clone(),
                     krate,
                     traits_in_scope,
                     name,
-                    &mut callback,
+                    trace.as_deref_mut(),
+                    &mut *callback,
                 ) {
                     return Some(result);
                 }
@@ -239,7 +434,7 @@ pub fn iterate_method_candidates<T>(
                 krate,
                 traits_in_scope,
                 name,
-                &mut callback,
+                callback,
             )
         }
     }
@@ -247,13 +442,18 @@ pub fn iterate_method_candidates<T>(
 
 fn iterate_method_candidates_with_autoref<T>(
     deref_chain: &[Canonical<Ty>],
+    autoderefs: usize,
     db: &impl HirDatabase,
     env: Arc<TraitEnvironment>,
     krate: CrateId,
     traits_in_scope: &FxHashSet<TraitId>,
     name: Option<&Name>,
-    mut callback: impl FnMut(&Ty, AssocItemId) -> Option<T>,
+    mut trace: Option<&mut MethodResolutionTrace>,
+    callback: &mut dyn FnMut(&Ty, AssocItemId) -> Option<T>,
 ) -> Option<T> {
+    if let Some(trace) = trace.as_deref_mut() {
+        trace.start_step(&deref_chain[0], autoderefs, None);
+    }
     if let Some(result) = iterate_method_candidates_by_receiver(
         &deref_chain[0],
         &deref_chain[1..],
@@ -262,41 +462,32 @@ fn iterate_method_candidates_with_autoref<T>(
         krate,
         &traits_in_scope,
         name,
-        &mut callback,
+        trace.as_deref_mut(),
+        &mut *callback,
     ) {
         return Some(result);
     }
-    let refed = Canonical {
-        num_vars: deref_chain[0].num_vars,
-        value: Ty::apply_one(TypeCtor::Ref(Mutability::Shared), deref_chain[0].value.clone()),
-    };
-    if let Some(result) = iterate_method_candidates_by_receiver(
-        &refed,
-        deref_chain,
-        db,
-        env.clone(),
-        krate,
-        &traits_in_scope,
-        name,
-        &mut callback,
-    ) {
-        return Some(result);
-    }
-    let ref_muted = Canonical {
-        num_vars: deref_chain[0].num_vars,
-        value: Ty::apply_one(TypeCtor::Ref(Mutability::Mut), deref_chain[0].value.clone()),
-    };
-    if let Some(result) = iterate_method_candidates_by_receiver(
-        &ref_muted,
-        deref_chain,
-        db,
-        env.clone(),
-        krate,
-        &traits_in_scope,
-        name,
-        &mut callback,
-    ) {
-        return Some(result);
+    for &mutability in &[Mutability::Shared, Mutability::Mut] {
+        let refed = Canonical {
+            num_vars: deref_chain[0].num_vars,
+            value: Ty::apply_one(TypeCtor::Ref(mutability), deref_chain[0].value.clone()),
+        };
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.start_step(&refed, autoderefs, Some(mutability));
+        }
+        if let Some(result) = iterate_method_candidates_by_receiver(
+            &refed,
+            deref_chain,
+            db,
+            env.clone(),
+            krate,
+            &traits_in_scope,
+            name,
+            trace.as_deref_mut(),
+            &mut *callback,
+        ) {
+            return Some(result);
+        }
     }
     None
 }
@@ -309,15 +500,22 @@ fn iterate_method_candidates_by_receiver<T>(
     krate: CrateId,
     traits_in_scope: &FxHashSet<TraitId>,
     name: Option<&Name>,
-    mut callback: impl FnMut(&Ty, AssocItemId) -> Option<T>,
+    mut trace: Option<&mut MethodResolutionTrace>,
+    callback: &mut dyn FnMut(&Ty, AssocItemId) -> Option<T>,
 ) -> Option<T> {
     // We're looking for methods with *receiver* type receiver_ty. These could
     // be found in any of the derefs of receiver_ty, so we have to go through
     // that.
     for self_ty in std::iter::once(receiver_ty).chain(rest_of_deref_chain) {
-        if let Some(result) =
-            iterate_inherent_methods(self_ty, db, name, Some(receiver_ty), krate, &mut callback)
-        {
+        if let Some(result) = iterate_inherent_methods(
+            self_ty,
+            db,
+            name,
+            Some(receiver_ty),
+            krate,
+            trace.as_deref_mut(),
+            &mut *callback,
+        ) {
             return Some(result);
         }
     }
@@ -330,7 +528,8 @@ fn iterate_method_candidates_by_receiver<T>(
             &traits_in_scope,
             name,
             Some(receiver_ty),
-            &mut callback,
+            trace.as_deref_mut(),
+            &mut *callback,
         ) {
             return Some(result);
         }
@@ -345,9 +544,9 @@ fn iterate_method_candidates_for_self_ty<T>(
     krate: CrateId,
     traits_in_scope: &FxHashSet<TraitId>,
     name: Option<&Name>,
-    mut callback: impl FnMut(&Ty, AssocItemId) -> Option<T>,
+    callback: &mut dyn FnMut(&Ty, AssocItemId) -> Option<T>,
 ) -> Option<T> {
-    if let Some(result) = iterate_inherent_methods(self_ty, db, name, None, krate, &mut callback) {
+    if let Some(result) = iterate_inherent_methods(self_ty, db, name, None, krate, None, callback) {
         return Some(result);
     }
     if let Some(result) = iterate_trait_method_candidates(
@@ -358,7 +557,8 @@ fn iterate_method_candidates_for_self_ty<T>(
         traits_in_scope,
         name,
         None,
-        &mut callback,
+        None,
+        callback,
     ) {
         return Some(result);
     }
@@ -373,18 +573,17 @@ fn iterate_trait_method_candidates<T>(
     traits_in_scope: &FxHashSet<TraitId>,
     name: Option<&Name>,
     receiver_ty: Option<&Canonical<Ty>>,
-    mut callback: impl FnMut(&Ty, AssocItemId) -> Option<T>,
+    mut trace: Option<&mut MethodResolutionTrace>,
+    callback: &mut dyn FnMut(&Ty, AssocItemId) -> Option<T>,
 ) -> Option<T> {
-    // if ty is `impl Trait` or `dyn Trait`, the trait doesn't need to be in scope
-    let inherent_trait = self_ty.value.inherent_trait().into_iter();
-    // if we have `T: Trait` in the param env, the trait doesn't need to be in scope
-    let traits_from_env = env
-        .trait_predicates_for_self_ty(&self_ty.value)
-        .map(|tr| tr.trait_)
-        .flat_map(|t| all_super_traits(db, t));
-    let traits = inherent_trait.chain(traits_from_env).chain(traits_in_scope.iter().copied());
+    let traits = candidate_traits(db, &env, &self_ty.value, traits_in_scope);
     'traits: for t in traits {
         let data = db.trait_data(t);
+        let implements_trait = || {
+            let goal = generic_implements_goal(db, env.clone(), t, self_ty.clone());
+            db.trait_solve(krate.into(), goal).is_some()
+        };
+        let not_implemented = || trait_not_implemented(db, env.clone(), krate, t, self_ty);
 
         // we'll be lazy about checking whether the type implements the
         // trait, but if we find out it doesn't, we'll skip the rest of the
@@ -392,16 +591,29 @@ fn iterate_trait_method_candidates<T>(
         let mut known_implemented = false;
         for (_name, item) in data.items.iter() {
             if !is_valid_candidate(db, name, receiver_ty, (*item).into(), self_ty) {
+                if let (Some(trace), AssocItemId::FunctionId(func)) = (trace.as_deref_mut(), item) {
+                    if name.map_or(false, |name| &db.function_data(*func).name == name) {
+                        let status = if implements_trait() {
+                            CandidateStatus::ReceiverMismatch
+                        } else {
+                            not_implemented()
+                        };
+                        trace.record(*func, status);
+                    }
+                }
                 continue;
             }
-            if !known_implemented {
-                let goal = generic_implements_goal(db, env.clone(), t, self_ty.clone());
-                if db.trait_solve(krate.into(), goal).is_none() {
-                    continue 'traits;
+            if !known_implemented && !implements_trait() {
+                if let (Some(trace), AssocItemId::FunctionId(func)) = (trace.as_deref_mut(), item) {
+                    trace.record(*func, not_implemented());
                 }
+                continue 'traits;
             }
             known_implemented = true;
             if let Some(result) = callback(&self_ty.value, (*item).into()) {
+                if let (Some(trace), AssocItemId::FunctionId(func)) = (trace, item) {
+                    trace.record(*func, CandidateStatus::Picked);
+                }
                 return Some(result);
             }
         }
@@ -409,13 +621,51 @@ fn iterate_trait_method_candidates<T>(
     None
 }
 
+/// Explains why `trait_` isn't implemented for `self_ty`, for the trace.
+fn trait_not_implemented(
+    db: &impl HirDatabase,
+    env: Arc<TraitEnvironment>,
+    krate: CrateId,
+    trait_: TraitId,
+    self_ty: &Canonical<Ty>,
+) -> CandidateStatus {
+    let substs = Substs::build_for_def(db, trait_).push(self_ty.value.clone()).fill_with_unknown();
+    let trait_ref = TraitRef { trait_, substs: substs.build() };
+    let impls = db
+        .impls_for_trait(krate, trait_)
+        .iter()
+        .copied()
+        .filter(|&impl_id| inherent_impl_substs(db, impl_id, self_ty).is_some())
+        .collect();
+    let unsatisfied_bound = unsatisfied_impl_bound(db, env, krate, &trait_ref).unwrap_or(trait_ref);
+    CandidateStatus::TraitNotImplemented { unsatisfied_bound, impls }
+}
+
+/// The traits whose methods are candidates for `self_ty`.
+fn candidate_traits(
+    db: &impl HirDatabase,
+    env: &TraitEnvironment,
+    self_ty: &Ty,
+    traits_in_scope: &FxHashSet<TraitId>,
+) -> Vec<TraitId> {
+    // if ty is `impl Trait` or `dyn Trait`, the trait doesn't need to be in scope
+    let inherent_trait = self_ty.inherent_trait(db).into_iter();
+    // if we have `T: Trait` in the param env, the trait doesn't need to be in scope
+    let traits_from_env = env
+        .trait_predicates_for_self_ty(self_ty)
+        .map(|tr| tr.trait_)
+        .flat_map(|t| all_super_traits(db, t));
+    inherent_trait.chain(traits_from_env).chain(traits_in_scope.iter().copied()).collect()
+}
+
 fn iterate_inherent_methods<T>(
     self_ty: &Canonical<Ty>,
     db: &impl HirDatabase,
     name: Option<&Name>,
     receiver_ty: Option<&Canonical<Ty>>,
     krate: CrateId,
-    mut callback: impl FnMut(&Ty, AssocItemId) -> Option<T>,
+    mut trace: Option<&mut MethodResolutionTrace>,
+    callback: &mut dyn FnMut(&Ty, AssocItemId) -> Option<T>,
 ) -> Option<T> {
     for krate in self_ty.value.def_crates(db, krate)? {
         let impls = db.impls_in_crate(krate);
@@ -423,9 +673,19 @@ fn iterate_inherent_methods<T>(
         for impl_block in impls.lookup_impl_blocks(&self_ty.value) {
             for &item in db.impl_data(impl_block).items.iter() {
                 if !is_valid_candidate(db, name, receiver_ty, item, self_ty) {
+                    if let (Some(trace), AssocItemId::FunctionId(func)) =
+                        (trace.as_deref_mut(), item)
+                    {
+                        if name.map_or(false, |name| &db.function_data(func).name == name) {
+                            trace.record(func, CandidateStatus::ReceiverMismatch);
+                        }
+                    }
                     continue;
                 }
                 if let Some(result) = callback(&self_ty.value, item) {
+                    if let (Some(trace), AssocItemId::FunctionId(func)) = (trace, item) {
+                        trace.record(func, CandidateStatus::Picked);
+                    }
                     return Some(result);
                 }
             }
@@ -482,6 +742,142 @@ pub(crate) fn inherent_impl_substs(
     super::infer::unify(&self_ty_with_vars, self_ty)
 }
 
+/// Finds the method of a trait impl which implements `trait_method` for the
+/// given trait parameters (starting with `Self`), looking at the impls in
+/// `krate` and its dependencies.
+pub fn lookup_impl_method(
+    db: &impl HirDatabase,
+    krate: CrateId,
+    trait_method: FunctionId,
+    trait_params: &[Ty],
+) -> Option<FunctionId> {
+    let trait_ = match trait_method.lookup(db).container {
+        AssocContainerId::TraitId(it) => it,
+        _ => return None,
+    };
+    let name = &db.function_data(trait_method).name;
+    let impl_id = find_impl_for_trait_params(db, krate, trait_, trait_params)?;
+    db.impl_data(impl_id).items.iter().find_map(|&item| match item {
+        AssocItemId::FunctionId(func) if &db.function_data(func).name == name => Some(func),
+        _ => None,
+    })
+}
+
+/// Like `lookup_impl_method`, but for associated consts.
+pub fn lookup_impl_const(
+    db: &impl HirDatabase,
+    krate: CrateId,
+    trait_const: ConstId,
+    trait_params: &[Ty],
+) -> Option<ConstId> {
+    let trait_ = match trait_const.lookup(db).container {
+        AssocContainerId::TraitId(it) => it,
+        _ => return None,
+    };
+    let name = db.const_data(trait_const).name.clone()?;
+    let impl_id = find_impl_for_trait_params(db, krate, trait_, trait_params)?;
+    db.impl_data(impl_id).items.iter().find_map(|&item| match item {
+        AssocItemId::ConstId(konst) if db.const_data(konst).name.as_ref() == Some(&name) => {
+            Some(konst)
+        }
+        _ => None,
+    })
+}
+
+fn find_impl_for_trait_params(
+    db: &impl HirDatabase,
+    krate: CrateId,
+    trait_: TraitId,
+    trait_params: &[Ty],
+) -> Option<ImplId> {
+    // Unify all trait parameters at once by wrapping them in a tuple.
+    let tuple =
+        |params: Substs| Ty::apply(TypeCtor::Tuple { cardinality: params.len() as u16 }, params);
+    let params = Canonical { num_vars: 0, value: tuple(Substs(trait_params.into())) };
+    db.impls_for_trait(krate, trait_).iter().copied().find(|&impl_id| {
+        let vars = Substs::build_for_def(db, impl_id).fill_with_bound_vars(0).build();
+        let impl_trait = match db.impl_trait(impl_id) {
+            Some(it) => it.subst(&vars),
+            None => return false,
+        };
+        if impl_trait.substs.len() != trait_params.len() {
+            return false;
+        }
+        let impl_params = Canonical { num_vars: vars.len(), value: tuple(impl_trait.substs) };
+        super::infer::unify(&impl_params, &params).is_some()
+    })
+}
+
+/// Given a trait bound that doesn't hold, tries to find the reason: if some
+/// impl's header matches `trait_ref`, returns the first of the impl's where
+/// clauses that isn't satisfied, following impls down to the innermost failing
+/// bound (e.g. `S: Clone` for `Vec<Wrapper<S>>: Clone`).
+pub fn unsatisfied_impl_bound(
+    db: &impl HirDatabase,
+    env: Arc<TraitEnvironment>,
+    krate: CrateId,
+    trait_ref: &TraitRef,
+) -> Option<TraitRef> {
+    unsatisfied_impl_bound_inner(db, &env, krate, trait_ref, 0)
+}
+
+fn unsatisfied_impl_bound_inner(
+    db: &impl HirDatabase,
+    env: &Arc<TraitEnvironment>,
+    krate: CrateId,
+    trait_ref: &TraitRef,
+    depth: usize,
+) -> Option<TraitRef> {
+    // guard against impls like `impl<T> Foo for T where Box<T>: Foo`
+    if depth > 8 {
+        return None;
+    }
+    let tuple =
+        |params: Substs| Ty::apply(TypeCtor::Tuple { cardinality: params.len() as u16 }, params);
+    let params = Canonical { num_vars: 0, value: tuple(trait_ref.substs.clone()) };
+    for &impl_id in db.impls_for_trait(krate, trait_ref.trait_).iter() {
+        let vars = Substs::build_for_def(db, impl_id).fill_with_bound_vars(0).build();
+        let impl_trait = match db.impl_trait(impl_id) {
+            Some(it) => it.subst(&vars),
+            None => continue,
+        };
+        if impl_trait.substs.len() != trait_ref.substs.len() {
+            continue;
+        }
+        let impl_params = Canonical { num_vars: vars.len(), value: tuple(impl_trait.substs) };
+        let impl_substs = match super::infer::unify(&impl_params, &params) {
+            Some(it) => it,
+            None => continue,
+        };
+        // impls can't overlap, so this is the only impl that could apply
+        for predicate in db.generic_predicates(impl_id.into()).iter() {
+            let bound = match predicate.clone().subst(&impl_substs) {
+                GenericPredicate::Implemented(it) => it,
+                _ => continue,
+            };
+            // impl params that aren't constrained by the header come out as
+            // `Unknown`; we can't say anything about bounds involving them
+            let mut has_unknown = false;
+            bound.walk(&mut |ty| has_unknown |= *ty == Ty::Unknown);
+            if has_unknown {
+                continue;
+            }
+            let goal = Canonical {
+                num_vars: 0,
+                value: InEnvironment::new(env.clone(), super::Obligation::Trait(bound.clone())),
+            };
+            if db.trait_solve(krate, goal).is_none() {
+                return Some(
+                    unsatisfied_impl_bound_inner(db, env, krate, &bound, depth + 1)
+                        .unwrap_or(bound),
+                );
+            }
+        }
+        return None;
+    }
+    None
+}
+
 fn transform_receiver_ty(
     db: &impl HirDatabase,
     function_id: FunctionId,
@@ -506,9 +902,9 @@ pub fn implements_trait(
     krate: CrateId,
     trait_: TraitId,
 ) -> bool {
-    if ty.value.inherent_trait() == Some(trait_) {
+    if ty.value.inherent_trait(db) == Some(trait_) {
         // FIXME this is a bit of a hack, since Chalk should say the same thing
-        // anyway, but currently Chalk doesn't implement `dyn/impl Trait` yet
+        // anyway, but currently Chalk doesn't implement `dyn Trait` yet
         return true;
     }
     let goal = generic_implements_goal(db, env, trait_, ty.clone());
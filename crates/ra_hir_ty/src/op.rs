@@ -1,9 +1,105 @@
 //! Helper functions for binary operator type inference.
-use hir_def::expr::{ArithOp, BinaryOp, CmpOp};
+use hir_def::expr::{ArithOp, BinaryOp, CmpOp, Ordering};
+use hir_expand::name::{name, Name};
 
 use super::{InferTy, Ty, TypeCtor};
 use crate::ApplicationTy;
 
+/// Whether `op` applied to the given operand types is handled by the compiler
+/// directly, without going through the operator traits.
+pub(super) fn is_builtin_binop(op: BinaryOp, lhs_ty: &Ty, rhs_ty: &Ty) -> bool {
+    match op {
+        BinaryOp::LogicOp(_) | BinaryOp::Assignment { op: None } => true,
+        _ => is_builtin_operand(lhs_ty) && is_builtin_operand(rhs_ty),
+    }
+}
+
+fn is_builtin_operand(ty: &Ty) -> bool {
+    match ty {
+        Ty::Apply(ApplicationTy { ctor, .. }) => match ctor {
+            TypeCtor::Int(..) | TypeCtor::Float(..) | TypeCtor::Bool | TypeCtor::Char => true,
+            _ => false,
+        },
+        // If we don't know the type (yet), stick to the primitive rules.
+        Ty::Infer(_) | Ty::Unknown => true,
+        _ => false,
+    }
+}
+
+/// The lang item of the trait overloading `op`.
+pub(super) fn binary_op_lang_item(op: BinaryOp) -> Option<&'static str> {
+    let lang_item = match op {
+        BinaryOp::LogicOp(_) | BinaryOp::Assignment { op: None } => return None,
+        BinaryOp::CmpOp(CmpOp::Eq { .. }) => "eq",
+        BinaryOp::CmpOp(CmpOp::Ord { .. }) => "partial_ord",
+        BinaryOp::ArithOp(op) => match op {
+            ArithOp::Add => "add",
+            ArithOp::Mul => "mul",
+            ArithOp::Sub => "sub",
+            ArithOp::Div => "div",
+            ArithOp::Rem => "rem",
+            ArithOp::Shl => "shl",
+            ArithOp::Shr => "shr",
+            ArithOp::BitXor => "bitxor",
+            ArithOp::BitOr => "bitor",
+            ArithOp::BitAnd => "bitand",
+        },
+        BinaryOp::Assignment { op: Some(op) } => match op {
+            ArithOp::Add => "add_assign",
+            ArithOp::Mul => "mul_assign",
+            ArithOp::Sub => "sub_assign",
+            ArithOp::Div => "div_assign",
+            ArithOp::Rem => "rem_assign",
+            ArithOp::Shl => "shl_assign",
+            ArithOp::Shr => "shr_assign",
+            ArithOp::BitXor => "bitxor_assign",
+            ArithOp::BitOr => "bitor_assign",
+            ArithOp::BitAnd => "bitand_assign",
+        },
+    };
+    Some(lang_item)
+}
+
+/// The name of the trait method that `op` desugars to.
+pub(super) fn binary_op_method_name(op: BinaryOp) -> Option<Name> {
+    let name = match op {
+        BinaryOp::LogicOp(_) | BinaryOp::Assignment { op: None } => return None,
+        BinaryOp::CmpOp(CmpOp::Eq { negated: false }) => name![eq],
+        BinaryOp::CmpOp(CmpOp::Eq { negated: true }) => name![ne],
+        BinaryOp::CmpOp(CmpOp::Ord { ordering, strict }) => match (ordering, strict) {
+            (Ordering::Less, true) => name![lt],
+            (Ordering::Less, false) => name![le],
+            (Ordering::Greater, true) => name![gt],
+            (Ordering::Greater, false) => name![ge],
+        },
+        BinaryOp::ArithOp(op) => match op {
+            ArithOp::Add => name![add],
+            ArithOp::Mul => name![mul],
+            ArithOp::Sub => name![sub],
+            ArithOp::Div => name![div],
+            ArithOp::Rem => name![rem],
+            ArithOp::Shl => name![shl],
+            ArithOp::Shr => name![shr],
+            ArithOp::BitXor => name![bitxor],
+            ArithOp::BitOr => name![bitor],
+            ArithOp::BitAnd => name![bitand],
+        },
+        BinaryOp::Assignment { op: Some(op) } => match op {
+            ArithOp::Add => name![add_assign],
+            ArithOp::Mul => name![mul_assign],
+            ArithOp::Sub => name![sub_assign],
+            ArithOp::Div => name![div_assign],
+            ArithOp::Rem => name![rem_assign],
+            ArithOp::Shl => name![shl_assign],
+            ArithOp::Shr => name![shr_assign],
+            ArithOp::BitXor => name![bitxor_assign],
+            ArithOp::BitOr => name![bitor_assign],
+            ArithOp::BitAnd => name![bitand_assign],
+        },
+    };
+    Some(name)
+}
+
 pub(super) fn binary_op_return_ty(op: BinaryOp, lhs_ty: Ty, rhs_ty: Ty) -> Ty {
     match op {
         BinaryOp::LogicOp(_) | BinaryOp::CmpOp(_) => Ty::simple(TypeCtor::Bool),
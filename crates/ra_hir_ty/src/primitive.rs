@@ -110,6 +110,23 @@ impl IntTy {
         IntTy { signedness: Signedness::Unsigned, bitness: IntBitness::X128 }
     }
 
+    /// The largest value of the type, i.e. `MAX`. `usize` and `isize` are
+    /// assumed to be 64 bits wide.
+    pub fn max_value(self) -> u128 {
+        let bits = match self.bitness {
+            IntBitness::X8 => 8,
+            IntBitness::X16 => 16,
+            IntBitness::X32 => 32,
+            IntBitness::X64 | IntBitness::Xsize => 64,
+            IntBitness::X128 => 128,
+        };
+        let bits = match self.signedness {
+            Signedness::Signed => bits - 1,
+            Signedness::Unsigned => bits,
+        };
+        u128::max_value() >> (128 - bits)
+    }
+
     pub fn ty_to_string(self) -> &'static str {
         match (self.signedness, self.bitness) {
             (Signedness::Signed, IntBitness::Xsize) => "isize",
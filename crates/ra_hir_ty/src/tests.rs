@@ -315,3 +315,310 @@ fn no_such_field_diagnostics() {
     "###
     );
 }
+
+#[test]
+fn unresolved_path_diagnostics() {
+    let diagnostics = TestDB::with_files(
+        r"
+        //- /lib.rs
+        mod foo { pub fn bar() {} }
+        struct S;
+        impl S { fn new() -> S { S } }
+        fn test(x: u32) {
+            x;
+            foo::bar();
+            S::new();
+            S::missing();
+            Nope;
+            nope;
+            foo::nope();
+        }
+        ",
+    )
+    .diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "Nope": unresolved path
+    "foo::nope": unresolved path
+    "###
+    );
+}
+
+#[test]
+fn break_with_value_outside_loop_diagnostics() {
+    let diagnostics = TestDB::with_files(
+        r"
+        //- /lib.rs
+        fn test(xs: &[u32]) {
+            let _ = loop { break 1; };
+            while true { break 2; }
+            for _ in xs { break; }
+            'block: { break 'block 3; };
+        }
+        ",
+    )
+    .diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "break 2": `break` with value from a `while` or `for` loop
+    "###
+    );
+}
+
+#[test]
+fn try_in_non_try_function_diagnostics() {
+    let diagnostics = TestDB::with_files(
+        r#"
+        //- /lib.rs
+        #[lang = "try"]
+        trait Try { type Ok; type Error; }
+        enum Option<T> { Some(T), None }
+        impl<T> Try for Option<T> { type Ok = T; type Error = (); }
+
+        fn get() -> Option<u32> { Option::None }
+        fn returns_option() -> Option<u32> { get()?; get() }
+        fn returns_unit() { get()?; }
+        fn in_try_block() { let _ = try { get()? }; }
+        fn closure_in_try_block() { let _ = try { let f = || -> () { get()?; }; get()? }; }
+        "#,
+    )
+    .diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "get()?": the `?` operator can only be used in a function that returns `Result` or `Option`
+    "get()?": the `?` operator can only be used in a function that returns `Result` or `Option`
+    "###
+    );
+}
+
+#[test]
+fn overflowing_literal_diagnostics() {
+    let diagnostics = TestDB::with_files(
+        r"
+        //- /lib.rs
+        fn test() {
+            let a: u8 = 255;
+            let b: u8 = 256;
+            let c = 2147483648;
+            let d: i8 = -128;
+            let e: i8 = 128;
+            let f = 0xffu8;
+            let g = 0x1_00u8;
+            let h: u128 = 340282366920938463463374607431768211455;
+        }
+        ",
+    )
+    .diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "256": literal out of range for `u8`
+    "2147483648": literal out of range for `i32`
+    "128": literal out of range for `i8`
+    "0x1_00u8": literal out of range for `u8`
+    "###
+    );
+}
+
+#[test]
+fn no_such_method_diagnostics() {
+    let diagnostics = TestDB::with_files(
+        r"
+        //- /lib.rs
+        struct S;
+        mod m {
+            pub trait Frobnicate { fn frobnicate(&self); }
+            impl Frobnicate for super::S { fn frobnicate(&self) {} }
+        }
+        impl S { fn inherent(&self) {} }
+
+        fn test(s: S, r: &S) {
+            s.frobnicate();
+            s.missing();
+            r.inherent();
+            m::Frobnicate::frobnicate(&s);
+        }
+        ",
+    )
+    .diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "s.frobnicate()": no method named `frobnicate` found for `S`; it is provided by `m::Frobnicate`, which is not in scope
+    "s.missing()": no method named `missing` found for `S`
+    "###
+    );
+}
+
+#[test]
+fn missing_methods_are_not_reported_with_unknown_impls() {
+    let diagnostics = TestDB::with_files(
+        r"
+        //- /lib.rs
+        #[derive(Serialize)]
+        struct S;
+        struct T;
+        macro generate() {}
+        #[generate]
+        struct G;
+        #[repr(C)]
+        struct R;
+        struct U;
+
+        fn test(s: S, t: T, g: G, r: R, u: U) {
+            impl T { fn local(&self) {} }
+            s.serialize();
+            t.local();
+            g.generated();
+            r.missing();
+            u.missing();
+        }
+        ",
+    )
+    .diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "r.missing()": no method named `missing` found for `R`
+    "u.missing()": no method named `missing` found for `U`
+    "###
+    );
+}
+
+#[test]
+fn unsatisfied_trait_bound_diagnostics() {
+    let diagnostics = TestDB::with_files(
+        r"
+        //- /lib.rs
+        trait Clone { fn clone(&self) -> Self; }
+        struct S;
+        struct C;
+        impl Clone for C { fn clone(&self) -> Self { C } }
+        struct Wrapper<T>(T);
+        impl<T: Clone> Clone for Wrapper<T> {
+            fn clone(&self) -> Self { Wrapper(self.0.clone()) }
+        }
+        struct Holder<T: Clone> { t: T }
+
+        fn dup<T: Clone>(t: T) {}
+
+        fn test<U: Clone>(u: U, missing: Missing) {
+            dup(C);
+            dup(u);
+            dup(S);
+            dup(Wrapper(C));
+            dup(Wrapper(S));
+            dup(missing);
+            dup(Wrapper(missing));
+            Holder { t: C };
+            Holder { t: S };
+        }
+        ",
+    )
+    .diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "dup(S)": the trait bound `S: Clone` is not satisfied
+    "dup(Wrapper(S))": the trait bound `Wrapper<S>: Clone` is not satisfied, because `S: Clone` is not satisfied
+    "Holder { t: S }": the trait bound `S: Clone` is not satisfied
+    "###
+    );
+}
+
+#[test]
+fn unsatisfied_trait_bounds_are_not_reported_with_unknown_impls() {
+    let diagnostics = TestDB::with_files(
+        r"
+        //- /lib.rs
+        trait Serialize {}
+        #[derive(Serialize)]
+        struct S;
+        struct P;
+
+        fn to_json<T: Serialize>(t: T) {}
+
+        fn test() {
+            to_json(S);
+            to_json(P);
+        }
+        ",
+    )
+    .diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "to_json(P)": the trait bound `P: Serialize` is not satisfied
+    "###
+    );
+}
+
+#[test]
+fn unresolved_paths_are_not_reported_with_unexpanded_macros() {
+    let diagnostics = TestDB::with_files(
+        r"
+        //- /lib.rs
+        generate_nope!();
+        fn test() {
+            Nope;
+        }
+        ",
+    )
+    .diagnostics();
+
+    assert_snapshot!(diagnostics, @"");
+}
+
+#[test]
+fn unresolved_paths_are_reported_outside_of_modules_with_unexpanded_macros() {
+    let diagnostics = TestDB::with_files(
+        r"
+        //- /lib.rs
+        mod a { generate_nope!(); }
+        mod b {}
+        mod c { pub use super::a::*; }
+        fn test() {
+            a::Nope;
+            b::Nope;
+            c::Nope;
+            Nope;
+        }
+        ",
+    )
+    .diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "b::Nope": unresolved path
+    "Nope": unresolved path
+    "###
+    );
+}
+
+#[test]
+fn self_param_of_macro_generated_method_resolves() {
+    let diagnostics = TestDB::with_files(
+        r"
+        //- /lib.rs
+        macro_rules! getter {
+            ($t:ident) => { impl $t { fn get(&self) -> u32 { self.0 } } };
+        }
+        struct S(u32);
+        getter!(S);
+        ",
+    )
+    .diagnostics();
+
+    assert_snapshot!(diagnostics, @"");
+}
+
+#[test]
+fn const_params_are_not_reported_as_unresolved() {
+    let diagnostics = TestDB::with_files(
+        r"
+        //- /lib.rs
+        struct Buf<const N: usize>;
+        impl<const N: usize> Buf<N> {
+            fn len(&self) -> usize { N }
+        }
+        fn f<const N: usize>() -> usize { N }
+        ",
+    )
+    .diagnostics();
+
+    assert_snapshot!(diagnostics, @"");
+}
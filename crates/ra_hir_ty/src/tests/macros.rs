@@ -333,6 +333,131 @@ pub fn baz() -> usize { 31usize }
     assert_eq!("(i32, usize)", type_at_pos(&db, pos));
 }
 
+#[test]
+fn infer_macro_local_does_not_shadow_caller_local() {
+    let t = type_at(
+        r#"
+//- /main.rs
+macro_rules! m {
+    ($e:expr) => {{ let x = 1u32; $e }}
+}
+
+fn test() {
+    let x = "hello";
+    let y = m!(x);
+    y<|>;
+}
+"#,
+    );
+    assert_eq!(t, "&str");
+}
+
+#[test]
+fn infer_macro_local_is_visible_inside_expansion() {
+    let t = type_at(
+        r#"
+//- /main.rs
+macro_rules! m {
+    () => {{ let x = 1u32; x }}
+}
+
+fn test() {
+    let x = "hello";
+    let y = m!();
+    y<|>;
+}
+"#,
+    );
+    assert_eq!(t, "u32");
+}
+
+#[test]
+fn infer_macro_2_0_expression() {
+    let t = type_at(
+        r#"
+//- /main.rs
+mod m {
+    pub macro twice {
+        ($e:expr) => {{ let x = $e; (x, x) }},
+    }
+}
+
+fn test() {
+    let x = "hello";
+    let y = m::twice!(1u32);
+    y<|>;
+}
+"#,
+    );
+    assert_eq!(t, "(u32, u32)");
+}
+
+#[test]
+fn infer_macro_2_0_def_site_paths() {
+    let t = type_at(
+        r#"
+//- /main.rs
+mod m {
+    fn helper() -> u32 { 0 }
+    pub macro call_helper() { helper() }
+}
+
+fn helper() -> i64 { 0 }
+
+fn test() {
+    let x = m::call_helper!();
+    x<|>;
+}
+"#,
+    );
+    assert_eq!(t, "u32");
+}
+
+#[test]
+fn infer_macro_2_0_def_site_pattern_and_record_paths() {
+    let t = type_at(
+        r#"
+//- /main.rs
+mod m {
+    struct S { x: u32 }
+    struct T(u32);
+    pub macro unwrap_all() {{
+        let S { x } = S { x: 0 };
+        let T(y) = T(x);
+        y
+    }}
+}
+
+struct S { x: i64 }
+struct T(i64);
+
+fn test() {
+    let x = m::unwrap_all!();
+    x<|>;
+}
+"#,
+    );
+    assert_eq!(t, "u32");
+}
+
+#[test]
+fn infer_partially_expanded_macro() {
+    let t = type_at(
+        r#"
+//- /main.rs
+macro_rules! first {
+    ($e:expr, $($rest:tt)+) => {{ $e }};
+}
+
+fn test() {
+    let y = first!(1u32,);
+    y<|>;
+}
+"#,
+    );
+    assert_eq!(t, "u32");
+}
+
 #[test]
 fn infer_type_value_non_legacy_macro_use_as() {
     assert_snapshot!(
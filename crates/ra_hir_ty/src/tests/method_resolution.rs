@@ -749,6 +749,34 @@ fn test() { (S::<u32>.foo(), S::<i32>.foo())<|>; }
     assert_eq!(t, "(u8, i8)");
 }
 
+#[test]
+fn method_resolution_unify_impl_const_arg() {
+    let t = type_at(
+        r#"
+//- /main.rs
+struct Buf<const N: usize>;
+impl Buf<16> { fn foo(&self) -> u8 {} }
+impl Buf<32> { fn foo(&self) -> i8 {} }
+fn test() { (Buf::<16>.foo(), Buf::<32>.foo())<|>; }
+"#,
+    );
+    assert_eq!(t, "(u8, i8)");
+}
+
+#[test]
+fn method_resolution_trait_impl_for_const_arg() {
+    let t = type_at(
+        r#"
+//- /main.rs
+trait Trait { fn foo(&self) -> u128; }
+struct Buf<const N: usize>;
+impl Trait for Buf<16> { fn foo(&self) -> u128 { 0 } }
+fn test(buf: Buf<16>) { buf.foo()<|>; }
+"#,
+    );
+    assert_eq!(t, "u128");
+}
+
 #[test]
 fn method_resolution_trait_before_autoref() {
     let t = type_at(
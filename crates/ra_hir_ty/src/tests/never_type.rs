@@ -244,3 +244,75 @@ fn test(a: i32) {
     );
     assert_eq!(t, "f64");
 }
+
+#[test]
+fn loop_break_with_value() {
+    let t = type_at(
+        r#"
+//- /main.rs
+fn test(a: bool) {
+    let x = loop {
+        if a {
+            break 1u32;
+        }
+        break 2;
+    };
+    x<|>;
+}
+"#,
+    );
+    assert_eq!(t, "u32");
+}
+
+#[test]
+fn loop_break_without_value() {
+    let t = type_at(
+        r#"
+//- /main.rs
+fn test() {
+    let x = loop {
+        break;
+    };
+    x<|>;
+}
+"#,
+    );
+    assert_eq!(t, "()");
+}
+
+#[test]
+fn labeled_break_with_value() {
+    let t = type_at(
+        r#"
+//- /main.rs
+fn test() {
+    let x = 'outer: loop {
+        loop {
+            break 'outer 1.0f64;
+        }
+    };
+    x<|>;
+}
+"#,
+    );
+    assert_eq!(t, "f64");
+}
+
+#[test]
+fn labeled_block_break_with_value() {
+    let t = type_at(
+        r#"
+//- /main.rs
+fn test(a: bool) {
+    let x = 'block: {
+        if a {
+            break 'block 1u8;
+        }
+        2
+    };
+    x<|>;
+}
+"#,
+    );
+    assert_eq!(t, "u8");
+}
@@ -386,6 +386,19 @@ fn test() {
     );
 }
 
+#[test]
+fn infer_literal_suffixes() {
+    let t = type_at(
+        r#"
+//- /main.rs
+fn test() {
+    (0x1f32, 1f32, 0xff_u8, 1_000i64, 92)<|>;
+}
+"#,
+    );
+    assert_eq!(t, "(i32, f32, u8, i64, i32)");
+}
+
 #[test]
 fn infer_unary_op() {
     assert_snapshot!(
@@ -1632,6 +1645,100 @@ fn main() {
     assert_eq!(t, "u32");
 }
 
+#[test]
+fn lifetimes_in_types() {
+    let t = type_at(
+        r#"
+//- /main.rs
+struct S<'a, T> { t: &'a T }
+
+fn foo<'a, 'b>(x: &'b S<'a, u32>, y: &S<u32>, z: &'static str) {
+    (x, y, z)<|>;
+}"#,
+    );
+    assert_eq!(t, "(&'b S<'a, u32>, &S<u32>, &'static str)");
+}
+
+#[test]
+fn lifetimes_of_fields_are_substituted() {
+    let t = type_at(
+        r#"
+//- /main.rs
+struct S<'a> { x: &'a u32 }
+
+fn foo<'b>(s: S<'b>, t: S) {
+    (s.x, t.x)<|>;
+}"#,
+    );
+    assert_eq!(t, "(&'b u32, &u32)");
+}
+
+#[test]
+fn trait_impls_for_types_with_lifetimes() {
+    let t = type_at(
+        r#"
+//- /main.rs
+trait Tr { fn get(&self) -> u32; }
+struct S<'a> { x: &'a u32 }
+impl<'a> Tr for S<'a> { fn get(&self) -> u32 { 0 } }
+impl<'a> Tr for &'a u8 { fn get(&self) -> u32 { 0 } }
+
+fn foo<'b>(s: S<'b>, r: &'static u8) {
+    (s.get(), r.get())<|>;
+}"#,
+    );
+    assert_eq!(t, "(u32, u32)");
+}
+
+#[test]
+fn trait_impls_with_lifetimes_in_where_clauses() {
+    let t = type_at(
+        r#"
+//- /main.rs
+trait Tr { fn get(&self) -> u32; }
+trait Bound {}
+impl<'a> Bound for &'a u32 {}
+struct S<'a> { x: &'a u32 }
+impl<'a> Tr for S<'a> where &'a u32: Bound { fn get(&self) -> u32 { 0 } }
+
+fn foo<'b>(s: S<'b>) {
+    s.get()<|>;
+}"#,
+    );
+    assert_eq!(t, "u32");
+}
+
+#[test]
+fn const_generic_args() {
+    let t = type_at(
+        r#"
+//- /main.rs
+struct Buf<T, const N: usize> { t: T }
+
+fn foo<const N: usize>(x: Buf<u8, N>, y: Buf<u8, 16>, z: Buf<u8, { 0x20 }>) {
+    (x, y, z)<|>;
+}"#,
+    );
+    assert_eq!(t, "(Buf<u8, N>, Buf<u8, 16>, Buf<u8, 32>)");
+}
+
+#[test]
+fn const_generic_param_in_value_position() {
+    let t = type_at(
+        r#"
+//- /main.rs
+struct Buf<const N: usize>;
+impl<const N: usize> Buf<N> {
+    fn len(&self) -> usize { N }
+}
+
+fn foo<const N: u8>() {
+    N<|>;
+}"#,
+    );
+    assert_eq!(t, "u8");
+}
+
 #[test]
 fn closure_return() {
     assert_snapshot!(
@@ -135,6 +135,98 @@ mod result {
     assert_eq!("i32", type_at_pos(&db, pos));
 }
 
+#[test]
+fn infer_try_via_lang_item() {
+    let t = type_at(
+        r#"
+//- /main.rs
+#[lang = "try"]
+trait Try { type Ok; type Error; }
+enum Option<T> { Some(T), None }
+impl<T> Try for Option<T> { type Ok = T; type Error = (); }
+enum Poll<T> { Ready(T), Pending }
+impl<T> Try for Poll<Option<T>> { type Ok = Poll<T>; type Error = (); }
+
+fn test(o: Option<u8>, p: Poll<Option<u16>>) -> Option<()> {
+    (o?, p?)<|>;
+    Option::None
+}
+"#,
+    );
+    assert_eq!(t, "(u8, Poll<u16>)");
+}
+
+#[test]
+fn infer_assoc_const_paths() {
+    let t = type_at(
+        r#"
+//- /main.rs
+trait Bounded { const MAX: Self; }
+trait Trait {
+    type Out;
+    const ID: Self::Out;
+    fn id(&self) -> Self::Out { Self::ID }
+}
+struct S;
+impl Trait for S {
+    type Out = u8;
+    const ID: u8 = 1;
+}
+
+fn test<T: Bounded>() {
+    (T::MAX, S::ID, <S as Trait>::ID)<|>;
+}
+"#,
+    );
+    assert_eq!(t, "(T, u8, u8)");
+}
+
+#[test]
+fn infer_assoc_const_in_trait_default_body() {
+    let t = type_at(
+        r#"
+//- /main.rs
+trait Trait {
+    const ID: u32;
+    fn id(&self) -> u32 { Self::ID<|> }
+}
+"#,
+    );
+    assert_eq!(t, "u32");
+}
+
+#[test]
+fn infer_assoc_type_default() {
+    let t = type_at(
+        r#"
+//- /main.rs
+struct Wrapper<T>(T);
+trait Trait {
+    type Out = u32;
+    type Wrapped = Wrapper<Self>;
+    fn out(&self) -> Self::Out;
+    fn wrapped(&self) -> Self::Wrapped;
+}
+struct S;
+impl Trait for S {
+    fn out(&self) -> Self::Out { loop {} }
+    fn wrapped(&self) -> Self::Wrapped { loop {} }
+}
+struct T;
+impl Trait for T {
+    type Out = i64;
+    fn out(&self) -> Self::Out { loop {} }
+    fn wrapped(&self) -> Self::Wrapped { loop {} }
+}
+
+fn test() {
+    (S.out(), S.wrapped(), T.out(), T.wrapped())<|>;
+}
+"#,
+    );
+    assert_eq!(t, "(u32, Wrapper<S>, i64, Wrapper<T>)");
+}
+
 #[test]
 fn infer_for_loop() {
     let (db, pos) = TestDB::with_position(
@@ -242,6 +334,135 @@ mod ops {
     assert_eq!("Foo", type_at_pos(&db, pos));
 }
 
+#[test]
+fn infer_ops_add() {
+    let (db, pos) = TestDB::with_position(
+        r#"
+//- /main.rs crate:main deps:std
+
+struct Vector;
+struct Sum;
+
+impl std::ops::Add for Vector {
+    type Output = Sum;
+}
+
+fn test() {
+    let a = Vector;
+    let b = a + Vector;
+    b<|>;
+}
+
+//- /std.rs crate:std
+
+#[prelude_import] use ops::*;
+mod ops {
+    #[lang = "add"]
+    pub trait Add<Rhs = Self> {
+        type Output;
+    }
+}
+"#,
+    );
+    assert_eq!("Sum", type_at_pos(&db, pos));
+}
+
+#[test]
+fn infer_ops_mul_with_rhs_param() {
+    let (db, pos) = TestDB::with_position(
+        r#"
+//- /main.rs crate:main deps:std
+
+struct Duration;
+
+impl std::ops::Mul<u32> for Duration {
+    type Output = Duration;
+}
+impl std::ops::Mul<Duration> for u32 {
+    type Output = Duration;
+}
+
+fn test(d: Duration) {
+    let x = d * 2u32;
+    let y = 2u32 * x;
+    y<|>;
+}
+
+//- /std.rs crate:std
+
+#[prelude_import] use ops::*;
+mod ops {
+    #[lang = "mul"]
+    pub trait Mul<Rhs = Self> {
+        type Output;
+    }
+}
+"#,
+    );
+    assert_eq!("Duration", type_at_pos(&db, pos));
+}
+
+#[test]
+fn infer_ops_add_assign() {
+    let (db, pos) = TestDB::with_position(
+        r#"
+//- /main.rs crate:main deps:std
+
+struct Vector;
+
+impl std::ops::AddAssign for Vector {}
+
+fn test(mut a: Vector) {
+    let b = a += Vector;
+    b<|>;
+}
+
+//- /std.rs crate:std
+
+#[prelude_import] use ops::*;
+mod ops {
+    #[lang = "add_assign"]
+    pub trait AddAssign<Rhs = Self> {
+        fn add_assign(&mut self, rhs: Rhs);
+    }
+}
+"#,
+    );
+    assert_eq!("()", type_at_pos(&db, pos));
+}
+
+#[test]
+fn infer_ops_add_assign_rhs_from_impl() {
+    let (db, pos) = TestDB::with_position(
+        r#"
+//- /main.rs crate:main deps:std
+
+struct Vector;
+
+impl std::ops::AddAssign<u64> for Vector {
+    fn add_assign(&mut self, rhs: u64) {}
+}
+
+fn test(mut a: Vector) {
+    let rhs = 1;
+    a += rhs;
+    rhs<|>;
+}
+
+//- /std.rs crate:std
+
+#[prelude_import] use ops::*;
+mod ops {
+    #[lang = "add_assign"]
+    pub trait AddAssign<Rhs = Self> {
+        fn add_assign(&mut self, rhs: Rhs);
+    }
+}
+"#,
+    );
+    assert_eq!("u64", type_at_pos(&db, pos));
+}
+
 #[test]
 fn infer_from_bound_1() {
     assert_snapshot!(
@@ -1324,6 +1545,87 @@ mod iter {
     assert_eq!("{unknown}", type_at_pos(&db, pos));
 }
 
+#[test]
+fn return_pos_impl_trait_assoc_binding() {
+    let t = type_at(
+        r#"
+//- /main.rs
+enum Option<T> { Some(T), None }
+trait Iterator {
+    type Item;
+    fn next(&mut self) -> Option<Self::Item>;
+}
+struct Foo;
+fn foo() -> impl Iterator<Item = Foo> { loop {} }
+fn wrap<T>(t: T) -> impl Iterator<Item = T> { loop {} }
+
+fn test() {
+    let mut a = foo();
+    let mut b = wrap(1u8);
+    (a.next(), b.next())<|>;
+}
+"#,
+    );
+    assert_eq!(t, "(Option<Foo>, Option<u8>)");
+}
+
+#[test]
+fn return_pos_impl_trait_unbound_assoc_type() {
+    let t = type_at(
+        r#"
+//- /main.rs
+enum Option<T> { Some(T), None }
+trait Iterator {
+    type Item;
+    fn next(&mut self) -> Option<Self::Item>;
+}
+fn foo() -> impl Iterator { loop {} }
+
+fn test() {
+    let mut a = foo();
+    a.next()<|>;
+}
+"#,
+    );
+    assert_eq!(t, "Option<Iterator::Item<impl Iterator>>");
+}
+
+#[test]
+fn return_pos_impl_trait_supertrait_method() {
+    let t = type_at(
+        r#"
+//- /main.rs
+trait Base { fn base(&self) -> u32; }
+trait Derived: Base { fn derived(&self) -> i64; }
+fn foo() -> impl Derived { loop {} }
+
+fn test() {
+    let x = foo();
+    (x.base(), x.derived())<|>;
+}
+"#,
+    );
+    assert_eq!(t, "(u32, i64)");
+}
+
+#[test]
+fn return_pos_impl_trait_hidden_type_inference() {
+    let t = type_at(
+        r#"
+//- /main.rs
+trait Trait<T> {}
+struct S<T>(T);
+impl<T> Trait<T> for S<T> {}
+fn default<T>() -> T { loop {} }
+
+fn test() -> impl Trait<u16> {
+    S(default())<|>
+}
+"#,
+    );
+    assert_eq!(t, "S<u16>");
+}
+
 #[test]
 fn projection_eq_within_chalk() {
     // std::env::set_var("CHALK_DEBUG", "1");
@@ -1839,7 +2141,7 @@ fn test() -> impl Trait<i32> {
     [172; 183) '{ loop {} }': T
     [174; 181) 'loop {}': !
     [179; 181) '{}': ()
-    [214; 310) '{     ...t()) }': S<{unknown}>
+    [214; 310) '{     ...t()) }': S<i32>
     [224; 226) 's1': S<u32>
     [229; 230) 'S': S<u32>(u32) -> S<u32>
     [229; 241) 'S(default())': S<u32>
@@ -1855,10 +2157,10 @@ fn test() -> impl Trait<i32> {
     [277; 289) 'S(default())': S<i32>
     [279; 286) 'default': fn default<i32>() -> i32
     [279; 288) 'default()': i32
-    [296; 297) 'S': S<{unknown}>({unknown}) -> S<{unknown}>
-    [296; 308) 'S(default())': S<{unknown}>
-    [298; 305) 'default': fn default<{unknown}>() -> {unknown}
-    [298; 307) 'default()': {unknown}
+    [296; 297) 'S': S<i32>(i32) -> S<i32>
+    [296; 308) 'S(default())': S<i32>
+    [298; 305) 'default': fn default<i32>() -> i32
+    [298; 307) 'default()': i32
     "###
     );
 }
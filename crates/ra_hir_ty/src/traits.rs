@@ -12,7 +12,9 @@ use rustc_hash::FxHashSet;
 
 use crate::db::HirDatabase;
 
-use super::{Canonical, GenericPredicate, HirDisplay, ProjectionTy, TraitRef, Ty, TypeWalk};
+use super::{
+    Canonical, GenericPredicate, HirDisplay, OpaqueTyId, ProjectionTy, TraitRef, Ty, TypeWalk,
+};
 
 use self::chalk::{from_chalk, ToChalk, TypeFamily};
 
@@ -119,18 +121,25 @@ pub(crate) fn impls_for_trait_query(
     krate: CrateId,
     trait_: TraitId,
 ) -> Arc<[ImplId]> {
-    let mut impls = FxHashSet::default();
+    // Keep the impls in a stable order (dependencies first, then the impls of
+    // this crate in source order): `lookup_impl_method` picks the first
+    // matching impl.
+    let mut seen = FxHashSet::default();
+    let mut impls = Vec::new();
     // We call the query recursively here. On the one hand, this means we can
     // reuse results from queries for different crates; on the other hand, this
     // will only ever get called for a few crates near the root of the tree (the
     // ones the user is editing), so this may actually be a waste of memory. I'm
     // doing it like this mainly for simplicity for now.
     for dep in db.crate_graph().dependencies(krate) {
-        impls.extend(db.impls_for_trait(dep.crate_id, trait_).iter());
+        let dep_impls = db.impls_for_trait(dep.crate_id, trait_);
+        impls.extend(dep_impls.iter().copied().filter(|&it| seen.insert(it)));
     }
     let crate_impl_blocks = db.impls_in_crate(krate);
-    impls.extend(crate_impl_blocks.lookup_impl_blocks_for_trait(trait_));
-    impls.into_iter().collect()
+    impls.extend(
+        crate_impl_blocks.lookup_impl_blocks_for_trait(trait_).filter(|&it| seen.insert(it)),
+    );
+    impls.into()
 }
 
 /// A set of clauses that we assume to be true. E.g. if we are inside this function:
@@ -335,6 +344,12 @@ pub struct ClosureFnTraitImplData {
     fn_trait: FnTrait,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OpaqueTyImplData {
+    opaque_ty: OpaqueTyId,
+    trait_: TraitId,
+}
+
 /// An impl. Usually this comes from an impl block, but some built-in types get
 /// synthetic impls.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -343,6 +358,9 @@ pub enum Impl {
     ImplBlock(ImplId),
     /// Closure types implement the Fn traits synthetically.
     ClosureFnTraitImpl(ClosureFnTraitImplData),
+    /// `impl Trait` types implement the traits from their bounds (and their
+    /// super traits) synthetically.
+    OpaqueTyImpl(OpaqueTyImplData),
 }
 /// This exists just for Chalk, because our ImplIds are only unique per module.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -356,8 +374,13 @@ impl_intern_key!(GlobalImplId);
 pub enum AssocTyValue {
     /// A normal assoc type value from an impl block.
     TypeAlias(TypeAliasId),
+    /// The default of an assoc type in a trait, for an impl block which doesn't
+    /// specify the assoc type itself.
+    TraitDefault { impl_: ImplId, type_alias: TypeAliasId },
     /// The output type of the Fn trait implementation.
     ClosureFnTraitImplOutput(ClosureFnTraitImplData),
+    /// An associated type of a trait implemented by an `impl Trait` type.
+    OpaqueTyImplAssocTy { impl_: OpaqueTyImplData, assoc_ty: TypeAliasId },
 }
 /// This exists just for Chalk, because it needs a unique ID for each associated
 /// type value in an impl (even synthetic ones).
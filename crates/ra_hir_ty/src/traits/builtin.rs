@@ -5,7 +5,9 @@ use hir_expand::name::name;
 use ra_db::CrateId;
 
 use super::{AssocTyValue, Impl};
-use crate::{db::HirDatabase, ApplicationTy, Substs, TraitRef, Ty, TypeCtor};
+use crate::{
+    db::HirDatabase, ApplicationTy, GenericPredicate, OpaqueTyId, Substs, TraitRef, Ty, TypeCtor,
+};
 
 pub(super) struct BuiltinImplData {
     pub num_vars: usize,
@@ -43,12 +45,19 @@ pub(super) fn get_builtin_impls(
             }
         }
     }
+    if let Ty::Apply(ApplicationTy { ctor: TypeCtor::OpaqueType(opaque_ty), .. }) = ty {
+        let (_, trait_refs) = opaque_ty_trait_refs(db, *opaque_ty);
+        if trait_refs.iter().any(|it| it.trait_ == trait_) {
+            callback(Impl::OpaqueTyImpl(super::OpaqueTyImplData { opaque_ty: *opaque_ty, trait_ }));
+        }
+    }
 }
 
 pub(super) fn impl_datum(db: &impl HirDatabase, krate: CrateId, impl_: Impl) -> BuiltinImplData {
     match impl_ {
         Impl::ImplBlock(_) => unreachable!(),
         Impl::ClosureFnTraitImpl(data) => closure_fn_trait_impl_datum(db, krate, data),
+        Impl::OpaqueTyImpl(data) => opaque_ty_impl_datum(db, data),
     }
 }
 
@@ -58,10 +67,13 @@ pub(super) fn associated_ty_value(
     data: AssocTyValue,
 ) -> BuiltinImplAssocTyValueData {
     match data {
-        AssocTyValue::TypeAlias(_) => unreachable!(),
+        AssocTyValue::TypeAlias(_) | AssocTyValue::TraitDefault { .. } => unreachable!(),
         AssocTyValue::ClosureFnTraitImplOutput(data) => {
             closure_fn_trait_output_assoc_ty_value(db, krate, data)
         }
+        AssocTyValue::OpaqueTyImplAssocTy { impl_, assoc_ty } => {
+            opaque_ty_impl_assoc_ty_value(db, impl_, assoc_ty)
+        }
     }
 }
 
@@ -165,6 +177,107 @@ fn closure_fn_trait_output_assoc_ty_value(
     }
 }
 
+/// Returns the trait refs implemented by an `impl Trait` type, i.e. its
+/// bounds and their super traits. They are bound over the opaque type's
+/// parameters, the number of which is returned as well.
+fn opaque_ty_trait_refs(db: &impl HirDatabase, opaque_ty: OpaqueTyId) -> (usize, Vec<TraitRef>) {
+    // impl<T...> Trait for impl_trait<T...>
+    let ctor = TypeCtor::OpaqueType(opaque_ty);
+    let num_vars = ctor.num_ty_params(db);
+    let self_ty =
+        Ty::apply(ctor, Substs::build_for_type_ctor(db, ctor).fill_with_bound_vars(0).build());
+    let mut trait_refs: Vec<TraitRef> = self_ty
+        .impl_trait_bounds(db)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|pred| match pred {
+            GenericPredicate::Implemented(trait_ref) => Some(trait_ref),
+            _ => None,
+        })
+        .collect();
+    let mut i = 0;
+    while i < trait_refs.len() {
+        let trait_ref = trait_refs[i].clone();
+        for pred in db.generic_predicates(trait_ref.trait_.into()).iter() {
+            if pred.num_binders != trait_ref.substs.len() {
+                continue;
+            }
+            if let GenericPredicate::Implemented(super_trait_ref) =
+                pred.clone().subst(&trait_ref.substs)
+            {
+                if super_trait_ref.self_ty() == &self_ty
+                    && !trait_refs.iter().any(|it| it.trait_ == super_trait_ref.trait_)
+                {
+                    trait_refs.push(super_trait_ref);
+                }
+            }
+        }
+        i += 1;
+    }
+    (num_vars, trait_refs)
+}
+
+fn opaque_ty_impl_datum(db: &impl HirDatabase, data: super::OpaqueTyImplData) -> BuiltinImplData {
+    let (num_vars, trait_refs) = opaque_ty_trait_refs(db, data.opaque_ty);
+    let trait_ref = match trait_refs.into_iter().find(|it| it.trait_ == data.trait_) {
+        Some(it) => it,
+        None => {
+            // `get_builtin_impls` only creates the impl for traits in the
+            // bounds, so this shouldn't happen; return an impl for the error
+            // type, which never applies
+            log::warn!("opaque type impl for trait not in its bounds: {:?}", data);
+            let substs = Substs::build_for_def(db, data.trait_).fill_with_unknown().build();
+            return BuiltinImplData {
+                num_vars: 0,
+                trait_ref: TraitRef { trait_: data.trait_, substs },
+                where_clauses: Vec::new(),
+                assoc_ty_values: Vec::new(),
+            };
+        }
+    };
+    let assoc_ty_values = db
+        .trait_data(data.trait_)
+        .associated_types()
+        .map(|assoc_ty| AssocTyValue::OpaqueTyImplAssocTy { impl_: data, assoc_ty })
+        .collect();
+    BuiltinImplData { num_vars, trait_ref, where_clauses: Vec::new(), assoc_ty_values }
+}
+
+fn opaque_ty_impl_assoc_ty_value(
+    db: &impl HirDatabase,
+    impl_: super::OpaqueTyImplData,
+    assoc_ty: TypeAliasId,
+) -> BuiltinImplAssocTyValueData {
+    let ctor = TypeCtor::OpaqueType(impl_.opaque_ty);
+    let (num_vars, trait_refs) = opaque_ty_trait_refs(db, impl_.opaque_ty);
+    let self_ty =
+        Ty::apply(ctor, Substs::build_for_type_ctor(db, ctor).fill_with_bound_vars(0).build());
+    // the value comes from an `Assoc = Ty` binding in the bounds; without one,
+    // it stays opaque, like the unnormalized `(Iterator::Item)<impl Iterator>`
+    // chalk uses for projections it can't normalize
+    let value = self_ty
+        .impl_trait_bounds(db)
+        .unwrap_or_default()
+        .into_iter()
+        .find_map(|pred| match pred {
+            GenericPredicate::Projection(proj) if proj.projection_ty.associated_ty == assoc_ty => {
+                Some(proj.ty)
+            }
+            _ => None,
+        })
+        .or_else(|| {
+            let trait_ref = trait_refs.into_iter().find(|it| it.trait_ == impl_.trait_)?;
+            Some(Ty::apply(TypeCtor::AssociatedType(assoc_ty), trait_ref.substs))
+        })
+        .unwrap_or(Ty::Unknown);
+    BuiltinImplAssocTyValueData {
+        impl_: Impl::OpaqueTyImpl(impl_),
+        assoc_ty_id: assoc_ty,
+        num_vars,
+        value,
+    }
+}
+
 fn get_fn_trait(
     db: &impl HirDatabase,
     krate: CrateId,
@@ -1,11 +1,12 @@
 //! Conversion code from/to Chalk.
-use std::{fmt, sync::Arc};
+use std::{fmt, iter, sync::Arc};
 
 use log::debug;
 
 use chalk_ir::{cast::Cast, GoalData, Parameter, PlaceholderIndex, TypeName, UniverseIndex};
 
 use hir_def::{AssocContainerId, AssocItemId, GenericDefId, HasModule, Lookup, TypeAliasId};
+use ra_arena::ArenaId;
 use ra_db::{
     salsa::{InternId, InternKey},
     CrateId,
@@ -13,8 +14,10 @@ use ra_db::{
 
 use super::{builtin, AssocTyValue, Canonical, ChalkContext, Impl, Obligation};
 use crate::{
-    db::HirDatabase, display::HirDisplay, utils::generics, ApplicationTy, GenericPredicate,
-    ProjectionTy, Substs, TraitRef, Ty, TypeCtor,
+    db::{GlobalLifetimeParamId, HirDatabase},
+    display::HirDisplay,
+    utils::generics,
+    GenericPredicate, Lifetime, ProjectionTy, Substs, TraitRef, Ty, TypeCtor, TypeWalk,
 };
 
 #[derive(Debug, Copy, Clone, Hash, PartialOrd, Ord, PartialEq, Eq)]
@@ -134,7 +137,22 @@ impl ToChalk for Ty {
         match self {
             Ty::Apply(apply_ty) => {
                 let name = apply_ty.ctor.to_chalk(db);
-                let substitution = apply_ty.parameters.to_chalk(db);
+                // The lifetimes follow the type parameters, and there have to
+                // be as many of them as the struct datum declares.
+                let num_lifetimes = apply_ty.ctor.num_lifetime_params(db);
+                let lifetimes = apply_ty
+                    .lifetimes
+                    .iter()
+                    .copied()
+                    .chain(iter::repeat(Lifetime::Unknown))
+                    .take(num_lifetimes);
+                let parameters: Vec<Parameter<TypeFamily>> = apply_ty
+                    .parameters
+                    .iter()
+                    .map(|ty| ty.clone().to_chalk(db).cast())
+                    .chain(lifetimes.map(|lifetime| lifetime.to_chalk(db).cast()))
+                    .collect();
+                let substitution = chalk_ir::Substitution::from(parameters);
                 chalk_ir::ApplicationTy { name, substitution }.cast().intern()
             }
             Ty::Projection(proj_ty) => {
@@ -162,7 +180,7 @@ impl ToChalk for Ty {
                 let bounded_ty = chalk_ir::DynTy { bounds: make_binders(where_clauses, 1) };
                 chalk_ir::TyData::Dyn(bounded_ty).intern()
             }
-            Ty::Opaque(_) | Ty::Unknown => {
+            Ty::Unknown => {
                 let substitution = chalk_ir::Substitution::empty();
                 let name = TypeName::Error;
                 chalk_ir::ApplicationTy { name, substitution }.cast().intern()
@@ -175,8 +193,16 @@ impl ToChalk for Ty {
                 TypeName::Error => Ty::Unknown,
                 _ => {
                     let ctor = from_chalk(db, apply_ty.name);
-                    let parameters = from_chalk(db, apply_ty.substitution);
-                    Ty::Apply(ApplicationTy { ctor, parameters })
+                    let mut parameters = Vec::new();
+                    let mut lifetimes = Vec::new();
+                    for parameter in apply_ty.substitution.into_iter() {
+                        if let Some(ty) = parameter.ty() {
+                            parameters.push(from_chalk(db, ty.clone()));
+                        } else if let Some(lifetime) = parameter.lifetime() {
+                            lifetimes.push(from_chalk(db, lifetime.clone()));
+                        }
+                    }
+                    Ty::apply_with_lifetimes(ctor, Substs(parameters.into()), lifetimes.into())
                 }
             },
             chalk_ir::TyData::Placeholder(idx) => {
@@ -212,14 +238,47 @@ impl ToChalk for Substs {
     }
 
     fn from_chalk(db: &impl HirDatabase, parameters: chalk_ir::Substitution<TypeFamily>) -> Substs {
-        let tys = parameters
-            .into_iter()
-            .map(|p| match p.ty() {
-                Some(ty) => from_chalk(db, ty.clone()),
-                None => unimplemented!(),
-            })
-            .collect();
-        Substs(tys)
+        // Lifetimes only appear in the substitutions of application types,
+        // which handle them separately.
+        let tys = parameters.into_iter().filter_map(|p| Some(from_chalk(db, p.ty()?.clone())));
+        Substs(tys.collect())
+    }
+}
+
+/// The placeholder indices for `'static` and unknown lifetimes; the ones of
+/// lifetime parameters are interned ids, which never get this large.
+const STATIC_LIFETIME_IDX: usize = usize::MAX;
+const UNKNOWN_LIFETIME_IDX: usize = usize::MAX - 1;
+
+impl ToChalk for Lifetime {
+    type Chalk = chalk_ir::Lifetime<TypeFamily>;
+
+    fn to_chalk(self, db: &impl HirDatabase) -> chalk_ir::Lifetime<TypeFamily> {
+        let idx = match self {
+            Lifetime::Param(id) => db.intern_lifetime_param_id(id).as_intern_id().as_usize(),
+            Lifetime::Bound(idx) => {
+                return chalk_ir::LifetimeData::BoundVar(idx as usize).intern();
+            }
+            Lifetime::Static => STATIC_LIFETIME_IDX,
+            Lifetime::Unknown => UNKNOWN_LIFETIME_IDX,
+        };
+        PlaceholderIndex { ui: UniverseIndex::ROOT, idx }.to_lifetime::<TypeFamily>()
+    }
+
+    fn from_chalk(db: &impl HirDatabase, lifetime: chalk_ir::Lifetime<TypeFamily>) -> Lifetime {
+        match lifetime.data() {
+            chalk_ir::LifetimeData::Placeholder(idx) => match idx.idx {
+                STATIC_LIFETIME_IDX => Lifetime::Static,
+                UNKNOWN_LIFETIME_IDX => Lifetime::Unknown,
+                idx => {
+                    let interned_id = GlobalLifetimeParamId::from_intern_id(InternId::from(idx));
+                    Lifetime::Param(db.lookup_intern_lifetime_param_id(interned_id))
+                }
+            },
+            // We don't do region inference, so we can't make sense of the
+            // lifetime variables Chalk introduces.
+            _ => Lifetime::Unknown,
+        }
     }
 }
 
@@ -261,7 +320,8 @@ impl ToChalk for TypeCtor {
                 TypeName::AssociatedType(type_id)
             }
             _ => {
-                // other TypeCtors get interned and turned into a chalk StructId
+                // other TypeCtors (including const generic values) get interned
+                // and turned into a chalk StructId
                 let struct_id = db.intern_type_ctor(self).into();
                 TypeName::Struct(struct_id)
             }
@@ -513,10 +573,45 @@ impl ToChalk for builtin::BuiltinImplAssocTyValueData {
 }
 
 fn make_binders<T>(value: T, num_vars: usize) -> chalk_ir::Binders<T> {
-    chalk_ir::Binders {
-        value,
-        binders: std::iter::repeat(chalk_ir::ParameterKind::Ty(())).take(num_vars).collect(),
-    }
+    make_binders_with_lifetimes(value, num_vars, 0)
+}
+
+fn make_binders_with_lifetimes<T>(
+    value: T,
+    num_vars: usize,
+    num_lifetimes: usize,
+) -> chalk_ir::Binders<T> {
+    let types = iter::repeat(chalk_ir::ParameterKind::Ty(())).take(num_vars);
+    let lifetimes = iter::repeat(chalk_ir::ParameterKind::Lifetime(())).take(num_lifetimes);
+    chalk_ir::Binders { value, binders: types.chain(lifetimes).collect() }
+}
+
+/// Replaces the lifetime parameters of `def` by bound variables, which come
+/// after the `num_vars` type variables in the binders of a datum. This way
+/// an impl for `&'a T` applies to references with any lifetime.
+fn bind_lifetime_params<T: TypeWalk>(mut value: T, def: GenericDefId, num_vars: usize) -> T {
+    value.walk_mut_binders(
+        &mut |ty, binders| {
+            if let Ty::Apply(a_ty) = ty {
+                if a_ty.lifetimes.is_empty() {
+                    return;
+                }
+                a_ty.lifetimes = a_ty
+                    .lifetimes
+                    .iter()
+                    .map(|&lifetime| match lifetime {
+                        Lifetime::Param(id) if id.parent == def => {
+                            let idx = u32::from(id.local_id.into_raw()) as usize;
+                            Lifetime::Bound((binders + num_vars + idx) as u32)
+                        }
+                        lifetime => lifetime,
+                    })
+                    .collect();
+            }
+        },
+        0,
+    );
+    value
 }
 
 fn convert_where_clauses(
@@ -688,8 +783,9 @@ pub(crate) fn struct_datum_query(
         fields: Vec::new(), // FIXME add fields (only relevant for auto traits)
         where_clauses,
     };
-    let struct_datum =
-        StructDatum { id: struct_id, binders: make_binders(struct_datum_bound, num_params), flags };
+    let num_lifetimes = type_ctor.num_lifetime_params(db);
+    let binders = make_binders_with_lifetimes(struct_datum_bound, num_params, num_lifetimes);
+    let struct_datum = StructDatum { id: struct_id, binders, flags };
     Arc::new(struct_datum)
 }
 
@@ -728,7 +824,17 @@ fn impl_block_datum(
     } else {
         chalk_rust_ir::ImplType::External
     };
-    let where_clauses = convert_where_clauses(db, impl_id.into(), &bound_vars);
+    // Like the trait ref, the where clauses are bound over the lifetime
+    // parameters of the impl.
+    let where_clauses = db
+        .generic_predicates(impl_id.into())
+        .iter()
+        .filter(|pred| !pred.value.is_error())
+        .map(|pred| {
+            let pred = pred.clone().subst(&bound_vars);
+            bind_lifetime_params(pred, impl_id.into(), bound_vars.len()).to_chalk(db)
+        })
+        .collect::<Vec<_>>();
     let negative = impl_data.is_negative;
     debug!(
         "impl {:?}: {}{} where {:?}",
@@ -737,7 +843,8 @@ fn impl_block_datum(
         trait_ref.display(db),
         where_clauses
     );
-    let trait_ref = trait_ref.to_chalk(db);
+    let num_lifetimes = db.generic_params(impl_id.into()).lifetimes.len();
+    let trait_ref = bind_lifetime_params(trait_ref, impl_id.into(), bound_vars.len()).to_chalk(db);
 
     let polarity = if negative {
         chalk_rust_ir::Polarity::Negative
@@ -747,7 +854,7 @@ fn impl_block_datum(
 
     let impl_datum_bound = chalk_rust_ir::ImplDatumBound { trait_ref, where_clauses };
     let trait_data = db.trait_data(trait_);
-    let associated_ty_value_ids = impl_data
+    let impl_assoc_types = impl_data
         .items
         .iter()
         .filter_map(|item| match item {
@@ -759,11 +866,26 @@ fn impl_block_datum(
             let name = &db.type_alias_data(type_alias).name;
             trait_data.associated_type_by_name(name).is_some()
         })
-        .map(|type_alias| AssocTyValue::TypeAlias(type_alias).to_chalk(db))
+        .collect::<Vec<_>>();
+    // associated types with a default in the trait may be left out of the impl
+    let defaulted = trait_data.associated_types().filter(|&trait_alias| {
+        let trait_alias_data = db.type_alias_data(trait_alias);
+        trait_alias_data.type_ref.is_some()
+            && !impl_assoc_types
+                .iter()
+                .any(|&it| db.type_alias_data(it).name == trait_alias_data.name)
+    });
+    let associated_ty_value_ids = impl_assoc_types
+        .iter()
+        .map(|&type_alias| AssocTyValue::TypeAlias(type_alias))
+        .chain(
+            defaulted.map(|type_alias| AssocTyValue::TraitDefault { impl_: impl_id, type_alias }),
+        )
+        .map(|value| value.to_chalk(db))
         .collect();
     debug!("impl_datum: {:?}", impl_datum_bound);
     let impl_datum = ImplDatum {
-        binders: make_binders(impl_datum_bound, bound_vars.len()),
+        binders: make_binders_with_lifetimes(impl_datum_bound, bound_vars.len(), num_lifetimes),
         impl_type,
         polarity,
         associated_ty_value_ids,
@@ -781,6 +903,9 @@ pub(crate) fn associated_ty_value_query(
         AssocTyValue::TypeAlias(type_alias) => {
             type_alias_associated_ty_value(db, krate, type_alias)
         }
+        AssocTyValue::TraitDefault { impl_, type_alias } => {
+            default_associated_ty_value(db, impl_, type_alias)
+        }
         _ => Arc::new(builtin::associated_ty_value(db, krate, data).to_chalk(db)),
     }
 }
@@ -803,11 +928,40 @@ fn type_alias_associated_ty_value(
         .associated_type_by_name(&type_alias_data.name)
         .expect("assoc ty value should not exist"); // validated when building the impl data as well
     let ty = db.ty(type_alias.into());
-    let value_bound = chalk_rust_ir::AssociatedTyValueBound { ty: ty.value.to_chalk(db) };
+    let num_lifetimes = db.generic_params(impl_id.into()).lifetimes.len();
+    let value_ty = bind_lifetime_params(ty.value, impl_id.into(), ty.num_binders);
+    let value_bound = chalk_rust_ir::AssociatedTyValueBound { ty: value_ty.to_chalk(db) };
     let value = chalk_rust_ir::AssociatedTyValue {
         impl_id: Impl::ImplBlock(impl_id.into()).to_chalk(db),
         associated_ty_id: assoc_ty.to_chalk(db),
-        value: make_binders(value_bound, ty.num_binders),
+        value: make_binders_with_lifetimes(value_bound, ty.num_binders, num_lifetimes),
+    };
+    Arc::new(value)
+}
+
+fn default_associated_ty_value(
+    db: &impl HirDatabase,
+    impl_id: hir_def::ImplId,
+    type_alias: TypeAliasId,
+) -> Arc<AssociatedTyValue> {
+    // we don't return any assoc ty values if the impl'd trait can't be resolved
+    let trait_ref = db.impl_trait(impl_id).expect("assoc ty value should not exist").value;
+    let num_impl_vars = generics(db, impl_id.into()).len();
+    // The default is bound over the trait's parameters (and those of the assoc
+    // type itself, which we don't support); substitute the impl's trait ref
+    // for them, which leaves us bound over the impl's parameters.
+    let default = db.ty(type_alias.into());
+    let substs = Substs::build_for_def(db, type_alias)
+        .use_parent_substs(&trait_ref.substs)
+        .fill_with_unknown()
+        .build();
+    let num_lifetimes = db.generic_params(impl_id.into()).lifetimes.len();
+    let ty = bind_lifetime_params(default.subst(&substs), impl_id.into(), num_impl_vars);
+    let value_bound = chalk_rust_ir::AssociatedTyValueBound { ty: ty.to_chalk(db) };
+    let value = chalk_rust_ir::AssociatedTyValue {
+        impl_id: Impl::ImplBlock(impl_id.into()).to_chalk(db),
+        associated_ty_id: type_alias.to_chalk(db),
+        value: make_binders_with_lifetimes(value_bound, num_impl_vars, num_lifetimes),
     };
     Arc::new(value)
 }
@@ -145,7 +145,7 @@ impl Generics {
         (parent + child, parent, child)
     }
 
-    /// (parent total, self param, type param list, impl trait)
+    /// (parent total, self param, type and const param list, impl trait)
     pub(crate) fn provenance_split(&self) -> (usize, usize, usize, usize) {
         let parent = self.parent_generics.as_ref().map_or(0, |p| p.len());
         let self_params = self
@@ -158,7 +158,10 @@ impl Generics {
             .params
             .types
             .iter()
-            .filter(|(_, p)| p.provenance == TypeParamProvenance::TypeParamList)
+            .filter(|(_, p)| {
+                p.provenance == TypeParamProvenance::TypeParamList
+                    || p.provenance == TypeParamProvenance::ConstParam
+            })
             .count();
         let impl_trait_params = self
             .params
@@ -545,4 +545,58 @@ mod tests {
         "###
         )
     }
+
+    #[test]
+    fn works_in_simple_macro_1() {
+        assert_debug_snapshot!(
+        do_ref_completion(
+            r"
+            macro_rules! m { ($e:expr) => { $e } }
+            struct A { the_field: u32 }
+            fn foo(a: A) {
+                m!(a.x<|>)
+            }
+            ",
+        ),
+        @r###"
+        [
+            CompletionItem {
+                label: "the_field",
+                source_range: [140; 141),
+                delete: [140; 141),
+                insert: "the_field",
+                kind: Field,
+                detail: "u32",
+            },
+        ]
+        "###
+        );
+    }
+
+    #[test]
+    fn works_in_simple_macro_recursive() {
+        assert_debug_snapshot!(
+        do_ref_completion(
+            r"
+            macro_rules! m { ($e:expr) => { $e } }
+            struct A { the_field: u32 }
+            fn foo(a: A) {
+                m!(m!(m!(a.x<|>)))
+            }
+            ",
+        ),
+        @r###"
+        [
+            CompletionItem {
+                label: "the_field",
+                source_range: [146; 147),
+                delete: [146; 147),
+                insert: "the_field",
+                kind: Field,
+                detail: "u32",
+            },
+        ]
+        "###
+        );
+    }
 }
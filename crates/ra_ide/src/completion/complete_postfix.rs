@@ -16,6 +16,12 @@ pub(super) fn complete_postfix(acc: &mut Completions, ctx: &CompletionContext) {
         return;
     }
 
+    // The receiver lives in the macro expansion, so there is no way to
+    // rewrite it in the original file.
+    if ctx.is_in_macro_call {
+        return;
+    }
+
     let dot_receiver = match &ctx.dot_receiver {
         Some(it) => it,
         None => return,
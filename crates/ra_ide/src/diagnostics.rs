@@ -1,16 +1,19 @@
 //! FIXME: write short doc here
 
-use std::cell::RefCell;
+use std::{cell::RefCell, collections::BTreeSet, iter::successors};
 
 use hir::diagnostics::{AstDiagnostic, Diagnostic as _, DiagnosticSink};
 use itertools::Itertools;
+use ra_assists::insert_use_statement;
 use ra_db::{RelativePath, SourceDatabase, SourceDatabaseExt};
-use ra_ide_db::RootDatabase;
+use ra_ide_db::{imports_locator::ImportsLocator, RootDatabase};
 use ra_prof::profile;
 use ra_syntax::{
     algo,
     ast::{self, make, AstNode},
-    Location, SyntaxNode, TextRange, T,
+    Location,
+    SyntaxKind::WHITESPACE,
+    SyntaxNode, TextRange, T,
 };
 use ra_text_edit::{TextEdit, TextEditBuilder};
 
@@ -20,6 +23,9 @@ use crate::{Diagnostic, FileId, FileSystemEdit, SourceChange, SourceFileEdit};
 pub enum Severity {
     Error,
     WeakWarning,
+    /// Code which is never used. Editors usually fade it out instead of
+    /// underlining it.
+    Unused,
 }
 
 pub(crate) fn diagnostics(db: &RootDatabase, file_id: FileId) -> Vec<Diagnostic> {
@@ -98,6 +104,26 @@ pub(crate) fn diagnostics(db: &RootDatabase, file_id: FileId) -> Vec<Diagnostic>
             fix,
         })
     })
+    .on::<hir::diagnostics::UnresolvedImport, _>(|d| {
+        let use_tree = d.ast(db);
+        let fix = fix_unresolved_import(db, file_id, &use_tree);
+        res.borrow_mut().push(Diagnostic {
+            range: d.highlight_range(),
+            message: d.message(),
+            severity: Severity::Error,
+            fix,
+        })
+    })
+    .on::<hir::diagnostics::UnresolvedPath, _>(|d| {
+        let path = d.ast(db);
+        let fix = auto_import(db, file_id, &path);
+        res.borrow_mut().push(Diagnostic {
+            range: d.highlight_range(),
+            message: d.message(),
+            severity: Severity::Error,
+            fix,
+        })
+    })
     .on::<hir::diagnostics::MissingOkInTailExpr, _>(|d| {
         let node = d.ast(db);
         let replacement = format!("Ok({})", node.syntax());
@@ -109,6 +135,39 @@ pub(crate) fn diagnostics(db: &RootDatabase, file_id: FileId) -> Vec<Diagnostic>
             severity: Severity::Error,
             fix: Some(fix),
         })
+    })
+    .on::<hir::diagnostics::TryInNonTryFunction, _>(|d| {
+        let fix = d.suggested_ret_ty.as_ref().and_then(|ret_ty| {
+            let try_expr = d.ast(db);
+            // `?` inside of a closure or an async block returns from there,
+            // not from the function
+            let fn_def = try_expr
+                .syntax()
+                .ancestors()
+                .find(|it| {
+                    ast::FnDef::can_cast(it.kind())
+                        || ast::LambdaExpr::can_cast(it.kind())
+                        || ast::BlockExpr::cast(it.clone()).map_or(false, |it| it.is_async())
+                })
+                .and_then(ast::FnDef::cast)?;
+            let mut builder = TextEditBuilder::default();
+            match fn_def.ret_type().and_then(|it| it.type_ref()) {
+                Some(type_ref) => builder.replace(type_ref.syntax().text_range(), ret_ty.clone()),
+                None => {
+                    let params_end = fn_def.param_list()?.syntax().text_range().end();
+                    builder.insert(params_end, format!(" -> {}", ret_ty))
+                }
+            };
+            wrap_returned_values_in_ok(&fn_def, &mut builder)?;
+            let edit = builder.finish();
+            Some(SourceChange::source_file_edit_from("wrap return type in Result", file_id, edit))
+        });
+        res.borrow_mut().push(Diagnostic {
+            range: d.highlight_range(),
+            message: d.message(),
+            severity: Severity::Error,
+            fix,
+        })
     });
     if let Some(m) = sb.to_module_def(file_id) {
         m.diagnostics(db, &mut sink);
@@ -116,6 +175,126 @@ pub(crate) fn diagnostics(db: &RootDatabase, file_id: FileId) -> Vec<Diagnostic>
     drop(sink);
     res.into_inner()
 }
+
+/// Wraps the values returned from `fn_def`, either by `return` or as the tail
+/// expression, in `Ok(..)`.
+fn wrap_returned_values_in_ok(fn_def: &ast::FnDef, builder: &mut TextEditBuilder) -> Option<()> {
+    let block = fn_def.body()?.block()?;
+    let mut return_exprs = Vec::new();
+    collect_return_exprs(block.syntax(), &mut return_exprs);
+    for return_expr in return_exprs {
+        match return_expr.expr() {
+            Some(expr) => wrap_in_ok(&expr, builder),
+            None => builder.insert(return_expr.syntax().text_range().end(), " Ok(())".to_string()),
+        }
+    }
+
+    match block.expr() {
+        Some(ast::Expr::ReturnExpr(_)) => {}
+        Some(tail) => wrap_in_ok(&tail, builder),
+        None => {
+            // The function used to return `()`, so add an explicit `Ok(())`
+            // after the last statement.
+            let last_stmt = block.statements().last()?;
+            let indent = last_stmt
+                .syntax()
+                .prev_sibling_or_token()
+                .and_then(|it| it.into_token())
+                .filter(|it| it.kind() == WHITESPACE)
+                .map_or_else(|| " ".to_string(), |it| it.text().to_string());
+            builder.insert(last_stmt.syntax().text_range().end(), format!("{}Ok(())", indent));
+        }
+    }
+    Some(())
+}
+
+/// Collects the `return` expressions in `node` which return from the enclosing
+/// function, skipping closures and nested items.
+fn collect_return_exprs(node: &SyntaxNode, acc: &mut Vec<ast::ReturnExpr>) {
+    for child in node.children() {
+        if ast::LambdaExpr::can_cast(child.kind()) || ast::ModuleItem::can_cast(child.kind()) {
+            continue;
+        }
+        if let Some(return_expr) = ast::ReturnExpr::cast(child.clone()) {
+            acc.push(return_expr);
+        }
+        collect_return_exprs(&child, acc);
+    }
+}
+
+fn wrap_in_ok(expr: &ast::Expr, builder: &mut TextEditBuilder) {
+    let range = expr.syntax().text_range();
+    builder.insert(range.start(), "Ok(".to_string());
+    builder.insert(range.end(), ")".to_string());
+}
+
+/// Fixes a path with an unresolved first segment by importing the item it
+/// should refer to, if there's exactly one candidate.
+fn auto_import(db: &RootDatabase, file_id: FileId, path: &ast::Path) -> Option<SourceChange> {
+    let first_segment = successors(Some(path.clone()), |it| it.qualifier()).last()?;
+    let mut sb = hir::SourceBinder::new(db);
+    let analyzer = sb.analyze(hir::InFile::new(file_id.into(), path.syntax()), None);
+    if analyzer.resolve_path(db, &first_segment).is_some() {
+        return None;
+    }
+    let name_ref = first_segment.segment()?.name_ref()?;
+    let import = find_import(db, analyzer.module()?, name_ref.text())?;
+
+    let position = match path.syntax().ancestors().find_map(ast::Module::cast) {
+        Some(module) => module.item_list()?.syntax().clone(),
+        None => path.syntax().ancestors().last()?,
+    };
+    let mut builder = TextEditBuilder::default();
+    insert_use_statement(&position, path.syntax(), &import, &mut builder);
+    let label = format!("import `{}`", import);
+    Some(SourceChange::source_file_edit_from(&label, file_id, builder.finish()))
+}
+
+/// Fixes `use foo::Bar;` if `Bar` actually lives elsewhere. Inside of a use
+/// group, only the leaf is rewritten, relative to the group's prefix.
+fn fix_unresolved_import(
+    db: &RootDatabase,
+    file_id: FileId,
+    use_tree: &ast::UseTree,
+) -> Option<SourceChange> {
+    let path = use_tree.path()?;
+    let name_ref = path.segment()?.name_ref()?;
+    let mut sb = hir::SourceBinder::new(db);
+    let module = sb.analyze(hir::InFile::new(file_id.into(), path.syntax()), None).module()?;
+    let import = find_import(db, module, name_ref.text())?.to_string();
+
+    let mut prefix = use_tree
+        .syntax()
+        .ancestors()
+        .skip(1)
+        .filter_map(ast::UseTree::cast)
+        .filter_map(|it| it.path())
+        .map(|it| it.syntax().text().to_string())
+        .collect::<Vec<_>>();
+    prefix.reverse();
+    let prefix = if prefix.is_empty() { String::new() } else { format!("{}::", prefix.join("::")) };
+    if !import.starts_with(&prefix) {
+        return None;
+    }
+    let replacement = &import[prefix.len()..];
+    let edit = TextEdit::replace(path.syntax().text_range(), replacement.to_string());
+    let label = format!("replace with `{}`", replacement);
+    Some(SourceChange::source_file_edit_from(&label, file_id, edit))
+}
+
+fn find_import(db: &RootDatabase, module: hir::Module, name: &str) -> Option<hir::ModPath> {
+    let candidates = ImportsLocator::new(db)
+        .find_imports(name)
+        .into_iter()
+        .filter_map(|def| module.find_use_path(db, def))
+        .filter(|it| !it.segments.is_empty())
+        .collect::<BTreeSet<_>>();
+    if candidates.len() != 1 {
+        return None;
+    }
+    candidates.into_iter().next()
+}
+
 fn location_to_range(location: Location) -> TextRange {
     match location {
         Location::Offset(offset) => TextRange::offset_len(offset, 1.into()),
@@ -207,7 +386,10 @@ mod tests {
     use ra_syntax::SourceFile;
     use test_utils::assert_eq_text;
 
-    use crate::mock_analysis::{analysis_and_position, single_file};
+    use crate::{
+        mock_analysis::{analysis_and_position, single_file, MockAnalysis},
+        AnalysisChange,
+    };
 
     use super::*;
 
@@ -335,6 +517,129 @@ mod tests {
         check_apply_diagnostic_fix_from_position(before, after);
     }
 
+    #[test]
+    fn test_try_in_non_try_function() {
+        let before = r#"
+            //- /main.rs
+            use std::{string::String, result::Result::{self, Ok, Err}};
+
+            fn parse(s: &str) -> Result<u32, String> { Ok(0) }
+
+            fn double(s: &str) -> u32 {
+                parse(s)?<|> * 2
+            }
+
+            //- /std/lib.rs
+            pub mod string {
+                pub struct String { }
+            }
+            pub mod ops {
+                #[lang = "try"]
+                pub trait Try { type Ok; type Error; }
+            }
+            pub mod result {
+                pub enum Result<T, E> { Ok(T), Err(E) }
+                impl<T, E> crate::ops::Try for Result<T, E> { type Ok = T; type Error = E; }
+            }
+        "#;
+        let after = r#"
+            use std::{string::String, result::Result::{self, Ok, Err}};
+
+            fn parse(s: &str) -> Result<u32, String> { Ok(0) }
+
+            fn double(s: &str) -> Result<u32, String> {
+                Ok(parse(s)? * 2)
+            }
+        "#;
+        check_apply_diagnostic_fix_from_position(before, after);
+    }
+
+    #[test]
+    fn test_try_in_non_try_function_wraps_returned_values() {
+        let before = r#"
+            //- /main.rs
+            use std::{string::String, result::Result::{self, Ok, Err}};
+
+            fn parse(s: &str) -> Result<u32, String> { Ok(0) }
+
+            fn check(s: &str, skip: bool) {
+                if skip {
+                    return;
+                }
+                let f = || { return 1; };
+                parse(s)?<|>;
+            }
+
+            //- /std/lib.rs
+            pub mod string {
+                pub struct String { }
+            }
+            pub mod ops {
+                #[lang = "try"]
+                pub trait Try { type Ok; type Error; }
+            }
+            pub mod result {
+                pub enum Result<T, E> { Ok(T), Err(E) }
+                impl<T, E> crate::ops::Try for Result<T, E> { type Ok = T; type Error = E; }
+            }
+        "#;
+        let after = r#"
+            use std::{string::String, result::Result::{self, Ok, Err}};
+
+            fn parse(s: &str) -> Result<u32, String> { Ok(0) }
+
+            fn check(s: &str, skip: bool) -> Result<(), String> {
+                if skip {
+                    return Ok(());
+                }
+                let f = || { return 1; };
+                parse(s)?;
+                Ok(())
+            }
+        "#;
+        check_apply_diagnostic_fix_from_position(before, after);
+    }
+
+    #[test]
+    fn test_try_checks_error_conversion() {
+        let (analysis, file_position) = analysis_and_position(
+            r#"
+            //- /main.rs
+            use std::{convert::From, result::Result::{self, Ok, Err}};
+
+            struct Io;
+            struct Other;
+            struct MyError;
+            impl From<Io> for MyError {}
+
+            fn io() -> Result<u32, Io> { Err(Io) }
+            fn other() -> Result<u32, Other> { Err(Other) }
+
+            fn same() -> Result<u32, Io> { io()?; Ok(0) }
+            fn converted() -> Result<u32, MyError> { io()?<|>; Ok(0) }
+            fn not_converted() -> Result<u32, MyError> { other()?; Ok(0) }
+            fn in_async_block() { let _ = async { io()?; other()?; Ok(0) }; }
+
+            //- /std/lib.rs
+            pub mod convert {
+                pub trait From<T> {}
+                impl<T> From<T> for T {}
+            }
+            pub mod ops {
+                #[lang = "try"]
+                pub trait Try { type Ok; type Error; }
+            }
+            pub mod result {
+                pub enum Result<T, E> { Ok(T), Err(E) }
+                impl<T, E> crate::ops::Try for Result<T, E> { type Ok = T; type Error = E; }
+            }
+            "#,
+        );
+        let diagnostics = analysis.diagnostics(file_position.file_id).unwrap();
+        let messages: Vec<_> = diagnostics.into_iter().map(|it| it.message).collect();
+        assert_eq!(messages, vec!["the trait bound `MyError: From<Other>` is not satisfied"]);
+    }
+
     #[test]
     fn test_wrap_return_type_handles_generic_functions() {
         let before = r#"
@@ -662,4 +967,262 @@ fn main() {
             check_struct_shorthand_initialization,
         );
     }
+
+    #[test]
+    fn test_unresolved_path_auto_import() {
+        let before = r"
+mod foo {
+    pub struct Bar;
+}
+
+fn main() {
+    let _ = Bar;
+}
+";
+        let after = r"
+use foo::Bar;
+
+mod foo {
+    pub struct Bar;
+}
+
+fn main() {
+    let _ = Bar;
+}
+";
+        check_apply_diagnostic_fix(before, after);
+    }
+
+    #[test]
+    fn test_unresolved_import_fix() {
+        let before = r"
+mod foo {
+    pub mod bar {
+        pub struct Baz;
+    }
+}
+
+use foo::Baz;
+";
+        let after = r"
+mod foo {
+    pub mod bar {
+        pub struct Baz;
+    }
+}
+
+use foo::bar::Baz;
+";
+        check_apply_diagnostic_fix(before, after);
+    }
+
+    #[test]
+    fn test_unresolved_import_fix_in_use_group() {
+        let before = r"
+mod foo {
+    pub struct Bar;
+    pub mod bar {
+        pub struct Baz;
+    }
+}
+
+use foo::{Bar, Baz};
+";
+        let after = r"
+mod foo {
+    pub struct Bar;
+    pub mod bar {
+        pub struct Baz;
+    }
+}
+
+use foo::{Bar, bar::Baz};
+";
+        check_apply_diagnostic_fix(before, after);
+    }
+
+    #[test]
+    fn test_unresolved_import_fix_outside_of_use_group_prefix() {
+        let (analysis, file_id) = single_file(
+            r"
+mod foo {
+    pub struct Bar;
+}
+mod baz {
+    pub struct Baz;
+}
+
+use foo::{Bar, Baz};
+",
+        );
+        let diagnostics = analysis.diagnostics(file_id).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].fix.is_none());
+    }
+
+    fn macro_errors(content: &str) -> Vec<(String, TextRange)> {
+        let (analysis, file_id) = single_file(content);
+        let diagnostics = analysis.diagnostics(file_id).unwrap();
+        diagnostics
+            .into_iter()
+            .filter(|it| it.message.starts_with("no rules expected"))
+            .map(|it| (it.message, it.range))
+            .collect()
+    }
+
+    #[test]
+    fn test_macro_no_matching_rule_in_item_position() {
+        let errors = macro_errors(
+            r"
+macro_rules! foo {
+    ($a:ident) => { struct $a; };
+}
+foo!(1);
+",
+        );
+        assert_eq!(
+            errors,
+            vec![(
+                "no rules expected this macro input: \
+                 rule #1 got furthest: expected `$a:ident`, found `1`"
+                    .to_string(),
+                TextRange::from_to(61.into(), 62.into()),
+            )]
+        );
+    }
+
+    #[test]
+    fn test_macro_no_matching_rule_in_expression() {
+        let errors = macro_errors(
+            r"
+macro_rules! id {
+    ($e:expr) => { $e };
+}
+fn main() {
+    let _ = id!();
+}
+",
+        );
+        assert_eq!(
+            errors,
+            vec![(
+                "no rules expected this macro input: \
+                 rule #1 got furthest: expected `$e:expr`, found end of input"
+                    .to_string(),
+                TextRange::from_to(70.into(), 75.into()),
+            )]
+        );
+    }
+
+    #[test]
+    fn test_macro_no_matching_rule_in_const() {
+        let errors = macro_errors(
+            r"
+macro_rules! id {
+    ($e:expr) => { $e };
+}
+const C: u32 = id!();
+",
+        );
+        assert_eq!(
+            errors,
+            vec![(
+                "no rules expected this macro input: \
+                 rule #1 got furthest: expected `$e:expr`, found end of input"
+                    .to_string(),
+                TextRange::from_to(61.into(), 66.into()),
+            )]
+        );
+    }
+
+    #[test]
+    fn test_macro_no_matching_rule_in_nested_expansion() {
+        let errors = macro_errors(
+            r"
+macro_rules! foo {
+    ($a:ident) => { struct $a; };
+}
+macro_rules! wrap {
+    () => { foo!(1); };
+}
+wrap!();
+",
+        );
+        assert_eq!(
+            errors,
+            vec![(
+                "no rules expected this macro input: \
+                 rule #1 got furthest: expected `$a:ident`, found `1`"
+                    .to_string(),
+                TextRange::from_to(102.into(), 110.into()),
+            )]
+        );
+    }
+
+    #[test]
+    fn test_recursive_macro_reports_recursion_limit() {
+        let (analysis, file_id) = single_file(
+            r#"
+#![recursion_limit = "4"]
+macro_rules! rec {
+    () => { rec!(); };
+}
+rec!();
+"#,
+        );
+        let diagnostics = analysis.diagnostics(file_id).unwrap();
+        let errors: Vec<_> = diagnostics
+            .into_iter()
+            .filter(|it| it.message.starts_with("recursion limit"))
+            .map(|it| (it.message, it.range))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![(
+                "recursion limit reached while expanding `rec!`; \
+                 consider adding `#![recursion_limit = \"8\"]` to the crate root"
+                    .to_string(),
+                TextRange::from_to(71.into(), 78.into()),
+            )]
+        );
+    }
+
+    #[test]
+    fn test_macro_in_body_reports_token_limit() {
+        let mut mock = MockAnalysis::new();
+        let file_id = mock.add_file(
+            "/lib.rs",
+            r#"
+macro_rules! many {
+    () => { (1, 2, 3, 4, 5, 6, 7, 8) };
+}
+fn f() {
+    let _ = many!();
+}
+"#,
+        );
+        let mut host = mock.analysis_host();
+        let mut crate_graph = (*host.raw_database().crate_graph()).clone();
+        let krate = crate_graph.iter().next().unwrap();
+        crate_graph.set_macro_token_limit(krate, 10);
+        let mut change = AnalysisChange::new();
+        change.set_crate_graph(crate_graph);
+        host.apply_change(change);
+
+        let diagnostics = host.analysis().diagnostics(file_id).unwrap();
+        let errors: Vec<_> = diagnostics
+            .into_iter()
+            .filter(|it| it.message.starts_with("macro expansion limit"))
+            .map(|it| (it.message, it.range))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![(
+                "macro expansion limit of 10 tokens reached while expanding `many!`; \
+                 consider raising the `rust-analyzer.macroTokenLimit` setting"
+                    .to_string(),
+                TextRange::from_to(84.into(), 91.into()),
+            )]
+        );
+    }
 }
@@ -122,7 +122,7 @@ impl FunctionSignature {
     }
 
     pub(crate) fn from_macro(db: &RootDatabase, macro_def: hir::MacroDef) -> Option<Self> {
-        let node: ast::MacroCall = macro_def.source(db).value;
+        let node: ast::Macro = macro_def.source(db).value;
 
         let params = vec![];
 
@@ -202,7 +202,6 @@ impl ToNav for FileSymbol {
 }
 
 pub(crate) trait ToNavFromAst {}
-impl ToNavFromAst for hir::Function {}
 impl ToNavFromAst for hir::Const {}
 impl ToNavFromAst for hir::Static {}
 impl ToNavFromAst for hir::Struct {}
@@ -249,6 +248,31 @@ impl ToNav for hir::Module {
     }
 }
 
+impl ToNav for hir::Function {
+    fn to_nav(&self, db: &RootDatabase) -> NavigationTarget {
+        let src = self.source(db);
+        // Derived methods don't exist in the source, show the derive instead.
+        if let Some(attr) = self.is_builtin_derive(db) {
+            let frange = original_range(db, attr.syntax());
+            return NavigationTarget::from_syntax(
+                frange.file_id,
+                self.name(db).to_string().into(),
+                None,
+                frange.range,
+                src.value.syntax().kind(),
+                None,
+                src.value.short_label(),
+            );
+        }
+        NavigationTarget::from_named(
+            db,
+            src.as_ref().map(|it| it as &dyn ast::NameOwner),
+            src.value.doc_comment_text(),
+            src.value.short_label(),
+        )
+    }
+}
+
 impl ToNav for hir::ImplBlock {
     fn to_nav(&self, db: &RootDatabase) -> NavigationTarget {
         let src = self.source(db);
@@ -362,7 +386,8 @@ impl ToNav for hir::TypeParam {
         let src = self.source(db);
         let range = match src.value {
             Either::Left(it) => it.syntax().text_range(),
-            Either::Right(it) => it.syntax().text_range(),
+            Either::Right(Either::Left(it)) => it.syntax().text_range(),
+            Either::Right(Either::Right(it)) => it.syntax().text_range(),
         };
         NavigationTarget {
             file_id: src.file_id.original_file(db),
@@ -394,6 +419,7 @@ pub(crate) fn docs_from_symbol(db: &RootDatabase, symbol: &FileSymbol) -> Option
             ast::RecordFieldDef(it) => { it.doc_comment_text() },
             ast::EnumVariant(it) => { it.doc_comment_text() },
             ast::MacroCall(it) => { it.doc_comment_text() },
+            ast::MacroDef(it) => { it.doc_comment_text() },
             _ => None,
         }
     }
@@ -4,26 +4,55 @@ use hir::db::AstDatabase;
 use ra_db::SourceDatabase;
 use ra_ide_db::RootDatabase;
 use ra_syntax::{
-    algo::{find_node_at_offset, replace_descendants},
-    ast, AstNode, NodeOrToken, SyntaxElement, SyntaxKind, SyntaxNode, WalkEvent, T,
+    algo::{find_covering_element, find_node_at_offset, replace_descendants},
+    ast, AstNode, NodeOrToken, SyntaxElement, SyntaxKind, SyntaxNode, TextRange, WalkEvent, T,
 };
 use rustc_hash::FxHashMap;
 
-use crate::FilePosition;
+use crate::{expand::original_range, FilePosition, FileRange};
+
+/// Recursive macros can expand forever, so nested calls are left unexpanded
+/// past the depth at which rustc gives up by default.
+const MAX_EXPANSION_DEPTH: usize = 128;
 
 pub struct ExpandedMacro {
     pub name: String,
     pub expansion: String,
 }
 
+/// One macro invocation in the tree of expansions rooted at the macro call
+/// under the cursor.
+#[derive(Debug)]
+pub struct MacroExpansionNode {
+    pub name: String,
+    /// The range of the invocation, mapped back to the original source.
+    pub range: FileRange,
+    /// Index of the `macro_rules!` arm which matched, if this is a
+    /// `macro_rules!` macro.
+    pub rule: Option<usize>,
+    pub bindings: Vec<MacroBinding>,
+    /// The tokens this invocation expanded to, before expanding nested calls.
+    pub expansion: String,
+    /// Macro calls which appear in `expansion`.
+    pub children: Vec<MacroExpansionNode>,
+}
+
+#[derive(Debug)]
+pub struct MacroBinding {
+    pub name: String,
+    pub fragments: hir::MetaVarFragments<FileRange>,
+}
+
 pub(crate) fn expand_macro(db: &RootDatabase, position: FilePosition) -> Option<ExpandedMacro> {
     let parse = db.parse(position.file_id);
     let file = parse.tree();
-    let name_ref = find_node_at_offset::<ast::NameRef>(file.syntax(), position.offset)?;
+    let name_ref = match find_node_at_offset::<ast::NameRef>(file.syntax(), position.offset) {
+        Some(it) => it,
+        None => return expand_derive(db, position, file.syntax()),
+    };
     let mac = name_ref.syntax().ancestors().find_map(ast::MacroCall::cast)?;
 
-    let source = hir::InFile::new(position.file_id.into(), mac.syntax());
-    let expanded = expand_macro_recur(db, source, source.with_value(&mac))?;
+    let expanded = expand_macro_recur(db, hir::InFile::new(position.file_id.into(), &mac), 0)?;
 
     // FIXME:
     // macro expansion may lose all white space information
@@ -32,22 +61,59 @@ pub(crate) fn expand_macro(db: &RootDatabase, position: FilePosition) -> Option<
     Some(ExpandedMacro { name: name_ref.text().to_string(), expansion })
 }
 
+/// Expands the builtin derive under the cursor, in a `#[derive(...)]`
+/// attribute.
+fn expand_derive(
+    db: &RootDatabase,
+    position: FilePosition,
+    file: &SyntaxNode,
+) -> Option<ExpandedMacro> {
+    let ident = file.token_at_offset(position.offset).find(|it| {
+        it.kind() == SyntaxKind::IDENT && it.parent().kind() == SyntaxKind::TOKEN_TREE
+    })?;
+    let attr = ident.parent().ancestors().find_map(ast::Attr::cast)?;
+    if attr.simple_name()? != "derive" {
+        return None;
+    }
+    let item = attr.syntax().parent().and_then(ast::ModuleItem::cast)?;
+
+    let source = hir::InFile::new(position.file_id.into(), item.syntax());
+    let analyzer = hir::SourceAnalyzer::new(db, source, None);
+    let expansion = analyzer.expand_derive(db, source.with_value(&item), &ident)?;
+    let expanded = db.parse_or_expand(expansion.file_id())?;
+    Some(ExpandedMacro { name: ident.text().to_string(), expansion: insert_whitespaces(expanded) })
+}
+
+/// Expands `macro_call`, which is `depth` expansions deep, resolving it in
+/// the file it appears in.
+fn expand_call(
+    db: &RootDatabase,
+    macro_call: hir::InFile<&ast::MacroCall>,
+    depth: usize,
+) -> Option<(hir::Expansion, SyntaxNode)> {
+    if depth > MAX_EXPANSION_DEPTH {
+        return None;
+    }
+    let analyzer = hir::SourceAnalyzer::new(db, macro_call.map(|it| it.syntax()), None);
+    let expansion = analyzer.expand(db, macro_call)?;
+    let expanded = db.parse_or_expand(expansion.file_id())?;
+    Some((expansion, expanded))
+}
+
 fn expand_macro_recur(
     db: &RootDatabase,
-    source: hir::InFile<&SyntaxNode>,
     macro_call: hir::InFile<&ast::MacroCall>,
+    depth: usize,
 ) -> Option<SyntaxNode> {
-    let analyzer = hir::SourceAnalyzer::new(db, source, None);
-    let expansion = analyzer.expand(db, macro_call)?;
+    let (expansion, mut expanded) = expand_call(db, macro_call, depth)?;
     let macro_file_id = expansion.file_id();
-    let mut expanded: SyntaxNode = db.parse_or_expand(macro_file_id)?;
 
     let children = expanded.descendants().filter_map(ast::MacroCall::cast);
     let mut replaces: FxHashMap<SyntaxElement, SyntaxElement> = FxHashMap::default();
 
     for child in children.into_iter() {
         let node = hir::InFile::new(macro_file_id, &child);
-        if let Some(new_node) = expand_macro_recur(db, source, node) {
+        if let Some(new_node) = expand_macro_recur(db, node, depth + 1) {
             // Replace the whole node if it is root
             // `replace_descendants` will not replace the parent node
             // but `SyntaxNode::descendants include itself
@@ -62,6 +128,68 @@ fn expand_macro_recur(
     Some(replace_descendants(&expanded, &|n| replaces.get(n).cloned()))
 }
 
+pub(crate) fn expand_macro_steps(
+    db: &RootDatabase,
+    position: FilePosition,
+) -> Option<MacroExpansionNode> {
+    let parse = db.parse(position.file_id);
+    let file = parse.tree();
+    let name_ref = find_node_at_offset::<ast::NameRef>(file.syntax(), position.offset)?;
+    let mac = name_ref.syntax().ancestors().find_map(ast::MacroCall::cast)?;
+
+    expansion_node(db, hir::InFile::new(position.file_id.into(), &mac), 0)
+}
+
+fn expansion_node(
+    db: &RootDatabase,
+    macro_call: hir::InFile<&ast::MacroCall>,
+    depth: usize,
+) -> Option<MacroExpansionNode> {
+    let (expansion, expanded) = expand_call(db, macro_call, depth)?;
+    let macro_file_id = expansion.file_id();
+
+    let name = macro_call.value.path()?.segment()?.name_ref()?.text().to_string();
+    let range = original_range(db, macro_call.map(|it| it.syntax()));
+    let call_file = db.parse_or_expand(macro_call.file_id)?;
+    let map_range = |range: TextRange| -> FileRange {
+        let node = match find_covering_element(&call_file, range) {
+            NodeOrToken::Node(it) => it,
+            NodeOrToken::Token(it) => it.parent(),
+        };
+        original_range(db, macro_call.with_value(&node))
+    };
+
+    let (rule, bindings) = match expansion.trace(db) {
+        Some(step) => {
+            let bindings = step
+                .bindings
+                .into_iter()
+                .map(|binding| MacroBinding {
+                    name: binding.name.to_string(),
+                    fragments: binding.fragments.map_range(&map_range),
+                })
+                .collect();
+            (Some(step.rule), bindings)
+        }
+        None => (None, Vec::new()),
+    };
+
+    let children = expanded
+        .descendants()
+        .filter_map(ast::MacroCall::cast)
+        .filter_map(|child| expansion_node(db, hir::InFile::new(macro_file_id, &child), depth + 1))
+        .collect();
+
+    Some(MacroExpansionNode {
+        name,
+        range,
+        rule,
+        bindings,
+        expansion: insert_whitespaces(expanded),
+        children,
+    })
+}
+
 // FIXME: It would also be cool to share logic here and in the mbe tests,
 // which are pretty unreadable at the moment.
 fn insert_whitespaces(syn: SyntaxNode) -> String {
@@ -289,4 +417,95 @@ fn some_thing() -> u32 {
         assert_eq!(res.name, "foo");
         assert_snapshot!(res.expansion, @r###"0"###);
     }
+
+    #[test]
+    fn macro_expand_steps() {
+        let (analysis, pos) = analysis_and_position(
+            r#"
+        //- /lib.rs
+        macro_rules! bar {
+            ($e:expr) => { $e };
+        }
+        macro_rules! foo {
+            () => { 0 };
+            ($($i:ident),*) => { bar!(1 + 1) };
+        }
+
+        fn main() {
+            let res = fo<|>o!(x, y);
+        }
+        "#,
+        );
+        let root = analysis.expand_macro_steps(pos).unwrap().unwrap();
+
+        assert_eq!(root.name, "foo");
+        assert_eq!(root.rule, Some(1));
+        assert_eq!(root.bindings.len(), 1);
+        let binding = &root.bindings[0];
+        assert_eq!(binding.name, "i");
+        let text = analysis.file_text(pos.file_id).unwrap();
+        let fragments: Vec<_> = match &binding.fragments {
+            hir::MetaVarFragments::Repeated(it) => it
+                .iter()
+                .map(|it| match it {
+                    hir::MetaVarFragments::Fragment(fragment, range) => {
+                        (fragment.as_str(), &text[range.unwrap().range])
+                    }
+                    it => panic!("expected a fragment, got {:?}", it),
+                })
+                .collect(),
+            it => panic!("expected a repetition, got {:?}", it),
+        };
+        assert_eq!(fragments, vec![("x", "x"), ("y", "y")]);
+
+        assert_eq!(root.children.len(), 1);
+        let child = &root.children[0];
+        assert_eq!(child.name, "bar");
+        assert_eq!(child.rule, Some(0));
+        match &child.bindings[0].fragments {
+            hir::MetaVarFragments::Fragment(fragment, _) => assert_eq!(fragment, "1 + 1"),
+            it => panic!("expected a fragment, got {:?}", it),
+        }
+        assert_snapshot!(child.expansion, @r###"1+1"###);
+    }
+
+    #[test]
+    fn macro_expand_stops_at_max_depth() {
+        let (analysis, pos) = analysis_and_position(
+            r#"
+        //- /lib.rs
+        macro_rules! forever {
+            () => { forever!() };
+        }
+
+        fn main() {
+            let res = fore<|>ver!();
+        }
+        "#,
+        );
+        assert!(analysis.expand_macro(pos).unwrap().is_some());
+
+        let mut node = analysis.expand_macro_steps(pos).unwrap().unwrap();
+        let mut depth = 0;
+        while let Some(child) = node.children.pop() {
+            node = child;
+            depth += 1;
+        }
+        assert_eq!(depth, MAX_EXPANSION_DEPTH);
+    }
+
+    #[test]
+    fn macro_expand_derive() {
+        let res = check_expand_macro(
+            r#"
+        //- /lib.rs
+        #[derive(Debug, Clo<|>ne)]
+        struct Foo { a: u32 }
+        "#,
+        );
+
+        assert_eq!(res.name, "Clone");
+        assert!(res.expansion.starts_with("impl std::clone::Clone for Foo {"), "{}", res.expansion);
+        assert!(res.expansion.contains("fn clone(&self) -> Self"), "{}", res.expansion);
+    }
 }
@@ -0,0 +1,196 @@
+//! Explains how the method of a method call is looked up: which receiver
+//! types are tried after autoderef and autoref, and which methods are
+//! considered for each of them.
+
+use hir::{CandidateStatus, HirDisplay, Mutability};
+use ra_db::SourceDatabase;
+use ra_ide_db::RootDatabase;
+use ra_syntax::{algo::find_node_at_offset, ast, AstNode};
+
+use crate::{
+    display::{FunctionSignature, ToNav},
+    FilePosition, NavigationTarget,
+};
+
+#[derive(Debug)]
+pub struct MethodLookupExplanation {
+    /// The name of the method being called.
+    pub name: String,
+    pub steps: Vec<MethodLookupStep>,
+}
+
+#[derive(Debug)]
+pub struct MethodLookupStep {
+    /// The receiver type tried at this step, e.g. `&Vec<u32>`.
+    pub receiver_ty: String,
+    /// How `receiver_ty` was derived from the receiver expression's type,
+    /// e.g. `2 derefs, then &`.
+    pub adjustment: String,
+    pub candidates: Vec<MethodLookupCandidate>,
+}
+
+#[derive(Debug)]
+pub struct MethodLookupCandidate {
+    pub signature: String,
+    pub target: NavigationTarget,
+    /// Why the candidate was or wasn't picked.
+    pub status: String,
+    /// For a method of a trait which isn't implemented, the impls of the trait
+    /// which were considered for the receiver type.
+    pub impls: Vec<NavigationTarget>,
+}
+
+pub(crate) fn explain_method_resolution(
+    db: &RootDatabase,
+    position: FilePosition,
+) -> Option<MethodLookupExplanation> {
+    let parse = db.parse(position.file_id);
+    let call = find_node_at_offset::<ast::MethodCallExpr>(parse.tree().syntax(), position.offset)?;
+    let name = call.name_ref()?.text().to_string();
+
+    let src = hir::InFile::new(position.file_id.into(), call.syntax());
+    let analyzer = hir::SourceAnalyzer::new(db, src, None);
+    let trace = analyzer.explain_method_call(db, &call)?;
+
+    let steps = trace
+        .steps
+        .into_iter()
+        .map(|step| {
+            let derefs = match step.autoderefs {
+                0 => "no deref".to_string(),
+                1 => "1 deref".to_string(),
+                n => format!("{} derefs", n),
+            };
+            let adjustment = match step.autoref {
+                None => derefs,
+                Some(Mutability::Shared) => format!("{}, then &", derefs),
+                Some(Mutability::Mut) => format!("{}, then &mut", derefs),
+            };
+            let candidates = step
+                .candidates
+                .into_iter()
+                .map(|(func, status)| {
+                    let (status, impls) = match status {
+                        CandidateStatus::Picked => ("picked".to_string(), Vec::new()),
+                        CandidateStatus::ReceiverMismatch => {
+                            ("receiver type doesn't match".to_string(), Vec::new())
+                        }
+                        CandidateStatus::TraitNotImplemented { unsatisfied_bound, impls } => (
+                            format!(
+                                "the trait bound `{}` is not satisfied",
+                                unsatisfied_bound.display(db)
+                            ),
+                            impls.into_iter().map(|it| it.to_nav(db)).collect(),
+                        ),
+                    };
+                    MethodLookupCandidate {
+                        signature: FunctionSignature::from_hir(db, func).to_string(),
+                        target: func.to_nav(db),
+                        status,
+                        impls,
+                    }
+                })
+                .collect();
+            MethodLookupStep {
+                receiver_ty: step.receiver_ty.display(db).to_string(),
+                adjustment,
+                candidates,
+            }
+        })
+        .collect();
+    Some(MethodLookupExplanation { name, steps })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock_analysis::analysis_and_position;
+
+    fn check_explanation(fixture: &str, expected: &[&str]) {
+        let (analysis, pos) = analysis_and_position(fixture);
+        let explanation = analysis.explain_method_resolution(pos).unwrap().unwrap();
+        let actual: Vec<String> = explanation
+            .steps
+            .iter()
+            .map(|step| {
+                // trait candidates come in no particular order
+                let mut candidates: Vec<String> = step
+                    .candidates
+                    .iter()
+                    .map(|it| {
+                        let impls: Vec<_> =
+                            it.impls.iter().map(|it| format!("{:?}", it.full_range())).collect();
+                        if impls.is_empty() {
+                            format!("{}: {}", it.signature, it.status)
+                        } else {
+                            format!("{}: {} (impls: {})", it.signature, it.status, impls.join(", "))
+                        }
+                    })
+                    .collect();
+                candidates.sort();
+                format!("{} ({}): [{}]", step.receiver_ty, step.adjustment, candidates.join("; "))
+            })
+            .collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn explains_autoref_and_trait_candidates() {
+        check_explanation(
+            r#"
+            //- /lib.rs
+            struct S;
+            trait Foo { fn foo(&self); }
+            trait Bar { fn foo(self); }
+            impl Foo for S { fn foo(&self) {} }
+            fn test(s: S) {
+                s.foo<|>();
+            }
+            "#,
+            &[
+                "S (no deref): [fn foo(&self): receiver type doesn't match; fn foo(self): the trait bound `S: Bar` is not satisfied]",
+                "&S (no deref, then &): [fn foo(&self): picked; fn foo(self): the trait bound `&S: Bar` is not satisfied]",
+            ],
+        );
+    }
+
+    #[test]
+    fn explains_unsatisfied_impl_bounds() {
+        check_explanation(
+            r#"
+            //- /lib.rs
+            struct S;
+            struct Wrapper<T>(T);
+            trait Foo { fn foo(&self); }
+            impl<T: Foo> Foo for Wrapper<T> { fn foo(&self) {} }
+            fn test(w: Wrapper<S>) {
+                w.foo<|>();
+            }
+            "#,
+            &[
+                "Wrapper<S> (no deref): [fn foo(&self): the trait bound `S: Foo` is not satisfied (impls: [61; 113))]",
+                "&Wrapper<S> (no deref, then &): [fn foo(&self): the trait bound `&Wrapper<S>: Foo` is not satisfied]",
+                "&mut Wrapper<S> (no deref, then &mut): [fn foo(&self): the trait bound `&mut Wrapper<S>: Foo` is not satisfied]",
+            ],
+        );
+    }
+
+    #[test]
+    fn explains_autoderef() {
+        check_explanation(
+            r#"
+            //- /lib.rs
+            struct S;
+            impl S { fn len(&self) -> usize { 0 } }
+            fn test(s: &&S) {
+                s.len<|>();
+            }
+            "#,
+            &[
+                "&&S (no deref): [fn len(&self) -> usize: receiver type doesn't match]",
+                "&&&S (no deref, then &): [fn len(&self) -> usize: receiver type doesn't match]",
+                "&mut &&S (no deref, then &mut): [fn len(&self) -> usize: receiver type doesn't match]",
+                "&S (1 deref): [fn len(&self) -> usize: picked]",
+            ],
+        );
+    }
+}
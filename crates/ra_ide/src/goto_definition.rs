@@ -12,8 +12,8 @@ use ra_syntax::{
 use crate::{
     display::{ShortLabel, ToNav},
     expand::descend_into_macros,
-    references::{classify_name_ref, NameKind::*},
-    FilePosition, NavigationTarget, RangeInfo,
+    references::{classify_name_ref, descend_from_macro_definition, NameKind::*},
+    FileId, FilePosition, NavigationTarget, RangeInfo,
 };
 
 pub(crate) fn goto_definition(
@@ -22,9 +22,17 @@ pub(crate) fn goto_definition(
 ) -> Option<RangeInfo<Vec<NavigationTarget>>> {
     let file = db.parse_or_expand(position.file_id.into())?;
     let original_token = pick_best(file.token_at_offset(position.offset))?;
-    let token = descend_into_macros(db, position.file_id, original_token.clone());
-
     let mut sb = SourceBinder::new(db);
+
+    if original_token.kind() == IDENT {
+        let nav_targets = derive_definition(&mut sb, position.file_id, &original_token)
+            .or_else(|| macro_definition_targets(&mut sb, position.file_id, &original_token));
+        if let Some(nav_targets) = nav_targets {
+            return Some(RangeInfo::new(original_token.text_range(), nav_targets));
+        }
+    }
+
+    let token = descend_into_macros(db, position.file_id, original_token.clone());
     let nav_targets = match_ast! {
         match (token.value.parent()) {
             ast::NameRef(name_ref) => {
@@ -33,6 +41,9 @@ pub(crate) fn goto_definition(
             ast::Name(name) => {
                 name_definition(&mut sb, token.with_value(&name))?
             },
+            ast::BinExpr(bin_expr) => {
+                bin_op_definition(&mut sb, token.with_value(&bin_expr), &token.value)?
+            },
             _ => return None,
         }
     };
@@ -51,6 +62,62 @@ fn pick_best(tokens: TokenAtOffset<SyntaxToken>) -> Option<SyntaxToken> {
     }
 }
 
+/// Goes to the trait named in a `#[derive]` attribute, by resolving the trait
+/// path of the derived impl.
+fn derive_definition(
+    sb: &mut SourceBinder<RootDatabase>,
+    file_id: FileId,
+    token: &SyntaxToken,
+) -> Option<Vec<NavigationTarget>> {
+    if token.parent().kind() != TOKEN_TREE {
+        return None;
+    }
+    let attr = token.parent().ancestors().find_map(ast::Attr::cast)?;
+    if attr.simple_name()? != "derive" {
+        return None;
+    }
+    let item = attr.syntax().parent().and_then(ast::ModuleItem::cast)?;
+
+    let source = InFile::new(file_id.into(), item.syntax());
+    let analyzer = sb.analyze(source, None);
+    let expansion = analyzer.expand_derive(sb.db, source.with_value(&item), token)?;
+    let expanded = sb.db.parse_or_expand(expansion.file_id())?;
+    let impl_block = expanded.descendants().find_map(ast::ImplBlock::cast)?;
+    let trait_path = match impl_block.target_trait()? {
+        ast::TypeRef::PathType(it) => it.path()?,
+        _ => return None,
+    };
+    let name_ref = trait_path.segment()?.name_ref()?;
+    match reference_definition(sb, InFile::new(expansion.file_id(), &name_ref)) {
+        ReferenceResult::Exact(nav) => Some(vec![nav]),
+        ReferenceResult::Approximate(_) => None,
+    }
+}
+
+/// Goes to the definitions an identifier in a macro definition body refers
+/// to, in the expansions of the calls of that macro. This also covers paths
+/// pieced together from the call arguments and the body.
+fn macro_definition_targets(
+    sb: &mut SourceBinder<RootDatabase>,
+    file_id: FileId,
+    token: &SyntaxToken,
+) -> Option<Vec<NavigationTarget>> {
+    let tokens = descend_from_macro_definition(sb.db, file_id, token)?;
+    let mut nav_targets = Vec::new();
+    for token in tokens {
+        let name_ref = match ast::NameRef::cast(token.value.parent()) {
+            Some(it) => it,
+            None => continue,
+        };
+        if let ReferenceResult::Exact(nav) = reference_definition(sb, token.with_value(&name_ref)) {
+            if !nav_targets.contains(&nav) {
+                nav_targets.push(nav);
+            }
+        }
+    }
+    Some(nav_targets)
+}
+
 #[derive(Debug)]
 pub(crate) enum ReferenceResult {
     Exact(NavigationTarget),
@@ -73,6 +140,10 @@ pub(crate) fn reference_definition(
 ) -> ReferenceResult {
     use self::ReferenceResult::*;
 
+    if let Some(nav) = impl_const_definition(sb, name_ref) {
+        return Exact(nav);
+    }
+
     let name_kind = classify_name_ref(sb, name_ref).map(|d| d.kind);
     match name_kind {
         Some(Macro(it)) => return Exact(it.to_nav(sb.db)),
@@ -100,6 +171,36 @@ pub(crate) fn reference_definition(
     Approximate(navs)
 }
 
+/// Paths to a trait's associated const resolve to the trait's const, but go to
+/// the value in the impl if it's known which impl is used.
+fn impl_const_definition(
+    sb: &mut SourceBinder<RootDatabase>,
+    name_ref: InFile<&ast::NameRef>,
+) -> Option<NavigationTarget> {
+    let segment = name_ref.value.syntax().parent().and_then(ast::PathSegment::cast)?;
+    let path = segment.parent_path();
+    if path.parent_path().is_some() {
+        return None;
+    }
+    let analyzer = sb.analyze(name_ref.map(|it| it.syntax()), None);
+    let konst = analyzer.resolve_path_to_impl_const(&path)?;
+    NavigationTarget::from_def(sb.db, hir::ModuleDef::Const(konst))
+}
+
+fn bin_op_definition(
+    sb: &mut SourceBinder<RootDatabase>,
+    bin_expr: InFile<&ast::BinExpr>,
+    token: &SyntaxToken,
+) -> Option<Vec<NavigationTarget>> {
+    let (op_token, _) = bin_expr.value.op_details()?;
+    if &op_token != token {
+        return None;
+    }
+    let analyzer = sb.analyze(bin_expr.map(|it| it.syntax()), None);
+    let func = analyzer.resolve_bin_expr(sb.db, bin_expr.value)?;
+    Some(vec![func.to_nav(sb.db)])
+}
+
 fn name_definition(
     sb: &mut SourceBinder<RootDatabase>,
     name: InFile<&ast::Name>,
@@ -480,6 +581,131 @@ mod tests {
         );
     }
 
+    #[test]
+    fn goto_def_for_overloaded_binary_operator() {
+        check_goto(
+            r#"
+            //- /main.rs crate:main deps:std
+            struct Vector;
+            impl std::ops::Add for Vector {
+                type Output = Vector;
+                fn add(self, rhs: Vector) -> Vector { self }
+            }
+
+            fn bar(a: Vector, b: Vector) {
+                a <|>+ b;
+            }
+
+            //- /std.rs crate:std
+            pub mod ops {
+                #[lang = "add"]
+                pub trait Add<Rhs = Self> {
+                    type Output;
+                    fn add(self, rhs: Rhs) -> Self::Output;
+                }
+            }
+            "#,
+            "add FN_DEF FileId(1) [77; 121) [80; 83)",
+            "fn add(self, rhs: Vector) -> Vector { self }|add",
+        );
+    }
+
+    #[test]
+    fn goto_def_for_compound_assignment_picks_impl_by_rhs() {
+        check_goto(
+            r#"
+            //- /main.rs crate:main deps:std
+            struct Vector;
+            impl std::ops::AddAssign for Vector {
+                fn add_assign(&mut self, rhs: Vector) {}
+            }
+            impl std::ops::AddAssign<u64> for Vector {
+                fn add_assign(&mut self, rhs: u64) {}
+            }
+
+            fn bar(mut a: Vector) {
+                a <|>+= 1u64;
+            }
+
+            //- /std.rs crate:std
+            pub mod ops {
+                #[lang = "add_assign"]
+                pub trait AddAssign<Rhs = Self> {
+                    fn add_assign(&mut self, rhs: Rhs);
+                }
+            }
+            "#,
+            "add_assign FN_DEF FileId(1) [147; 184) [150; 160)",
+            "fn add_assign(&mut self, rhs: u64) {}|add_assign",
+        );
+    }
+
+    #[test]
+    fn goto_def_for_derived_methods() {
+        check_goto(
+            "
+            //- /main.rs
+            use std::clone::Clone;
+
+            #[derive(Clone)]
+            struct Foo;
+
+            fn bar(foo: &Foo) {
+                foo.clo<|>ne();
+            }
+
+            //- /std/lib.rs
+            pub mod clone {
+                pub trait Clone {
+                    fn clone(&self) -> Self;
+                }
+            }
+            ",
+            "clone FN_DEF FileId(1) [24; 40)",
+            "#[derive(Clone)]",
+        );
+    }
+
+    #[test]
+    fn goto_def_for_derive() {
+        check_goto(
+            "
+            //- /main.rs
+            #[derive(Clo<|>ne)]
+            struct Foo;
+
+            //- /std/lib.rs
+            pub mod clone {
+                pub trait Clone {
+                    fn clone(&self) -> Self;
+                }
+            }
+            ",
+            "Clone TRAIT_DEF FileId(2) [20; 76) [30; 35)",
+            "pub trait Clone {...}|Clone",
+        );
+    }
+
+    #[test]
+    fn goto_def_from_macro_definition() {
+        check_goto(
+            "
+            //- /lib.rs
+            mod foo {
+                pub fn spam() {}
+            }
+            macro_rules! call_spam {
+                ($m:ident) => { $m::sp<|>am() };
+            }
+            fn bar() {
+                call_spam!(foo);
+            }
+            ",
+            "spam FN_DEF FileId(1) [14; 30) [21; 25)",
+            "pub fn spam() {}|spam",
+        );
+    }
+
     #[test]
     fn goto_def_for_fields() {
         covers!(goto_def_for_fields);
@@ -594,6 +820,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn goto_def_for_assoc_const_resolves_to_impl() {
+        check_goto(
+            "
+            //- /lib.rs
+            trait Trait {
+                const ID: u32;
+            }
+            struct Foo;
+            impl Trait for Foo {
+                const ID: u32 = 92;
+            }
+
+            fn bar() {
+                Foo::ID<|>;
+            }
+            ",
+            "ID CONST_DEF FileId(1) [72; 91) [78; 80)",
+            "const ID: u32 = 92;|ID",
+        );
+    }
+
     #[test]
     fn goto_definition_on_self() {
         check_goto(
@@ -854,6 +1102,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn goto_for_const_param_in_value_position() {
+        check_goto(
+            "
+            //- /lib.rs
+            fn foo<const N: usize>() -> usize { <|>N }
+            ",
+            "N TYPE_PARAM FileId(1) [7; 21)",
+            "const N: usize",
+        );
+    }
+
     #[test]
     fn goto_within_macro() {
         check_goto(
@@ -12,7 +12,7 @@ use ra_syntax::{
 };
 
 use crate::{
-    display::{macro_label, rust_code_markup, rust_code_markup_with_doc, ShortLabel},
+    display::{macro_label, rust_code_markup_with_doc, ShortLabel},
     expand::descend_into_macros,
     references::{classify_name, classify_name_ref, NameKind, NameKind::*},
     FilePosition, FileRange, RangeInfo,
@@ -112,7 +112,13 @@ fn hover_text_from_name_kind(db: &RootDatabase, name_kind: NameKind) -> Option<S
                 }
                 _ => None,
             },
-            hir::ModuleDef::Function(it) => from_def_source(db, it),
+            hir::ModuleDef::Function(it) => match it.is_builtin_derive(db) {
+                Some(attr) => {
+                    let docs = format!("Automatically derived with `{}`", attr.value.syntax());
+                    hover_text(Some(docs), it.source(db).value.short_label())
+                }
+                None => from_def_source(db, it),
+            },
             hir::ModuleDef::Adt(Adt::Struct(it)) => from_def_source(db, it),
             hir::ModuleDef::Adt(Adt::Union(it)) => from_def_source(db, it),
             hir::ModuleDef::Adt(Adt::Enum(it)) => from_def_source(db, it),
@@ -123,7 +129,7 @@ fn hover_text_from_name_kind(db: &RootDatabase, name_kind: NameKind) -> Option<S
             hir::ModuleDef::TypeAlias(it) => from_def_source(db, it),
             hir::ModuleDef::BuiltinType(it) => Some(it.to_string()),
         },
-        Local(it) => Some(rust_code_markup(it.ty(db).display_truncated(db, None).to_string())),
+        Local(it) => Some(type_markup(db, &it.ty(db))),
         TypeParam(_) | SelfType(_) => {
             // FIXME: Hover for generic param
             None
@@ -173,7 +179,7 @@ pub(crate) fn hover(db: &RootDatabase, position: FilePosition) -> Option<RangeIn
 
     // The following logic will not work if token is coming from a macro
     let frange = FileRange { file_id: position.file_id, range: node.text_range() };
-    res.extend(type_of(db, frange).map(rust_code_markup));
+    res.extend(type_at(db, frange).map(|ty| type_markup(db, &ty)));
     if res.is_empty() {
         return None;
     }
@@ -182,6 +188,15 @@ pub(crate) fn hover(db: &RootDatabase, position: FilePosition) -> Option<RangeIn
     Some(RangeInfo::new(range, res))
 }
 
+fn type_markup(db: &RootDatabase, ty: &hir::Type) -> String {
+    // for `impl Trait`s, also reveal the type behind them, if we know it
+    let hidden = ty
+        .hidden_type(db)
+        .filter(|it| !it.is_unknown())
+        .map(|it| format!("Hidden type: `{}`", it.display_truncated(db, None)));
+    rust_code_markup_with_doc(ty.display_truncated(db, None).to_string(), hidden)
+}
+
 fn pick_best(tokens: TokenAtOffset<SyntaxToken>) -> Option<SyntaxToken> {
     return tokens.max_by_key(priority);
     fn priority(n: &SyntaxToken) -> usize {
@@ -195,6 +210,11 @@ fn pick_best(tokens: TokenAtOffset<SyntaxToken>) -> Option<SyntaxToken> {
 }
 
 pub(crate) fn type_of(db: &RootDatabase, frange: FileRange) -> Option<String> {
+    let ty = type_at(db, frange)?;
+    Some(ty.display_truncated(db, None).to_string())
+}
+
+fn type_at(db: &RootDatabase, frange: FileRange) -> Option<hir::Type> {
     let parse = db.parse(frange.file_id);
     let leaf_node = find_covering_element(parse.tree().syntax(), frange.range);
     // if we picked identifier, expand to pattern/expression
@@ -212,7 +232,7 @@ pub(crate) fn type_of(db: &RootDatabase, frange: FileRange) -> Option<String> {
     } else {
         return None;
     };
-    Some(ty.display_truncated(db, None).to_string())
+    Some(ty)
 }
 
 #[cfg(test)]
@@ -403,6 +423,38 @@ fn main() {
         );
     }
 
+    #[test]
+    fn hover_shows_lifetimes() {
+        check_hover_result(
+            r#"
+//- /main.rs
+struct Foo<'a, T> {
+    t: &'a T,
+}
+
+fn foo<'a>(foo: &'a Foo<'a, u32>) {
+    let zz<|> = foo;
+}"#,
+            &["&'a Foo<'a, u32>"],
+        );
+    }
+
+    #[test]
+    fn hover_shows_const_generic_args() {
+        check_hover_result(
+            r#"
+//- /main.rs
+struct Buf<const N: usize> {}
+
+fn make() -> Buf<16> { loop {} }
+
+fn main() {
+    let buf<|> = make();
+}"#,
+            &["Buf<16>"],
+        );
+    }
+
     #[test]
     fn hover_some() {
         let (analysis, position) = single_file_with_position(
@@ -432,6 +484,40 @@ fn main() {
         assert_eq!(trim_markup_opt(hover.info.first()), Some("Option<i32>"));
     }
 
+    #[test]
+    fn hover_impl_trait_shows_hidden_type() {
+        check_hover_result(
+            r#"
+            //- /main.rs
+            trait Trait {}
+            struct S;
+            impl Trait for S {}
+            fn foo() -> impl Trait { S }
+
+            fn main() {
+                let f<|>oo = foo();
+            }
+            "#,
+            &["impl Trait\n```\n\nHidden type: `S`"],
+        );
+    }
+
+    #[test]
+    fn hover_impl_trait_hides_unknown_hidden_type() {
+        check_hover_result(
+            r#"
+            //- /main.rs
+            trait Trait {}
+            fn foo() -> impl Trait { missing() }
+
+            fn main() {
+                let f<|>oo = foo();
+            }
+            "#,
+            &["impl Trait"],
+        );
+    }
+
     #[test]
     fn hover_enum_variant() {
         check_hover_result(
@@ -601,6 +687,49 @@ fn func(foo: i32) { if true { <|>foo; }; }
         assert_eq!(hover.info.is_exact(), true);
     }
 
+    #[test]
+    fn test_hover_trait_assoc_const() {
+        let (analysis, position) = single_file_with_position(
+            "
+            trait Trait {
+                /// The id.
+                const ID: u32;
+            }
+            struct Foo;
+            impl Trait for Foo {
+                const ID: u32 = 92;
+            }
+
+            fn main() {
+                let _ = Foo::ID<|>;
+            }
+            ",
+        );
+        let hover = analysis.hover(position).unwrap().unwrap();
+        assert_eq!(trim_markup_opt(hover.info.first()), Some("const ID: u32\n```\n\nThe id."));
+        assert_eq!(hover.info.is_exact(), true);
+    }
+
+    #[test]
+    fn test_hover_trait_assoc_const_default() {
+        let (analysis, position) = single_file_with_position(
+            "
+            trait Trait {
+                const ID: u32 = 0;
+            }
+            struct Foo;
+            impl Trait for Foo {}
+
+            fn main() {
+                let _ = <Foo as Trait>::ID<|>;
+            }
+            ",
+        );
+        let hover = analysis.hover(position).unwrap().unwrap();
+        assert_eq!(trim_markup_opt(hover.info.first()), Some("const ID: u32"));
+        assert_eq!(hover.info.is_exact(), true);
+    }
+
     #[test]
     fn test_hover_self() {
         let (analysis, position) = single_file_with_position(
@@ -743,4 +872,32 @@ fn func(foo: i32) { if true { <|>foo; }; }
             &["u32"],
         );
     }
+
+    #[test]
+    fn test_hover_derived_method() {
+        let (analysis, position) = analysis_and_position(
+            "
+            //- /main.rs
+            use std::clone::Clone;
+
+            #[derive(Clone)]
+            struct Foo;
+
+            fn bar(foo: &Foo) {
+                foo.clo<|>ne();
+            }
+
+            //- /std/lib.rs
+            pub mod clone {
+                pub trait Clone {
+                    fn clone(&self) -> Self;
+                }
+            }
+            ",
+        );
+        let hover = analysis.hover(position).unwrap().unwrap();
+        let text = hover.info.first().unwrap();
+        assert!(text.contains("fn clone(&self) -> Self"), "{}", text);
+        assert!(text.ends_with("Automatically derived with `#[derive(Clone)]`"), "{}", text);
+    }
 }
@@ -268,6 +268,31 @@ fn main() {
         );
     }
 
+    #[test]
+    fn const_generic_args_are_displayed() {
+        let (analysis, file_id) = single_file(
+            r#"
+struct Buf<const N: usize> {}
+
+fn make() -> Buf<16> { loop {} }
+
+fn main() {
+    let buf = make();
+}"#,
+        );
+
+        assert_debug_snapshot!(analysis.inlay_hints(file_id, None).unwrap(), @r###"
+        [
+            InlayHint {
+                range: [86; 89),
+                kind: TypeHint,
+                label: "Buf<16>",
+            },
+        ]
+        "###
+        );
+    }
+
     #[test]
     fn let_statement() {
         let (analysis, file_id) = single_file(
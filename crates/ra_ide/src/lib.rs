@@ -28,6 +28,7 @@ mod references;
 mod impls;
 mod assists;
 mod diagnostics;
+mod unused;
 mod syntax_tree;
 mod folding_ranges;
 mod join_lines;
@@ -37,6 +38,7 @@ mod display;
 mod inlay_hints;
 mod expand;
 mod expand_macro;
+mod explain_method;
 
 #[cfg(test)]
 mod marks;
@@ -64,25 +66,28 @@ pub use crate::{
     completion::{CompletionItem, CompletionItemKind, InsertTextFormat},
     diagnostics::Severity,
     display::{file_structure, FunctionSignature, NavigationTarget, StructureNode},
-    expand_macro::ExpandedMacro,
+    expand_macro::{ExpandedMacro, MacroBinding, MacroExpansionNode},
+    explain_method::{MethodLookupCandidate, MethodLookupExplanation, MethodLookupStep},
     folding_ranges::{Fold, FoldKind},
     hover::HoverResult,
     inlay_hints::{InlayHint, InlayKind},
     references::{
-        Declaration, Reference, ReferenceAccess, ReferenceKind, ReferenceSearchResult, SearchScope,
+        Declaration, Reference, ReferenceAccess, ReferenceKind, ReferenceSearchResult, RenameError,
+        SearchScope,
     },
     runnables::{Runnable, RunnableKind},
     source_change::{FileSystemEdit, SourceChange, SourceFileEdit},
     syntax_highlighting::HighlightedRange,
 };
 
-pub use hir::Documentation;
+pub use hir::{Documentation, MetaVarFragments};
 pub use ra_db::{
     Canceled, CrateGraph, CrateId, Edition, FileId, FilePosition, FileRange, SourceRootId,
 };
 pub use ra_ide_db::{
     change::{AnalysisChange, LibraryData},
     feature_flags::FeatureFlags,
+    library_cache::{CacheEntry, LibraryCache},
     line_index::{LineCol, LineIndex},
     line_index_utils::translate_offset_with_edit,
     symbol_index::Query,
@@ -270,6 +275,24 @@ impl Analysis {
         self.with_db(|db| expand_macro::expand_macro(db, position))
     }
 
+    /// Returns the tree of expansions of the macro call under the cursor,
+    /// along with the `macro_rules!` arm and bindings used at each step.
+    pub fn expand_macro_steps(
+        &self,
+        position: FilePosition,
+    ) -> Cancelable<Option<MacroExpansionNode>> {
+        self.with_db(|db| expand_macro::expand_macro_steps(db, position))
+    }
+
+    /// Explains how the method of the method call under the cursor is looked
+    /// up, listing each receiver type tried and the methods considered for it.
+    pub fn explain_method_resolution(
+        &self,
+        position: FilePosition,
+    ) -> Cancelable<Option<MethodLookupExplanation>> {
+        self.with_db(|db| explain_method::explain_method_resolution(db, position))
+    }
+
     /// Returns an edit to remove all newlines in the range, cleaning up minor
     /// stuff like trailing commas.
     pub fn join_lines(&self, frange: FileRange) -> Cancelable<SourceChange> {
@@ -449,18 +472,24 @@ impl Analysis {
         self.with_db(|db| diagnostics::diagnostics(db, file_id))
     }
 
+    /// Computes diagnostics for the items and imports declared in the given
+    /// file which are never used.
+    pub fn unused(&self, file_id: FileId) -> Cancelable<Vec<Diagnostic>> {
+        self.with_db(|db| unused::unused(db, file_id))
+    }
+
     /// Computes the type of the expression at the given position.
     pub fn type_of(&self, frange: FileRange) -> Cancelable<Option<String>> {
         self.with_db(|db| hover::type_of(db, frange))
     }
 
     /// Returns the edit required to rename reference at the position to the new
-    /// name.
+    /// name, or an error if the rename can't be done safely.
     pub fn rename(
         &self,
         position: FilePosition,
         new_name: &str,
-    ) -> Cancelable<Option<RangeInfo<SourceChange>>> {
+    ) -> Cancelable<Result<Option<RangeInfo<SourceChange>>, RenameError>> {
         self.with_db(|db| references::rename(db, position, new_name))
     }
 
@@ -547,6 +576,77 @@ struct Foo;
         assert_eq!(struct_match, Some(STRUCT_DEF));
     }
 
+    #[test]
+    fn test_world_symbols_in_impl_blocks_use_self_type_as_container() {
+        let code = r#"
+struct Vec<T> {}
+impl<T> Vec<T> {
+    fn push(&mut self, item: T) {}
+}
+struct Stack {}
+impl Stack {
+    fn push(&mut self) {}
+}
+        "#;
+
+        let symbols = get_symbols_matching(code, "push");
+        let mut containers: Vec<_> =
+            symbols.iter().map(|s| s.container_name().unwrap().clone()).collect();
+        containers.sort();
+        assert_eq!(containers, vec![SmolStr::new("Stack"), SmolStr::new("Vec")]);
+
+        let symbols = get_symbols_matching(code, "Vec::push");
+        assert_eq!(symbols.len(), 1);
+        assert_eq!(symbols[0].name(), "push");
+        assert_eq!(symbols[0].container_name(), Some(&SmolStr::new("Vec")));
+    }
+
+    #[test]
+    fn test_world_symbols_are_ranked() {
+        let code = r#"
+struct HashSetMap;
+struct Hash_map;
+struct HashMap;
+struct HashMapExt;
+        "#;
+
+        let symbols = get_symbols_matching(code, "HashMap");
+        let names: Vec<_> = symbols.iter().map(|s| s.name().as_str()).collect();
+        assert_eq!(names, vec!["HashMap", "HashMapExt", "Hash_map", "HashSetMap"]);
+    }
+
+    #[test]
+    fn test_world_symbols_keep_best_matches_within_limit() {
+        let code = r#"
+struct HashSetMap;
+struct Hash_map;
+struct HashMap;
+struct HashMapExt;
+        "#;
+
+        let (analysis, _) = single_file(code);
+        let mut query = Query::new("HashMap".into());
+        query.limit(2);
+        let symbols = analysis.symbol_search(query).unwrap();
+        let names: Vec<_> = symbols.iter().map(|s| s.name().as_str()).collect();
+        assert_eq!(names, vec!["HashMap", "HashMapExt"]);
+    }
+
+    #[test]
+    fn test_world_symbols_only_functions() {
+        let code = r#"
+struct Foo;
+fn foo() {}
+        "#;
+
+        let (analysis, _) = single_file(code);
+        let mut query = Query::new("foo".into());
+        query.only_functions();
+        let symbols = analysis.symbol_search(query).unwrap();
+        let kinds: Vec<_> = symbols.iter().map(|s| s.kind()).collect();
+        assert_eq!(kinds, vec![FN_DEF]);
+    }
+
     fn get_symbols_matching(text: &str, query: &str) -> Vec<NavigationTarget> {
         let (analysis, _) = single_file(text);
         analysis.symbol_search(Query::new(query.into())).unwrap()
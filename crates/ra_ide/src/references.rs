@@ -8,12 +8,16 @@
 //! for text occurrences of the identifier. If there's an `ast::NameRef`
 //! at the index that the match starts at and its tree parent is
 //! resolved to the search element definition, we get a reference.
+//! Identifiers inside macro call arguments are mapped into the expansion
+//! first, and resolved there. The uses of struct fields in derived impls are
+//! found by expanding the derives.
 
 mod classify;
 mod rename;
 mod search_scope;
 
-use hir::{InFile, SourceBinder};
+use either::Either;
+use hir::{db::AstDatabase, ExpansionInfo, HasSource, HirFileId, InFile, Origin, SourceBinder};
 use once_cell::unsync::Lazy;
 use ra_db::{SourceDatabase, SourceDatabaseExt};
 use ra_ide_db::RootDatabase;
@@ -21,10 +25,16 @@ use ra_prof::profile;
 use ra_syntax::{
     algo::find_node_at_offset,
     ast::{self, NameOwner},
-    match_ast, AstNode, SourceFile, SyntaxKind, SyntaxNode, TextRange, TextUnit, TokenAtOffset,
+    match_ast, AstNode, SourceFile, SyntaxKind, SyntaxNode, SyntaxToken, TextRange, TextUnit,
+    TokenAtOffset, T,
 };
+use rustc_hash::FxHashMap;
 
-use crate::{display::ToNav, FilePosition, FileRange, NavigationTarget, RangeInfo};
+use crate::{
+    display::ToNav,
+    expand::{descend_into_macros, descend_into_macros_with_analyzer, original_range},
+    FileId, FilePosition, FileRange, NavigationTarget, RangeInfo,
+};
 
 pub(crate) use self::{
     classify::{classify_name, classify_name_ref},
@@ -32,12 +42,15 @@ pub(crate) use self::{
 };
 pub(crate) use ra_ide_db::defs::{NameDefinition, NameKind};
 
-pub use self::search_scope::SearchScope;
+pub use self::{rename::RenameError, search_scope::SearchScope};
 
 #[derive(Debug, Clone)]
 pub struct ReferenceSearchResult {
     declaration: Declaration,
     references: Vec<Reference>,
+    /// Occurrences of the name inside macro definition bodies which become
+    /// references in the expansion of some call of the macro.
+    macro_definition_occurrences: Vec<FileRange>,
 }
 
 #[derive(Debug, Clone)]
@@ -79,6 +92,10 @@ impl ReferenceSearchResult {
         &self.references
     }
 
+    pub fn macro_definition_occurrences(&self) -> &[FileRange] {
+        &self.macro_definition_occurrences
+    }
+
     /// Total number of references
     /// At least 1 since all valid references should
     /// Have a declaration
@@ -150,12 +167,38 @@ pub(crate) fn find_all_refs(
         access: decl_access(&def.kind, &name, &syntax, decl_range),
     };
 
-    let references = process_definition(db, def, name, search_scope)
+    let (mut references, macro_definition_tokens) =
+        process_definition(db, &def, name, search_scope);
+    if let NameKind::StructField(field) = def.kind {
+        // The uses of the field in derived impls come from its name, so most of
+        // them are the declaration itself, which is already covered.
+        let decl_file_id = declaration.nav.file_id();
+        for file_range in derived_field_references(db, &def, field) {
+            let is_known = (file_range.file_id == decl_file_id && file_range.range == decl_range)
+                || references.iter().any(|it| {
+                    it.file_range.file_id == file_range.file_id
+                        && it.file_range.range == file_range.range
+                });
+            if !is_known {
+                references.push(Reference {
+                    file_range,
+                    kind: ReferenceKind::Other,
+                    access: Some(ReferenceAccess::Read),
+                });
+            }
+        }
+    }
+    let references = references
         .into_iter()
         .filter(|r| search_kind == ReferenceKind::Other || search_kind == r.kind)
         .collect();
+    let macro_definition_occurrences =
+        macro_definition_occurrences(db, &def, macro_definition_tokens);
 
-    Some(RangeInfo::new(range, ReferenceSearchResult { declaration, references }))
+    Some(RangeInfo::new(
+        range,
+        ReferenceSearchResult { declaration, references, macro_definition_occurrences },
+    ))
 }
 
 fn find_name(
@@ -170,66 +213,352 @@ fn find_name(
         let range = name.syntax().text_range();
         return Some(RangeInfo::new(range, (name.text().to_string(), def)));
     }
-    let name_ref = find_node_at_offset::<ast::NameRef>(&syntax, position.offset)?;
-    let def = classify_name_ref(&mut sb, InFile::new(position.file_id.into(), &name_ref))?;
-    let range = name_ref.syntax().text_range();
-    Some(RangeInfo::new(range, (name_ref.text().to_string(), def)))
+    if let Some(name_ref) = find_node_at_offset::<ast::NameRef>(&syntax, position.offset) {
+        let def = classify_name_ref(&mut sb, InFile::new(position.file_id.into(), &name_ref))?;
+        let range = name_ref.syntax().text_range();
+        return Some(RangeInfo::new(range, (name_ref.text().to_string(), def)));
+    }
+
+    // The cursor might be on an identifier in a macro call, which only
+    // becomes a name or a name reference after expansion.
+    let token =
+        syntax.token_at_offset(position.offset).find(|it| it.kind() == SyntaxKind::IDENT)?;
+    let range = token.text_range();
+    let token = descend_into_macros(db, position.file_id, token);
+    let def = classify_token(&mut sb, token.as_ref())?;
+    Some(RangeInfo::new(range, (token.value.text().to_string(), def)))
+}
+
+fn classify_token(
+    sb: &mut SourceBinder<RootDatabase>,
+    token: InFile<&SyntaxToken>,
+) -> Option<NameDefinition> {
+    let parent = token.value.parent();
+    if let Some(name_ref) = ast::NameRef::cast(parent.clone()) {
+        classify_name_ref(sb, token.with_value(&name_ref))
+    } else {
+        let name = ast::Name::cast(parent)?;
+        classify_name(sb, token.with_value(&name))
+    }
 }
 
 fn process_definition(
     db: &RootDatabase,
-    def: NameDefinition,
+    def: &NameDefinition,
     name: String,
     scope: SearchScope,
-) -> Vec<Reference> {
+) -> (Vec<Reference>, Vec<(FileId, SyntaxToken)>) {
     let _p = profile("process_definition");
 
     let pat = name.as_str();
     let mut refs = vec![];
+    let mut macro_definition_tokens = vec![];
 
     for (file_id, search_range) in scope {
         let text = db.file_text(file_id);
 
-        let parse = Lazy::new(|| SourceFile::parse(&text));
+        let syntax = Lazy::new(|| SourceFile::parse(&text).tree().syntax().clone());
         let mut sb = Lazy::new(|| SourceBinder::new(db));
+        let mut macro_calls = MacroCallExpansions::new(db, file_id);
 
         for (idx, _) in text.match_indices(pat) {
             let offset = TextUnit::from_usize(idx);
 
-            if let Some(name_ref) =
-                find_node_at_offset::<ast::NameRef>(parse.tree().syntax(), offset)
-            {
-                let range = name_ref.syntax().text_range();
-                if let Some(search_range) = search_range {
-                    if !range.is_subrange(&search_range) {
+            let (range, found) = match find_node_at_offset::<ast::NameRef>(&syntax, offset) {
+                Some(name_ref) => (name_ref.syntax().text_range(), Either::Left(name_ref)),
+                None => match syntax.token_at_offset(offset).right_biased() {
+                    Some(it) if it.kind() == SyntaxKind::IDENT && it.text() == pat => {
+                        (it.text_range(), Either::Right(it))
+                    }
+                    _ => continue,
+                },
+            };
+            if let Some(search_range) = search_range {
+                if !range.is_subrange(&search_range) {
+                    continue;
+                }
+            }
+            let name_ref = match found {
+                Either::Left(name_ref) => InFile::new(file_id.into(), name_ref),
+                Either::Right(token) => {
+                    if macro_definition_of(&token).is_some() {
+                        macro_definition_tokens.push((file_id, token));
                         continue;
                     }
+                    match macro_calls.name_ref(token) {
+                        Some(name_ref) => name_ref,
+                        None => continue,
+                    }
                 }
-                // FIXME: reuse sb
-                // See https://github.com/rust-lang/rust/pull/68198#issuecomment-574269098
-
-                if let Some(d) = classify_name_ref(&mut sb, InFile::new(file_id.into(), &name_ref))
-                {
-                    if d == def {
-                        let kind = if is_record_lit_name_ref(&name_ref)
-                            || is_call_expr_name_ref(&name_ref)
-                        {
+            };
+            // FIXME: reuse sb
+            // See https://github.com/rust-lang/rust/pull/68198#issuecomment-574269098
+
+            if let Some(d) = classify_name_ref(&mut sb, name_ref.as_ref()) {
+                if &d == def {
+                    let name_ref = name_ref.value;
+                    let kind =
+                        if is_record_lit_name_ref(&name_ref) || is_call_expr_name_ref(&name_ref) {
                             ReferenceKind::StructLiteral
                         } else {
                             ReferenceKind::Other
                         };
 
-                        refs.push(Reference {
-                            file_range: FileRange { file_id, range },
-                            kind,
-                            access: reference_access(&d.kind, &name_ref),
-                        });
-                    }
+                    refs.push(Reference {
+                        file_range: FileRange { file_id, range },
+                        kind,
+                        access: reference_access(&d.kind, &name_ref),
+                    });
                 }
             }
         }
     }
-    refs
+    (refs, macro_definition_tokens)
+}
+
+/// The expansions of the macro calls in a file, so that each call is only
+/// expanded once, however many of the searched identifiers its arguments
+/// contain.
+struct MacroCallExpansions<'a> {
+    db: &'a RootDatabase,
+    file_id: FileId,
+    expansions: FxHashMap<TextRange, Option<ExpansionInfo>>,
+}
+
+impl<'a> MacroCallExpansions<'a> {
+    fn new(db: &'a RootDatabase, file_id: FileId) -> MacroCallExpansions<'a> {
+        MacroCallExpansions { db, file_id, expansions: FxHashMap::default() }
+    }
+
+    /// Maps `token`, an identifier in the arguments of a macro call, to the
+    /// name reference it becomes in the expansion.
+    fn name_ref(&mut self, token: SyntaxToken) -> Option<InFile<ast::NameRef>> {
+        let (db, file_id) = (self.db, self.file_id);
+        let macro_call = token.ancestors().find_map(ast::MacroCall::cast)?;
+        let tt = macro_call.token_tree()?;
+        if !token.text_range().is_subrange(&tt.syntax().text_range()) {
+            return None;
+        }
+        let info = self
+            .expansions
+            .entry(macro_call.syntax().text_range())
+            .or_insert_with(|| {
+                let src = InFile::new(file_id.into(), &macro_call);
+                let analyzer = hir::SourceAnalyzer::new(db, src.map(|it| it.syntax()), None);
+                analyzer.expand(db, src)?.file_id().expansion_info(db)
+            })
+            .as_ref()?;
+        let token = info.map_token_down(InFile::new(file_id.into(), &token))?;
+        // Macro calls nested in the expansion are rare enough not to be cached.
+        let token = if token.value.ancestors().any(|it| ast::MacroCall::can_cast(it.kind())) {
+            let analyzer =
+                hir::SourceAnalyzer::new(db, token.with_value(token.value.parent()).as_ref(), None);
+            descend_into_macros_with_analyzer(db, &analyzer, token)
+        } else {
+            token
+        };
+        let name_ref = ast::NameRef::cast(token.value.parent())?;
+        Some(token.with_value(name_ref))
+    }
+}
+
+/// Finds the uses of `field` in the impls derived for the ADT which declares
+/// it, mapped back to the original source.
+fn derived_field_references(
+    db: &RootDatabase,
+    def: &NameDefinition,
+    field: hir::StructField,
+) -> Vec<FileRange> {
+    let adt = match field.parent_def(db) {
+        hir::VariantDef::Struct(it) => it.source(db).map(|it| it.syntax().clone()),
+        hir::VariantDef::Union(it) => it.source(db).map(|it| it.syntax().clone()),
+        hir::VariantDef::EnumVariant(it) => {
+            it.parent_enum(db).source(db).map(|it| it.syntax().clone())
+        }
+    };
+    let item = match ast::ModuleItem::cast(adt.value.clone()) {
+        Some(it) => it,
+        None => return Vec::new(),
+    };
+    let name = field.name(db).to_string();
+    let mut sb = SourceBinder::new(db);
+    let analyzer = sb.analyze(adt.as_ref(), None);
+
+    let derives = adt
+        .value
+        .children()
+        .filter_map(ast::Attr::cast)
+        .filter(|attr| attr.simple_name().map_or(false, |it| it == "derive"))
+        .flat_map(|attr| attr.syntax().descendants_with_tokens())
+        .filter_map(|it| it.into_token())
+        .filter(|it| it.kind() == SyntaxKind::IDENT && it.parent().kind() == SyntaxKind::TOKEN_TREE)
+        .collect::<Vec<_>>();
+
+    let mut res = Vec::new();
+    for derive in derives {
+        let expansion = match analyzer.expand_derive(db, adt.with_value(&item), &derive) {
+            Some(it) => it.file_id(),
+            None => continue,
+        };
+        let root = match db.parse_or_expand(expansion) {
+            Some(it) => it,
+            None => continue,
+        };
+        let uses = root
+            .descendants_with_tokens()
+            .filter_map(|it| it.into_token())
+            .filter(|it| it.kind() == SyntaxKind::IDENT && it.text() == name.as_str())
+            .filter(|it| {
+                ast::NameRef::can_cast(it.parent().kind())
+                    && classify_token(&mut sb, InFile::new(expansion, it)).as_ref() == Some(def)
+            });
+        for token in uses {
+            res.push(original_range(db, InFile::new(expansion, &token.parent())));
+        }
+    }
+    res
+}
+
+/// Keeps the occurrences of the name inside macro definition bodies which
+/// become references to `def` in the expansion of some call of the macro.
+fn macro_definition_occurrences(
+    db: &RootDatabase,
+    def: &NameDefinition,
+    tokens: Vec<(FileId, SyntaxToken)>,
+) -> Vec<FileRange> {
+    let mut sb = SourceBinder::new(db);
+    let mut expansions: FxHashMap<(FileId, TextRange), Vec<MacroExpansion>> = FxHashMap::default();
+    tokens
+        .into_iter()
+        .filter(|(file_id, token)| {
+            let mac = match macro_definition_of(token) {
+                Some(it) => it,
+                None => return false,
+            };
+            let expansions = expansions
+                .entry((*file_id, mac.syntax().text_range()))
+                .or_insert_with(|| macro_expansions(db, *file_id, &mac));
+            expansions.iter().any(|expansion| {
+                tokens_from_macro_definition(expansion, *file_id, token)
+                    .iter()
+                    .any(|it| classify_token(&mut sb, it.as_ref()).as_ref() == Some(def))
+            })
+        })
+        .map(|(file_id, token)| FileRange { file_id, range: token.text_range() })
+        .collect()
+}
+
+/// Maps `token`, an identifier in the body of a macro definition, to the
+/// tokens it becomes in the expansions of the calls of that macro.
+pub(crate) fn descend_from_macro_definition(
+    db: &RootDatabase,
+    file_id: FileId,
+    token: &SyntaxToken,
+) -> Option<Vec<InFile<SyntaxToken>>> {
+    let mac = macro_definition_of(token)?;
+    let tokens = macro_expansions(db, file_id, &mac)
+        .iter()
+        .flat_map(|expansion| tokens_from_macro_definition(expansion, file_id, token))
+        .collect();
+    Some(tokens)
+}
+
+/// An expansion of a macro call, with what's needed to map the tokens of the
+/// macro definition into it.
+struct MacroExpansion {
+    file_id: HirFileId,
+    info: ExpansionInfo,
+    root: SyntaxNode,
+}
+
+/// Expands every call of `mac` which refers to it by name.
+fn macro_expansions(db: &RootDatabase, file_id: FileId, mac: &ast::Macro) -> Vec<MacroExpansion> {
+    let mut sb = SourceBinder::new(db);
+    let name = match mac.name() {
+        Some(it) => it,
+        None => return Vec::new(),
+    };
+    let def = match classify_name(&mut sb, InFile::new(file_id.into(), &name)) {
+        Some(it) => it,
+        None => return Vec::new(),
+    };
+    let scope = SearchScope::for_def(&def, db);
+    let (calls, _) = process_definition(db, &def, name.text().to_string(), scope);
+    calls
+        .into_iter()
+        .filter_map(|call| {
+            let FileRange { file_id, range } = call.file_range;
+            let parse = db.parse(file_id);
+            let macro_call =
+                find_node_at_offset::<ast::MacroCall>(parse.tree().syntax(), range.start())?;
+            let path_range = macro_call.path()?.segment()?.name_ref()?.syntax().text_range();
+            if path_range != range {
+                return None;
+            }
+            let src = InFile::new(file_id.into(), &macro_call);
+            let analyzer = sb.analyze(src.map(|it| it.syntax()), None);
+            let file_id = analyzer.expand(db, src)?.file_id();
+            let info = file_id.expansion_info(db)?;
+            let root = db.parse_or_expand(file_id)?;
+            Some(MacroExpansion { file_id, info, root })
+        })
+        .collect()
+}
+
+/// Finds the tokens of `expansion` which come from `token`, in the body of
+/// the macro definition.
+fn tokens_from_macro_definition(
+    expansion: &MacroExpansion,
+    file_id: FileId,
+    token: &SyntaxToken,
+) -> Vec<InFile<SyntaxToken>> {
+    expansion
+        .root
+        .descendants_with_tokens()
+        .filter_map(|it| it.into_token())
+        .filter(|it| it.kind() == token.kind() && it.text() == token.text())
+        .filter(|it| match expansion.info.map_token_up(InFile::new(expansion.file_id, it)) {
+            Some((up, Origin::Def)) => {
+                up.file_id == HirFileId::from(file_id)
+                    && up.value.text_range() == token.text_range()
+            }
+            _ => false,
+        })
+        .map(|it| InFile::new(expansion.file_id, it))
+        .collect()
+}
+
+/// Returns the `macro_rules!` or `macro` whose body contains `token`, unless
+/// `token` is a metavariable. Such tokens can't be resolved without expanding
+/// the macro.
+fn macro_definition_of(token: &SyntaxToken) -> Option<ast::Macro> {
+    if token.prev_token().map(|it| it.kind()) == Some(T![$]) {
+        return None;
+    }
+    let in_token_tree =
+        |tt: ast::TokenTree| token.text_range().is_subrange(&tt.syntax().text_range());
+    token.ancestors().find_map(|node| {
+        match_ast! {
+            match node {
+                ast::MacroDef(it) => {
+                    // The shorthand form `macro m($x) { .. }` has two token trees.
+                    let in_body =
+                        it.syntax().children().filter_map(ast::TokenTree::cast).any(in_token_tree);
+                    if in_body { Some(it.into()) } else { None }
+                },
+                ast::MacroCall(it) => {
+                    let is_macro_rules = it
+                        .path()
+                        .and_then(|it| it.segment())
+                        .and_then(|it| it.name_ref())
+                        .map_or(false, |it| it.text() == "macro_rules");
+                    let in_body = is_macro_rules && it.token_tree().map_or(false, in_token_tree);
+                    if in_body { Some(it.into()) } else { None }
+                },
+                _ => None,
+            }
+        }
+    })
 }
 
 fn decl_access(
@@ -728,6 +1057,91 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_find_all_refs_in_macro_call() {
+        let code = r#"
+    macro_rules! id {
+        ($($tt:tt)*) => { $($tt)* };
+    }
+    struct Foo {
+        spam: u32,
+    }
+    fn f(foo: Foo) -> u32 {
+        id!(foo.spa<|>m)
+    }"#;
+
+        let refs = get_all_refs(code);
+        check_result(
+            refs,
+            "spam RECORD_FIELD_DEF FileId(1) [91; 100) [91; 95) Other",
+            &["FileId(1) [152; 156) Other Read"],
+        );
+    }
+
+    #[test]
+    fn test_find_all_refs_reports_macro_definition_occurrences() {
+        let code = r#"
+    macro_rules! get_spam {
+        ($e:expr) => { $e.spam };
+    }
+    struct Foo {
+        spam<|>: u32,
+    }
+    fn f(foo: Foo) -> u32 {
+        get_spam!(foo)
+    }"#;
+
+        let refs = get_all_refs(code);
+        assert!(refs.references().is_empty());
+        let occurrences: Vec<_> =
+            refs.macro_definition_occurrences().iter().map(|it| it.range).collect();
+        assert_eq!(format!("{:?}", occurrences), "[[55; 59)]");
+    }
+
+    #[test]
+    fn test_find_all_refs_skips_unrelated_macro_definition_occurrences() {
+        let code = r#"
+    macro_rules! get_spam {
+        ($e:expr) => { $e.spam };
+    }
+    macro_rules! unused_spam {
+        ($e:expr) => { $e.spam };
+    }
+    struct Foo {
+        spam: u32,
+    }
+    struct Bar {
+        spam<|>: u32,
+    }
+    fn f(foo: Foo) -> u32 {
+        get_spam!(foo)
+    }"#;
+
+        let refs = get_all_refs(code);
+        assert!(refs.references().is_empty());
+        assert!(refs.macro_definition_occurrences().is_empty());
+    }
+
+    #[test]
+    fn test_find_all_refs_field_used_by_derive() {
+        let code = r#"
+    #[derive(Clone)]
+    struct Foo {
+        spam<|>: u32,
+    }
+    fn f(foo: Foo) -> u32 {
+        foo.spam
+    }"#;
+
+        // The uses in the derived impl are the declaration itself.
+        let refs = get_all_refs(code);
+        check_result(
+            refs,
+            "spam RECORD_FIELD_DEF FileId(1) [47; 56) [47; 51) Other",
+            &["FileId(1) [104; 108) Other Read"],
+        );
+    }
+
     fn get_all_refs(text: &str) -> ReferenceSearchResult {
         let (analysis, position) = single_file_with_position(text);
         analysis.find_all_refs(position, None).unwrap().unwrap()
@@ -22,6 +22,12 @@ pub(crate) fn classify_name_ref(
     if let Some(method_call) = ast::MethodCallExpr::cast(parent.clone()) {
         tested_by!(goto_def_for_methods);
         if let Some(func) = analyzer.resolve_method_call(&method_call) {
+            // Calls of derived methods go to the derive that generated them
+            // rather than to the trait declaration.
+            let func = match analyzer.resolve_method_call_impl(sb.db, &method_call) {
+                Some(impl_func) if impl_func.is_builtin_derive(sb.db).is_some() => impl_func,
+                _ => func,
+            };
             return Some(from_module_def(sb.db, func.into(), None));
         }
     }
@@ -1,5 +1,7 @@
 //! FIXME: write short doc here
 
+use std::fmt;
+
 use hir::ModuleSource;
 use ra_db::{RelativePath, RelativePathBuf, SourceDatabase, SourceDatabaseExt};
 use ra_ide_db::RootDatabase;
@@ -14,14 +16,24 @@ use crate::{
 
 use super::find_all_refs;
 
+/// The reason why a rename can't be performed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenameError(pub String);
+
+impl fmt::Display for RenameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
 pub(crate) fn rename(
     db: &RootDatabase,
     position: FilePosition,
     new_name: &str,
-) -> Option<RangeInfo<SourceChange>> {
-    match lex_single_valid_syntax_kind(new_name)? {
-        SyntaxKind::IDENT | SyntaxKind::UNDERSCORE => (),
-        _ => return None,
+) -> Result<Option<RangeInfo<SourceChange>>, RenameError> {
+    match lex_single_valid_syntax_kind(new_name) {
+        Some(SyntaxKind::IDENT) | Some(SyntaxKind::UNDERSCORE) => (),
+        _ => return Ok(None),
     }
 
     let parse = db.parse(position.file_id);
@@ -29,8 +41,8 @@ pub(crate) fn rename(
         find_name_and_module_at_offset(parse.tree().syntax(), position)
     {
         let range = ast_name.syntax().text_range();
-        rename_mod(db, &ast_name, &ast_module, position, new_name)
-            .map(|info| RangeInfo::new(range, info))
+        Ok(rename_mod(db, &ast_name, &ast_module, position, new_name)
+            .map(|info| RangeInfo::new(range, info)))
     } else {
         rename_reference(db, position, new_name)
     }
@@ -105,8 +117,19 @@ fn rename_reference(
     db: &RootDatabase,
     position: FilePosition,
     new_name: &str,
-) -> Option<RangeInfo<SourceChange>> {
-    let RangeInfo { range, info: refs } = find_all_refs(db, position, None)?;
+) -> Result<Option<RangeInfo<SourceChange>>, RenameError> {
+    let RangeInfo { range, info: refs } = match find_all_refs(db, position, None) {
+        Some(it) => it,
+        None => return Ok(None),
+    };
+
+    // Editing a macro definition body would change every expansion of the
+    // macro, not just the ones referring to this definition.
+    if !refs.macro_definition_occurrences().is_empty() {
+        return Err(RenameError(
+            "the name is used inside a macro definition, which can't be renamed".to_string(),
+        ));
+    }
 
     let edit = refs
         .into_iter()
@@ -120,10 +143,10 @@ fn rename_reference(
         .collect::<Vec<_>>();
 
     if edit.is_empty() {
-        return None;
+        return Ok(None);
     }
 
-    Some(RangeInfo::new(range, SourceChange::source_file_edits("rename", edit)))
+    Ok(Some(RangeInfo::new(range, SourceChange::source_file_edits("rename", edit))))
 }
 
 #[cfg(test)]
@@ -175,7 +198,7 @@ mod tests {
     }",
         );
         let new_name = "invalid!";
-        let source_change = analysis.rename(position, new_name).unwrap();
+        let source_change = analysis.rename(position, new_name).unwrap().unwrap();
         assert!(source_change.is_none());
     }
 
@@ -210,6 +233,35 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_rename_trait_assoc_const() {
+        test_rename(
+            r#"
+    trait Trait {
+        const ID: u32 = 0;
+        fn id(&self) -> u32 { Self::ID }
+    }
+    struct Foo;
+    impl Trait for Foo {}
+
+    fn main() {
+        let _ = Foo::ID<|> + <Foo as Trait>::ID;
+    }"#,
+            "NEW_ID",
+            r#"
+    trait Trait {
+        const NEW_ID: u32 = 0;
+        fn id(&self) -> u32 { Self::NEW_ID }
+    }
+    struct Foo;
+    impl Trait for Foo {}
+
+    fn main() {
+        let _ = Foo::NEW_ID + <Foo as Trait>::NEW_ID;
+    }"#,
+        );
+    }
+
     #[test]
     fn test_rename_for_param_inside() {
         test_rename(
@@ -270,7 +322,7 @@ mod tests {
             ",
         );
         let new_name = "foo2";
-        let source_change = analysis.rename(position, new_name).unwrap();
+        let source_change = analysis.rename(position, new_name).unwrap().unwrap();
         assert_debug_snapshot!(&source_change,
 @r###"
         Some(
@@ -322,7 +374,7 @@ mod tests {
             ",
         );
         let new_name = "foo2";
-        let source_change = analysis.rename(position, new_name).unwrap();
+        let source_change = analysis.rename(position, new_name).unwrap().unwrap();
         assert_debug_snapshot!(&source_change,
         @r###"
         Some(
@@ -364,9 +416,110 @@ mod tests {
                );
     }
 
+    #[test]
+    fn test_rename_field_used_in_macro_call() {
+        test_rename(
+            r#"
+    macro_rules! id {
+        ($($tt:tt)*) => { $($tt)* };
+    }
+    struct Foo {
+        spam<|>: u32,
+    }
+    fn f(foo: Foo) -> u32 {
+        id!(foo.spam)
+    }"#,
+            "eggs",
+            r#"
+    macro_rules! id {
+        ($($tt:tt)*) => { $($tt)* };
+    }
+    struct Foo {
+        eggs: u32,
+    }
+    fn f(foo: Foo) -> u32 {
+        id!(foo.eggs)
+    }"#,
+        );
+    }
+
+    #[test]
+    fn test_rename_field_used_by_derive() {
+        test_rename(
+            r#"
+    #[derive(Clone)]
+    struct Foo {
+        spam<|>: u32,
+    }
+    fn f(foo: Foo) -> u32 {
+        foo.spam
+    }"#,
+            "eggs",
+            r#"
+    #[derive(Clone)]
+    struct Foo {
+        eggs: u32,
+    }
+    fn f(foo: Foo) -> u32 {
+        foo.eggs
+    }"#,
+        );
+    }
+
+    #[test]
+    fn test_rename_refuses_to_edit_macro_definitions() {
+        let (analysis, position) = single_file_with_position(
+            "
+    macro_rules! get_spam {
+        ($e:expr) => { $e.spam };
+    }
+    struct Foo {
+        spam<|>: u32,
+    }
+    fn f(foo: Foo) -> u32 {
+        get_spam!(foo)
+    }",
+        );
+        let source_change = analysis.rename(position, "eggs").unwrap();
+        assert!(source_change.is_err());
+    }
+
+    #[test]
+    fn test_rename_ignores_unrelated_macro_definitions() {
+        test_rename(
+            r#"
+    macro_rules! get_spam {
+        ($e:expr) => { $e.spam };
+    }
+    struct Foo {
+        spam: u32,
+    }
+    struct Bar {
+        spam<|>: u32,
+    }
+    fn f(foo: Foo, bar: Bar) -> u32 {
+        get_spam!(foo) + bar.spam
+    }"#,
+            "eggs",
+            r#"
+    macro_rules! get_spam {
+        ($e:expr) => { $e.spam };
+    }
+    struct Foo {
+        spam: u32,
+    }
+    struct Bar {
+        eggs: u32,
+    }
+    fn f(foo: Foo, bar: Bar) -> u32 {
+        get_spam!(foo) + bar.eggs
+    }"#,
+        );
+    }
+
     fn test_rename(text: &str, new_name: &str, expected: &str) {
         let (analysis, position) = single_file_with_position(text);
-        let source_change = analysis.rename(position, new_name).unwrap();
+        let source_change = analysis.rename(position, new_name).unwrap().unwrap();
         let mut text_edit_builder = TextEditBuilder::default();
         let mut file_id: Option<FileId> = None;
         if let Some(change) = source_change {
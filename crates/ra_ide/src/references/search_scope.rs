@@ -4,7 +4,7 @@
 //! e.g. for things like local variables.
 use std::mem;
 
-use hir::{DefWithBody, HasSource, ModuleSource};
+use hir::{DefWithBody, HasSource, Module, ModuleSource};
 use ra_db::{FileId, SourceDatabaseExt};
 use ra_prof::profile;
 use ra_syntax::{AstNode, TextRange};
@@ -40,24 +40,7 @@ impl SearchScope {
 
         if vis.as_str() == "pub(super)" {
             if let Some(parent_module) = def.container.parent(db) {
-                let mut res = FxHashMap::default();
-                let parent_src = parent_module.definition_source(db);
-                let file_id = parent_src.file_id.original_file(db);
-
-                match parent_src.value {
-                    ModuleSource::Module(m) => {
-                        let range = Some(m.syntax().text_range());
-                        res.insert(file_id, range);
-                    }
-                    ModuleSource::SourceFile(_) => {
-                        res.insert(file_id, None);
-                        res.extend(parent_module.children(db).map(|m| {
-                            let src = m.definition_source(db);
-                            (src.file_id.original_file(db), None)
-                        }));
-                    }
-                }
-                return SearchScope::new(res);
+                return SearchScope::for_module(db, parent_module);
             }
         }
 
@@ -98,6 +81,24 @@ impl SearchScope {
     pub fn single_file(file: FileId) -> SearchScope {
         SearchScope::new(std::iter::once((file, None)).collect())
     }
+    /// The source of `module`, together with the files of all of its
+    /// descendants, which can see the private items of the module.
+    pub(crate) fn for_module(db: &RootDatabase, module: Module) -> SearchScope {
+        let mut res = FxHashMap::default();
+        let mut modules = vec![module];
+        while let Some(module) = modules.pop() {
+            let src = module.definition_source(db);
+            let file_id = src.file_id.original_file(db);
+            let range = match src.value {
+                ModuleSource::Module(m) => Some(m.syntax().text_range()),
+                ModuleSource::SourceFile(_) => None,
+            };
+            // Inline modules are inside of the range of their parent.
+            res.entry(file_id).or_insert(range);
+            modules.extend(module.children(db));
+        }
+        SearchScope::new(res)
+    }
     pub(crate) fn intersection(&self, other: &SearchScope) -> SearchScope {
         let (mut small, mut large) = (&self.entries, &other.entries);
         if small.len() > large.len() {
@@ -1,6 +1,6 @@
 //! FIXME: write short doc here
 
-use hir::InFile;
+use hir::{BuiltinAttrExpander, InFile, SourceBinder};
 use itertools::Itertools;
 use ra_db::SourceDatabase;
 use ra_ide_db::RootDatabase;
@@ -8,6 +8,7 @@ use ra_syntax::{
     ast::{self, AstNode, AttrsOwner, ModuleItemOwner, NameOwner},
     match_ast, SyntaxNode, TextRange,
 };
+use rustc_hash::FxHashMap;
 
 use crate::FileId;
 
@@ -27,48 +28,115 @@ pub enum RunnableKind {
 
 pub(crate) fn runnables(db: &RootDatabase, file_id: FileId) -> Vec<Runnable> {
     let parse = db.parse(file_id);
-    parse.tree().syntax().descendants().filter_map(|i| runnable(db, file_id, i)).collect()
+    let mut sb = SourceBinder::new(db);
+    // Test functions are looked at twice, on their own and for their module.
+    let mut test_attrs = FxHashMap::default();
+    parse
+        .tree()
+        .syntax()
+        .descendants()
+        .filter_map(|i| runnable(&mut sb, &mut test_attrs, file_id, i))
+        .collect()
 }
 
-fn runnable(db: &RootDatabase, file_id: FileId, item: SyntaxNode) -> Option<Runnable> {
+fn runnable(
+    sb: &mut SourceBinder<RootDatabase>,
+    test_attrs: &mut FxHashMap<ast::FnDef, Option<TestAttr>>,
+    file_id: FileId,
+    item: SyntaxNode,
+) -> Option<Runnable> {
     match_ast! {
         match item {
-            ast::FnDef(it) => { runnable_fn(it) },
-            ast::Module(it) => { runnable_mod(db, file_id, it) },
+            ast::FnDef(it) => { runnable_fn(sb, test_attrs, file_id, it) },
+            ast::Module(it) => { runnable_mod(sb, test_attrs, file_id, it) },
             _ => { None },
         }
     }
 }
 
-fn runnable_fn(fn_def: ast::FnDef) -> Option<Runnable> {
+fn runnable_fn(
+    sb: &mut SourceBinder<RootDatabase>,
+    test_attrs: &mut FxHashMap<ast::FnDef, Option<TestAttr>>,
+    file_id: FileId,
+    fn_def: ast::FnDef,
+) -> Option<Runnable> {
     let name = fn_def.name()?.text().clone();
     let kind = if name == "main" {
         RunnableKind::Bin
-    } else if has_test_related_attribute(&fn_def) {
-        RunnableKind::Test { name: name.to_string() }
-    } else if fn_def.has_atom_attr("bench") {
-        RunnableKind::Bench { name: name.to_string() }
     } else {
-        return None;
+        match cached_test_attr(sb, test_attrs, file_id, &fn_def)? {
+            TestAttr::Test => RunnableKind::Test { name: name.to_string() },
+            TestAttr::Bench => RunnableKind::Bench { name: name.to_string() },
+        }
     };
     Some(Runnable { range: fn_def.syntax().text_range(), kind })
 }
 
-/// This is a method with a heuristics to support test methods annotated with custom test annotations, such as
-/// `#[test_case(...)]`, `#[tokio::test]` and similar.
-/// Also a regular `#[test]` annotation is supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TestAttr {
+    Test,
+    Bench,
+}
+
+fn cached_test_attr(
+    sb: &mut SourceBinder<RootDatabase>,
+    test_attrs: &mut FxHashMap<ast::FnDef, Option<TestAttr>>,
+    file_id: FileId,
+    fn_def: &ast::FnDef,
+) -> Option<TestAttr> {
+    if let Some(&attr) = test_attrs.get(fn_def) {
+        return attr;
+    }
+    let attr = test_attr(sb, file_id, fn_def);
+    test_attrs.insert(fn_def.clone(), attr);
+    attr
+}
+
+/// Finds the attribute which makes `fn_def` a test or a benchmark.
 ///
-/// It may produce false positives, for example, `#[wasm_bindgen_test]` requires a different command to run the test,
-/// but it's better than not to have the runnables for the tests at all.
-fn has_test_related_attribute(fn_def: &ast::FnDef) -> bool {
-    fn_def
-        .attrs()
-        .filter_map(|attr| attr.path())
-        .map(|path| path.syntax().to_string().to_lowercase())
-        .any(|attribute_text| attribute_text.contains("test"))
+/// Attributes are resolved, so that the builtin `#[test]` and `#[bench]` are
+/// recognized as well as test macros from libraries, such as `#[tokio::test]`
+/// or `#[rstest]`. Attributes which can't be resolved, like procedural macros,
+/// fall back to a heuristic on their path: it may produce false positives, for
+/// example, `#[wasm_bindgen_test]` requires a different command to run the
+/// test, but it's better than not to have the runnables for the tests at all.
+fn test_attr(
+    sb: &mut SourceBinder<RootDatabase>,
+    file_id: FileId,
+    fn_def: &ast::FnDef,
+) -> Option<TestAttr> {
+    let db = sb.db;
+    let analyzer = sb.analyze(InFile::new(file_id.into(), fn_def.syntax()), None);
+    fn_def.attrs().find_map(|attr| {
+        let is_test_name = |name: &str| name.to_lowercase().contains("test");
+        match analyzer.resolve_attr(db, InFile::new(file_id.into(), &attr)) {
+            Some(mac) => match mac.builtin_attr() {
+                Some(BuiltinAttrExpander::Test) | Some(BuiltinAttrExpander::TestCase) => {
+                    Some(TestAttr::Test)
+                }
+                Some(BuiltinAttrExpander::Bench) => Some(TestAttr::Bench),
+                Some(BuiltinAttrExpander::CfgEval) | Some(BuiltinAttrExpander::GlobalAllocator) => {
+                    None
+                }
+                None => mac
+                    .name(db)
+                    .filter(|name| is_test_name(&name.to_string()))
+                    .map(|_| TestAttr::Test),
+            },
+            None => attr
+                .path()
+                .filter(|path| is_test_name(&path.syntax().to_string()))
+                .map(|_| TestAttr::Test),
+        }
+    })
 }
 
-fn runnable_mod(db: &RootDatabase, file_id: FileId, module: ast::Module) -> Option<Runnable> {
+fn runnable_mod(
+    sb: &mut SourceBinder<RootDatabase>,
+    test_attrs: &mut FxHashMap<ast::FnDef, Option<TestAttr>>,
+    file_id: FileId,
+    module: ast::Module,
+) -> Option<Runnable> {
     let has_test_function = module
         .item_list()?
         .items()
@@ -76,14 +144,14 @@ fn runnable_mod(db: &RootDatabase, file_id: FileId, module: ast::Module) -> Opti
             ast::ModuleItem::FnDef(it) => Some(it),
             _ => None,
         })
-        .any(|f| f.has_atom_attr("test"));
+        .any(|f| cached_test_attr(sb, test_attrs, file_id, &f) == Some(TestAttr::Test));
     if !has_test_function {
         return None;
     }
     let range = module.syntax().text_range();
-    let mut sb = hir::SourceBinder::new(db);
     let module = sb.to_def(InFile::new(file_id.into(), module))?;
 
+    let db = sb.db;
     let path = module.path_to_root(db).into_iter().rev().filter_map(|it| it.name(db)).join("::");
     Some(Runnable { range, kind: RunnableKind::TestMod { path } })
 }
@@ -135,6 +203,51 @@ mod tests {
                 );
     }
 
+    #[test]
+    fn test_runnables_resolve_attributes() {
+        let (analysis, pos) = analysis_and_position(
+            r#"
+        //- /lib.rs
+        <|> //empty
+        use tokio::helper as helper_test;
+
+        #[tokio::test]
+        fn test_foo() {}
+
+        #[helper_test]
+        fn not_a_test() {}
+
+        #[bench]
+        fn bench_foo() {}
+
+        //- /tokio/lib.rs
+        #[macro_export]
+        macro_rules! test { () => {} }
+        #[macro_export]
+        macro_rules! helper { () => {} }
+        "#,
+        );
+        let runnables = analysis.runnables(pos.file_id).unwrap();
+        assert_debug_snapshot!(&runnables,
+        @r###"
+        [
+            Runnable {
+                range: [44; 75),
+                kind: Test {
+                    name: "test_foo",
+                },
+            },
+            Runnable {
+                range: [112; 138),
+                kind: Bench {
+                    name: "bench_foo",
+                },
+            },
+        ]
+        "###
+                );
+    }
+
     #[test]
     fn test_runnables_module() {
         let (analysis, pos) = analysis_and_position(
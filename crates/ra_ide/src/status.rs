@@ -94,10 +94,12 @@ impl FromIterator<TableEntry<FileId, Parse<ast::SourceFile>>> for SyntaxTreeStat
     }
 }
 
-impl<M> FromIterator<TableEntry<MacroFile, Option<(Parse<SyntaxNode>, M)>>> for SyntaxTreeStats {
+impl<M, E> FromIterator<TableEntry<MacroFile, (Option<(Parse<SyntaxNode>, M)>, E)>>
+    for SyntaxTreeStats
+{
     fn from_iter<T>(iter: T) -> SyntaxTreeStats
     where
-        T: IntoIterator<Item = TableEntry<MacroFile, Option<(Parse<SyntaxNode>, M)>>>,
+        T: IntoIterator<Item = TableEntry<MacroFile, (Option<(Parse<SyntaxNode>, M)>, E)>>,
     {
         let mut res = SyntaxTreeStats::default();
         for entry in iter {
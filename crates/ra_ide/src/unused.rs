@@ -0,0 +1,701 @@
+//! Finds items which are declared, but never used.
+//!
+//! We walk the scopes of the modules defined in a file, and collect items,
+//! struct fields, enum variants and imports which aren't visible outside of
+//! the crate. For each
+//! of them, we look for a single reference in its search scope, using the same
+//! text search + resolve approach as find usages. Items which are only
+//! mentioned inside macro calls are conservatively treated as used: we don't
+//! know what the macro does with the tokens.
+
+use hir::{
+    Adt, AssocItem, HasSource, HirFileId, InFile, Module, ModuleDef, ModuleSource, PathResolution,
+    SourceBinder,
+};
+use ra_db::{FileId, FileRange, SourceDatabase};
+use ra_ide_db::{
+    defs::{from_module_def, from_struct_field, NameDefinition, NameKind},
+    RootDatabase,
+};
+use ra_prof::profile;
+use ra_syntax::{
+    algo::non_trivia_sibling,
+    ast::{self, AttrsOwner, NameOwner, VisibilityOwner},
+    AstNode, Direction, SyntaxKind, SyntaxNode, SyntaxToken, TextRange, TextUnit, T,
+};
+use ra_text_edit::TextEdit;
+
+use crate::{
+    diagnostics::Severity,
+    references::{classify_name_ref, SearchScope},
+    Diagnostic, SourceChange,
+};
+
+pub(crate) fn unused(db: &RootDatabase, file_id: FileId) -> Vec<Diagnostic> {
+    let _p = profile("unused");
+    let mut ctx = UnusedCtx { sb: SourceBinder::new(db), file_id, acc: Vec::new() };
+    let root = match ctx.sb.to_module_def(file_id) {
+        Some(it) => it,
+        None => return Vec::new(),
+    };
+
+    let mut modules = vec![root];
+    while let Some(module) = modules.pop() {
+        let src = module.definition_source(db);
+        if src.file_id != HirFileId::from(file_id) {
+            continue;
+        }
+        modules.extend(module.children(db));
+        ctx.check_module(module, src.value);
+    }
+
+    let mut res = ctx.acc;
+    res.sort_by_key(|it| it.range.start());
+    res
+}
+
+struct UnusedCtx<'a> {
+    sb: SourceBinder<'a, RootDatabase>,
+    file_id: FileId,
+    acc: Vec<Diagnostic>,
+}
+
+impl UnusedCtx<'_> {
+    fn check_module(&mut self, module: Module, src: ModuleSource) {
+        let db = self.sb.db;
+        let is_crate_root = module.parent(db).is_none();
+
+        for decl in module.declarations(db) {
+            match decl {
+                ModuleDef::Function(it) => {
+                    let src = it.source(db);
+                    if is_crate_root && it.name(db).to_string() == "main" {
+                        continue;
+                    }
+                    self.check_item(src, decl, "function");
+                }
+                ModuleDef::Adt(Adt::Struct(it)) => {
+                    let src = it.source(db);
+                    if self.check_item(src.clone(), decl, "struct") {
+                        self.check_fields(src.map(|it| it.syntax().clone()), it.fields(db));
+                    }
+                }
+                ModuleDef::Adt(Adt::Union(it)) => {
+                    let src = it.source(db);
+                    if self.check_item(src.clone(), decl, "union") {
+                        self.check_fields(src.map(|it| it.syntax().clone()), it.fields(db));
+                    }
+                }
+                ModuleDef::Adt(Adt::Enum(it)) => {
+                    let src = it.source(db);
+                    if self.check_item(src.clone(), decl, "enum") {
+                        self.check_variants(src, it.variants(db));
+                    }
+                }
+                ModuleDef::Const(it) => {
+                    self.check_item(it.source(db), decl, "constant");
+                }
+                ModuleDef::Static(it) => {
+                    self.check_item(it.source(db), decl, "static");
+                }
+                ModuleDef::Trait(it) => {
+                    self.check_item(it.source(db), decl, "trait");
+                }
+                ModuleDef::TypeAlias(it) => {
+                    self.check_item(it.source(db), decl, "type alias");
+                }
+                ModuleDef::Module(_) | ModuleDef::EnumVariant(_) | ModuleDef::BuiltinType(_) => {}
+            }
+        }
+
+        for impl_block in module.impl_blocks(db) {
+            // Trait impls are used whenever the trait is.
+            if impl_block.target_trait(db).is_some() {
+                continue;
+            }
+            for item in impl_block.items(db) {
+                match item {
+                    AssocItem::Function(it) => {
+                        self.check_item(it.source(db), it.into(), "method");
+                    }
+                    AssocItem::Const(it) => {
+                        self.check_item(it.source(db), it.into(), "associated constant");
+                    }
+                    AssocItem::TypeAlias(_) => {}
+                }
+            }
+        }
+
+        let items = match &src {
+            ModuleSource::SourceFile(it) => it.syntax().clone(),
+            ModuleSource::Module(it) => match it.item_list() {
+                Some(it) => it.syntax().clone(),
+                None => return,
+            },
+        };
+        for use_item in items.children().filter_map(ast::UseItem::cast) {
+            if use_item.visibility().is_some() || is_allowed(use_item.syntax(), "unused_imports") {
+                continue;
+            }
+            if let Some(use_tree) = use_item.use_tree() {
+                self.check_use_tree(module, &use_item, use_tree);
+            }
+        }
+    }
+
+    /// Returns `true` if the item is crate-local and used, so that it makes
+    /// sense to check its fields or variants.
+    fn check_item<N>(&mut self, src: InFile<N>, def: ModuleDef, kind: &str) -> bool
+    where
+        N: NameOwner + VisibilityOwner + AttrsOwner,
+    {
+        if !is_local_visibility(src.value.visibility()) {
+            return false;
+        }
+        let def = from_module_def(self.sb.db, def, None);
+        self.check_def(src.as_ref().map(|it| it.syntax()), src.value.name(), def, kind)
+    }
+
+    fn check_fields(&mut self, adt: InFile<SyntaxNode>, fields: Vec<hir::StructField>) {
+        if is_repr(&adt.value) {
+            return;
+        }
+        for field in fields {
+            let src = field.source(self.sb.db);
+            let field_def = match &src.value {
+                hir::FieldSource::Named(it) => it.clone(),
+                hir::FieldSource::Pos(_) => continue,
+            };
+            if !is_local_visibility(field_def.visibility()) {
+                continue;
+            }
+            let def = from_struct_field(self.sb.db, field);
+            let syntax = src.with_value(field_def.syntax());
+            self.check_def(syntax, field_def.name(), def, "field");
+        }
+    }
+
+    fn check_variants(&mut self, adt: InFile<ast::EnumDef>, variants: Vec<hir::EnumVariant>) {
+        if is_repr(adt.value.syntax()) {
+            return;
+        }
+        for variant in variants {
+            let src = variant.source(self.sb.db);
+            let def = from_module_def(self.sb.db, variant.into(), None);
+            self.check_def(src.as_ref().map(|it| it.syntax()), src.value.name(), def, "variant");
+        }
+    }
+
+    fn check_def(
+        &mut self,
+        src: InFile<&SyntaxNode>,
+        name: Option<ast::Name>,
+        def: NameDefinition,
+        kind: &str,
+    ) -> bool {
+        let name = match name {
+            Some(it) => it,
+            None => return true,
+        };
+        // Items produced by macros are the macro author's business.
+        if src.file_id != HirFileId::from(self.file_id) {
+            return true;
+        }
+        let text = name.text().clone();
+        if text.starts_with('_') || is_allowed(src.value, "dead_code") || is_entry_point(src.value)
+        {
+            return true;
+        }
+
+        let scope = SearchScope::for_def(&def, self.sb.db);
+        // References from the item's own body, like recursive calls, don't count.
+        let own_range = FileRange { file_id: self.file_id, range: src.value.text_range() };
+        if self.has_reference(&def, &text, scope, own_range, false) {
+            return true;
+        }
+        self.acc.push(Diagnostic {
+            message: format!("{} `{}` is never used", kind, text),
+            range: name.syntax().text_range(),
+            severity: Severity::Unused,
+            fix: None,
+        });
+        false
+    }
+
+    fn check_use_tree(&mut self, module: Module, use_item: &ast::UseItem, tree: ast::UseTree) {
+        if let Some(list) = tree.use_tree_list() {
+            for tree in list.use_trees() {
+                self.check_use_tree(module, use_item, tree);
+            }
+            return;
+        }
+        if tree.has_star() {
+            return;
+        }
+        let path = match tree.path() {
+            Some(it) => it,
+            None => return,
+        };
+        let name_ref = match path.segment().and_then(|it| it.name_ref()) {
+            Some(it) => it,
+            None => return,
+        };
+        let name = match tree.alias() {
+            Some(alias) => match alias.name() {
+                Some(it) => it.text().clone(),
+                // `use foo::Trait as _;`
+                None => return,
+            },
+            None => name_ref.text().clone(),
+        };
+
+        let def = match classify_name_ref(&mut self.sb, InFile::new(self.file_id.into(), &name_ref))
+        {
+            Some(it) => it,
+            None => return,
+        };
+        // Traits are used by method resolution, and macros by name, so we
+        // can't see their uses.
+        match def.kind {
+            NameKind::ModuleDef(ModuleDef::Trait(_)) | NameKind::Macro(_) => return,
+            _ => {}
+        }
+
+        // Child modules can use the import too, through `super::` or a glob.
+        let scope = SearchScope::for_module(self.sb.db, module);
+        let own_range = FileRange { file_id: self.file_id, range: use_item.syntax().text_range() };
+        if self.has_reference(&def, &name, scope, own_range, Some(module)) {
+            return;
+        }
+
+        let edit = TextEdit::delete(remove_use_tree_range(&tree));
+        self.acc.push(Diagnostic {
+            message: format!("unused import: `{}`", tree.syntax()),
+            range: tree.syntax().text_range(),
+            severity: Severity::Unused,
+            fix: Some(SourceChange::source_file_edit_from(
+                "remove unused import",
+                self.file_id,
+                edit,
+            )),
+        });
+    }
+
+    /// Looks for the first reference to `def` in `scope`, ignoring the ones in
+    /// `own_range`. If `import_module` is set, we are looking for uses of an
+    /// import in that module, so only unqualified references, or ones
+    /// qualified by the module itself, count.
+    fn has_reference(
+        &mut self,
+        def: &NameDefinition,
+        name: &str,
+        scope: SearchScope,
+        own_range: FileRange,
+        import_module: Option<Module>,
+    ) -> bool {
+        let db = self.sb.db;
+        for (file_id, search_range) in scope {
+            let text = db.file_text(file_id);
+            let parse = db.parse(file_id);
+            let root = parse.tree().syntax().clone();
+
+            for (idx, _) in text.match_indices(name) {
+                let offset = TextUnit::from_usize(idx);
+                if let Some(search_range) = search_range {
+                    if !search_range.contains(offset) {
+                        continue;
+                    }
+                }
+                if file_id == own_range.file_id && own_range.range.contains(offset) {
+                    continue;
+                }
+                let token = match root.token_at_offset(offset).right_biased() {
+                    Some(it) => it,
+                    None => continue,
+                };
+                if token.text_range().start() != offset || token.text().as_str() != name {
+                    continue;
+                }
+                if self.is_reference(file_id, &token, def, import_module) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn is_reference(
+        &mut self,
+        file_id: FileId,
+        token: &SyntaxToken,
+        def: &NameDefinition,
+        import_module: Option<Module>,
+    ) -> bool {
+        let parent = token.parent();
+        if parent.ancestors().any(|it| it.kind() == SyntaxKind::TOKEN_TREE) {
+            return true;
+        }
+        if let Some(name_ref) = ast::NameRef::cast(parent.clone()) {
+            if let Some(module) = import_module {
+                if !self.goes_through_module(file_id, &name_ref, module) {
+                    return false;
+                }
+            }
+            return classify_name_ref(&mut self.sb, InFile::new(file_id.into(), &name_ref))
+                .as_ref()
+                == Some(def);
+        }
+        // `let S { field } = s;` uses the field through a binding.
+        if let NameKind::StructField(_) = def.kind {
+            return ast::Name::cast(parent)
+                .and_then(|it| it.syntax().parent())
+                .and_then(ast::BindPat::cast)
+                .and_then(|it| it.syntax().parent())
+                .map_or(false, |it| it.kind() == SyntaxKind::RECORD_FIELD_PAT_LIST);
+        }
+        false
+    }
+
+    /// Whether the path segment is resolved in the scope of `module`: it's
+    /// either unqualified, or qualified by a path to the module, like
+    /// `self::A` or, from a child module, `super::A`.
+    fn goes_through_module(
+        &mut self,
+        file_id: FileId,
+        name_ref: &ast::NameRef,
+        module: Module,
+    ) -> bool {
+        let qualifier = match name_ref
+            .syntax()
+            .parent()
+            .and_then(ast::PathSegment::cast)
+            .and_then(|it| it.parent_path().qualifier())
+        {
+            Some(it) => it,
+            None => return true,
+        };
+        let analyzer = self.sb.analyze(InFile::new(file_id.into(), qualifier.syntax()), None);
+        analyzer.resolve_path(self.sb.db, &qualifier) == Some(PathResolution::Def(module.into()))
+    }
+}
+
+/// Private, `pub(self)`, `pub(super)`, `pub(crate)` and `pub(in path)` items
+/// can't be used outside of the crate.
+fn is_local_visibility(vis: Option<ast::Visibility>) -> bool {
+    let vis = match vis {
+        Some(it) => it,
+        None => return true,
+    };
+    match vis.kind() {
+        // `pub(self)` doesn't have a kind of its own.
+        ast::VisibilityKind::Pub => {
+            vis.syntax().children_with_tokens().any(|it| it.kind() == T![self])
+        }
+        _ => true,
+    }
+}
+
+/// Items with these attributes are used by the compiler or the test harness.
+fn is_entry_point(node: &SyntaxNode) -> bool {
+    attrs(node).any(|attr| match attr.simple_name() {
+        Some(name) => {
+            ["test", "bench", "no_mangle", "export_name", "lang"].contains(&name.as_str())
+        }
+        None => false,
+    })
+}
+
+fn is_repr(node: &SyntaxNode) -> bool {
+    attrs(node).any(|attr| attr.simple_name().map_or(false, |it| it == "repr"))
+}
+
+/// Checks for `#[allow(lint)]` or `#[allow(unused)]` on the node or on any of
+/// its parents.
+fn is_allowed(node: &SyntaxNode, lint: &str) -> bool {
+    node.ancestors().flat_map(|it| attrs(&it).collect::<Vec<_>>()).any(|attr| {
+        match attr.as_simple_call() {
+            Some((name, tt)) if name == "allow" => {
+                tt.syntax().children_with_tokens().filter_map(|it| it.into_token()).any(|it| {
+                    it.kind() == SyntaxKind::IDENT && [lint, "unused"].contains(&it.text().as_str())
+                })
+            }
+            _ => false,
+        }
+    })
+}
+
+fn attrs(node: &SyntaxNode) -> impl Iterator<Item = ast::Attr> {
+    node.children().filter_map(ast::Attr::cast)
+}
+
+/// Computes the range to delete to remove `use_tree`, together with its
+/// separating comma. If the tree is the only one in its list, the enclosing
+/// tree is removed instead, up to the whole `use` item.
+fn remove_use_tree_range(use_tree: &ast::UseTree) -> TextRange {
+    let mut tree = use_tree.clone();
+    while let Some(list) = tree.syntax().parent().and_then(ast::UseTreeList::cast) {
+        if list.use_trees().count() > 1 {
+            return range_with_comma(tree.syntax());
+        }
+        tree = list.parent_use_tree();
+    }
+    match tree.syntax().parent().and_then(ast::UseItem::cast) {
+        Some(use_item) => {
+            let range = use_item.syntax().text_range();
+            match use_item.syntax().next_sibling_or_token() {
+                Some(ws) if ws.kind() == SyntaxKind::WHITESPACE => {
+                    TextRange::from_to(range.start(), ws.text_range().end())
+                }
+                _ => range,
+            }
+        }
+        None => tree.syntax().text_range(),
+    }
+}
+
+fn range_with_comma(node: &SyntaxNode) -> TextRange {
+    let range = node.text_range();
+    if let Some(comma) = non_trivia_sibling(node.clone().into(), Direction::Next) {
+        if comma.kind() == T![,] {
+            let end = match non_trivia_sibling(comma.clone(), Direction::Next) {
+                Some(next) => next.text_range().start(),
+                None => comma.text_range().end(),
+            };
+            return TextRange::from_to(range.start(), end);
+        }
+    }
+    match non_trivia_sibling(node.clone().into(), Direction::Prev) {
+        Some(comma) if comma.kind() == T![,] => {
+            TextRange::from_to(comma.text_range().start(), range.end())
+        }
+        _ => range,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_utils::assert_eq_text;
+
+    use crate::mock_analysis::single_file;
+
+    fn check_unused(code: &str, expected: &[&str]) {
+        let (analysis, file_id) = single_file(code);
+        let unused = analysis.unused(file_id).unwrap();
+        let messages: Vec<&str> = unused.iter().map(|it| it.message.as_str()).collect();
+        assert_eq!(messages, expected);
+    }
+
+    fn check_remove_import(before: &str, after: &str) {
+        let (analysis, file_id) = single_file(before);
+        let diagnostic = analysis.unused(file_id).unwrap().pop().unwrap();
+        let mut fix = diagnostic.fix.unwrap();
+        let edit = fix.source_file_edits.pop().unwrap().edit;
+        let actual = edit.apply(&before);
+        assert_eq_text!(after, &actual);
+    }
+
+    #[test]
+    fn unused_functions() {
+        check_unused(
+            r"
+            fn main() { used() }
+            fn used() {}
+            fn unused() {}
+            fn recursive() { recursive() }
+            pub fn exported() {}
+            fn _ignored() {}
+            #[test]
+            fn test() {}
+            #[allow(dead_code)]
+            fn allowed() {}
+            ",
+            &["function `unused` is never used", "function `recursive` is never used"],
+        );
+    }
+
+    #[test]
+    fn unused_items_in_modules() {
+        check_unused(
+            r"
+            fn main() { foo::used(); }
+            mod foo {
+                pub(crate) fn used() { helper(Helper) }
+                pub(crate) fn unused() {}
+                struct Helper;
+                fn helper(_: Helper) {}
+                type Alias = ();
+                const C: u32 = 0;
+            }
+            ",
+            &[
+                "function `unused` is never used",
+                "type alias `Alias` is never used",
+                "constant `C` is never used",
+            ],
+        );
+    }
+
+    #[test]
+    fn unused_methods() {
+        check_unused(
+            r"
+            struct S;
+            impl S {
+                fn used(&self) {}
+                fn unused(&self) {}
+                pub fn exported(&self) {}
+            }
+            trait Tr { fn tr(&self); }
+            impl Tr for S { fn tr(&self) {} }
+            fn main() { S.used(); S.tr() }
+            ",
+            &["method `unused` is never used"],
+        );
+    }
+
+    #[test]
+    fn unused_fields_and_variants() {
+        check_unused(
+            r"
+            struct S { used: u32, unused: u32, pub exported: u32, bound: u32 }
+            enum E { Used, Unused }
+            fn main() {
+                let s = S { used: 0, unused: 0, exported: 0, bound: 0 };
+                let S { bound, .. } = s;
+                let _ = (s.used, bound, E::Used);
+            }
+            ",
+            &["variant `Unused` is never used"],
+        );
+        check_unused(
+            r"
+            struct S { used: u32, unused: u32 }
+            fn main() { let _ = S::new().used; }
+            impl S { pub fn new() -> S { loop {} } }
+            ",
+            &["field `unused` is never used"],
+        );
+    }
+
+    #[test]
+    fn restricted_visibility_is_local() {
+        check_unused(
+            r"
+            fn main() { a::b::used() }
+            mod a {
+                pub mod b {
+                    pub(super) fn unused_super() {}
+                    pub(in crate::a) fn unused_in() {}
+                    pub(self) fn unused_self() {}
+                    pub(crate) fn used() { super::c::helper() }
+                }
+                mod c {
+                    pub(super) fn helper() {}
+                }
+            }
+            ",
+            &[
+                "function `unused_super` is never used",
+                "function `unused_in` is never used",
+                "function `unused_self` is never used",
+            ],
+        );
+    }
+
+    #[test]
+    fn members_of_exported_items_are_not_checked() {
+        check_unused(
+            r"
+            pub enum E { A, B }
+            pub struct S { field: u32 }
+            fn main() {}
+            ",
+            &[],
+        );
+    }
+
+    #[test]
+    fn mentions_in_macros_count_as_uses() {
+        check_unused(
+            r"
+            macro_rules! call { ($e:expr) => { $e() } }
+            fn main() { call!(foo) }
+            fn foo() {}
+            ",
+            &[],
+        );
+    }
+
+    #[test]
+    fn unused_imports() {
+        check_unused(
+            r"
+            mod foo { pub struct A; pub struct B; pub struct C; pub trait T {} }
+            use foo::{A, B as Bee, C, T};
+            use foo::*;
+            fn main() { let _ = (Bee, foo::C); }
+            ",
+            &["unused import: `A`", "unused import: `C`"],
+        );
+    }
+
+    #[test]
+    fn imports_used_by_child_modules() {
+        check_unused(
+            r"
+            struct A;
+            struct B;
+            fn main() { m::f() }
+            mod m {
+                use super::{A, B};
+                pub(super) fn f() { inner::g(); inner::h(); }
+                mod inner {
+                    use super::*;
+                    pub(super) fn g() { let _ = A; }
+                    pub(super) fn h() { let _ = super::B; }
+                }
+            }
+            ",
+            &[],
+        );
+    }
+
+    #[test]
+    fn remove_unused_import() {
+        check_remove_import(
+            r"
+            mod foo { pub struct A; pub struct B; }
+            use foo::{A, B};
+            fn main() { let _ = A; }
+            ",
+            r"
+            mod foo { pub struct A; pub struct B; }
+            use foo::{A};
+            fn main() { let _ = A; }
+            ",
+        );
+        check_remove_import(
+            r"
+            mod foo { pub struct A; pub mod bar { pub struct B; } }
+            use foo::{A, bar::{B}};
+            fn main() { let _ = A; }
+            ",
+            r"
+            mod foo { pub struct A; pub mod bar { pub struct B; } }
+            use foo::{A};
+            fn main() { let _ = A; }
+            ",
+        );
+        check_remove_import(
+            r"
+            mod foo { pub struct A; }
+            use foo::A;
+            fn main() {}
+            ",
+            r"
+            mod foo { pub struct A; }
+            fn main() {}
+            ",
+        );
+    }
+}
@@ -15,6 +15,7 @@ use rayon::prelude::*;
 use rustc_hash::FxHashMap;
 
 use crate::{
+    library_cache::LibraryCache,
     symbol_index::{SymbolIndex, SymbolsDatabase},
     DebugData, RootDatabase,
 };
@@ -147,16 +148,41 @@ impl LibraryData {
         files: Vec<(FileId, RelativePathBuf, Arc<String>)>,
     ) -> LibraryData {
         let _p = profile("LibraryData::prepare");
+        let symbol_index = index_library(&files);
+        LibraryData::new(root_id, files, symbol_index)
+    }
 
-        #[cfg(not(feature = "wasm"))]
-        let iter = files.par_iter();
-        #[cfg(feature = "wasm")]
-        let iter = files.iter();
+    /// Like `prepare`, but loads the symbol index from `cache` if it is
+    /// present there, and stores it otherwise. `name` should identify the
+    /// library, like `serde-1.0.104`.
+    pub fn prepare_with_cache(
+        root_id: SourceRootId,
+        name: &str,
+        files: Vec<(FileId, RelativePathBuf, Arc<String>)>,
+        cache: &LibraryCache,
+    ) -> LibraryData {
+        let _p = profile("LibraryData::prepare_with_cache");
+        let symbol_index = match cache.load(name, &files) {
+            Some(it) => {
+                log::info!("loaded {} from library cache", name);
+                it
+            }
+            None => {
+                let symbol_index = index_library(&files);
+                if let Err(err) = cache.store(name, &files, &symbol_index) {
+                    log::warn!("failed to store {} in library cache: {}", name, err);
+                }
+                symbol_index
+            }
+        };
+        LibraryData::new(root_id, files, symbol_index)
+    }
 
-        let symbol_index = SymbolIndex::for_files(iter.map(|(file_id, _, text)| {
-            let parse = SourceFile::parse(text);
-            (*file_id, parse)
-        }));
+    fn new(
+        root_id: SourceRootId,
+        files: Vec<(FileId, RelativePathBuf, Arc<String>)>,
+        symbol_index: SymbolIndex,
+    ) -> LibraryData {
         let mut root_change = RootChange::default();
         root_change.added = files
             .into_iter()
@@ -166,6 +192,18 @@ impl LibraryData {
     }
 }
 
+fn index_library(files: &[(FileId, RelativePathBuf, Arc<String>)]) -> SymbolIndex {
+    #[cfg(not(feature = "wasm"))]
+    let iter = files.par_iter();
+    #[cfg(feature = "wasm")]
+    let iter = files.iter();
+
+    SymbolIndex::for_files(iter.map(|(file_id, _, text)| {
+        let parse = SourceFile::parse(text);
+        (*file_id, parse)
+    }))
+}
+
 const GC_COOLDOWN: time::Duration = time::Duration::from_millis(100);
 
 impl RootDatabase {
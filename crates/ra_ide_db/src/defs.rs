@@ -115,6 +115,18 @@ pub fn classify_name(
                     kind: NameKind::Macro(def),
                 })
             },
+            ast::MacroDef(it) => {
+                let src = name.with_value(it);
+                let def = sb.to_def(src.clone())?;
+
+                let module = sb.to_module_def(src.file_id.original_file(sb.db))?;
+
+                Some(NameDefinition {
+                    visibility: src.value.visibility(),
+                    container: module,
+                    kind: NameKind::Macro(def),
+                })
+            },
             ast::TypeParam(it) => {
                 let src = name.with_value(it);
                 let def = sb.to_def(src)?;
@@ -124,6 +136,15 @@ pub fn classify_name(
                     kind: NameKind::TypeParam(def),
                 })
             },
+            ast::ConstParam(it) => {
+                let src = name.with_value(it);
+                let def = sb.to_def(src)?;
+                Some(NameDefinition {
+                    visibility: None,
+                    container: def.module(sb.db),
+                    kind: NameKind::TypeParam(def),
+                })
+            },
             _ => None,
         }
     }
@@ -53,6 +53,7 @@ impl Default for FeatureFlags {
     fn default() -> FeatureFlags {
         FeatureFlags::new(&[
             ("lsp.diagnostics", true),
+            ("lsp.diagnostics.unused", false),
             ("completion.insertion.add-call-parenthesis", true),
             ("completion.enable-postfix", true),
             ("notifications.workspace-loaded", true),
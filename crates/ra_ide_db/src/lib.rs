@@ -7,6 +7,7 @@ pub mod line_index_utils;
 pub mod feature_flags;
 pub mod symbol_index;
 pub mod change;
+pub mod library_cache;
 pub mod defs;
 pub mod imports_locator;
 mod wasm_shims;
@@ -0,0 +1,493 @@
+//! On-disk cache for data derived from libraries.
+//!
+//! Libraries (the sysroot and crates.io dependencies) are assumed to never
+//! change, yet we used to re-parse and re-index all of them on every start.
+//! `LibraryCache` persists the `SymbolIndex` of each library source root under
+//! a cache directory, so that subsequent starts can skip parsing.
+//!
+//! An entry is named after the package of the library (like `serde-1.0.104`),
+//! and its file name contains a 128-bit fingerprint of the cache format and of
+//! the paths and contents of all files in the root. That is, a stale entry is
+//! never loaded: if a single file changes, we just look for a different entry.
+//! The entry itself records the paths and lengths of the files, which are
+//! checked again on load. Storing an entry removes the older entries of the
+//! same library, and the least recently stored entries once the cache grows
+//! over its size limit.
+//!
+//! Only symbol indices are cached. Def maps are salsa query results over the
+//! whole crate graph rather than per-library data, so persisting them is out
+//! of scope here: they are recomputed on each start, and the cache is opt-in.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
+
+use ra_db::{FileId, RelativePathBuf};
+use ra_syntax::{SmolStr, SyntaxKind, SyntaxNodePtr, TextRange, TextUnit};
+use rustc_hash::FxHashMap;
+
+use crate::symbol_index::{FileSymbol, SymbolIndex};
+
+/// Bump this when changing the layout of the cache files.
+const FORMAT_VERSION: u32 = 2;
+const SYMBOLS_EXT: &str = "symbols";
+const FST_EXT: &str = "fst";
+const TMP_EXT: &str = "tmp";
+const DEFAULT_SIZE_LIMIT: u64 = 512 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct LibraryCache {
+    dir: PathBuf,
+    size_limit: u64,
+}
+
+/// A single cached library, as reported by `LibraryCache::entries`.
+#[derive(Debug)]
+pub struct CacheEntry {
+    pub name: String,
+    pub path: PathBuf,
+    /// Total size of the entry on disk, in bytes.
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+impl LibraryCache {
+    pub fn new(dir: PathBuf) -> LibraryCache {
+        LibraryCache { dir, size_limit: DEFAULT_SIZE_LIMIT }
+    }
+
+    /// Sets the total size, in bytes, above which the least recently stored
+    /// entries are evicted.
+    pub fn with_size_limit(mut self, size_limit: u64) -> LibraryCache {
+        self.size_limit = size_limit;
+        self
+    }
+
+    /// The cache directory to use if none is specified explicitly:
+    /// `$RA_CACHE_DIR`, or `rust-analyzer` in the platform cache directory.
+    pub fn default_dir() -> Option<PathBuf> {
+        if let Some(dir) = std::env::var_os("RA_CACHE_DIR") {
+            return Some(dir.into());
+        }
+        let base = match std::env::var_os("XDG_CACHE_HOME") {
+            Some(it) => PathBuf::from(it),
+            None => {
+                let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+                PathBuf::from(home).join(".cache")
+            }
+        };
+        Some(base.join("rust-analyzer"))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Lists all libraries stored in the cache.
+    pub fn entries(&self) -> io::Result<Vec<CacheEntry>> {
+        let mut res = Vec::new();
+        let read_dir = match fs::read_dir(&self.dir) {
+            Ok(it) => it,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(res),
+            Err(err) => return Err(err),
+        };
+        for entry in read_dir {
+            let path = entry?.path();
+            if path.extension().and_then(|it| it.to_str()) != Some(SYMBOLS_EXT) {
+                continue;
+            }
+            let name = match path.file_stem().and_then(|it| it.to_str()) {
+                // strip the `-hash` suffix
+                Some(stem) => stem.rsplitn(2, '-').nth(1).unwrap_or(stem).to_string(),
+                None => continue,
+            };
+            let metadata = match fs::metadata(&path) {
+                Ok(it) => it,
+                // Removed concurrently by another instance.
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            };
+            let fst_path = path.with_extension(FST_EXT);
+            let size = metadata.len() + fs::metadata(&fst_path).map_or(0, |it| it.len());
+            let modified = metadata.modified()?;
+            res.push(CacheEntry { name, path, size, modified });
+        }
+        res.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(res)
+    }
+
+    /// Removes all entries from the cache, returning their number.
+    pub fn purge(&self) -> io::Result<usize> {
+        let entries = self.entries()?;
+        for entry in entries.iter() {
+            remove_entry(&entry.path)?;
+        }
+        // Leftovers of instances which were killed while storing an entry.
+        if let Ok(read_dir) = fs::read_dir(&self.dir) {
+            for entry in read_dir {
+                let path = entry?.path();
+                if path.extension().and_then(|it| it.to_str()) == Some(TMP_EXT) {
+                    remove_file_if_exists(&path)?;
+                }
+            }
+        }
+        Ok(entries.len())
+    }
+
+    pub(crate) fn load(
+        &self,
+        name: &str,
+        files: &[(FileId, RelativePathBuf, Arc<String>)],
+    ) -> Option<SymbolIndex> {
+        let path = self.entry_path(name, files)?;
+        let symbols = read_symbols(&path, files).ok()??;
+        let fst = fs::read(path.with_extension(FST_EXT)).ok()?;
+        SymbolIndex::from_parts(symbols, fst)
+    }
+
+    pub(crate) fn store(
+        &self,
+        name: &str,
+        files: &[(FileId, RelativePathBuf, Arc<String>)],
+        symbol_index: &SymbolIndex,
+    ) -> io::Result<()> {
+        let path = match self.entry_path(name, files) {
+            Some(it) => it,
+            None => return Ok(()),
+        };
+        fs::create_dir_all(&self.dir)?;
+        // Write the fst first: an entry without `.symbols` is never loaded.
+        write_atomically(&path.with_extension(FST_EXT), |tmp_path| {
+            fs::write(tmp_path, symbol_index.fst_bytes())
+        })?;
+        write_atomically(&path, |tmp_path| write_symbols(tmp_path, files, symbol_index.symbols()))?;
+        self.evict(&path)
+    }
+
+    /// Removes the other entries of the library stored at `path`, which are
+    /// stale, and then the oldest entries until the cache fits its size limit.
+    fn evict(&self, path: &Path) -> io::Result<()> {
+        let mut entries = self.entries()?;
+        let name = match entries.iter().find(|entry| entry.path == path) {
+            Some(entry) => entry.name.clone(),
+            None => return Ok(()),
+        };
+        let mut stale = Vec::new();
+        entries.retain(|entry| {
+            let is_stale = entry.name == name && entry.path != path;
+            if is_stale {
+                stale.push(entry.path.clone());
+            }
+            !is_stale
+        });
+        for path in stale {
+            remove_entry(&path)?;
+        }
+
+        let mut total_size: u64 = entries.iter().map(|entry| entry.size).sum();
+        entries.sort_by_key(|entry| entry.modified);
+        for entry in entries {
+            if total_size <= self.size_limit {
+                break;
+            }
+            if entry.path == path {
+                continue;
+            }
+            remove_entry(&entry.path)?;
+            total_size -= entry.size;
+        }
+        Ok(())
+    }
+
+    fn entry_path(
+        &self,
+        name: &str,
+        files: &[(FileId, RelativePathBuf, Arc<String>)],
+    ) -> Option<PathBuf> {
+        // We use a line-based format, so we can't store weird paths.
+        if files.iter().any(|(_, path, _)| path.as_str().contains(|c: char| c == '\n' || c == '\t'))
+        {
+            return None;
+        }
+        let name: String = name
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+            .collect();
+
+        let file_name = format!("{}-{:032x}.{}", name, fingerprint(files), SYMBOLS_EXT);
+        Some(self.dir.join(file_name))
+    }
+}
+
+/// Entries are looked up by fingerprint, so it must be wide enough for
+/// collisions to be practically impossible. We combine two differently seeded
+/// SipHash runs, as std has nothing wider. If std ever changes the algorithm,
+/// we just miss the old entries.
+fn fingerprint(files: &[(FileId, RelativePathBuf, Arc<String>)]) -> u128 {
+    let hash = |seed: u64| {
+        let mut hasher = DefaultHasher::new();
+        seed.hash(&mut hasher);
+        FORMAT_VERSION.hash(&mut hasher);
+        (SyntaxKind::__LAST as u16).hash(&mut hasher);
+        for (_, path, text) in files.iter() {
+            path.as_str().hash(&mut hasher);
+            text.hash(&mut hasher);
+        }
+        hasher.finish()
+    };
+    u128::from(hash(0)) << 64 | u128::from(hash(1))
+}
+
+/// Writes `path` through a temporary file which is then renamed, so that
+/// readers never see a partially written file. The temporary file is unique
+/// to this write, as several instances may store the same entry at once.
+fn write_atomically(path: &Path, write: impl FnOnce(&Path) -> io::Result<()>) -> io::Result<()> {
+    static NEXT_TMP_ID: AtomicUsize = AtomicUsize::new(0);
+    let tmp_id = NEXT_TMP_ID.fetch_add(1, Ordering::Relaxed);
+    let tmp_path = path.with_extension(format!("{}-{}.{}", process::id(), tmp_id, TMP_EXT));
+    let res = write(&tmp_path).and_then(|()| fs::rename(&tmp_path, path));
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    res
+}
+
+fn remove_entry(path: &Path) -> io::Result<()> {
+    // Remove `.symbols` first, so that the entry is never loaded without its fst.
+    remove_file_if_exists(path)?;
+    remove_file_if_exists(&path.with_extension(FST_EXT))
+}
+
+/// Another instance may be removing the same file.
+fn remove_file_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        res => res,
+    }
+}
+
+// The `.symbols` file starts with the list of file paths and lengths, followed
+// by one symbol per line. File ids are not stable across runs, so symbols refer to
+// files by their index in the list.
+fn write_symbols(
+    path: &Path,
+    files: &[(FileId, RelativePathBuf, Arc<String>)],
+    symbols: &[FileSymbol],
+) -> io::Result<()> {
+    let file_idx: FxHashMap<FileId, usize> =
+        files.iter().enumerate().map(|(idx, (file_id, _, _))| (*file_id, idx)).collect();
+
+    let mut out = BufWriter::new(fs::File::create(path)?);
+    writeln!(out, "{}", files.len())?;
+    for (_, path, text) in files.iter() {
+        writeln!(out, "{}\t{}", path.as_str(), text.len())?;
+    }
+    writeln!(out, "{}", symbols.len())?;
+    for symbol in symbols {
+        let range = symbol.ptr.range();
+        let (name_start, name_end) = match symbol.name_range {
+            Some(it) => (it.start().to_usize().to_string(), it.end().to_usize().to_string()),
+            None => ("-".to_string(), "-".to_string()),
+        };
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            file_idx[&symbol.file_id],
+            symbol.name,
+            u16::from(symbol.ptr.kind()),
+            range.start().to_usize(),
+            range.end().to_usize(),
+            name_start,
+            name_end,
+            symbol.container_name.as_ref().map_or("-", |it| it.as_str()),
+        )?;
+    }
+    out.flush()
+}
+
+/// Returns `Ok(None)` if the file is malformed.
+fn read_symbols(
+    path: &Path,
+    files: &[(FileId, RelativePathBuf, Arc<String>)],
+) -> io::Result<Option<Vec<FileSymbol>>> {
+    let mut lines = BufReader::new(fs::File::open(path)?).lines();
+    let mut next_line = || -> io::Result<Option<String>> { lines.next().transpose() };
+
+    let n_files: usize = match next_line()?.and_then(|it| it.parse().ok()) {
+        Some(it) => it,
+        None => return Ok(None),
+    };
+    if n_files != files.len() {
+        return Ok(None);
+    }
+    for (_, path, text) in files.iter() {
+        let line = match next_line()? {
+            Some(it) => it,
+            None => return Ok(None),
+        };
+        if line != format!("{}\t{}", path.as_str(), text.len()) {
+            return Ok(None);
+        }
+    }
+
+    let n_symbols: usize = match next_line()?.and_then(|it| it.parse().ok()) {
+        Some(it) => it,
+        None => return Ok(None),
+    };
+    let mut symbols = Vec::with_capacity(n_symbols);
+    for _ in 0..n_symbols {
+        let symbol = next_line()?.and_then(|line| parse_symbol(&line, files));
+        match symbol {
+            Some(it) => symbols.push(it),
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(symbols))
+}
+
+fn parse_symbol(
+    line: &str,
+    files: &[(FileId, RelativePathBuf, Arc<String>)],
+) -> Option<FileSymbol> {
+    fn offset(s: &str) -> Option<TextUnit> {
+        s.parse::<u32>().ok().map(TextUnit::from)
+    }
+    fn range(start: &str, end: &str) -> Option<TextRange> {
+        let (start, end) = (offset(start)?, offset(end)?);
+        if start > end {
+            return None;
+        }
+        Some(TextRange::from_to(start, end))
+    }
+
+    let fields: Vec<&str> = line.split('\t').collect();
+    if fields.len() != 8 {
+        return None;
+    }
+    let (file_id, _, _) = files.get(fields[0].parse::<usize>().ok()?)?;
+    let kind = fields[2].parse::<u16>().ok()?;
+    if kind >= SyntaxKind::__LAST as u16 {
+        return None;
+    }
+    let range = range(fields[3], fields[4])?;
+    let name_range = match (fields[5], fields[6]) {
+        ("-", "-") => None,
+        (start, end) => Some(range(start, end)?),
+    };
+    let container_name = match fields[7] {
+        "-" => None,
+        it => Some(SmolStr::new(it)),
+    };
+    Some(FileSymbol {
+        file_id: *file_id,
+        name: SmolStr::new(fields[1]),
+        ptr: SyntaxNodePtr::from_parts(range, SyntaxKind::from(kind)),
+        name_range,
+        container_name,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ra_db::{FileId, RelativePathBuf};
+    use rayon::prelude::*;
+
+    use super::{parse_symbol, LibraryCache};
+    use crate::symbol_index::SymbolIndex;
+
+    #[test]
+    fn library_cache_roundtrip() {
+        let dir =
+            std::env::temp_dir().join(format!("ra-library-cache-test-{}", std::process::id()));
+        let cache = LibraryCache::new(dir.clone());
+        let files = vec![
+            (
+                FileId(0),
+                RelativePathBuf::from("lib.rs"),
+                Arc::new("mod foo; struct Vec; impl Vec { fn push() {} }".to_string()),
+            ),
+            (FileId(1), RelativePathBuf::from("foo.rs"), Arc::new("fn bar() {}".to_string())),
+        ];
+        let index = SymbolIndex::for_files(
+            files
+                .par_iter()
+                .map(|(file_id, _, text)| (*file_id, ra_syntax::SourceFile::parse(text))),
+        );
+
+        assert!(cache.load("foo-1.0.0", &files).is_none());
+        cache.store("foo-1.0.0", &files, &index).unwrap();
+        assert_eq!(cache.load("foo-1.0.0", &files), Some(index));
+
+        let mut changed = files.clone();
+        changed[1].2 = Arc::new("fn baz() {}".to_string());
+        assert!(cache.load("foo-1.0.0", &changed).is_none());
+
+        let entries = cache.entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "foo-1.0.0");
+        assert_eq!(cache.purge().unwrap(), 1);
+        assert!(cache.entries().unwrap().is_empty());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn library_cache_rejects_corrupt_ranges() {
+        let files = vec![(FileId(0), RelativePathBuf::from("lib.rs"), Arc::new(String::new()))];
+        assert!(parse_symbol("0\tfoo\t1\t0\t3\t-\t-\t-", &files).is_some());
+        assert!(parse_symbol("0\tfoo\t1\t3\t0\t-\t-\t-", &files).is_none());
+        assert!(parse_symbol("0\tfoo\t1\t0\t3\t2\t1\t-", &files).is_none());
+    }
+
+    #[test]
+    fn library_cache_eviction() {
+        let dir = std::env::temp_dir()
+            .join(format!("ra-library-cache-eviction-test-{}", std::process::id()));
+        let cache = LibraryCache::new(dir.clone());
+        let files = |text: &str| {
+            vec![(FileId(0), RelativePathBuf::from("lib.rs"), Arc::new(text.to_string()))]
+        };
+        let index = |files: &[(FileId, RelativePathBuf, Arc<String>)]| {
+            SymbolIndex::for_files(
+                files
+                    .par_iter()
+                    .map(|(file_id, _, text)| (*file_id, ra_syntax::SourceFile::parse(text))),
+            )
+        };
+        let names = |cache: &LibraryCache| {
+            cache.entries().unwrap().into_iter().map(|it| it.name).collect::<Vec<_>>()
+        };
+
+        let foo = files("fn foo() {}");
+        let bar = files("fn bar() {}");
+        cache.store("foo-1.0.0", &foo, &index(&foo)).unwrap();
+        cache.store("bar-1.0.0", &bar, &index(&bar)).unwrap();
+        assert_eq!(names(&cache), vec!["bar-1.0.0", "foo-1.0.0"]);
+
+        // A new version of the same library replaces the old one.
+        let changed = files("fn foo() {} fn baz() {}");
+        cache.store("foo-1.0.0", &changed, &index(&changed)).unwrap();
+        assert_eq!(names(&cache), vec!["bar-1.0.0", "foo-1.0.0"]);
+        assert!(cache.load("foo-1.0.0", &foo).is_none());
+        assert!(cache.load("foo-1.0.0", &changed).is_some());
+
+        // The entry just stored is kept even if it doesn't fit.
+        let cache = cache.with_size_limit(0);
+        cache.store("bar-1.0.0", &bar, &index(&bar)).unwrap();
+        assert_eq!(names(&cache), vec!["bar-1.0.0"]);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -19,8 +19,15 @@
 //! for each library (which is assumed to never change) and an FST for each Rust
 //! file in the current workspace, and run a query against the union of all
 //! those FSTs.
+//!
+//! The FST only tells us which names contain the query as a subsequence, so the
+//! matches are then ranked: exact matches come first, followed by prefix
+//! matches, CamelCase-hump matches (`HM` for `HashMap`) and finally arbitrary
+//! subsequence matches.
 
 use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
     fmt,
     hash::{Hash, Hasher},
     mem,
@@ -47,7 +54,11 @@ use crate::RootDatabase;
 pub struct Query {
     query: String,
     lowercased: String,
+    /// For `Vec::push`-like queries, the (lowercased) name of the container,
+    /// that is, `vec`.
+    container: Option<String>,
     only_types: bool,
+    only_functions: bool,
     libs: bool,
     exact: bool,
     limit: usize,
@@ -55,21 +66,41 @@ pub struct Query {
 
 impl Query {
     pub fn new(query: String) -> Query {
+        let (container, query) = match query.rfind("::") {
+            Some(idx) => {
+                let container = query[..idx].rsplit("::").next().unwrap_or_default().trim();
+                let container =
+                    if container.is_empty() { None } else { Some(container.to_lowercase()) };
+                (container, query[idx + 2..].trim().to_string())
+            }
+            None => (None, query),
+        };
         let lowercased = query.to_lowercase();
         Query {
             query,
             lowercased,
+            container,
             only_types: false,
+            only_functions: false,
             libs: false,
             exact: false,
             limit: usize::max_value(),
         }
     }
 
+    /// Whether the query is qualified with a container, like `Vec::push`.
+    pub fn has_container(&self) -> bool {
+        self.container.is_some()
+    }
+
     pub fn only_types(&mut self) {
         self.only_types = true;
     }
 
+    pub fn only_functions(&mut self) {
+        self.only_functions = true;
+    }
+
     pub fn libs(&mut self) {
         self.libs = true;
     }
@@ -219,6 +250,22 @@ impl SymbolIndex {
         SymbolIndex { symbols, map }
     }
 
+    /// Restores an index from `symbols` (in the order of `SymbolIndex::symbols`)
+    /// and the bytes of the corresponding fst, as persisted by the
+    /// `LibraryCache`.
+    pub(crate) fn from_parts(symbols: Vec<FileSymbol>, fst: Vec<u8>) -> Option<SymbolIndex> {
+        let map = fst::Map::from_bytes(fst).ok()?;
+        Some(SymbolIndex { symbols, map })
+    }
+
+    pub(crate) fn symbols(&self) -> &[FileSymbol] {
+        &self.symbols
+    }
+
+    pub(crate) fn fst_bytes(&self) -> Vec<u8> {
+        self.map.as_fst().to_vec()
+    }
+
     pub fn len(&self) -> usize {
         self.symbols.len()
     }
@@ -263,17 +310,18 @@ impl SymbolIndex {
 
 impl Query {
     pub(crate) fn search(self, indices: &[Arc<SymbolIndex>]) -> Vec<FileSymbol> {
+        if self.limit == 0 {
+            return Vec::new();
+        }
         let mut op = fst::map::OpBuilder::new();
         for file_symbols in indices.iter() {
             let automaton = fst::automaton::Subsequence::new(&self.lowercased);
             op = op.add(file_symbols.map.search(automaton))
         }
         let mut stream = op.union();
-        let mut res = Vec::new();
-        while let Some((_, indexed_values)) = stream.next() {
-            if res.len() >= self.limit {
-                break;
-            }
+        // Keeps the best `limit` matches, with the worst one on top.
+        let mut res: BinaryHeap<RankedSymbol> = BinaryHeap::new();
+        'stream: while let Some((_, indexed_values)) = stream.next() {
             for indexed_value in indexed_values {
                 let symbol_index = &indices[indexed_value.index];
                 let (start, end) = SymbolIndex::map_value_to_range(indexed_value.value);
@@ -282,14 +330,147 @@ impl Query {
                     if self.only_types && !is_type(symbol.ptr.kind()) {
                         continue;
                     }
+                    if self.only_functions && symbol.ptr.kind() != FN_DEF {
+                        continue;
+                    }
                     if self.exact && symbol.name != self.query {
                         continue;
                     }
-                    res.push(symbol.clone());
+                    if let Some(container) = &self.container {
+                        let matches_container = symbol
+                            .container_name
+                            .as_ref()
+                            .map_or(false, |it| match_kind(container, it.as_str()).is_some());
+                        if !matches_container {
+                            continue;
+                        }
+                    }
+                    let kind = match match_kind(&self.query, symbol.name.as_str()) {
+                        Some(it) => it,
+                        None => continue,
+                    };
+                    let ranked = RankedSymbol::new(kind, symbol);
+                    if res.len() == self.limit {
+                        match res.peek() {
+                            Some(worst) if ranked < *worst => {
+                                res.pop();
+                            }
+                            _ => continue,
+                        }
+                    }
+                    res.push(ranked);
+                    // Nothing can beat a full set of exact matches.
+                    if res.len() == self.limit
+                        && res.peek().map_or(false, |it| it.kind == MatchKind::Exact)
+                    {
+                        break 'stream;
+                    }
                 }
             }
         }
-        res
+        res.into_sorted_vec().into_iter().map(|it| it.symbol).collect()
+    }
+}
+
+/// A matching symbol, ordered from the best match to the worst.
+#[derive(Debug)]
+struct RankedSymbol {
+    kind: MatchKind,
+    symbol: FileSymbol,
+}
+
+impl RankedSymbol {
+    fn new(kind: MatchKind, symbol: &FileSymbol) -> RankedSymbol {
+        RankedSymbol { kind, symbol: symbol.clone() }
+    }
+
+    fn key(&self) -> (Reverse<MatchKind>, usize, &SmolStr) {
+        (Reverse(self.kind), self.symbol.name.len(), &self.symbol.name)
+    }
+}
+
+impl PartialEq for RankedSymbol {
+    fn eq(&self, other: &RankedSymbol) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for RankedSymbol {}
+
+impl PartialOrd for RankedSymbol {
+    fn partial_cmp(&self, other: &RankedSymbol) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RankedSymbol {
+    fn cmp(&self, other: &RankedSymbol) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// How well a symbol name matches the query, worst to best.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum MatchKind {
+    Subsequence,
+    CamelHumps,
+    Prefix,
+    ExactIgnoreCase,
+    Exact,
+}
+
+fn match_kind(query: &str, name: &str) -> Option<MatchKind> {
+    if query == name {
+        return Some(MatchKind::Exact);
+    }
+    let query_lower = query.to_lowercase();
+    let name_lower = name.to_lowercase();
+    if query_lower == name_lower {
+        return Some(MatchKind::ExactIgnoreCase);
+    }
+    if name_lower.starts_with(&query_lower) {
+        return Some(MatchKind::Prefix);
+    }
+    let query_chars: Vec<char> = query_lower.chars().collect();
+    let name_chars: Vec<char> = name.chars().collect();
+    if camel_humps_match(&query_chars, &name_chars, 0) {
+        return Some(MatchKind::CamelHumps);
+    }
+    let mut name_chars = name_lower.chars();
+    if query_lower.chars().all(|q| name_chars.any(|n| n == q)) {
+        return Some(MatchKind::Subsequence);
+    }
+    None
+}
+
+/// Checks if each char of `query` (lowercased) either continues the hump of
+/// `name` matched so far, or starts a later one. Humps start at the beginning of the name, at
+/// uppercase letters following a non-uppercase one and after underscores, so
+/// `hm` and `hamap` both match `HashMap`, and `fb` matches `foo_bar`.
+fn camel_humps_match(query: &[char], name: &[char], pos: usize) -> bool {
+    let (&q, rest) = match query.split_first() {
+        Some(it) => it,
+        None => return true,
+    };
+    if pos < name.len()
+        && !is_hump_start(name, pos)
+        && name[pos].to_lowercase().eq(std::iter::once(q))
+        && camel_humps_match(rest, name, pos + 1)
+    {
+        return true;
+    }
+    (pos..name.len()).filter(|&idx| is_hump_start(name, idx)).any(|idx| {
+        name[idx].to_lowercase().eq(std::iter::once(q)) && camel_humps_match(rest, name, idx + 1)
+    })
+}
+
+fn is_hump_start(name: &[char], idx: usize) -> bool {
+    if name[idx] == '_' {
+        return false;
+    }
+    match idx.checked_sub(1).map(|prev| name[prev]) {
+        None | Some('_') => true,
+        Some(prev) => name[idx].is_uppercase() && !prev.is_uppercase(),
     }
 }
 
@@ -323,11 +504,13 @@ fn source_file_to_file_symbols(source_file: &SourceFile, file_id: FileId) -> Vec
 
                     stack.push(symbol.name.clone());
                     symbols.push(symbol);
+                } else if let Some(name) = impl_block_container_name(&node) {
+                    stack.push(name);
                 }
             }
 
             WalkEvent::Leave(node) => {
-                if to_symbol(&node).is_some() {
+                if to_symbol(&node).is_some() || impl_block_container_name(&node).is_some() {
                     stack.pop();
                 }
             }
@@ -337,6 +520,18 @@ fn source_file_to_file_symbols(source_file: &SourceFile, file_id: FileId) -> Vec
     symbols
 }
 
+/// Items inside `impl Foo<T> { ... }` and `impl Trait for Foo<T> { ... }` use
+/// `Foo` as their container name.
+fn impl_block_container_name(node: &SyntaxNode) -> Option<SmolStr> {
+    let impl_block = ast::ImplBlock::cast(node.clone())?;
+    let self_ty = match impl_block.target_type()? {
+        ast::TypeRef::PathType(it) => it,
+        _ => return None,
+    };
+    let segment = self_ty.path()?.segment()?;
+    Some(segment.name_ref()?.text().clone())
+}
+
 fn to_symbol(node: &SyntaxNode) -> Option<(SmolStr, SyntaxNodePtr, TextRange)> {
     fn decl<N: NameOwner>(node: N) -> Option<(SmolStr, SyntaxNodePtr, TextRange)> {
         let name = node.name()?;
@@ -370,3 +565,20 @@ fn to_file_symbol(node: &SyntaxNode, file_id: FileId) -> Option<FileSymbol> {
         container_name: None,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{match_kind, MatchKind};
+
+    #[test]
+    fn test_match_kind() {
+        assert_eq!(match_kind("HashMap", "HashMap"), Some(MatchKind::Exact));
+        assert_eq!(match_kind("hashmap", "HashMap"), Some(MatchKind::ExactIgnoreCase));
+        assert_eq!(match_kind("hash", "HashMap"), Some(MatchKind::Prefix));
+        assert_eq!(match_kind("hm", "HashMap"), Some(MatchKind::CamelHumps));
+        assert_eq!(match_kind("hamap", "HashMap"), Some(MatchKind::CamelHumps));
+        assert_eq!(match_kind("fb", "foo_bar"), Some(MatchKind::CamelHumps));
+        assert_eq!(match_kind("hsp", "HashMap"), Some(MatchKind::Subsequence));
+        assert_eq!(match_kind("mh", "HashMap"), None);
+    }
+}
@@ -41,6 +41,17 @@ pub struct ServerConfig {
     #[serde(deserialize_with = "nullable_bool_true")]
     pub with_sysroot: bool,
 
+    /// Whether to persist symbol indices of libraries on disk. Def maps are
+    /// not cached.
+    ///
+    /// Defaults to `false`
+    #[serde(deserialize_with = "nullable_bool_false")]
+    pub use_library_cache: bool,
+
+    /// How many tokens macro expansions can produce, per crate and per
+    /// function body.
+    pub macro_token_limit: Option<usize>,
+
     /// Fine grained feature flags to disable specific features.
     pub feature_flags: FxHashMap<String, bool>,
 
@@ -61,6 +72,8 @@ impl Default for ServerConfig {
             cargo_watch_command: "check".to_string(),
             cargo_watch_all_targets: true,
             with_sysroot: true,
+            use_library_cache: false,
+            macro_token_limit: None,
             feature_flags: FxHashMap::default(),
             cargo_features: Default::default(),
         }
@@ -99,6 +99,7 @@ impl Conv for Severity {
         match self {
             Severity::Error => DiagnosticSeverity::Error,
             Severity::WeakWarning => DiagnosticSeverity::Hint,
+            Severity::Unused => DiagnosticSeverity::Hint,
         }
     }
 }
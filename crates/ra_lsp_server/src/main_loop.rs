@@ -18,9 +18,9 @@ use crossbeam_channel::{select, unbounded, RecvError, Sender};
 use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
 use lsp_types::{ClientCapabilities, NumberOrString};
 use ra_cargo_watch::{url_from_path_with_drive_lowercasing, CheckOptions, CheckTask};
-use ra_ide::{Canceled, FeatureFlags, FileId, LibraryData, SourceRootId};
+use ra_ide::{Canceled, FeatureFlags, FileId, LibraryCache, LibraryData, SourceRootId};
 use ra_prof::profile;
-use ra_vfs::{VfsFile, VfsTask, Watch};
+use ra_vfs::{VfsFile, VfsRoot, VfsTask, Watch};
 use relative_path::RelativePathBuf;
 use rustc_hash::FxHashSet;
 use serde::{de::DeserializeOwned, Serialize};
@@ -178,6 +178,12 @@ pub fn main_loop(
                     command: config.cargo_watch_command,
                     all_targets: config.cargo_watch_all_targets,
                 },
+                library_cache: if config.use_library_cache {
+                    LibraryCache::default_dir().map(LibraryCache::new)
+                } else {
+                    None
+                },
+                macro_token_limit: config.macro_token_limit,
             }
         };
 
@@ -403,9 +409,17 @@ fn loop_turn(
         let (root, files) = loop_state.pending_libraries.pop().unwrap();
         loop_state.in_flight_libraries += 1;
         let sender = libdata_sender.clone();
+        let cache = world_state.options.library_cache.clone();
+        let root_path = world_state.vfs.read().root2path(VfsRoot(root.0));
+        let name = world_state.package_names.get(&root_path).cloned();
         pool.execute(move || {
             log::info!("indexing {:?} ... ", root);
-            let data = LibraryData::prepare(root, files);
+            let data = match (cache, name) {
+                (Some(cache), Some(name)) => {
+                    LibraryData::prepare_with_cache(root, &name, files, &cache)
+                }
+                _ => LibraryData::prepare(root, files),
+            };
             sender.send(data).unwrap();
         });
     }
@@ -501,6 +515,8 @@ fn on_request(
         .on::<req::AnalyzerStatus>(handlers::handle_analyzer_status)?
         .on::<req::SyntaxTree>(handlers::handle_syntax_tree)?
         .on::<req::ExpandMacro>(handlers::handle_expand_macro)?
+        .on::<req::ExpandMacroSteps>(handlers::handle_expand_macro_steps)?
+        .on::<req::ExplainMethodResolution>(handlers::handle_explain_method_resolution)?
         .on::<req::OnTypeFormatting>(handlers::handle_on_type_formatting)?
         .on::<req::DocumentSymbolRequest>(handlers::handle_document_symbol)?
         .on::<req::WorkspaceSymbol>(handlers::handle_workspace_symbol)?
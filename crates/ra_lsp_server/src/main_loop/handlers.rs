@@ -13,14 +13,14 @@ use lsp_types::{
     CallHierarchyIncomingCall, CallHierarchyIncomingCallsParams, CallHierarchyItem,
     CallHierarchyOutgoingCall, CallHierarchyOutgoingCallsParams, CallHierarchyPrepareParams,
     CodeAction, CodeActionOrCommand, CodeActionResponse, CodeLens, Command, CompletionItem,
-    Diagnostic, DocumentFormattingParams, DocumentHighlight, DocumentSymbol, FoldingRange,
-    FoldingRangeParams, Hover, HoverContents, Location, MarkupContent, MarkupKind, Position,
-    PrepareRenameResponse, Range, RenameParams, SymbolInformation, TextDocumentIdentifier,
-    TextEdit, WorkspaceEdit,
+    Diagnostic, DiagnosticTag, DocumentFormattingParams, DocumentHighlight, DocumentSymbol,
+    FoldingRange, FoldingRangeParams, Hover, HoverContents, Location, MarkupContent, MarkupKind,
+    Position, PrepareRenameResponse, Range, RenameParams, SymbolInformation,
+    TextDocumentIdentifier, TextEdit, WorkspaceEdit,
 };
 use ra_ide::{
-    AssistId, FileId, FilePosition, FileRange, Query, RangeInfo, Runnable, RunnableKind,
-    SearchScope,
+    AssistId, FileId, FilePosition, FileRange, MetaVarFragments, Query, RangeInfo, RenameError,
+    Runnable, RunnableKind, SearchScope, Severity,
 };
 use ra_prof::profile;
 use ra_syntax::{AstNode, SyntaxKind, TextRange, TextUnit};
@@ -80,6 +80,98 @@ pub fn handle_expand_macro(
     }
 }
 
+pub fn handle_expand_macro_steps(
+    world: WorldSnapshot,
+    params: req::ExpandMacroParams,
+) -> Result<Option<req::MacroExpansionStep>> {
+    let _p = profile("handle_expand_macro_steps");
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let line_index = world.analysis().file_line_index(file_id)?;
+    let offset = match params.position {
+        None => return Ok(None),
+        Some(position) => position.conv_with(&line_index),
+    };
+
+    let res = world.analysis().expand_macro_steps(FilePosition { file_id, offset })?;
+    return res.map(|node| to_step(&world, node)).transpose();
+
+    fn file_range_location(world: &WorldSnapshot, range: FileRange) -> Result<Location> {
+        let line_index = world.analysis().file_line_index(range.file_id)?;
+        to_location(range.file_id, range.range, world, &line_index)
+    }
+
+    fn to_step(
+        world: &WorldSnapshot,
+        node: ra_ide::MacroExpansionNode,
+    ) -> Result<req::MacroExpansionStep> {
+        let bindings = node
+            .bindings
+            .into_iter()
+            .map(|binding| {
+                let fragments = to_fragments(world, binding.fragments)?;
+                Ok(req::MacroBinding { name: binding.name, fragments })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let children =
+            node.children.into_iter().map(|it| to_step(world, it)).collect::<Result<Vec<_>>>()?;
+        Ok(req::MacroExpansionStep {
+            name: node.name,
+            location: file_range_location(world, node.range)?,
+            rule: node.rule,
+            bindings,
+            expansion: node.expansion,
+            children,
+        })
+    }
+
+    fn to_fragments(
+        world: &WorldSnapshot,
+        fragments: MetaVarFragments<FileRange>,
+    ) -> Result<req::MacroFragments> {
+        let res = match fragments {
+            MetaVarFragments::Fragment(text, range) => {
+                let location = range.map(|it| file_range_location(world, it)).transpose()?;
+                req::MacroFragments::Fragment(req::MacroFragment { text, location })
+            }
+            MetaVarFragments::Repeated(it) => req::MacroFragments::Repeated(
+                it.into_iter().map(|it| to_fragments(world, it)).collect::<Result<Vec<_>>>()?,
+            ),
+        };
+        Ok(res)
+    }
+}
+
+pub fn handle_explain_method_resolution(
+    world: WorldSnapshot,
+    params: req::TextDocumentPositionParams,
+) -> Result<Option<req::MethodLookupExplanation>> {
+    let _p = profile("handle_explain_method_resolution");
+    let position = params.try_conv_with(&world)?;
+    let explanation = match world.analysis().explain_method_resolution(position)? {
+        None => return Ok(None),
+        Some(it) => it,
+    };
+    let mut steps = Vec::new();
+    for step in explanation.steps {
+        let mut candidates = Vec::new();
+        for candidate in step.candidates {
+            let impls = candidate.impls.iter().try_conv_with_to_vec(&world)?;
+            candidates.push(req::MethodLookupCandidate {
+                signature: candidate.signature,
+                location: candidate.target.try_conv_with(&world)?,
+                status: candidate.status,
+                impls,
+            });
+        }
+        steps.push(req::MethodLookupStep {
+            receiver_ty: step.receiver_ty,
+            adjustment: step.adjustment,
+            candidates,
+        });
+    }
+    Ok(Some(req::MethodLookupExplanation { name: explanation.name, steps }))
+}
+
 pub fn handle_selection_range(
     world: WorldSnapshot,
     params: req::SelectionRangeParams,
@@ -247,12 +339,15 @@ pub fn handle_workspace_symbol(
     params: req::WorkspaceSymbolParams,
 ) -> Result<Option<Vec<SymbolInformation>>> {
     let _p = profile("handle_workspace_symbol");
-    let all_symbols = params.query.contains('#');
+    let functions = params.query.contains('#');
     let libs = params.query.contains('*');
     let query = {
         let query: String = params.query.chars().filter(|&c| c != '#' && c != '*').collect();
         let mut q = Query::new(query);
-        if !all_symbols {
+        if functions {
+            q.only_functions();
+        } else if !q.has_container() {
+            // `Vec::push` is looking for a method, not for a type
             q.only_types();
         }
         if libs {
@@ -262,7 +357,7 @@ pub fn handle_workspace_symbol(
         q
     };
     let mut res = exec_query(&world, query)?;
-    if res.is_empty() && !all_symbols {
+    if res.is_empty() && !functions {
         let mut query = Query::new(params.query);
         query.limit(128);
         res = exec_query(&world, query)?;
@@ -495,10 +590,12 @@ pub fn handle_prepare_rename(
     let _p = profile("handle_prepare_rename");
     let position = params.try_conv_with(&world)?;
 
+    // The reason why a rename can't be done is reported by the rename request
+    // itself, prepareRename just tells that there's nothing to rename.
     let optional_change = world.analysis().rename(position, "dummy")?;
     let range = match optional_change {
-        None => return Ok(None),
-        Some(it) => it.range,
+        Ok(Some(it)) => it.range,
+        Ok(None) | Err(_) => return Ok(None),
     };
 
     let file_id = params.text_document.try_conv_with(&world)?;
@@ -520,7 +617,7 @@ pub fn handle_rename(world: WorldSnapshot, params: RenameParams) -> Result<Optio
     }
 
     let optional_change = world.analysis().rename(position, &*params.new_name)?;
-    let change = match optional_change {
+    let change = match optional_change.map_err(rename_error)? {
         None => return Ok(None),
         Some(it) => it.info,
     };
@@ -530,6 +627,10 @@ pub fn handle_rename(world: WorldSnapshot, params: RenameParams) -> Result<Optio
     Ok(Some(source_change_req.workspace_edit))
 }
 
+fn rename_error(err: RenameError) -> LspError {
+    LspError::new(ErrorCode::InvalidRequest as i32, err.to_string())
+}
+
 pub fn handle_references(
     world: WorldSnapshot,
     params: req::ReferenceParams,
@@ -650,7 +751,10 @@ pub fn handle_code_action(
     let line_index = world.analysis().file_line_index(file_id)?;
     let range = params.range.conv_with(&line_index);
 
-    let diagnostics = world.analysis().diagnostics(file_id)?;
+    let mut diagnostics = world.analysis().diagnostics(file_id)?;
+    if world.feature_flags().get("lsp.diagnostics.unused") {
+        diagnostics.extend(world.analysis().unused(file_id)?);
+    }
     let mut res = CodeActionResponse::default();
 
     let fixes_from_diagnostics = diagnostics
@@ -884,18 +988,23 @@ pub fn handle_document_highlight(
 pub fn publish_diagnostics(world: &WorldSnapshot, file_id: FileId) -> Result<DiagnosticTask> {
     let _p = profile("publish_diagnostics");
     let line_index = world.analysis().file_line_index(file_id)?;
-    let diagnostics: Vec<Diagnostic> = world
-        .analysis()
-        .diagnostics(file_id)?
+    let mut diagnostics = world.analysis().diagnostics(file_id)?;
+    if world.feature_flags().get("lsp.diagnostics.unused") {
+        diagnostics.extend(world.analysis().unused(file_id)?);
+    }
+    let diagnostics: Vec<Diagnostic> = diagnostics
         .into_iter()
         .map(|d| Diagnostic {
             range: d.range.conv_with(&line_index),
             severity: Some(d.severity.conv()),
             code: None,
             source: Some("rust-analyzer".to_string()),
+            tags: match d.severity {
+                Severity::Unused => Some(vec![DiagnosticTag::Unnecessary]),
+                _ => None,
+            },
             message: d.message,
             related_information: None,
-            tags: None,
         })
         .collect();
     Ok(DiagnosticTask::SetNative(file_id, diagnostics))
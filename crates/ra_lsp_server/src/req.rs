@@ -70,6 +70,80 @@ pub struct ExpandMacroParams {
     pub position: Option<Position>,
 }
 
+pub enum ExpandMacroSteps {}
+
+impl Request for ExpandMacroSteps {
+    type Params = ExpandMacroParams;
+    type Result = Option<MacroExpansionStep>;
+    const METHOD: &'static str = "rust-analyzer/expandMacroSteps";
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MacroExpansionStep {
+    pub name: String,
+    pub location: Location,
+    pub rule: Option<usize>,
+    pub bindings: Vec<MacroBinding>,
+    pub expansion: String,
+    pub children: Vec<MacroExpansionStep>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MacroBinding {
+    pub name: String,
+    pub fragments: MacroFragments,
+}
+
+/// A single fragment, or an array with the fragments of each iteration of a
+/// repetition.
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum MacroFragments {
+    Fragment(MacroFragment),
+    Repeated(Vec<MacroFragments>),
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MacroFragment {
+    pub text: String,
+    pub location: Option<Location>,
+}
+
+pub enum ExplainMethodResolution {}
+
+impl Request for ExplainMethodResolution {
+    type Params = TextDocumentPositionParams;
+    type Result = Option<MethodLookupExplanation>;
+    const METHOD: &'static str = "rust-analyzer/explainMethodResolution";
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MethodLookupExplanation {
+    pub name: String,
+    pub steps: Vec<MethodLookupStep>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MethodLookupStep {
+    pub receiver_ty: String,
+    pub adjustment: String,
+    pub candidates: Vec<MethodLookupCandidate>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MethodLookupCandidate {
+    pub signature: String,
+    pub location: Location,
+    pub status: String,
+    pub impls: Vec<Location>,
+}
+
 pub enum FindMatchingBrace {}
 
 impl Request for FindMatchingBrace {
@@ -14,13 +14,14 @@ use lsp_types::Url;
 use parking_lot::RwLock;
 use ra_cargo_watch::{url_from_path_with_drive_lowercasing, CheckOptions, CheckWatcher};
 use ra_ide::{
-    Analysis, AnalysisChange, AnalysisHost, CrateGraph, FeatureFlags, FileId, LibraryData,
-    SourceRootId,
+    Analysis, AnalysisChange, AnalysisHost, CrateGraph, FeatureFlags, FileId, LibraryCache,
+    LibraryData, SourceRootId,
 };
 use ra_project_model::{get_rustc_cfg_options, ProjectWorkspace};
 use ra_vfs::{LineEndings, RootEntry, Vfs, VfsChange, VfsFile, VfsRoot, VfsTask, Watch};
 use ra_vfs_glob::{Glob, RustPackageFilterBuilder};
 use relative_path::RelativePathBuf;
+use rustc_hash::FxHashMap;
 
 use crate::{
     diagnostics::{CheckFixes, DiagnosticCollection},
@@ -35,6 +36,8 @@ pub struct Options {
     pub line_folding_only: bool,
     pub max_inlay_hint_length: Option<usize>,
     pub cargo_watch: CheckOptions,
+    pub library_cache: Option<LibraryCache>,
+    pub macro_token_limit: Option<usize>,
 }
 
 /// `WorldState` is the primary mutable state of the language server
@@ -49,6 +52,9 @@ pub struct WorldState {
     pub roots_to_scan: usize,
     pub roots: Vec<PathBuf>,
     pub workspaces: Arc<Vec<ProjectWorkspace>>,
+    /// Names of the packages in the workspaces, by root path. Used to name
+    /// library cache entries.
+    pub package_names: FxHashMap<PathBuf, String>,
     pub analysis_host: AnalysisHost,
     pub vfs: Arc<RwLock<Vfs>>,
     pub task_receiver: Receiver<VfsTask>,
@@ -87,8 +93,12 @@ impl WorldState {
             }
             RootEntry::new(path.clone(), filter.into_vfs_filter())
         }));
+        let mut package_names = FxHashMap::default();
         for ws in workspaces.iter() {
             roots.extend(ws.to_roots().into_iter().map(|pkg_root| {
+                if let Some(name) = pkg_root.name() {
+                    package_names.insert(pkg_root.path().clone(), name.to_string());
+                }
                 let mut filter =
                     RustPackageFilterBuilder::default().set_member(pkg_root.is_member());
                 for glob in exclude_globs.iter() {
@@ -129,6 +139,12 @@ impl WorldState {
                 change.set_debug_crate_name(crate_id.shift(shift), name)
             }
         }
+        if let Some(limit) = options.macro_token_limit {
+            let crates: Vec<_> = crate_graph.iter().collect();
+            for crate_id in crates {
+                crate_graph.set_macro_token_limit(crate_id, limit);
+            }
+        }
         change.set_crate_graph(crate_graph);
 
         // FIXME: Figure out the multi-workspace situation
@@ -154,6 +170,7 @@ impl WorldState {
             roots_to_scan,
             roots: folder_roots,
             workspaces: Arc::new(workspaces),
+            package_names,
             analysis_host,
             vfs: Arc::new(RwLock::new(vfs)),
             task_receiver,
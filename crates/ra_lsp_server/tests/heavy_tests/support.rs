@@ -116,7 +116,11 @@ impl Server {
                         window: None,
                         experimental: None,
                     },
-                    ServerConfig { with_sysroot, ..ServerConfig::default() },
+                    ServerConfig {
+                        with_sysroot,
+                        use_library_cache: false,
+                        ..ServerConfig::default()
+                    },
                     connection,
                 )
                 .unwrap()
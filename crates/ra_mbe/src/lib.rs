@@ -9,6 +9,10 @@ mod syntax_bridge;
 mod tt_iter;
 mod subtree_source;
 
+use std::fmt;
+
+use ra_syntax::SmolStr;
+
 pub use tt::{Delimiter, Punct};
 
 use crate::{
@@ -21,7 +25,7 @@ pub enum ParseError {
     Expected(String),
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ExpandError {
     NoMatchingRule,
     UnexpectedToken,
@@ -30,8 +34,136 @@ pub enum ExpandError {
     InvalidRepeat,
 }
 
+impl fmt::Display for ExpandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExpandError::NoMatchingRule => f.write_str("no rules expected this macro input"),
+            ExpandError::UnexpectedToken => f.write_str("unexpected token in macro input"),
+            ExpandError::BindingError(msg) => write!(f, "macro binding error: {}", msg),
+            ExpandError::ConversionError => f.write_str("could not convert tokens"),
+            ExpandError::InvalidRepeat => f.write_str("invalid macro repetition"),
+        }
+    }
+}
+
+/// The result of expanding a macro: a best-effort token tree, together with
+/// the first error encountered while producing it.
+///
+/// Even if the input doesn't match any rule, the expansion contains as much of
+/// the closest rule as could be transcribed, so that analysis of the macro
+/// body degrades gracefully while the user is typing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpandResult<T>(pub T, pub Option<ExpandError>);
+
+impl<T> ExpandResult<T> {
+    pub fn ok(value: T) -> ExpandResult<T> {
+        ExpandResult(value, None)
+    }
+
+    pub fn only_err(err: ExpandError) -> ExpandResult<T>
+    where
+        T: Default,
+    {
+        ExpandResult(Default::default(), Some(err))
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> ExpandResult<U> {
+        ExpandResult(f(self.0), self.1)
+    }
+
+    pub fn result(self) -> Result<T, ExpandError> {
+        match self.1 {
+            None => Ok(self.0),
+            Some(err) => Err(err),
+        }
+    }
+}
+
+impl<T> From<Result<T, ExpandError>> for ExpandResult<T>
+where
+    T: Default,
+{
+    fn from(result: Result<T, ExpandError>) -> ExpandResult<T> {
+        match result {
+            Ok(it) => ExpandResult::ok(it),
+            Err(err) => ExpandResult::only_err(err),
+        }
+    }
+}
+
+/// A single expansion of a `macro_rules!`, with details useful for debugging
+/// the macro.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpansionTrace {
+    /// Index of the rule which matched.
+    pub rule: usize,
+    /// The fragments bound to each metavariable, sorted by name. Token ids
+    /// are the same as in the expansion, use `MacroRules::map_id_up` to map
+    /// them back to the input.
+    pub bindings: Vec<(SmolStr, MetaVarFragments)>,
+    pub expansion: tt::Subtree,
+}
+
+/// What a metavariable is bound to. A metavariable inside of `$(...)*`
+/// repetitions is bound to a fragment per iteration, nested the same way as
+/// the repetitions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetaVarFragments {
+    Fragment(tt::TokenTree),
+    Repeated(Vec<MetaVarFragments>),
+}
+
+impl fmt::Display for MetaVarFragments {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetaVarFragments::Fragment(it) => write!(f, "{}", it),
+            MetaVarFragments::Repeated(it) => {
+                write!(f, "[")?;
+                for (i, fragments) in it.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", fragments)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+/// Explains why a macro call didn't match any rule of a `macro_rules!`, by
+/// describing how far the rule which got furthest into the input went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoMatchExplanation {
+    /// Index of the rule which matched the longest prefix of the input.
+    pub rule: usize,
+    /// The matcher fragment which failed, like `$e:expr` or `;`. `None` if
+    /// the rule didn't expect any more tokens.
+    pub expected: Option<String>,
+    /// The input token at which matching failed, `None` if the input ended
+    /// prematurely. For subtrees, this is the opening delimiter.
+    pub found: Option<String>,
+    /// The id of the `found` token in the macro input.
+    pub found_id: Option<tt::TokenId>,
+}
+
+impl fmt::Display for NoMatchExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "rule #{} got furthest: ", self.rule + 1)?;
+        match (&self.expected, &self.found) {
+            (Some(expected), Some(found)) => {
+                write!(f, "expected `{}`, found `{}`", expected, found)
+            }
+            (Some(expected), None) => write!(f, "expected `{}`, found end of input", expected),
+            (None, Some(found)) => write!(f, "no more tokens expected, found `{}`", found),
+            (None, None) => f.write_str("unexpected end of input"),
+        }
+    }
+}
+
 pub use crate::syntax_bridge::{
-    ast_to_token_tree, syntax_node_to_token_tree, token_tree_to_syntax_node, TokenMap,
+    ast_to_token_tree, syntax_node_to_token_tree, token_tree_to_partial_syntax_node,
+    token_tree_to_syntax_node, TokenMap, TokenTextRange,
 };
 
 /// This struct contains AST for a single `macro_rules` definition. What might
@@ -149,13 +281,70 @@ impl MacroRules {
         Ok(MacroRules { rules, shift: Shift::new(tt) })
     }
 
-    pub fn expand(&self, tt: &tt::Subtree) -> Result<tt::Subtree, ExpandError> {
+    /// Parses the body of a declarative macro 2.0 (`macro` item).
+    ///
+    /// Both the multi-arm form (`macro m { (..) => { .. }, .. }`) and the
+    /// single-rule shorthand (`macro m(..) { .. }`) are supported.
+    pub fn parse_macro2(tt: &tt::Subtree) -> Result<MacroRules, ParseError> {
+        let mut src = TtIter::new(tt);
+        let mut rules = Vec::new();
+
+        if Some(tt::DelimiterKind::Brace) == tt.delimiter_kind() {
+            while src.len() > 0 {
+                let rule = Rule::parse(&mut src)?;
+                rules.push(rule);
+                if let Err(()) = src.expect_char(',') {
+                    if src.len() > 0 {
+                        return Err(ParseError::Expected("expected `,`".to_string()));
+                    }
+                    break;
+                }
+            }
+        } else {
+            let mut lhs = src
+                .expect_subtree()
+                .map_err(|()| ParseError::Expected("expected subtree".to_string()))?
+                .clone();
+            lhs.delimiter = None;
+            let mut rhs = src
+                .expect_subtree()
+                .map_err(|()| ParseError::Expected("expected subtree".to_string()))?
+                .clone();
+            rhs.delimiter = None;
+            if src.len() > 0 {
+                return Err(ParseError::Expected("unexpected tokens after macro body".to_string()));
+            }
+            rules.push(Rule { lhs, rhs });
+        }
+
+        for rule in rules.iter() {
+            validate(&rule.lhs)?;
+        }
+
+        Ok(MacroRules { rules, shift: Shift::new(tt) })
+    }
+
+    pub fn expand(&self, tt: &tt::Subtree) -> ExpandResult<tt::Subtree> {
         // apply shift
         let mut tt = tt.clone();
         self.shift.shift_all(&mut tt);
         mbe_expander::expand(self, &tt)
     }
 
+    /// Expands `tt`, reporting which rule matched and how.
+    pub fn expand_traced(&self, tt: &tt::Subtree) -> Result<ExpansionTrace, ExpandError> {
+        // apply shift
+        let mut tt = tt.clone();
+        self.shift.shift_all(&mut tt);
+        mbe_expander::expand_traced(self, &tt)
+    }
+
+    /// Explains why `tt`, the input of a macro call, doesn't match any of the
+    /// rules. Returns `None` if some rule does match.
+    pub fn explain_no_match(&self, tt: &tt::Subtree) -> Option<NoMatchExplanation> {
+        mbe_expander::explain_no_match(self, tt)
+    }
+
     pub fn map_id_down(&self, id: tt::TokenId) -> tt::TokenId {
         self.shift.shift(id)
     }
@@ -8,19 +8,103 @@ mod transcriber;
 use ra_syntax::SmolStr;
 use rustc_hash::FxHashMap;
 
-use crate::ExpandError;
+use crate::{ExpandError, ExpandResult, ExpansionTrace, MetaVarFragments, NoMatchExplanation};
 
-pub(crate) fn expand(
+/// Expands `input` with the first rule which matches it.
+///
+/// If no rule matches, the rule which got furthest into the input is
+/// transcribed anyway, with the metavariables it didn't get to bound to
+/// nothing, so that callers still get a best-effort expansion.
+pub(crate) fn expand(rules: &crate::MacroRules, input: &tt::Subtree) -> ExpandResult<tt::Subtree> {
+    expand_rules(rules, input).0
+}
+
+/// Like `expand`, but also reports which rule matched and the metavariable
+/// bindings.
+pub(crate) fn expand_traced(
     rules: &crate::MacroRules,
     input: &tt::Subtree,
-) -> Result<tt::Subtree, ExpandError> {
-    rules.rules.iter().find_map(|it| expand_rule(it, input).ok()).ok_or(ExpandError::NoMatchingRule)
+) -> Result<ExpansionTrace, ExpandError> {
+    match expand_rules(rules, input) {
+        (ExpandResult(expansion, None), Some((rule, bindings))) => {
+            Ok(ExpansionTrace { rule, bindings: bindings.fragments(), expansion })
+        }
+        (ExpandResult(_, err), _) => Err(err.unwrap_or(ExpandError::NoMatchingRule)),
+    }
 }
 
-fn expand_rule(rule: &crate::Rule, input: &tt::Subtree) -> Result<tt::Subtree, ExpandError> {
-    let bindings = matcher::match_(&rule.lhs, input)?;
-    let res = transcriber::transcribe(&rule.rhs, &bindings)?;
-    Ok(res)
+/// Expands `input`, returning the index and the bindings of the rule which
+/// matched and was transcribed successfully, if any.
+fn expand_rules(
+    rules: &crate::MacroRules,
+    input: &tt::Subtree,
+) -> (ExpandResult<tt::Subtree>, Option<(usize, Bindings)>) {
+    // The first rule which matched but couldn't be transcribed.
+    let mut transcribe_failure: Option<ExpandResult<tt::Subtree>> = None;
+    // The rule which got furthest before failing to match.
+    let mut partial: Option<(Vec<usize>, &crate::Rule, Bindings)> = None;
+
+    for (idx, rule) in rules.rules.iter().enumerate() {
+        match matcher::match_(&rule.lhs, input) {
+            Ok(bindings) => {
+                let res = transcriber::transcribe(&rule.rhs, &bindings);
+                if res.1.is_none() {
+                    return (res, Some((idx, bindings)));
+                }
+                if transcribe_failure.is_none() {
+                    transcribe_failure = Some(res);
+                }
+            }
+            Err(err) => {
+                let position = err.failure.map(|it| it.position).unwrap_or_default();
+                match &partial {
+                    Some((best, _, _)) if *best >= position => (),
+                    _ => partial = Some((position, rule, err.bindings)),
+                }
+            }
+        }
+    }
+
+    if let Some(res) = transcribe_failure {
+        return (res, None);
+    }
+    let res = match partial {
+        Some((_, rule, mut bindings)) => {
+            matcher::bind_missing(&mut bindings, &rule.lhs);
+            let ExpandResult(tt, _) = transcriber::transcribe(&rule.rhs, &bindings);
+            ExpandResult(tt, Some(ExpandError::NoMatchingRule))
+        }
+        None => ExpandResult::only_err(ExpandError::NoMatchingRule),
+    };
+    (res, None)
+}
+
+/// Finds the rule which got furthest into `input` before failing to match,
+/// and describes where it failed.
+pub(crate) fn explain_no_match(
+    rules: &crate::MacroRules,
+    input: &tt::Subtree,
+) -> Option<NoMatchExplanation> {
+    let mut best: Option<(usize, matcher::MatchFailure)> = None;
+    for (idx, rule) in rules.rules.iter().enumerate() {
+        let failure = match matcher::match_(&rule.lhs, input) {
+            Ok(_) => return None,
+            Err(err) => match err.failure {
+                Some(it) => it,
+                None => continue,
+            },
+        };
+        match &best {
+            Some((_, best)) if best.position >= failure.position => (),
+            _ => best = Some((idx, failure)),
+        }
+    }
+    let (rule, failure) = best?;
+    let (found, found_id) = match failure.found {
+        Some((text, id)) => (Some(text), id),
+        None => (None, None),
+    };
+    Some(NoMatchExplanation { rule, expected: failure.expected, found, found_id })
 }
 
 /// The actual algorithm for expansion is not too hard, but is pretty tricky.
@@ -80,6 +164,31 @@ enum Binding {
     Empty,
 }
 
+impl Bindings {
+    /// Lists the fragments bound to each metavariable, sorted by name.
+    fn fragments(&self) -> Vec<(SmolStr, MetaVarFragments)> {
+        let mut res: Vec<_> =
+            self.inner.iter().map(|(name, binding)| (name.clone(), binding.fragments())).collect();
+        res.sort_by(|(a, _), (b, _)| a.cmp(b));
+        res
+    }
+}
+
+impl Binding {
+    fn fragments(&self) -> MetaVarFragments {
+        match self {
+            Binding::Fragment(Fragment::Tokens(it)) | Binding::Fragment(Fragment::Ast(it)) => {
+                MetaVarFragments::Fragment(it.clone())
+            }
+            Binding::Nested(it) => {
+                MetaVarFragments::Repeated(it.iter().map(Binding::fragments).collect())
+            }
+            // A repetition which matched nothing.
+            Binding::Empty => MetaVarFragments::Repeated(Vec::new()),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 enum Fragment {
     /// token fragments are just copy-pasted into the output
@@ -146,6 +255,8 @@ mod tests {
         let (invocation_tt, _) =
             ast_to_token_tree(&macro_invocation.token_tree().unwrap()).unwrap();
 
-        expand_rule(&rules.rules[0], &invocation_tt)
+        let rule = &rules.rules[0];
+        let bindings = matcher::match_(&rule.lhs, &invocation_tt).map_err(|it| it.err)?;
+        transcriber::transcribe(&rule.rhs, &bindings).result()
     }
 }
@@ -64,70 +64,207 @@ macro_rules! bail {
     };
 }
 
-pub(super) fn match_(pattern: &tt::Subtree, src: &tt::Subtree) -> Result<Bindings, ExpandError> {
+/// Describes where and why a rule failed to match its input.
+#[derive(Debug)]
+pub(super) struct MatchFailure {
+    /// The matcher fragment which didn't match, `None` if the rule didn't
+    /// expect any more tokens.
+    pub(super) expected: Option<String>,
+    /// The input token at which matching failed (the opening delimiter for
+    /// subtrees), `None` if the input ended prematurely.
+    pub(super) found: Option<(String, Option<tt::TokenId>)>,
+    /// The index of the offending token in each enclosing subtree, outermost
+    /// first. A rule which got further into the input has a larger position.
+    pub(super) position: Vec<usize>,
+    /// While the failure bubbles up: the number of tokens left at the current
+    /// level, including the offending one.
+    remaining: usize,
+}
+
+#[derive(Debug)]
+pub(super) struct MatchError {
+    pub(super) err: ExpandError,
+    pub(super) failure: Option<MatchFailure>,
+    /// The metavariables bound before matching failed.
+    pub(super) bindings: Bindings,
+}
+
+pub(super) fn match_(pattern: &tt::Subtree, src: &tt::Subtree) -> Result<Bindings, MatchError> {
     assert!(pattern.delimiter == None);
 
     let mut res = Bindings::default();
-    let mut src = TtIter::new(src);
+    let mut failure = None;
+    let mut src_iter = TtIter::new(src);
 
-    match_subtree(&mut res, pattern, &mut src)?;
+    let result = match_subtree(&mut res, pattern, &mut src_iter, &mut failure).and_then(|()| {
+        if src_iter.len() > 0 {
+            record_failure(&mut failure, None, &src_iter);
+            bail!("leftover tokens");
+        }
+        Ok(())
+    });
 
-    if src.len() > 0 {
-        bail!("leftover tokens");
+    match result {
+        Ok(()) => Ok(res),
+        Err(err) => {
+            let failure = failure.map(|mut it| {
+                enter_parent(&mut it, src, src.token_trees.len());
+                it.position.reverse();
+                it
+            });
+            Err(MatchError { err, failure, bindings: res })
+        }
     }
+}
 
-    Ok(res)
+fn record_failure(failure: &mut Option<MatchFailure>, expected: Option<String>, src: &TtIter) {
+    if failure.is_some() {
+        return;
+    }
+    let found = src.clone().next().map(|tt| match tt {
+        tt::TokenTree::Leaf(leaf) => (leaf.to_string(), Some(leaf_id(leaf))),
+        tt::TokenTree::Subtree(subtree) => match subtree.delimiter {
+            Some(delimiter) => (open_delimiter(delimiter.kind).to_string(), Some(delimiter.id)),
+            None => (subtree.to_string(), None),
+        },
+    });
+    *failure = Some(MatchFailure { expected, found, position: Vec::new(), remaining: src.len() });
+}
+
+/// Converts the position of `failure`, recorded inside `subtree`, into a
+/// position in the parent subtree, where `remaining` tokens are left
+/// (including `subtree` itself).
+fn enter_parent(failure: &mut MatchFailure, subtree: &tt::Subtree, remaining: usize) {
+    failure.position.push(subtree.token_trees.len() - failure.remaining);
+    failure.remaining = remaining;
+}
+
+fn leaf_id(leaf: &tt::Leaf) -> tt::TokenId {
+    match leaf {
+        tt::Leaf::Ident(it) => it.id,
+        tt::Leaf::Punct(it) => it.id,
+        tt::Leaf::Literal(it) => it.id,
+    }
+}
+
+fn open_delimiter(kind: tt::DelimiterKind) -> &'static str {
+    match kind {
+        tt::DelimiterKind::Parenthesis => "(",
+        tt::DelimiterKind::Brace => "{",
+        tt::DelimiterKind::Bracket => "[",
+    }
+}
+
+fn close_delimiter(kind: tt::DelimiterKind) -> &'static str {
+    match kind {
+        tt::DelimiterKind::Parenthesis => ")",
+        tt::DelimiterKind::Brace => "}",
+        tt::DelimiterKind::Bracket => "]",
+    }
+}
+
+fn describe_op(op: &Op) -> String {
+    match op {
+        Op::Var { name, kind: Some(kind) } => format!("${}:{}", name, kind),
+        Op::Var { name, kind: None } => format!("${}", name),
+        Op::Repeat { subtree, kind, separator } => {
+            let separator = match separator {
+                Some(Separator::Ident(it)) => it.text.to_string(),
+                Some(Separator::Literal(it)) => it.text.to_string(),
+                Some(Separator::Puncts(it)) => it.iter().map(|it| it.char).collect(),
+                None => String::new(),
+            };
+            let kind = match kind {
+                RepeatKind::ZeroOrMore => '*',
+                RepeatKind::OneOrMore => '+',
+                RepeatKind::ZeroOrOne => '?',
+            };
+            format!("$({}){}{}", subtree, separator, kind)
+        }
+        Op::TokenTree(tt::TokenTree::Leaf(leaf)) => leaf.to_string(),
+        Op::TokenTree(tt::TokenTree::Subtree(subtree)) => match subtree.delimiter {
+            Some(delimiter) => open_delimiter(delimiter.kind).to_string(),
+            None => subtree.to_string(),
+        },
+    }
 }
 
 fn match_subtree(
     bindings: &mut Bindings,
     pattern: &tt::Subtree,
     src: &mut TtIter,
+    failure: &mut Option<MatchFailure>,
 ) -> Result<(), ExpandError> {
     for op in parse_pattern(pattern) {
-        match op? {
-            Op::TokenTree(tt::TokenTree::Leaf(lhs)) => {
-                let rhs = src.expect_leaf().map_err(|()| err!("expected leaf: `{}`", lhs))?;
-                match (lhs, rhs) {
-                    (
-                        tt::Leaf::Punct(tt::Punct { char: lhs, .. }),
-                        tt::Leaf::Punct(tt::Punct { char: rhs, .. }),
-                    ) if lhs == rhs => (),
-                    (
-                        tt::Leaf::Ident(tt::Ident { text: lhs, .. }),
-                        tt::Leaf::Ident(tt::Ident { text: rhs, .. }),
-                    ) if lhs == rhs => (),
-                    (
-                        tt::Leaf::Literal(tt::Literal { text: lhs, .. }),
-                        tt::Leaf::Literal(tt::Literal { text: rhs, .. }),
-                    ) if lhs == rhs => (),
-                    _ => Err(ExpandError::UnexpectedToken)?,
-                }
+        let op = op?;
+        let before = src.clone();
+        if let Err(err) = match_op(bindings, &op, src, failure) {
+            record_failure(failure, Some(describe_op(&op)), &before);
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+fn match_op(
+    bindings: &mut Bindings,
+    op: &Op,
+    src: &mut TtIter,
+    failure: &mut Option<MatchFailure>,
+) -> Result<(), ExpandError> {
+    match op {
+        Op::TokenTree(tt::TokenTree::Leaf(lhs)) => {
+            let rhs = src.expect_leaf().map_err(|()| err!("expected leaf: `{}`", lhs))?;
+            match (lhs, rhs) {
+                (
+                    tt::Leaf::Punct(tt::Punct { char: lhs, .. }),
+                    tt::Leaf::Punct(tt::Punct { char: rhs, .. }),
+                ) if lhs == rhs => (),
+                (
+                    tt::Leaf::Ident(tt::Ident { text: lhs, .. }),
+                    tt::Leaf::Ident(tt::Ident { text: rhs, .. }),
+                ) if lhs == rhs => (),
+                (
+                    tt::Leaf::Literal(tt::Literal { text: lhs, .. }),
+                    tt::Leaf::Literal(tt::Literal { text: rhs, .. }),
+                ) if lhs == rhs => (),
+                _ => Err(ExpandError::UnexpectedToken)?,
             }
-            Op::TokenTree(tt::TokenTree::Subtree(lhs)) => {
-                let rhs = src.expect_subtree().map_err(|()| err!("expected subtree"))?;
-                if lhs.delimiter_kind() != rhs.delimiter_kind() {
-                    bail!("mismatched delimiter")
-                }
-                let mut src = TtIter::new(rhs);
-                match_subtree(bindings, lhs, &mut src)?;
-                if src.len() > 0 {
+        }
+        Op::TokenTree(tt::TokenTree::Subtree(lhs)) => {
+            let remaining = src.len();
+            let rhs = src.expect_subtree().map_err(|()| err!("expected subtree"))?;
+            if lhs.delimiter_kind() != rhs.delimiter_kind() {
+                bail!("mismatched delimiter")
+            }
+            let mut inner = TtIter::new(rhs);
+            let res = match_subtree(bindings, lhs, &mut inner, failure).and_then(|()| {
+                if inner.len() > 0 {
+                    let expected = lhs.delimiter.map(|it| close_delimiter(it.kind).to_string());
+                    record_failure(failure, expected, &inner);
                     bail!("leftover tokens");
                 }
-            }
-            Op::Var { name, kind } => {
-                let kind = kind.as_ref().ok_or(ExpandError::UnexpectedToken)?;
-                match match_meta_var(kind.as_str(), src)? {
-                    Some(fragment) => {
-                        bindings.inner.insert(name.clone(), Binding::Fragment(fragment));
-                    }
-                    None => bindings.push_optional(name),
+                Ok(())
+            });
+            if res.is_err() {
+                if let Some(failure) = failure {
+                    enter_parent(failure, rhs, remaining);
                 }
             }
-            Op::Repeat { subtree, kind, separator } => {
-                match_repeat(bindings, subtree, kind, separator, src)?
+            res?
+        }
+        Op::Var { name, kind } => {
+            let kind = kind.as_ref().ok_or(ExpandError::UnexpectedToken)?;
+            match match_meta_var(kind.as_str(), src)? {
+                Some(fragment) => {
+                    bindings.inner.insert((*name).clone(), Binding::Fragment(fragment));
+                }
+                None => bindings.push_optional(name),
             }
         }
+        Op::Repeat { subtree, kind, separator } => {
+            match_repeat(bindings, subtree, kind.clone(), separator.clone(), src, failure)?
+        }
     }
     Ok(())
 }
@@ -234,11 +371,15 @@ pub(super) fn match_repeat(
     kind: RepeatKind,
     separator: Option<Separator>,
     src: &mut TtIter,
+    failure: &mut Option<MatchFailure>,
 ) -> Result<(), ExpandError> {
     // Dirty hack to make macro-expansion terminate.
     // This should be replaced by a propper macro-by-example implementation
     let mut limit = 65536;
     let mut counter = 0;
+    // Failures inside the repetition just end it, but we keep the last one
+    // around in case we end up with too few repetitions.
+    let mut last_failure = None;
 
     for i in 0.. {
         let mut fork = src.clone();
@@ -250,7 +391,7 @@ pub(super) fn match_repeat(
         }
 
         let mut nested = Bindings::default();
-        match match_subtree(&mut nested, pattern, &mut fork) {
+        match match_subtree(&mut nested, pattern, &mut fork, failure) {
             Ok(()) => {
                 limit -= 1;
                 if limit == 0 {
@@ -267,12 +408,18 @@ pub(super) fn match_repeat(
                     }
                 }
             }
-            Err(_) => break,
+            Err(_) => {
+                last_failure = failure.take();
+                break;
+            }
         }
     }
 
     match (kind, counter) {
-        (RepeatKind::OneOrMore, 0) => return Err(ExpandError::UnexpectedToken),
+        (RepeatKind::OneOrMore, 0) => {
+            *failure = last_failure;
+            return Err(ExpandError::UnexpectedToken);
+        }
         (_, 0) => {
             // Collect all empty variables in subtrees
             let mut vars = Vec::new();
@@ -326,6 +473,34 @@ fn match_meta_var(kind: &str, input: &mut TtIter) -> Result<Option<Fragment>, Ex
     Ok(Some(fragment))
 }
 
+/// Binds the metavariables of `pattern` which are left unbound after a failed
+/// match, so that the rule can still be transcribed: plain variables expand
+/// to nothing and repetitions are repeated zero times.
+pub(super) fn bind_missing(bindings: &mut Bindings, pattern: &tt::Subtree) {
+    for op in parse_pattern(pattern) {
+        match op {
+            Ok(Op::Var { name, .. }) => {
+                if !bindings.inner.contains_key(name) {
+                    bindings.push_optional(name);
+                }
+            }
+            Ok(Op::TokenTree(tt::TokenTree::Subtree(subtree))) => bind_missing(bindings, subtree),
+            Ok(Op::Repeat { subtree, .. }) => {
+                let mut vars = Vec::new();
+                if collect_vars(&mut vars, subtree).is_err() {
+                    continue;
+                }
+                for var in vars {
+                    if !bindings.inner.contains_key(&var) {
+                        bindings.push_empty(&var);
+                    }
+                }
+            }
+            Ok(Op::TokenTree(tt::TokenTree::Leaf(_))) | Err(_) => (),
+        }
+    }
+}
+
 fn collect_vars(buf: &mut Vec<SmolStr>, pattern: &tt::Subtree) -> Result<(), ExpandError> {
     for op in parse_pattern(pattern) {
         match op? {
@@ -10,7 +10,7 @@ use std::iter::successors;
 use tt::buffer::{Cursor, TokenBuffer};
 
 use crate::subtree_source::SubtreeTokenSource;
-use crate::ExpandError;
+use crate::{ExpandError, ExpandResult};
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum TokenTextRange {
@@ -69,6 +69,19 @@ pub fn token_tree_to_syntax_node(
     tt: &tt::Subtree,
     fragment_kind: FragmentKind,
 ) -> Result<(Parse<SyntaxNode>, TokenMap), ExpandError> {
+    match token_tree_to_partial_syntax_node(tt, fragment_kind) {
+        ExpandResult(Some(it), None) => Ok(it),
+        ExpandResult(_, err) => Err(err.unwrap_or(ExpandError::ConversionError)),
+    }
+}
+
+/// Like `token_tree_to_syntax_node`, but if the tokens parse as several
+/// `fragment_kind` nodes, keeps the first one together with the error. Only
+/// fails to produce a tree if the parser built no node at all.
+pub fn token_tree_to_partial_syntax_node(
+    tt: &tt::Subtree,
+    fragment_kind: FragmentKind,
+) -> ExpandResult<Option<(Parse<SyntaxNode>, TokenMap)>> {
     let tmp;
     let tokens = match tt {
         tt::Subtree { delimiter: None, token_trees } => token_trees.as_slice(),
@@ -81,12 +94,12 @@ pub fn token_tree_to_syntax_node(
     let mut token_source = SubtreeTokenSource::new(&buffer);
     let mut tree_sink = TtTreeSink::new(buffer.begin());
     ra_parser::parse_fragment(&mut token_source, &mut tree_sink, fragment_kind);
-    if tree_sink.roots.len() != 1 {
-        return Err(ExpandError::ConversionError);
+    if tree_sink.roots.is_empty() {
+        return ExpandResult(None, Some(ExpandError::ConversionError));
     }
+    let err = if tree_sink.has_extra_roots { Some(ExpandError::ConversionError) } else { None };
     //FIXME: would be cool to report errors
-    let (parse, range_map) = tree_sink.finish();
-    Ok((parse, range_map))
+    ExpandResult(Some(tree_sink.finish()), err)
 }
 
 impl TokenMap {
@@ -348,6 +361,9 @@ struct TtTreeSink<'a> {
     // Number of roots
     // Use for detect ill-form tree which is not single root
     roots: smallvec::SmallVec<[usize; 1]>,
+    // Whether the parser went on after finishing the first root. Everything
+    // after it is dropped, so that we still get a tree.
+    has_extra_roots: bool,
 }
 
 impl<'a> TtTreeSink<'a> {
@@ -359,10 +375,17 @@ impl<'a> TtTreeSink<'a> {
             text_pos: 0.into(),
             inner: SyntaxTreeBuilder::default(),
             roots: smallvec::SmallVec::new(),
+            has_extra_roots: false,
             token_map: TokenMap::default(),
         }
     }
 
+    /// Whether the first root is already finished, so that further events
+    /// would start another one.
+    fn after_first_root(&self) -> bool {
+        self.has_extra_roots || self.roots.last() == Some(&0)
+    }
+
     fn finish(self) -> (Parse<SyntaxNode>, TokenMap) {
         (self.inner.finish(), self.token_map)
     }
@@ -383,6 +406,10 @@ fn delim_to_str(d: Option<tt::DelimiterKind>, closing: bool) -> SmolStr {
 
 impl<'a> TreeSink for TtTreeSink<'a> {
     fn token(&mut self, kind: SyntaxKind, n_tokens: u8) {
+        if self.after_first_root() {
+            self.has_extra_roots = true;
+            return;
+        }
         if kind == L_DOLLAR || kind == R_DOLLAR {
             self.cursor = self.cursor.bump_subtree();
             return;
@@ -455,6 +482,10 @@ impl<'a> TreeSink for TtTreeSink<'a> {
     }
 
     fn start_node(&mut self, kind: SyntaxKind) {
+        if self.after_first_root() {
+            self.has_extra_roots = true;
+            return;
+        }
         self.inner.start_node(kind);
 
         match self.roots.last_mut() {
@@ -464,11 +495,17 @@ impl<'a> TreeSink for TtTreeSink<'a> {
     }
 
     fn finish_node(&mut self) {
+        if self.has_extra_roots {
+            return;
+        }
         self.inner.finish_node();
         *self.roots.last_mut().unwrap() -= 1;
     }
 
     fn error(&mut self, error: ParseError) {
+        if self.has_extra_roots {
+            return;
+        }
         self.inner.error(error, self.text_pos)
     }
 }
@@ -543,6 +580,33 @@ mod tests {
         assert!(token_tree_to_syntax_node(&expansion, FragmentKind::Expr).is_err());
     }
 
+    #[test]
+    fn tt_tree_sink_keeps_first_root() {
+        let expansion = parse_macro(
+            r#"
+            macro_rules! literals {
+                () => {
+                    1 2
+                }
+            }
+            "#,
+        )
+        .expand_tt("literals!();");
+        let buffer = TokenBuffer::new(&expansion.token_trees);
+        let mut tree_sink = TtTreeSink::new(buffer.begin());
+        for _ in 0..2 {
+            tree_sink.start_node(SyntaxKind::LITERAL);
+            tree_sink.token(SyntaxKind::INT_NUMBER, 1);
+            tree_sink.finish_node();
+        }
+
+        assert!(tree_sink.has_extra_roots);
+        let (parse, _) = tree_sink.finish();
+        let node = parse.syntax_node();
+        assert_eq!(node.kind(), SyntaxKind::LITERAL);
+        assert_eq!(node.to_string().trim(), "1");
+    }
+
     #[test]
     fn test_token_tree_last_child_is_white_space() {
         let source_file = ast::SourceFile::parse("f!({} );").ok().unwrap();
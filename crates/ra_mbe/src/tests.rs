@@ -42,6 +42,22 @@ mod rule_parsing {
         check("($i:) => ($i)", "invalid macro definition");
     }
 
+    #[test]
+    fn test_macro2_forms() {
+        fn check(macro_definition: &str) {
+            let source_file = ast::SourceFile::parse(macro_definition).ok().unwrap();
+            let macro_definition =
+                source_file.syntax().descendants().find_map(ast::MacroDef::cast).unwrap();
+            let (definition_tt, _) =
+                ast_to_token_tree(&macro_definition.token_tree().unwrap()).unwrap();
+            crate::MacroRules::parse_macro2(&definition_tt).unwrap();
+        }
+
+        check("macro m($i:ident) { $i }");
+        check("macro m { ($i:ident) => { $i } }");
+        check("macro m { ($i:ident) => { $i }, () => {}, }");
+    }
+
     fn parse_macro_arm(arm_definition: &str) -> Result<crate::MacroRules, ParseError> {
         let macro_definition = format!(" macro_rules! m {{ {} }} ", arm_definition);
         let source_file = ast::SourceFile::parse(&macro_definition).ok().unwrap();
@@ -1368,6 +1384,95 @@ fn expr_interpolation() {
     assert_eq!(expanded.to_string(), "map(x+foo)");
 }
 
+#[test]
+fn test_macro2_shorthand() {
+    parse_macro2(
+        r#"
+macro foo($i:ident) {
+    fn $i() {}
+}
+"#,
+    )
+    .assert_expand_items("foo!(bar);", "fn bar () {}");
+}
+
+#[test]
+fn test_macro2_multiple_arms() {
+    parse_macro2(
+        r#"
+macro foo {
+    ($i:ident) => {
+        fn $i() {}
+    },
+    ($i:ident, $j:ident) => {
+        fn $i() {} fn $j() {}
+    },
+}
+"#,
+    )
+    .assert_expand_items("foo!(bar);", "fn bar () {}")
+    .assert_expand_items("foo!(bar, baz);", "fn bar () {} fn baz () {}");
+}
+
+#[test]
+fn test_no_match_explanation() {
+    parse_macro(
+        r#"
+macro_rules! foo {
+    ($a:ident) => {};
+    ($a:ident, $b:expr;) => {};
+    ((a b)) => {};
+}
+"#,
+    )
+    .assert_no_match("foo!(x, 1 +);", "rule #2 got furthest: expected `$b:expr`, found `1`")
+    .assert_no_match("foo!(x, 1);", "rule #2 got furthest: expected `;`, found end of input")
+    .assert_no_match("foo!(x y);", "rule #1 got furthest: no more tokens expected, found `y`")
+    .assert_no_match("foo!((a c));", "rule #3 got furthest: expected `b`, found `c`");
+}
+
+#[test]
+fn test_partial_expansion() {
+    parse_macro(
+        r#"
+macro_rules! foo {
+    ($i:ident, $e:expr) => { fn $i() -> u32 { $e } struct Bar; };
+    ($i:ident; $($e:expr),*) => { fn $i() { $($e;)* } };
+}
+"#,
+    )
+    .assert_expand_partial("foo!(f, 1 +);", "fn f () -> u32 {} struct Bar ;")
+    .assert_expand_partial("foo!(1, 2);", "fn () -> u32 {} struct Bar ;")
+    .assert_expand_partial("foo!(f; 1, 2 +);", "fn f () {1 ;}");
+}
+
+#[test]
+fn test_expand_traced() {
+    let rules = parse_macro(
+        r#"
+macro_rules! foo {
+    ($a:ident) => { $a };
+    ($($a:ident),* ; $e:expr; $($($b:ident)*),*) => { $($a)* $e $($($b)*)* };
+}
+"#,
+    )
+    .rules;
+    let source_file = ast::SourceFile::parse("foo!(x, y; 1 + 1; a b, c);").ok().unwrap();
+    let macro_invocation =
+        source_file.syntax().descendants().find_map(ast::MacroCall::cast).unwrap();
+    let (invocation_tt, _) = ast_to_token_tree(&macro_invocation.token_tree().unwrap()).unwrap();
+
+    let trace = rules.expand_traced(&invocation_tt).unwrap();
+    assert_eq!(trace.rule, 1);
+    let bindings = trace
+        .bindings
+        .iter()
+        .map(|(name, fragments)| format!("{} = {}", name, fragments))
+        .collect::<Vec<_>>();
+    assert_eq!(bindings, vec!["a = [x, y]", "b = [[a, b], [c]]", "e = 1 + 1"]);
+    assert_eq!(trace.expansion, rules.expand(&invocation_tt).result().unwrap());
+}
+
 pub(crate) struct MacroFixture {
     rules: MacroRules,
 }
@@ -1381,7 +1486,7 @@ impl MacroFixture {
         let (invocation_tt, _) =
             ast_to_token_tree(&macro_invocation.token_tree().unwrap()).unwrap();
 
-        self.rules.expand(&invocation_tt).unwrap()
+        self.rules.expand(&invocation_tt).result().unwrap()
     }
 
     fn expand_items(&self, invocation: &str) -> SyntaxNode {
@@ -1399,6 +1504,32 @@ impl MacroFixture {
         token_tree_to_syntax_node(&expanded, FragmentKind::Expr).unwrap().0.syntax_node()
     }
 
+    fn assert_no_match(&self, invocation: &str, explanation: &str) -> &MacroFixture {
+        let source_file = ast::SourceFile::parse(invocation).ok().unwrap();
+        let macro_invocation =
+            source_file.syntax().descendants().find_map(ast::MacroCall::cast).unwrap();
+        let (invocation_tt, _) =
+            ast_to_token_tree(&macro_invocation.token_tree().unwrap()).unwrap();
+
+        assert_eq!(self.rules.expand(&invocation_tt).1, Some(ExpandError::NoMatchingRule));
+        let actual = self.rules.explain_no_match(&invocation_tt).unwrap();
+        assert_eq!(actual.to_string(), explanation);
+        self
+    }
+
+    fn assert_expand_partial(&self, invocation: &str, expected: &str) -> &MacroFixture {
+        let source_file = ast::SourceFile::parse(invocation).ok().unwrap();
+        let macro_invocation =
+            source_file.syntax().descendants().find_map(ast::MacroCall::cast).unwrap();
+        let (invocation_tt, _) =
+            ast_to_token_tree(&macro_invocation.token_tree().unwrap()).unwrap();
+
+        let ExpandResult(expansion, err) = self.rules.expand(&invocation_tt);
+        assert_eq!(err, Some(ExpandError::NoMatchingRule));
+        assert_eq!(expansion.to_string(), expected);
+        self
+    }
+
     fn assert_expand_tt(&self, invocation: &str, expected: &str) {
         let expansion = self.expand_tt(invocation);
         assert_eq!(expansion.to_string(), expected);
@@ -1456,6 +1587,16 @@ pub(crate) fn parse_macro(macro_definition: &str) -> MacroFixture {
     MacroFixture { rules }
 }
 
+pub(crate) fn parse_macro2(macro_definition: &str) -> MacroFixture {
+    let source_file = ast::SourceFile::parse(macro_definition).ok().unwrap();
+    let macro_definition =
+        source_file.syntax().descendants().find_map(ast::MacroDef::cast).unwrap();
+
+    let (definition_tt, _) = ast_to_token_tree(&macro_definition.token_tree().unwrap()).unwrap();
+    let rules = MacroRules::parse_macro2(&definition_tt).unwrap();
+    MacroFixture { rules }
+}
+
 fn debug_dump_ignore_spaces(node: &ra_syntax::SyntaxNode) -> String {
     let mut level = 0;
     let mut buf = String::new();
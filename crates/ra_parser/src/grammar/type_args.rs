@@ -51,8 +51,8 @@ fn type_arg(p: &mut Parser) {
             expressions::block(p);
             m.complete(p, CONST_ARG);
         }
-        k if k.is_literal() => {
-            p.bump(k);
+        _ if p.at_ts(expressions::LITERAL_FIRST) => {
+            expressions::literal(p);
             m.complete(p, CONST_ARG);
         }
         _ => {
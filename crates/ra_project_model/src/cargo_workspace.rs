@@ -55,6 +55,7 @@ impl_arena_id!(Target);
 #[derive(Debug, Clone)]
 struct PackageData {
     name: String,
+    version: String,
     manifest: PathBuf,
     targets: Vec<Target>,
     is_member: bool,
@@ -110,6 +111,9 @@ impl Package {
     pub fn name(self, ws: &CargoWorkspace) -> &str {
         ws.packages[self].name.as_str()
     }
+    pub fn version(self, ws: &CargoWorkspace) -> &str {
+        ws.packages[self].version.as_str()
+    }
     pub fn root(self, ws: &CargoWorkspace) -> &Path {
         ws.packages[self].manifest.parent().unwrap()
     }
@@ -179,11 +183,13 @@ impl CargoWorkspace {
         let ws_members = &meta.workspace_members;
 
         for meta_pkg in meta.packages {
-            let cargo_metadata::Package { id, edition, name, manifest_path, .. } = meta_pkg;
+            let cargo_metadata::Package { id, edition, name, version, manifest_path, .. } =
+                meta_pkg;
             let is_member = ws_members.contains(&id);
             let edition = edition.parse::<Edition>()?;
             let pkg = packages.alloc(PackageData {
                 name,
+                version: version.to_string(),
                 manifest: manifest_path,
                 targets: Vec::new(),
                 is_member,
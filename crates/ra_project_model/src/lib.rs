@@ -53,11 +53,13 @@ pub struct PackageRoot {
     path: PathBuf,
     /// Is a member of the current workspace
     is_member: bool,
+    /// Name and version of the package, like `serde-1.0.104`, if known
+    name: Option<String>,
 }
 
 impl PackageRoot {
-    pub fn new(path: PathBuf, is_member: bool) -> PackageRoot {
-        PackageRoot { path, is_member }
+    pub fn new(path: PathBuf, is_member: bool, name: Option<String>) -> PackageRoot {
+        PackageRoot { path, is_member, name }
     }
 
     pub fn path(&self) -> &PathBuf {
@@ -67,6 +69,10 @@ impl PackageRoot {
     pub fn is_member(&self) -> bool {
         self.is_member
     }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
 }
 
 impl ProjectWorkspace {
@@ -103,7 +109,7 @@ impl ProjectWorkspace {
             ProjectWorkspace::Json { project } => {
                 let mut roots = Vec::with_capacity(project.roots.len());
                 for root in &project.roots {
-                    roots.push(PackageRoot::new(root.path.clone(), true));
+                    roots.push(PackageRoot::new(root.path.clone(), true, None));
                 }
                 roots
             }
@@ -112,10 +118,15 @@ impl ProjectWorkspace {
                 for pkg in cargo.packages() {
                     let root = pkg.root(&cargo).to_path_buf();
                     let member = pkg.is_member(&cargo);
-                    roots.push(PackageRoot::new(root, member));
+                    let name = format!("{}-{}", pkg.name(&cargo), pkg.version(&cargo));
+                    roots.push(PackageRoot::new(root, member, Some(name)));
                 }
                 for krate in sysroot.crates() {
-                    roots.push(PackageRoot::new(krate.root_dir(&sysroot).to_path_buf(), false))
+                    roots.push(PackageRoot::new(
+                        krate.root_dir(&sysroot).to_path_buf(),
+                        false,
+                        Some(krate.name(&sysroot).to_string()),
+                    ))
                 }
                 roots
             }
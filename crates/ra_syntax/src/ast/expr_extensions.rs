@@ -327,6 +327,26 @@ impl ast::Literal {
             .map(|&suffix| SmolStr::new(suffix))
     }
 
+    /// The value of an integer literal, ignoring its suffix. Returns `None`
+    /// for other kinds of literals, and for integers which don't fit in `u128`.
+    pub fn int_value(&self) -> Option<u128> {
+        let suffix = match self.kind() {
+            LiteralKind::IntNumber { suffix } => suffix,
+            _ => return None,
+        };
+        let token = self.token();
+        let text = token.text().as_str();
+        let text = text.trim_end_matches(suffix.as_ref().map_or("", |it| it.as_str()));
+        let text = text.replace('_', "");
+        let (digits, radix) = match text.get(..2) {
+            Some("0x") => (&text[2..], 16),
+            Some("0o") => (&text[2..], 8),
+            Some("0b") => (&text[2..], 2),
+            _ => (&text[..], 10),
+        };
+        u128::from_str_radix(digits, radix).ok()
+    }
+
     pub fn kind(&self) -> LiteralKind {
         const INT_SUFFIXES: [&'static str; 12] = [
             "u64", "u32", "u16", "u8", "usize", "isize", "i64", "i32", "i16", "i8", "u128", "i128",
@@ -343,8 +363,12 @@ impl ast::Literal {
                 // and the comments on the linked PR.
 
                 let text = token.text();
+                // `0x1f32` is a hex integer, not a float
+                let is_hex = text.starts_with("0x");
 
-                if let suffix @ Some(_) = Self::find_suffix(&text, &FLOAT_SUFFIXES) {
+                if let (false, suffix @ Some(_)) =
+                    (is_hex, Self::find_suffix(&text, &FLOAT_SUFFIXES))
+                {
                     LiteralKind::FloatNumber { suffix }
                 } else {
                     LiteralKind::IntNumber { suffix: Self::find_suffix(&text, &INT_SUFFIXES) }
@@ -227,6 +227,12 @@ impl ast::FnDef {
     }
 }
 
+impl ast::BlockExpr {
+    pub fn is_async(&self) -> bool {
+        self.syntax().children_with_tokens().any(|it| it.kind() == T![async])
+    }
+}
+
 impl ast::LetStmt {
     pub fn has_semi(&self) -> bool {
         match self.syntax().last_child_or_token() {
@@ -303,6 +309,13 @@ impl ast::ReferenceType {
     pub fn is_mut(&self) -> bool {
         self.syntax().children_with_tokens().any(|n| n.kind() == T![mut])
     }
+
+    pub fn lifetime_token(&self) -> Option<SyntaxToken> {
+        self.syntax()
+            .children_with_tokens()
+            .filter_map(|it| it.into_token())
+            .find(|it| it.kind() == LIFETIME)
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
@@ -324,6 +337,13 @@ impl ast::SelfParam {
             .expect("invalid tree: self param must have self")
     }
 
+    pub fn lifetime_token(&self) -> Option<SyntaxToken> {
+        self.syntax()
+            .children_with_tokens()
+            .filter_map(|it| it.into_token())
+            .find(|it| it.kind() == LIFETIME)
+    }
+
     pub fn kind(&self) -> SelfParamKind {
         let borrowed = self.syntax().children_with_tokens().any(|n| n.kind() == T![&]);
         if borrowed {
@@ -353,6 +373,52 @@ impl ast::LifetimeParam {
     }
 }
 
+impl ast::Macro {
+    /// The token tree with the rules of a `macro_rules!` or of a `macro`.
+    pub fn token_tree(&self) -> Option<ast::TokenTree> {
+        match self {
+            ast::Macro::MacroCall(it) => it.token_tree(),
+            ast::Macro::MacroDef(it) => it.token_tree(),
+        }
+    }
+}
+
+impl ast::LifetimeArg {
+    pub fn lifetime_token(&self) -> Option<SyntaxToken> {
+        self.syntax()
+            .children_with_tokens()
+            .filter_map(|it| it.into_token())
+            .find(|it| it.kind() == LIFETIME)
+    }
+}
+
+impl ast::Label {
+    pub fn lifetime_token(&self) -> Option<SyntaxToken> {
+        self.syntax()
+            .children_with_tokens()
+            .filter_map(|it| it.into_token())
+            .find(|it| it.kind() == LIFETIME)
+    }
+}
+
+impl ast::BreakExpr {
+    pub fn lifetime_token(&self) -> Option<SyntaxToken> {
+        self.syntax()
+            .children_with_tokens()
+            .filter_map(|it| it.into_token())
+            .find(|it| it.kind() == LIFETIME)
+    }
+}
+
+impl ast::ContinueExpr {
+    pub fn lifetime_token(&self) -> Option<SyntaxToken> {
+        self.syntax()
+            .children_with_tokens()
+            .filter_map(|it| it.into_token())
+            .find(|it| it.kind() == LIFETIME)
+    }
+}
+
 impl ast::TypeParam {
     pub fn colon_token(&self) -> Option<SyntaxToken> {
         self.syntax()
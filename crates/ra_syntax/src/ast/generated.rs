@@ -29,6 +29,7 @@ impl AstNode for SourceFile {
 }
 impl ast::ModuleItemOwner for SourceFile {}
 impl ast::FnDefOwner for SourceFile {}
+impl ast::AttrsOwner for SourceFile {}
 impl SourceFile {
     pub fn modules(&self) -> AstChildren<Module> {
         AstChildren::new(&self.syntax)
@@ -1297,6 +1298,9 @@ impl AstNode for BlockExpr {
     }
 }
 impl BlockExpr {
+    pub fn label(&self) -> Option<Label> {
+        AstChildren::new(&self.syntax).next()
+    }
     pub fn block(&self) -> Option<Block> {
         AstChildren::new(&self.syntax).next()
     }
@@ -2416,6 +2420,37 @@ impl MacroCall {
     }
 }
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MacroDef {
+    pub(crate) syntax: SyntaxNode,
+}
+impl AstNode for MacroDef {
+    fn can_cast(kind: SyntaxKind) -> bool {
+        match kind {
+            MACRO_DEF => true,
+            _ => false,
+        }
+    }
+    fn cast(syntax: SyntaxNode) -> Option<Self> {
+        if Self::can_cast(syntax.kind()) {
+            Some(Self { syntax })
+        } else {
+            None
+        }
+    }
+    fn syntax(&self) -> &SyntaxNode {
+        &self.syntax
+    }
+}
+impl ast::NameOwner for MacroDef {}
+impl ast::AttrsOwner for MacroDef {}
+impl ast::DocCommentsOwner for MacroDef {}
+impl ast::VisibilityOwner for MacroDef {}
+impl MacroDef {
+    pub fn token_tree(&self) -> Option<TokenTree> {
+        AstChildren::new(&self.syntax).next()
+    }
+}
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Attr {
     pub(crate) syntax: SyntaxNode,
 }
@@ -2497,6 +2532,9 @@ impl TypeParamList {
     pub fn lifetime_params(&self) -> AstChildren<LifetimeParam> {
         AstChildren::new(&self.syntax)
     }
+    pub fn const_params(&self) -> AstChildren<ConstParam> {
+        AstChildren::new(&self.syntax)
+    }
 }
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct TypeParam {
@@ -3633,6 +3671,46 @@ impl AstNode for ModuleItem {
 impl ast::AttrsOwner for ModuleItem {}
 impl ast::VisibilityOwner for ModuleItem {}
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Macro {
+    MacroCall(MacroCall),
+    MacroDef(MacroDef),
+}
+impl From<MacroCall> for Macro {
+    fn from(node: MacroCall) -> Macro {
+        Macro::MacroCall(node)
+    }
+}
+impl From<MacroDef> for Macro {
+    fn from(node: MacroDef) -> Macro {
+        Macro::MacroDef(node)
+    }
+}
+impl AstNode for Macro {
+    fn can_cast(kind: SyntaxKind) -> bool {
+        match kind {
+            MACRO_CALL | MACRO_DEF => true,
+            _ => false,
+        }
+    }
+    fn cast(syntax: SyntaxNode) -> Option<Self> {
+        let res = match syntax.kind() {
+            MACRO_CALL => Macro::MacroCall(MacroCall { syntax }),
+            MACRO_DEF => Macro::MacroDef(MacroDef { syntax }),
+            _ => return None,
+        };
+        Some(res)
+    }
+    fn syntax(&self) -> &SyntaxNode {
+        match self {
+            Macro::MacroCall(it) => &it.syntax,
+            Macro::MacroDef(it) => &it.syntax,
+        }
+    }
+}
+impl ast::NameOwner for Macro {}
+impl ast::AttrsOwner for Macro {}
+impl ast::DocCommentsOwner for Macro {}
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ImplItem {
     FnDef(FnDef),
     TypeAliasDef(TypeAliasDef),
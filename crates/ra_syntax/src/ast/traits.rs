@@ -32,6 +32,10 @@ pub trait LoopBodyOwner: AstNode {
     fn loop_body(&self) -> Option<ast::BlockExpr> {
         child_opt(self)
     }
+
+    fn label(&self) -> Option<ast::Label> {
+        child_opt(self)
+    }
 }
 
 pub trait ArgListOwner: AstNode {
@@ -50,6 +54,7 @@ pub trait FnDefOwner: AstNode {
 pub enum ItemOrMacro {
     Item(ast::ModuleItem),
     Macro(ast::MacroCall),
+    MacroDef(ast::MacroDef),
 }
 
 pub trait ModuleItemOwner: AstNode {
@@ -73,6 +78,7 @@ impl Iterator for ItemOrMacroIter {
                 match n {
                     ast::ModuleItem(it) => { return Some(ItemOrMacro::Item(it)) },
                     ast::MacroCall(it) => { return Some(ItemOrMacro::Macro(it)) },
+                    ast::MacroDef(it) => { return Some(ItemOrMacro::MacroDef(it)) },
                     _ => {},
                 }
             }
@@ -21,6 +21,12 @@ impl SyntaxNodePtr {
         SyntaxNodePtr { range: node.text_range(), kind: node.kind() }
     }
 
+    /// Creates a pointer from its raw parts, for example when restoring it
+    /// from a persistent cache.
+    pub fn from_parts(range: TextRange, kind: SyntaxKind) -> SyntaxNodePtr {
+        SyntaxNodePtr { range, kind }
+    }
+
     pub fn to_node(self, root: &SyntaxNode) -> SyntaxNode {
         assert!(root.parent().is_none());
         successors(Some(root.clone()), |node| {
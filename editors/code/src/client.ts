@@ -36,6 +36,8 @@ export async function createClient(config: Config): Promise<null | lc.LanguageCl
             cargoWatchAllTargets: config.cargoWatchOptions.allTargets,
             excludeGlobs: config.excludeGlobs,
             useClientWatching: config.useClientWatching,
+            useLibraryCache: config.useLibraryCache,
+            macroTokenLimit: config.macroTokenLimit,
             featureFlags: config.featureFlags,
             withSysroot: config.withSysroot,
             cargoFeatures: config.cargoFeatures,
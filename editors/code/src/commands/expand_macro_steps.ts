@@ -0,0 +1,126 @@
+import * as vscode from 'vscode';
+import * as lc from 'vscode-languageclient';
+
+import { Ctx, Cmd } from '../ctx';
+
+// Opens the virtual file that will show how the macro call under the cursor
+// expands, one macro call at a time.
+//
+// The contents of the file come from the `TextDocumentContentProvider`
+export function expandMacroSteps(ctx: Ctx): Cmd {
+    const tdcp = new TextDocumentContentProvider(ctx);
+    ctx.pushCleanup(
+        vscode.workspace.registerTextDocumentContentProvider(
+            'rust-analyzer-macro-steps',
+            tdcp,
+        ),
+    );
+
+    return async () => {
+        const document = await vscode.workspace.openTextDocument(tdcp.uri);
+        tdcp.eventEmitter.fire(tdcp.uri);
+        return vscode.window.showTextDocument(
+            document,
+            vscode.ViewColumn.Two,
+            true,
+        );
+    };
+}
+
+interface MacroFragment {
+    text: string;
+    location: lc.Location | null;
+}
+
+// Metavariables inside of repetitions are bound to an array with the
+// fragments of each iteration.
+type MacroFragments = MacroFragment | MacroFragments[];
+
+interface MacroBinding {
+    name: string;
+    fragments: MacroFragments;
+}
+
+interface MacroExpansionStep {
+    name: string;
+    location: lc.Location;
+    rule: number | null;
+    bindings: MacroBinding[];
+    expansion: string;
+    children: MacroExpansionStep[];
+}
+
+function formatFragments(fragments: MacroFragments): string {
+    if (Array.isArray(fragments)) {
+        return `[${fragments.map(formatFragments).join(', ')}]`;
+    }
+    return fragments.text;
+}
+
+function formatStep(step: MacroExpansionStep, indent: string): string {
+    const line = step.location.range.start.line + 1;
+    let result = `${indent}// ${step.name}! at line ${line}`;
+    if (step.rule != null) {
+        result += `, matched rule #${step.rule}`;
+    }
+    result += '\n';
+    for (const binding of step.bindings) {
+        const fragments = formatFragments(binding.fragments);
+        result += `${indent}//     $${binding.name} = ${fragments}\n`;
+    }
+    result += step.expansion
+        .split('\n')
+        .map(it => indent + it)
+        .join('\n');
+    result += '\n';
+    for (const child of step.children) {
+        result += '\n' + formatStep(child, indent + '    ');
+    }
+    return result;
+}
+
+function code_format(step: MacroExpansionStep): string {
+    let result = `// Expansion steps of ${step.name}! macro\n`;
+    result += '// ' + '='.repeat(result.length - 3);
+    result += '\n\n';
+    result += formatStep(step, '');
+
+    return result;
+}
+
+class TextDocumentContentProvider
+    implements vscode.TextDocumentContentProvider {
+    private ctx: Ctx;
+    uri = vscode.Uri.parse(
+        'rust-analyzer-macro-steps://expandMacroSteps/[STEPS].rs',
+    );
+    eventEmitter = new vscode.EventEmitter<vscode.Uri>();
+
+    constructor(ctx: Ctx) {
+        this.ctx = ctx;
+    }
+
+    async provideTextDocumentContent(_uri: vscode.Uri): Promise<string> {
+        const editor = vscode.window.activeTextEditor;
+        const client = this.ctx.client;
+        if (!editor || !client) return '';
+
+        const position = editor.selection.active;
+        const request: lc.TextDocumentPositionParams = {
+            textDocument: { uri: editor.document.uri.toString() },
+            position,
+        };
+        const step = await client.sendRequest<MacroExpansionStep>(
+            'rust-analyzer/expandMacroSteps',
+            request,
+        );
+
+        if (step == null) return 'Not available';
+
+        return code_format(step);
+    }
+
+    get onDidChange(): vscode.Event<vscode.Uri> {
+        return this.eventEmitter.event;
+    }
+}
@@ -0,0 +1,112 @@
+import * as vscode from 'vscode';
+import * as lc from 'vscode-languageclient';
+
+import { Ctx, Cmd } from '../ctx';
+
+// Opens the virtual file that will explain how the method of the method call
+// under the cursor is looked up.
+//
+// The contents of the file come from the `TextDocumentContentProvider`
+export function explainMethodResolution(ctx: Ctx): Cmd {
+    const tdcp = new TextDocumentContentProvider(ctx);
+    ctx.pushCleanup(
+        vscode.workspace.registerTextDocumentContentProvider(
+            'rust-analyzer-method-resolution',
+            tdcp,
+        ),
+    );
+
+    return async () => {
+        const document = await vscode.workspace.openTextDocument(tdcp.uri);
+        tdcp.eventEmitter.fire(tdcp.uri);
+        return vscode.window.showTextDocument(
+            document,
+            vscode.ViewColumn.Two,
+            true,
+        );
+    };
+}
+
+interface MethodLookupCandidate {
+    signature: string;
+    location: lc.Location;
+    status: string;
+    impls: lc.Location[];
+}
+
+interface MethodLookupStep {
+    receiverTy: string;
+    adjustment: string;
+    candidates: MethodLookupCandidate[];
+}
+
+interface MethodLookupExplanation {
+    name: string;
+    steps: MethodLookupStep[];
+}
+
+function formatLocation(location: lc.Location): string {
+    const file = vscode.Uri.parse(location.uri).path.split('/').pop();
+    return `${file}:${location.range.start.line + 1}`;
+}
+
+function formatStep(step: MethodLookupStep): string {
+    let result = `// ${step.receiverTy} (${step.adjustment})\n`;
+    if (step.candidates.length === 0) {
+        result += '//     no methods with this name\n';
+    }
+    for (const candidate of step.candidates) {
+        const location = formatLocation(candidate.location);
+        result += `${candidate.signature}; // ${location}: ${candidate.status}\n`;
+        for (const impl of candidate.impls) {
+            result += `//     considered impl at ${formatLocation(impl)}\n`;
+        }
+    }
+    return result;
+}
+
+function code_format(explanation: MethodLookupExplanation): string {
+    let result = `// Method resolution of .${explanation.name}()\n`;
+    result += '// ' + '='.repeat(result.length - 3);
+    result += '\n\n';
+    result += explanation.steps.map(formatStep).join('\n');
+
+    return result;
+}
+
+class TextDocumentContentProvider
+    implements vscode.TextDocumentContentProvider {
+    private ctx: Ctx;
+    uri = vscode.Uri.parse(
+        'rust-analyzer-method-resolution://explainMethodResolution/[METHOD].rs',
+    );
+    eventEmitter = new vscode.EventEmitter<vscode.Uri>();
+
+    constructor(ctx: Ctx) {
+        this.ctx = ctx;
+    }
+
+    async provideTextDocumentContent(_uri: vscode.Uri): Promise<string> {
+        const editor = vscode.window.activeTextEditor;
+        const client = this.ctx.client;
+        if (!editor || !client) return '';
+
+        const position = editor.selection.active;
+        const request: lc.TextDocumentPositionParams = {
+            textDocument: { uri: editor.document.uri.toString() },
+            position,
+        };
+        const explanation = await client.sendRequest<MethodLookupExplanation>(
+            'rust-analyzer/explainMethodResolution',
+            request,
+        );
+
+        if (explanation == null) return 'Not available';
+
+        return code_format(explanation);
+    }
+
+    get onDidChange(): vscode.Event<vscode.Uri> {
+        return this.eventEmitter.event;
+    }
+}
@@ -11,6 +11,8 @@ export * from './on_enter';
 export * from './parent_module';
 export * from './syntax_tree';
 export * from './expand_macro';
+export * from './expand_macro_steps';
+export * from './explain_method_resolution';
 export * from './runnables';
 
 export function collectGarbage(ctx: Ctx): Cmd {
@@ -28,6 +28,8 @@ export class Config {
     maxInlayHintLength: null | number = null;
     excludeGlobs: string[] = [];
     useClientWatching = true;
+    useLibraryCache = false;
+    macroTokenLimit: null | number = null;
     featureFlags: Record<string, boolean> = {};
     // for internal use
     withSysroot: null | boolean = null;
@@ -211,6 +213,12 @@ export class Config {
         if (config.has('useClientWatching')) {
             this.useClientWatching = config.get('useClientWatching') || true;
         }
+        if (config.has('useLibraryCache')) {
+            this.useLibraryCache = config.get('useLibraryCache') || false;
+        }
+        if (config.has('macroTokenLimit')) {
+            this.macroTokenLimit = config.get('macroTokenLimit') as number;
+        }
         if (config.has('featureFlags')) {
             this.featureFlags = config.get('featureFlags') || {};
         }
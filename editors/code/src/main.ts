@@ -19,6 +19,8 @@ export async function activate(context: vscode.ExtensionContext) {
     ctx.registerCommand('parentModule', commands.parentModule);
     ctx.registerCommand('syntaxTree', commands.syntaxTree);
     ctx.registerCommand('expandMacro', commands.expandMacro);
+    ctx.registerCommand('expandMacroSteps', commands.expandMacroSteps);
+    ctx.registerCommand('explainMethodResolution', commands.explainMethodResolution);
     ctx.registerCommand('run', commands.run);
     ctx.registerCommand('reload', commands.reload);
     ctx.registerCommand('onEnter', commands.onEnter);
@@ -294,7 +294,7 @@ macro_rules! ast_enums {
 
 pub(crate) const AST_SRC: AstSrc = AstSrc {
     nodes: &ast_nodes! {
-        struct SourceFile: ModuleItemOwner, FnDefOwner {
+        struct SourceFile: ModuleItemOwner, FnDefOwner, AttrsOwner {
             modules: [Module],
         }
 
@@ -393,7 +393,7 @@ pub(crate) const AST_SRC: AstSrc = AstSrc {
         struct ContinueExpr {}
         struct BreakExpr { Expr }
         struct Label {}
-        struct BlockExpr { Block  }
+        struct BlockExpr { Label, Block }
         struct ReturnExpr { Expr }
         struct CallExpr: ArgListOwner { Expr }
         struct MethodCallExpr: ArgListOwner {
@@ -456,11 +456,15 @@ pub(crate) const AST_SRC: AstSrc = AstSrc {
         struct MacroCall: NameOwner, AttrsOwner,DocCommentsOwner {
             TokenTree, Path
         }
+        struct MacroDef: NameOwner, AttrsOwner, DocCommentsOwner, VisibilityOwner {
+            TokenTree
+        }
         struct Attr { Path, input: AttrInput }
         struct TokenTree {}
         struct TypeParamList {
             type_params: [TypeParam],
             lifetime_params: [LifetimeParam],
+            const_params: [ConstParam],
         }
         struct TypeParam: NameOwner, AttrsOwner, TypeBoundsOwner {
             default_type: TypeRef,
@@ -566,6 +570,10 @@ pub(crate) const AST_SRC: AstSrc = AstSrc {
             Module,
         }
 
+        enum Macro: NameOwner, AttrsOwner, DocCommentsOwner {
+            MacroCall, MacroDef,
+        }
+
         enum ImplItem: AttrsOwner {
             FnDef, TypeAliasDef, ConstDef,
         }